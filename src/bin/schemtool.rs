@@ -19,7 +19,9 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 use chrono::DateTime;
 use clap::{command, Parser, Subcommand};
 use mc_schem::schem;
-use mc_schem::schem::{RawMetaData, Schematic};
+use mc_schem::schem::{MaterialListOption, RawMetaData, Schematic};
+use mc_schem::schem::diff::{BlockDiff, BlockEntityDiff};
+use mc_schem::render::{MapArtOption, RenderOption, RenderView, TexturePack};
 
 /// Read, write, convert minecraft schematic files via different versions
 #[derive(Parser, Debug)]
@@ -73,6 +75,78 @@ enum Commands {
         #[arg(long, default_value_t = false)]
         benchmark: bool,
     },
+    /// Compare two schematics in global coordinates
+    Diff {
+        /// The old schematic
+        #[arg()]
+        old: String,
+        /// The new schematic
+        #[arg()]
+        new: String,
+        /// Print every changed position
+        #[arg(long, default_value_t = false)]
+        verbose: bool,
+        /// Save changed cells to this file, unchanged cells are filled with structure void
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Render a schematic to png
+    Render {
+        /// Schematic file to load
+        #[arg()]
+        file: String,
+        /// top-down or isometric
+        #[arg(long, default_value_t = String::from("isometric"), value_parser = ["top-down", "isometric"])]
+        view: String,
+        /// Pixels per block edge
+        #[arg(long, default_value_t = 4)]
+        block_size: u32,
+        /// Rotate clockwise by 90 degrees for this many times
+        #[arg(long, default_value_t = 0)]
+        rotation: u8,
+        /// Unzipped resource pack to take block colors from
+        #[arg(long)]
+        texture_pack: Option<String>,
+        /// Save every y layer as <output>_y<layer>.png instead of rendering the whole schematic
+        #[arg(long, default_value_t = false)]
+        slices: bool,
+        /// Output png file
+        #[arg(short, long, default_value_t = String::from("out.png"))]
+        output: String,
+    },
+    /// Compute how a region looks on a vanilla map
+    MapArt {
+        /// Schematic file to load
+        #[arg()]
+        file: String,
+        /// Index of region
+        #[arg(long, default_value_t = 0)]
+        region: usize,
+        /// Relative x of the north-west corner of the map
+        #[arg(long, default_value_t = 0)]
+        origin_x: i32,
+        /// Relative z of the north-west corner of the map
+        #[arg(long, default_value_t = 0)]
+        origin_z: i32,
+        /// Output png file
+        #[arg(short, long, default_value_t = String::from("map.png"))]
+        output: String,
+    },
+    /// Count items required to build a schematic
+    Materials {
+        /// Schematic file to load
+        #[arg()]
+        file: String,
+        /// Count items in containers like chests and shulker boxes
+        #[arg(long, default_value_t = false)]
+        inventories: bool,
+        /// Output format, csv or json
+        #[arg(long, default_value_t = String::from("csv"), value_parser = ["csv", "json"])]
+        format: String,
+        /// Write the list to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<String>,
+    },
 }
 
 
@@ -186,6 +260,127 @@ fn main() {
                 }
             }
         }
+        Commands::Diff { old, new, verbose, output } => {
+            let load = |file: &str| match Schematic::from_file(file) {
+                Ok(s) => s.0,
+                Err(e) => {
+                    eprintln!("Failed to load {}: {e}", file);
+                    std::process::exit(1);
+                }
+            };
+            let old_schem = load(&old);
+            let new_schem = load(&new);
+            let diff = old_schem.diff(&new_schem);
+
+            let (added, removed, id_changed, state_changed) = diff.block_change_counts();
+            let (be_added, be_removed, be_changed) = diff.block_entity_change_counts();
+            println!("Blocks: {added} added, {removed} removed, {id_changed} id changed, {state_changed} state changed");
+            println!("Block entities: {be_added} added, {be_removed} removed, {be_changed} changed");
+            println!("Entities: {} added, {} removed", diff.added_entities.len(), diff.removed_entities.len());
+
+            if verbose {
+                for (pos, d) in &diff.blocks {
+                    let pos = schem::common::format_size(pos);
+                    match d {
+                        BlockDiff::Added(b) => println!("\t{pos} added {b}"),
+                        BlockDiff::Removed(b) => println!("\t{pos} removed {b}"),
+                        BlockDiff::IdChanged { old, new } => println!("\t{pos} {old} -> {new}"),
+                        BlockDiff::StateChanged { old, new } => println!("\t{pos} {old} -> {new}"),
+                    }
+                }
+                for (pos, d) in &diff.block_entities {
+                    let pos = schem::common::format_size(pos);
+                    match d {
+                        BlockEntityDiff::Added(_) => println!("\t{pos} block entity added"),
+                        BlockEntityDiff::Removed(_) => println!("\t{pos} block entity removed"),
+                        BlockEntityDiff::Changed { .. } => println!("\t{pos} block entity changed"),
+                    }
+                }
+            }
+
+            if let Some(output) = output {
+                let saved = diff.to_schematic(new_schem.metadata.clone())
+                    .and_then(|s| s.save_to_file(&output));
+                if let Err(e) = saved {
+                    eprintln!("Failed to save {}: {e}", output);
+                    std::process::exit(2);
+                }
+            }
+        }
+        Commands::Render { file, view, block_size, rotation, texture_pack, slices, output } => {
+            let schem = match Schematic::from_file(&file) {
+                Ok(s) => s.0,
+                Err(e) => {
+                    eprintln!("Failed to load {}: {e}", file);
+                    std::process::exit(1);
+                }
+            };
+            let mut option = RenderOption { block_size, rotation, ..RenderOption::default() };
+            if let Some(dir) = texture_pack {
+                match TexturePack::from_dir(&dir) {
+                    Ok(t) => option.texture_pack = Some(t),
+                    Err(e) => {
+                        eprintln!("Failed to load texture pack {}: {e}", dir);
+                        std::process::exit(1);
+                    }
+                }
+            }
+
+            let images = if slices {
+                let prefix = output.strip_suffix(".png").unwrap_or(&output);
+                schem.render_slices(&option).into_iter().enumerate()
+                    .map(|(y, image)| (format!("{prefix}_y{y}.png"), image)).collect()
+            } else {
+                let view = if view == "top-down" { RenderView::TopDown } else { RenderView::Isometric };
+                vec![(output.clone(), schem.render(view, &option))]
+            };
+            for (filename, image) in images {
+                if let Err(e) = image.save_png(&filename) {
+                    eprintln!("Failed to save {}: {e}", filename);
+                    std::process::exit(2);
+                }
+            }
+        }
+        Commands::MapArt { file, region, origin_x, origin_z, output } => {
+            let schem = match Schematic::from_file(&file) {
+                Ok(s) => s.0,
+                Err(e) => {
+                    eprintln!("Failed to load {}: {e}", file);
+                    std::process::exit(1);
+                }
+            };
+            let Some(reg) = schem.regions.get(region) else {
+                eprintln!("{} has {} region(s), region {region} doesn't exist", file, schem.regions.len());
+                std::process::exit(1);
+            };
+            let map = reg.map_art(&MapArtOption { origin: [origin_x, origin_z] });
+            if let Err(e) = map.to_image().save_png(&output) {
+                eprintln!("Failed to save {}: {e}", output);
+                std::process::exit(2);
+            }
+        }
+        Commands::Materials { file, inventories, format, output } => {
+            let schem = match Schematic::from_file(&file) {
+                Ok(s) => s.0,
+                Err(e) => {
+                    eprintln!("Failed to load {}: {e}", file);
+                    std::process::exit(1);
+                }
+            };
+            let option = MaterialListOption { include_inventories: inventories };
+            let list = schem.material_list(&option);
+            let text = if format == "json" { list.to_json() } else { list.to_csv() };
+
+            match output {
+                Some(output) => {
+                    if let Err(e) = std::fs::write(&output, text) {
+                        eprintln!("Failed to write {}: {e}", output);
+                        std::process::exit(2);
+                    }
+                }
+                None => print!("{text}"),
+            }
+        }
         Commands::Print { supported_formats, loadable_formats, savable_formats } => {
             if supported_formats {
                 println!("Supported formats:");
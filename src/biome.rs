@@ -107,4 +107,131 @@ impl Default for Biome {
     fn default() -> Self {
         return Self::the_void;
     }
-}
\ No newline at end of file
+}
+impl Biome {
+    /// Convert numeric biome id used before 1.18 (in `Biomes` int array of chunks) into biome.
+    /// Biomes removed in 1.18 are converted like the game does, for example `wooded_hills` becomes
+    /// `forest`. Unknown ids are converted to `plains`.
+    pub fn from_legacy_id(id: i32) -> Biome {
+        return match id {
+            0 => Biome::ocean,
+            1 => Biome::plains,
+            2 | 17 | 130 => Biome::desert,
+            3 | 20 => Biome::windswept_hills,
+            4 | 18 => Biome::forest,
+            5 | 19 | 133 => Biome::taiga,
+            6 | 134 => Biome::swamp,
+            7 => Biome::river,
+            8 => Biome::nether_wastes,
+            9 => Biome::the_end,
+            10 => Biome::frozen_ocean,
+            11 => Biome::frozen_river,
+            12 | 13 => Biome::snowy_plains,
+            14 | 15 => Biome::mushroom_fields,
+            16 => Biome::beach,
+            21 | 22 | 149 => Biome::jungle,
+            23 | 151 => Biome::sparse_jungle,
+            24 => Biome::deep_ocean,
+            25 => Biome::stony_shore,
+            26 => Biome::snowy_beach,
+            27 | 28 => Biome::birch_forest,
+            29 | 157 => Biome::dark_forest,
+            30 | 31 | 158 => Biome::snowy_taiga,
+            32 | 33 => Biome::old_growth_pine_taiga,
+            34 => Biome::windswept_forest,
+            35 => Biome::savanna,
+            36 => Biome::savanna_plateau,
+            37 | 39 | 167 => Biome::badlands,
+            38 | 166 => Biome::wooded_badlands,
+            40 => Biome::small_end_islands,
+            41 => Biome::end_midlands,
+            42 => Biome::end_highlands,
+            43 => Biome::end_barrens,
+            44 | 47 => Biome::warm_ocean,
+            45 => Biome::lukewarm_ocean,
+            46 => Biome::cold_ocean,
+            48 => Biome::deep_lukewarm_ocean,
+            49 => Biome::deep_cold_ocean,
+            50 => Biome::deep_frozen_ocean,
+            127 => Biome::the_void,
+            129 => Biome::sunflower_plains,
+            131 | 162 => Biome::windswept_gravelly_hills,
+            132 => Biome::flower_forest,
+            140 => Biome::ice_spikes,
+            155 | 156 => Biome::old_growth_birch_forest,
+            160 | 161 => Biome::old_growth_spruce_taiga,
+            163 | 164 => Biome::windswept_savanna,
+            165 => Biome::eroded_badlands,
+            168 | 169 => Biome::bamboo_jungle,
+            170 => Biome::soul_sand_valley,
+            171 => Biome::crimson_forest,
+            172 => Biome::warped_forest,
+            173 => Biome::basalt_deltas,
+            174 => Biome::dripstone_caves,
+            175 => Biome::lush_caves,
+            _ => Biome::plains,
+        };
+    }
+
+    /// Numeric biome id used before 1.18. Biomes added in 1.18 and later are replaced by the
+    /// closest biome that existed before.
+    pub fn legacy_id(&self) -> i32 {
+        return match self {
+            Biome::the_void => 127,
+            Biome::plains | Biome::meadow => 1,
+            Biome::sunflower_plains => 129,
+            Biome::snowy_plains | Biome::snowy_slopes => 12,
+            Biome::ice_spikes => 140,
+            Biome::desert => 2,
+            Biome::swamp | Biome::mangrove_swamp => 6,
+            Biome::forest => 4,
+            Biome::flower_forest | Biome::cherry_grove => 132,
+            Biome::birch_forest => 27,
+            Biome::dark_forest => 29,
+            Biome::old_growth_birch_forest => 155,
+            Biome::old_growth_pine_taiga => 32,
+            Biome::old_growth_spruce_taiga => 160,
+            Biome::taiga => 5,
+            Biome::snowy_taiga | Biome::grove => 30,
+            Biome::savanna => 35,
+            Biome::savanna_plateau => 36,
+            Biome::windswept_hills | Biome::frozen_peaks | Biome::jagged_peaks | Biome::stony_peaks => 3,
+            Biome::windswept_gravelly_hills => 131,
+            Biome::windswept_forest => 34,
+            Biome::windswept_savanna => 163,
+            Biome::jungle => 21,
+            Biome::sparse_jungle => 23,
+            Biome::bamboo_jungle => 168,
+            Biome::badlands => 37,
+            Biome::eroded_badlands => 165,
+            Biome::wooded_badlands => 38,
+            Biome::river => 7,
+            Biome::frozen_river => 11,
+            Biome::beach => 16,
+            Biome::snowy_beach => 26,
+            Biome::stony_shore => 25,
+            Biome::warm_ocean => 44,
+            Biome::lukewarm_ocean => 45,
+            Biome::deep_lukewarm_ocean => 48,
+            Biome::ocean => 0,
+            Biome::deep_ocean => 24,
+            Biome::cold_ocean => 46,
+            Biome::deep_cold_ocean => 49,
+            Biome::frozen_ocean => 10,
+            Biome::deep_frozen_ocean => 50,
+            Biome::mushroom_fields => 14,
+            Biome::dripstone_caves => 174,
+            Biome::lush_caves | Biome::deep_dark => 175,
+            Biome::nether_wastes => 8,
+            Biome::warped_forest => 172,
+            Biome::crimson_forest => 171,
+            Biome::soul_sand_valley => 170,
+            Biome::basalt_deltas => 173,
+            Biome::the_end => 9,
+            Biome::end_highlands => 42,
+            Biome::end_midlands => 41,
+            Biome::small_end_islands => 40,
+            Biome::end_barrens => 43,
+        };
+    }
+}
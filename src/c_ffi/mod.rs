@@ -32,7 +32,8 @@ use flate2::Compression;
 use crate::block::{CommonBlock, Block, BlockIdParseError};
 use crate::error::Error;
 #[allow(unused_imports)]
-use crate::region::{BlockEntity, Entity, PendingTick};
+use crate::Entity;
+use crate::region::{BlockEntity, PendingTick};
 use crate::schem::{Schematic, LitematicaLoadOption, VanillaStructureLoadOption, WorldEdit13LoadOption, WorldEdit12LoadOption, DataVersion, LitematicaSaveOption, VanillaStructureSaveOption, WorldEdit13SaveOption, MetaDataIR};
 
 mod map_ffi;
@@ -475,7 +476,7 @@ struct CLitematicaLoadOption {
 sa::const_assert!(size_of::<CLitematicaLoadOption>() == 512);
 impl CLitematicaLoadOption {
     pub fn to_option(&self) -> LitematicaLoadOption {
-        return LitematicaLoadOption {};
+        return LitematicaLoadOption::default();
     }
 
     pub fn from_option(_src: &LitematicaLoadOption) -> Self {
@@ -492,7 +493,10 @@ struct CVanillaStructureLoadOption {
 sa::const_assert!(size_of::<CVanillaStructureLoadOption>()==512);
 impl CVanillaStructureLoadOption {
     pub fn to_option(&self) -> VanillaStructureLoadOption {
-        return VanillaStructureLoadOption { background_block: self.background_block };
+        return VanillaStructureLoadOption {
+            background_block: self.background_block,
+            ..VanillaStructureLoadOption::default()
+        };
     }
     pub fn from_option(src: &VanillaStructureLoadOption) -> Self {
         return Self {
@@ -510,7 +514,7 @@ struct CWE13LoadOption {
 sa::const_assert!(size_of::<CWE13LoadOption>()==512);
 impl CWE13LoadOption {
     pub fn to_option(&self) -> WorldEdit13LoadOption {
-        return WorldEdit13LoadOption {};
+        return WorldEdit13LoadOption::default();
     }
 
     pub fn from_option(_src: &WorldEdit13LoadOption) -> Self {
@@ -528,7 +532,8 @@ sa::const_assert!(size_of::<CWE12LoadOption>()==512);
 impl CWE12LoadOption {
     pub fn to_option(&self) -> WorldEdit12LoadOption {
         return WorldEdit12LoadOption {
-            data_version: self.data_version
+            data_version: self.data_version,
+            ..WorldEdit12LoadOption::default()
         };
     }
     pub fn from_option(src: &WorldEdit12LoadOption) -> Self {
@@ -763,6 +768,7 @@ impl CMetadata {
 
             litematica_version: self.litematica_version,
             litematica_subversion: self.litematica_subversion.to_option(),
+            litematica_preview: None,
 
             schem_version: self.schem_version,
             schem_offset: self.schem_offset,
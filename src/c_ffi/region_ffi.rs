@@ -20,14 +20,16 @@ use std::collections::HashMap;
 use std::mem::swap;
 use std::ptr::{drop_in_place, null, null_mut};
 use fastnbt::Value;
-use crate::Block;
+use crate::{Block, Entity};
 use crate::c_ffi::{CArrayView, CMapRef, CPendingTickType, CPosDouble, CPosInt, CRegionBlockInfo, CStringView, error_to_box};
-use crate::region::{BlockEntity, Entity, HasPalette, PendingTick, PendingTickInfo, Region, WorldSlice};
+use crate::entity::OtherEntity;
+use crate::player::EntityBox;
+use crate::region::{BlockEntity, HasPalette, PendingTick, PendingTickInfo, Region, WorldSlice};
 use crate::error::Error;
 
 #[no_mangle]
 extern "C" fn MC_SCHEM_create_entity() -> Box<Entity> {
-    return Box::new(Entity::new());
+    return Box::new(EntityBox::new(Box::new(OtherEntity::default())));
 }
 
 #[no_mangle]
@@ -46,7 +48,7 @@ unsafe extern "C" fn MC_SCHEM_swap_entity(a: *mut Entity, b: *mut Entity) {
 extern "C" fn MC_SCHEM_entity_get_block_pos(entity: *const Entity) -> CPosInt {
     unsafe {
         let entity = &*entity;
-        return CPosInt { pos: entity.block_pos };
+        return CPosInt { pos: entity.block_pos() };
     }
 }
 
@@ -54,29 +56,33 @@ extern "C" fn MC_SCHEM_entity_get_block_pos(entity: *const Entity) -> CPosInt {
 extern "C" fn MC_SCHEM_entity_get_pos(entity: *const Entity) -> CPosDouble {
     unsafe {
         let entity = &*entity;
-        return CPosDouble { pos: entity.position };
+        return CPosDouble { pos: entity.pos() };
     }
 }
 
+/// Block pos is computed from position, so the entity is moved by whole blocks instead
 #[no_mangle]
 extern "C" fn MC_SCHEM_entity_set_block_pos(entity: *mut Entity, pos: CPosInt) {
     unsafe {
-        (*entity).block_pos = pos.pos;
+        let entity = &mut *entity;
+        let old = entity.block_pos();
+        entity.pos_shift([pos.pos[0] - old[0], pos.pos[1] - old[1], pos.pos[2] - old[2]]);
     }
 }
 
 #[no_mangle]
 extern "C" fn MC_SCHEM_entity_set_pos(entity: *mut Entity, pos: CPosDouble) {
     unsafe {
-        (*entity).position = pos.pos;
+        (*entity).entity_fields_mut().pos = pos.pos;
     }
 }
 
+/// Tags that are not parsed into fields
 #[no_mangle]
 extern "C" fn MC_SCHEM_entity_get_tags(entity: *const Entity) -> CMapRef {
     unsafe {
         let entity = &*entity;
-        return CMapRef::StrValue(&entity.tags
+        return CMapRef::StrValue(entity.unparsed_tags()
             as *const HashMap<String, Value>
             as *mut HashMap<String, Value>);
     }
@@ -220,7 +220,10 @@ unsafe extern "C" fn MC_SCHEM_schem_get_metadata(schem: *const Schematic) -> CMe
 
 #[no_mangle]
 unsafe extern "C" fn MC_SCHEM_schem_set_metadata(schem: *mut Schematic, md: *const CMetadata) {
+    // preview image is not exposed to C, so keep it
+    let preview = (*schem).metadata.litematica_preview.take();
     (*schem).metadata = (*md).to_metadata();
+    (*schem).metadata.litematica_preview = preview;
 }
 
 #[no_mangle]
@@ -0,0 +1,546 @@
+use std::any::Any;
+use std::collections::HashMap;
+use fastnbt::Value;
+use crate::block::Block;
+use crate::error::{Error, remove_opt, remove_opt_bool, unwrap_opt_f32, unwrap_opt_i8, unwrap_opt_i32, unwrap_opt_string};
+use crate::item::{Inventory, Item};
+use crate::player::{bool_to_byte, EntityBox, EntityFields, GetEntity, GetEntityMut, GetMob, GetMobMut, GetPlayer, GetPlayerMut, MobFields, insert_opt, Player, remove_compound, remove_list};
+use crate::schem::common::parse_block;
+use crate::schem::{DataVersion, id_of_nbt_tag};
+use crate::unwrap_tag;
+
+/// Implements `GetEntity` and `GetEntityMut` with fields `id`, `entity_fields`, `unparsed_tags` and the inherent
+/// `to_nbt`. Types with `mob_fields` also get `GetMob` and `GetMobMut`.
+macro_rules! impl_get_entity {
+    ($t:ty) => {
+        impl GetEntity for $t {
+            fn id(&self) -> &str {
+                return &self.id;
+            }
+            fn entity_fields(&self) -> &EntityFields {
+                return &self.entity_fields;
+            }
+            fn clone_as_entity(&self) -> Box<dyn GetEntityMut> {
+                return Box::new(self.clone());
+            }
+            fn to_nbt(&self) -> HashMap<String, Value> {
+                return <$t>::to_nbt(self);
+            }
+            fn unparsed_tags(&self) -> &HashMap<String, Value> {
+                return &self.unparsed_tags;
+            }
+            fn as_any(&self) -> &dyn Any {
+                return self;
+            }
+        }
+
+        impl GetEntityMut for $t {
+            fn entity_fields_mut(&mut self) -> &mut EntityFields {
+                return &mut self.entity_fields;
+            }
+            fn unparsed_tags_mut(&mut self) -> &mut HashMap<String, Value> {
+                return &mut self.unparsed_tags;
+            }
+            fn as_any_mut(&mut self) -> &mut dyn Any {
+                return self;
+            }
+        }
+    };
+    ($t:ty, mob) => {
+        impl GetEntity for $t {
+            fn id(&self) -> &str {
+                return &self.id;
+            }
+            fn entity_fields(&self) -> &EntityFields {
+                return &self.entity_fields;
+            }
+            fn to_mob_fields(&self) -> Option<&dyn GetMob> {
+                return Some(self);
+            }
+            fn clone_as_entity(&self) -> Box<dyn GetEntityMut> {
+                return Box::new(self.clone());
+            }
+            fn to_nbt(&self) -> HashMap<String, Value> {
+                return <$t>::to_nbt(self);
+            }
+            fn unparsed_tags(&self) -> &HashMap<String, Value> {
+                return &self.unparsed_tags;
+            }
+            fn as_any(&self) -> &dyn Any {
+                return self;
+            }
+        }
+
+        impl GetEntityMut for $t {
+            fn entity_fields_mut(&mut self) -> &mut EntityFields {
+                return &mut self.entity_fields;
+            }
+            fn to_mob_mut(&mut self) -> Option<&mut dyn GetMobMut> {
+                return Some(self);
+            }
+            fn unparsed_tags_mut(&mut self) -> &mut HashMap<String, Value> {
+                return &mut self.unparsed_tags;
+            }
+            fn as_any_mut(&mut self) -> &mut dyn Any {
+                return self;
+            }
+        }
+
+        impl GetMob for $t {
+            fn mob_fields(&self) -> &MobFields {
+                return &self.mob_fields;
+            }
+            fn to_player(&self) -> Option<&dyn GetPlayer> {
+                return None;
+            }
+            fn clone_as_living_body(&self) -> Box<dyn GetMobMut> {
+                return Box::new(self.clone());
+            }
+        }
+
+        impl GetMobMut for $t {
+            fn mob_fields_mut(&mut self) -> &mut MobFields {
+                return &mut self.mob_fields;
+            }
+            fn to_player_mut(&mut self) -> Option<&mut dyn GetPlayerMut> {
+                return None;
+            }
+        }
+    };
+}
+
+/// The block that a hanging entity is attached to
+#[derive(Debug, Clone, Default)]
+pub struct HangingFields {
+    /// For item frames, 0 to 5 for down, up, north, south, west and east. For paintings, 0 to 3 for
+    /// south, west, north and east.
+    pub facing: i8,
+    /// `TileX`, `TileY` and `TileZ`
+    pub tile_pos: [i32; 3],
+}
+
+/// Item frame and glow item frame
+#[derive(Debug, Clone)]
+pub struct ItemFrame {
+    /// `minecraft:item_frame` or `minecraft:glow_item_frame`
+    pub id: String,
+    pub entity_fields: EntityFields,
+    pub hanging_fields: HangingFields,
+    pub item: Option<Item>,
+    pub item_drop_chance: Option<f32>,
+    /// Rotation of the item in 45 degrees, 0 to 7
+    pub item_rotation: Option<i8>,
+    /// Fixed frames can't be broken, and the item can't be rotated or taken out
+    pub fixed: Option<bool>,
+    pub invisible: Option<bool>,
+    pub unparsed_tags: HashMap<String, Value>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Painting {
+    pub id: String,
+    pub entity_fields: EntityFields,
+    pub hanging_fields: HangingFields,
+    /// Like `minecraft:kebab`. It was named `Motive` before 1.19.
+    pub variant: String,
+    /// Whether the tags before 1.19 (`Motive` and `Facing`) are used instead of `variant` and
+    /// `facing`
+    pub legacy_keys: bool,
+    pub unparsed_tags: HashMap<String, Value>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ArmorStand {
+    pub id: String,
+    pub entity_fields: EntityFields,
+    pub mob_fields: MobFields,
+    /// Feet, legs, chest and head. `None` if there is no `ArmorItems`.
+    pub armor_items: Option<[Option<Item>; 4]>,
+    /// Main hand and off hand. `None` if there is no `HandItems`.
+    pub hand_items: Option<[Option<Item>; 2]>,
+    /// Bit flags of slots that players can't change
+    pub disabled_slots: Option<i32>,
+    pub invisible: Option<bool>,
+    /// Marker armor stands have no hit box
+    pub marker: Option<bool>,
+    pub no_base_plate: Option<bool>,
+    pub show_arms: Option<bool>,
+    pub small: Option<bool>,
+    /// Tags that are not parsed, like `Pose`. They are kept to be written back.
+    pub unparsed_tags: HashMap<String, Value>,
+}
+
+/// All kinds of minecarts, like `minecraft:chest_minecart` and `minecraft:hopper_minecart`
+#[derive(Debug, Clone)]
+pub struct Minecart {
+    pub id: String,
+    pub entity_fields: EntityFields,
+    /// The custom block shown in the minecart, `None` for the default one
+    pub display_block: Option<Block>,
+    /// Offset of the custom block in pixels
+    pub display_offset: Option<i32>,
+    /// Items in chest minecarts and hopper minecarts, `None` if there is no `Items`
+    pub items: Option<Inventory>,
+    pub unparsed_tags: HashMap<String, Value>,
+}
+
+/// Living entities other than players and armor stands, like zombies and villagers. Tags that
+/// are specific to each kind are kept in `unparsed_tags`.
+#[derive(Debug, Clone)]
+pub struct Mob {
+    pub id: String,
+    pub entity_fields: EntityFields,
+    pub mob_fields: MobFields,
+    pub unparsed_tags: HashMap<String, Value>,
+}
+
+/// Entities that are not parsed into a specific type, like items and arrows
+#[derive(Debug, Clone, Default)]
+pub struct OtherEntity {
+    pub id: String,
+    pub entity_fields: EntityFields,
+    pub unparsed_tags: HashMap<String, Value>,
+}
+
+impl_get_entity!(ItemFrame);
+impl_get_entity!(Painting);
+impl_get_entity!(ArmorStand, mob);
+impl_get_entity!(Minecart);
+impl_get_entity!(Mob, mob);
+impl_get_entity!(OtherEntity);
+
+fn remove_id(nbt: &mut HashMap<String, Value>, tag_path: &str) -> Result<String, Error> {
+    let id = unwrap_opt_string(nbt, "id", tag_path)?.clone();
+    nbt.remove("id");
+    return Ok(id);
+}
+
+/// Parse an item in a compound, returns `None` for empty compounds which mean empty slots
+fn parse_optional_item(tag: &Value, tag_path: &str) -> Result<Option<Item>, Error> {
+    let nbt = unwrap_tag!(tag,Compound,HashMap::new(),tag_path);
+    if nbt.is_empty() {
+        return Ok(None);
+    }
+    return Ok(Some(Item::from_nbt(nbt, tag_path)?));
+}
+
+fn optional_item_to_nbt(item: &Option<Item>) -> Value {
+    return Value::Compound(item.as_ref().map_or_else(HashMap::new, |i| i.to_nbt()));
+}
+
+/// Parse a list of items like `ArmorItems`, empty slots are stored as empty compounds. Returns
+/// `None` if the list doesn't exist.
+fn remove_item_list<const N: usize>(nbt: &mut HashMap<String, Value>, key: &str, tag_path: &str) -> Result<Option<[Option<Item>; N]>, Error> {
+    if !nbt.contains_key(key) {
+        return Ok(None);
+    }
+    let list = remove_list(nbt, key, tag_path)?;
+    let list_path = format!("{tag_path}/{key}");
+    if list.len() != N {
+        return Err(Error::InvalidValue {
+            tag_path: list_path,
+            error: format!("Expected {N} items, but found {}", list.len()),
+        });
+    }
+    let mut result: [Option<Item>; N] = std::array::from_fn(|_| None);
+    for (idx, tag) in list.iter().enumerate() {
+        result[idx] = parse_optional_item(tag, &format!("{list_path}/[{idx}]"))?;
+    }
+    return Ok(Some(result));
+}
+
+fn item_list_to_nbt(items: &[Option<Item>]) -> Value {
+    return Value::List(items.iter().map(optional_item_to_nbt).collect());
+}
+
+impl HangingFields {
+    fn from_nbt(nbt: &mut HashMap<String, Value>, facing_key: &str, tag_path: &str) -> Result<HangingFields, Error> {
+        let result = HangingFields {
+            facing: unwrap_opt_i8(nbt, facing_key, tag_path)?,
+            tile_pos: [unwrap_opt_i32(nbt, "TileX", tag_path)?,
+                unwrap_opt_i32(nbt, "TileY", tag_path)?,
+                unwrap_opt_i32(nbt, "TileZ", tag_path)?],
+        };
+        for key in [facing_key, "TileX", "TileY", "TileZ"] {
+            nbt.remove(key);
+        }
+        return Ok(result);
+    }
+
+    fn to_nbt(&self, nbt: &mut HashMap<String, Value>, facing_key: &str) {
+        nbt.insert(facing_key.to_string(), Value::Byte(self.facing));
+        for (key, value) in ["TileX", "TileY", "TileZ"].iter().zip(self.tile_pos) {
+            nbt.insert(key.to_string(), Value::Int(value));
+        }
+    }
+}
+
+impl ItemFrame {
+    pub fn from_nbt(mut nbt: HashMap<String, Value>, tag_path: &str) -> Result<ItemFrame, Error> {
+        let nbt = &mut nbt;
+        let item = match nbt.remove("Item") {
+            Some(tag) => parse_optional_item(&tag, &format!("{tag_path}/Item"))?,
+            None => None,
+        };
+        return Ok(ItemFrame {
+            id: remove_id(nbt, tag_path)?,
+            entity_fields: EntityFields::from_nbt(nbt, tag_path)?,
+            hanging_fields: HangingFields::from_nbt(nbt, "Facing", tag_path)?,
+            item,
+            item_drop_chance: remove_opt(nbt, "ItemDropChance", tag_path, unwrap_opt_f32)?,
+            item_rotation: remove_opt(nbt, "ItemRotation", tag_path, unwrap_opt_i8)?,
+            fixed: remove_opt_bool(nbt, "Fixed", tag_path)?,
+            invisible: remove_opt_bool(nbt, "Invisible", tag_path)?,
+            unparsed_tags: std::mem::take(nbt),
+        });
+    }
+
+    pub fn to_nbt(&self) -> HashMap<String, Value> {
+        let mut nbt = self.unparsed_tags.clone();
+        nbt.insert("id".to_string(), Value::String(self.id.clone()));
+        self.entity_fields.to_nbt(&mut nbt);
+        self.hanging_fields.to_nbt(&mut nbt, "Facing");
+        if let Some(item) = &self.item {
+            nbt.insert("Item".to_string(), Value::Compound(item.to_nbt()));
+        }
+        insert_opt(&mut nbt, "ItemDropChance", self.item_drop_chance, Value::Float);
+        insert_opt(&mut nbt, "ItemRotation", self.item_rotation, Value::Byte);
+        insert_opt(&mut nbt, "Fixed", self.fixed, bool_to_byte);
+        insert_opt(&mut nbt, "Invisible", self.invisible, bool_to_byte);
+        return nbt;
+    }
+}
+
+impl Painting {
+    /// Tags before 1.19 (`Motive` and `Facing`) are also accepted, and written back in the names
+    /// they are read with.
+    pub fn from_nbt(mut nbt: HashMap<String, Value>, tag_path: &str) -> Result<Painting, Error> {
+        let nbt = &mut nbt;
+        let legacy_keys = !nbt.contains_key("variant") && nbt.contains_key("Motive");
+        let (variant_key, facing_key) = Self::keys(legacy_keys);
+        let variant = unwrap_opt_string(nbt, variant_key, tag_path)?.clone();
+        nbt.remove(variant_key);
+        return Ok(Painting {
+            id: remove_id(nbt, tag_path)?,
+            entity_fields: EntityFields::from_nbt(nbt, tag_path)?,
+            hanging_fields: HangingFields::from_nbt(nbt, facing_key, tag_path)?,
+            variant,
+            legacy_keys,
+            unparsed_tags: std::mem::take(nbt),
+        });
+    }
+
+    pub fn to_nbt(&self) -> HashMap<String, Value> {
+        let (variant_key, facing_key) = Self::keys(self.legacy_keys);
+        let mut nbt = self.unparsed_tags.clone();
+        nbt.insert("id".to_string(), Value::String(self.id.clone()));
+        self.entity_fields.to_nbt(&mut nbt);
+        self.hanging_fields.to_nbt(&mut nbt, facing_key);
+        nbt.insert(variant_key.to_string(), Value::String(self.variant.clone()));
+        return nbt;
+    }
+
+    /// Keys of variant and facing
+    fn keys(legacy_keys: bool) -> (&'static str, &'static str) {
+        return if legacy_keys { ("Motive", "Facing") } else { ("variant", "facing") };
+    }
+}
+
+/// Entities in chunks and schematics have no data version, so it is deduced from the keys of
+/// attributes, which are lowercase since 24w09a (1.20.5).
+fn data_version_of_attributes(nbt: &HashMap<String, Value>) -> i32 {
+    if nbt.contains_key("attributes") {
+        return DataVersion::Snapshot_24w09a as i32;
+    }
+    return DataVersion::Java_1_20_4 as i32;
+}
+
+impl ArmorStand {
+    pub fn from_nbt(mut nbt: HashMap<String, Value>, tag_path: &str) -> Result<ArmorStand, Error> {
+        let nbt = &mut nbt;
+        return Ok(ArmorStand {
+            id: remove_id(nbt, tag_path)?,
+            entity_fields: EntityFields::from_nbt(nbt, tag_path)?,
+            mob_fields: MobFields::from_nbt(nbt, tag_path, data_version_of_attributes(nbt))?,
+            armor_items: remove_item_list(nbt, "ArmorItems", tag_path)?,
+            hand_items: remove_item_list(nbt, "HandItems", tag_path)?,
+            disabled_slots: remove_opt(nbt, "DisabledSlots", tag_path, unwrap_opt_i32)?,
+            invisible: remove_opt_bool(nbt, "Invisible", tag_path)?,
+            marker: remove_opt_bool(nbt, "Marker", tag_path)?,
+            no_base_plate: remove_opt_bool(nbt, "NoBasePlate", tag_path)?,
+            show_arms: remove_opt_bool(nbt, "ShowArms", tag_path)?,
+            small: remove_opt_bool(nbt, "Small", tag_path)?,
+            unparsed_tags: std::mem::take(nbt),
+        });
+    }
+
+    pub fn to_nbt(&self) -> HashMap<String, Value> {
+        let mut nbt = self.unparsed_tags.clone();
+        nbt.insert("id".to_string(), Value::String(self.id.clone()));
+        self.entity_fields.to_nbt(&mut nbt);
+        self.mob_fields.to_nbt(&mut nbt);
+        if let Some(items) = &self.armor_items {
+            nbt.insert("ArmorItems".to_string(), item_list_to_nbt(items));
+        }
+        if let Some(items) = &self.hand_items {
+            nbt.insert("HandItems".to_string(), item_list_to_nbt(items));
+        }
+        insert_opt(&mut nbt, "DisabledSlots", self.disabled_slots, Value::Int);
+        insert_opt(&mut nbt, "Invisible", self.invisible, bool_to_byte);
+        insert_opt(&mut nbt, "Marker", self.marker, bool_to_byte);
+        insert_opt(&mut nbt, "NoBasePlate", self.no_base_plate, bool_to_byte);
+        insert_opt(&mut nbt, "ShowArms", self.show_arms, bool_to_byte);
+        insert_opt(&mut nbt, "Small", self.small, bool_to_byte);
+        return nbt;
+    }
+}
+
+impl Minecart {
+    pub fn from_nbt(mut nbt: HashMap<String, Value>, tag_path: &str) -> Result<Minecart, Error> {
+        let nbt = &mut nbt;
+        // the display block is parsed only if it's shown, otherwise the tags are kept as unparsed
+        let mut display_block = None;
+        if matches!(nbt.get("CustomDisplayTile"), Some(Value::Byte(1))) && nbt.contains_key("DisplayState") {
+            let state = remove_compound(nbt, "DisplayState", tag_path)?.unwrap();
+            display_block = Some(parse_block(&state, &format!("{tag_path}/DisplayState"))?);
+            nbt.remove("CustomDisplayTile");
+        }
+        let items = match nbt.contains_key("Items") {
+            true => Some(Inventory::from_nbt(&remove_list(nbt, "Items", tag_path)?, &format!("{tag_path}/Items"))?),
+            false => None,
+        };
+        return Ok(Minecart {
+            id: remove_id(nbt, tag_path)?,
+            entity_fields: EntityFields::from_nbt(nbt, tag_path)?,
+            display_block,
+            display_offset: remove_opt(nbt, "DisplayOffset", tag_path, unwrap_opt_i32)?,
+            items,
+            unparsed_tags: std::mem::take(nbt),
+        });
+    }
+
+    pub fn to_nbt(&self) -> HashMap<String, Value> {
+        let mut nbt = self.unparsed_tags.clone();
+        nbt.insert("id".to_string(), Value::String(self.id.clone()));
+        self.entity_fields.to_nbt(&mut nbt);
+        if let Some(block) = &self.display_block {
+            nbt.insert("CustomDisplayTile".to_string(), Value::Byte(1));
+            nbt.insert("DisplayState".to_string(), Value::Compound(block.to_nbt()));
+        }
+        insert_opt(&mut nbt, "DisplayOffset", self.display_offset, Value::Int);
+        if let Some(items) = &self.items {
+            nbt.insert("Items".to_string(), Value::List(items.to_nbt()));
+        }
+        return nbt;
+    }
+}
+
+impl Mob {
+    pub fn from_nbt(mut nbt: HashMap<String, Value>, tag_path: &str) -> Result<Mob, Error> {
+        let nbt = &mut nbt;
+        return Ok(Mob {
+            id: remove_id(nbt, tag_path)?,
+            entity_fields: EntityFields::from_nbt(nbt, tag_path)?,
+            mob_fields: MobFields::from_nbt(nbt, tag_path, data_version_of_attributes(nbt))?,
+            unparsed_tags: std::mem::take(nbt),
+        });
+    }
+
+    pub fn to_nbt(&self) -> HashMap<String, Value> {
+        let mut nbt = self.unparsed_tags.clone();
+        nbt.insert("id".to_string(), Value::String(self.id.clone()));
+        self.entity_fields.to_nbt(&mut nbt);
+        self.mob_fields.to_nbt(&mut nbt);
+        return nbt;
+    }
+}
+
+impl OtherEntity {
+    pub fn from_nbt(mut nbt: HashMap<String, Value>, tag_path: &str) -> Result<OtherEntity, Error> {
+        let nbt = &mut nbt;
+        return Ok(OtherEntity {
+            id: remove_id(nbt, tag_path)?,
+            entity_fields: EntityFields::from_nbt(nbt, tag_path)?,
+            unparsed_tags: std::mem::take(nbt),
+        });
+    }
+
+    pub fn to_nbt(&self) -> HashMap<String, Value> {
+        let mut nbt = self.unparsed_tags.clone();
+        nbt.insert("id".to_string(), Value::String(self.id.clone()));
+        self.entity_fields.to_nbt(&mut nbt);
+        return nbt;
+    }
+}
+
+impl EntityBox {
+    /// Parse an entity according to its id. Living entities of other kinds are parsed as `Mob`,
+    /// and the rest as `OtherEntity`.
+    pub fn from_nbt(nbt: HashMap<String, Value>, tag_path: &str) -> Result<EntityBox, Error> {
+        if !nbt.contains_key("id") && nbt.contains_key("playerGameType") {
+            return Ok(EntityBox::new(Box::new(Player::from_nbt(nbt, tag_path)?)));
+        }
+        let id = unwrap_opt_string(&nbt, "id", tag_path)?;
+        let short_id = id.strip_prefix("minecraft:").unwrap_or(id);
+        let entity: Box<dyn GetEntityMut> = match short_id {
+            "item_frame" | "glow_item_frame" => Box::new(ItemFrame::from_nbt(nbt, tag_path)?),
+            "painting" => Box::new(Painting::from_nbt(nbt, tag_path)?),
+            "armor_stand" => Box::new(ArmorStand::from_nbt(nbt, tag_path)?),
+            _ if short_id.ends_with("minecart") => Box::new(Minecart::from_nbt(nbt, tag_path)?),
+            // items and experience orbs also have `Health`, but in short
+            _ if matches!(nbt.get("Health"), Some(Value::Float(_))) => Box::new(Mob::from_nbt(nbt, tag_path)?),
+            _ => Box::new(OtherEntity::from_nbt(nbt, tag_path)?),
+        };
+        return Ok(EntityBox::new(entity));
+    }
+}
+
+impl EntityBox {
+    /// Hanging fields of item frames and paintings
+    pub fn hanging_fields(&self) -> Option<&HangingFields> {
+        if let Some(frame) = self.downcast_ref::<ItemFrame>() {
+            return Some(&frame.hanging_fields);
+        }
+        return self.downcast_ref::<Painting>().map(|p| &p.hanging_fields);
+    }
+
+    pub fn hanging_fields_mut(&mut self) -> Option<&mut HangingFields> {
+        if self.downcast_ref::<ItemFrame>().is_some() {
+            return self.downcast_mut::<ItemFrame>().map(|f| &mut f.hanging_fields);
+        }
+        return self.downcast_mut::<Painting>().map(|p| &mut p.hanging_fields);
+    }
+
+    /// The block that a hanging entity is attached to, or the block containing `pos` for other
+    /// entities. Leash knots keep `TileX`, `TileY` and `TileZ` in unparsed tags.
+    pub fn block_pos(&self) -> [i32; 3] {
+        if let Some(hanging) = self.hanging_fields() {
+            return hanging.tile_pos;
+        }
+        let tags = self.unparsed_tags();
+        if let (Some(Value::Int(x)), Some(Value::Int(y)), Some(Value::Int(z)))
+            = (tags.get("TileX"), tags.get("TileY"), tags.get("TileZ")) {
+            return [*x, *y, *z];
+        }
+        let pos = self.pos();
+        return [pos[0].floor() as i32, pos[1].floor() as i32, pos[2].floor() as i32];
+    }
+
+    /// Add adder to position, and also the block that hanging entities are attached to
+    pub fn pos_shift(&mut self, adder: [i32; 3]) {
+        let pos = &mut self.entity_fields_mut().pos;
+        for dim in 0..3 {
+            pos[dim] += adder[dim] as f64;
+        }
+        if let Some(hanging) = self.hanging_fields_mut() {
+            for dim in 0..3 {
+                hanging.tile_pos[dim] += adder[dim];
+            }
+        }
+        for (key, dim) in [("TileX", 0), ("TileY", 1), ("TileZ", 2)] {
+            if let Some(Value::Int(val)) = self.unparsed_tags_mut().get_mut(key) {
+                *val += adder[dim];
+            }
+        }
+    }
+}
@@ -17,15 +17,18 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
 use std::collections::HashMap;
-use std::fmt::{Display, Formatter};
+use std::fmt::{Debug, Display, Formatter};
 use std::ops::Range;
+use std::sync::Mutex;
 use fastnbt::{ByteArray, IntArray, LongArray, Value};
 use serde::de::{StdError};
 // use serde::Deserializer;
 use strum::Display;
+use crate::biome::Biome;
 use crate::block::{Block, BlockIdParseError};
 use crate::item::Item;
 use crate::old_block::OldBlockParseError;
+use crate::player::DimensionId;
 use crate::region::Region;
 use crate::schem::common::{format_range, format_size};
 use crate::schem::id_of_nbt_tag;
@@ -95,7 +98,13 @@ pub enum Error {
     UnrecognisedExtension {
         extension: String,
     },
+    UnrecognisedFormat {
+        detail: String,
+    },
     //write error
+    BlockWithoutNumberId {
+        block: String,
+    },
     NBTWriteError(fastnbt::error::Error),
     NegativeSize { size: [i32; 3], region_name: String },
     BlockIndexOutOfRangeWriting { r_pos: [i32; 3], block_index: u16, max_index: u16 },
@@ -120,7 +129,14 @@ pub enum Error {
     InvalidMCACompressType {
         compress_label: u8,
     },
+    InvalidLZ4Block {
+        detail: String,
+    },
+    UnsupportedCustomCompression {
+        algorithm: String,
+    },
     IOReadError(std::io::Error),
+    IOWriteError(std::io::Error),
     SevenZipDecompressError(sevenz_rust::Error),
     NoSuchFile {
         filename: String,
@@ -149,17 +165,36 @@ pub enum Error {
         exception_value: Range<i32>,
     },
     IncorrectYRangeInChunk {
-        dimension_id: i32,
+        dimension_id: DimensionId,
         dimension_y_range: Range<i32>,
         exception_chunk_x: i32,
         exception_chunk_z: i32,
         exception_value: Range<i32>,
     },
+    MissingChunk {
+        chunk_x: i32,
+        chunk_z: i32,
+    },
+    BlockPosOutOfDimension {
+        pos: [i32; 3],
+        dimension_y_range: Range<i32>,
+    },
     MultipleItemsInOneSlot {
         slot: i8,
         former: (Item, String),
         latter: (Item, String),
     },
+    InvalidPNG {
+        detail: String,
+    },
+    ImageSizeMismatch {
+        size: [u32; 2],
+        expected_size: [u32; 2],
+    },
+    InvalidJson {
+        filename: String,
+        detail: String,
+    },
     CustomError(String),
 }
 
@@ -197,7 +232,11 @@ impl Display for Error {
             => write!(f, "Invalid number id at {tag_path}, detail: {detail}"),
             Error::UnrecognisedExtension { extension }
             => write!(f, "Unrecognised extension {extension}, can not deduce schematic format from filename extension, try loading with explicit format."),
+            Error::UnrecognisedFormat { detail }
+            => write!(f, "Can not deduce schematic format from content, detail: {detail}"),
 
+            Error::BlockWithoutNumberId { block }
+            => write!(f, "Block \"{block}\" has no number id, it can not be saved in formats before 1.13"),
             Error::NBTWriteError(err) => write!(f, "Failed to write nbt, detail: {}", err),
             Error::NegativeSize { size, region_name }
             => write!(f, "region \"{}\" has negative size: {}", region_name, format_size(size)),
@@ -221,9 +260,15 @@ impl Display for Error {
             , {}) segments, the file has {total_segments} segments, the range is invalid",
                       offset_by_segment + num_segments),
             Error::InvalidMCACompressType { compress_label }
-            => write!(f, "Invalid compress type {compress_label}, valid values: [1, 2, 3, 128, 129, 130]"),
+            => write!(f, "Invalid compress type {compress_label}, valid values: 1 (gzip), 2 (zlib), 3 (none), 4 (lz4) and 127 (custom), plus 128 if the chunk is stored in an external .mcc file"),
+            Error::InvalidLZ4Block { detail }
+            => write!(f, "Invalid LZ4 block in chunk data, detail: {detail}"),
+            Error::UnsupportedCustomCompression { algorithm }
+            => write!(f, "Chunk data is compressed by custom algorithm \"{algorithm}\", which is not supported"),
             Error::IOReadError(e)
             => write!(f, "IOReadError, detail: {e}"),
+            Error::IOWriteError(e)
+            => write!(f, "IOWriteError, detail: {e}"),
             Error::SevenZipDecompressError(e7z)
             => write!(f, "7z decompress failed, detail: {e7z}"),
             Error::NoSuchFile { filename, expected_to_exist_in }
@@ -240,8 +285,18 @@ impl Display for Error {
             => write!(f, "Chunk ({exception_chunk_x}, {exception_chunk_z}) has different y range ({}) different from majority value({})", format_range(exception_value), format_range(majority_y_range)),
             Error::IncorrectYRangeInChunk { dimension_id, dimension_y_range, exception_chunk_x, exception_chunk_z, exception_value }
             => write!(f, "The y range of dimension {dimension_id} is {}, but y range of chunk ({exception_chunk_x}, {exception_chunk_z}) is {}", format_range(dimension_y_range), format_range(exception_value)),
+            Error::MissingChunk { chunk_x, chunk_z }
+            => write!(f, "Chunk ({chunk_x}, {chunk_z}) doesn't exist in the dimension"),
+            Error::BlockPosOutOfDimension { pos, dimension_y_range }
+            => write!(f, "Block pos {} is outside the dimension, y should be in range {}", format_size(pos), format_range(dimension_y_range)),
             Error::MultipleItemsInOneSlot { slot, former, latter }
             => write!(f, "Found multiple items in slot {slot} when parsing inventory, the former is {:?}, defined at {}, the latter is {:?}, defined at {}", former.0, former.1, latter.0, latter.1),
+            Error::InvalidPNG { detail }
+            => write!(f, "Invalid png image, detail: {detail}"),
+            Error::ImageSizeMismatch { size, expected_size }
+            => write!(f, "Image size is {}x{}, but it should be {}x{}", size[0], size[1], expected_size[0], expected_size[1]),
+            Error::InvalidJson { filename, detail }
+            => write!(f, "Invalid json file {filename}, detail: {detail}"),
             Error::CustomError(s)
             => write!(f, "Custom error : \"{s}\"")
         }
@@ -397,6 +452,41 @@ pub fn unwrap_opt_long_array_remove(nbt: &mut HashMap<String, Value>, key: &str,
     Ok(unwrap_opt_tag!(nbt.remove(key),LongArray,LongArray::new(vec![]),format!("{nbt_path}/{key}")))
 }
 
+/// Functions like `unwrap_opt_i32`
+pub type TagParser<T> = fn(&HashMap<String, Value>, &str, &str) -> Result<T, Error>;
+
+/// Parse `key` with `parse` and remove it if it exists, otherwise returns `default`. Useful for
+/// optional tags, while the remaining tags can be kept as unparsed.
+pub fn remove_opt_or<T>(nbt: &mut HashMap<String, Value>, key: &str, nbt_path: &str, default: T, parse: TagParser<T>) -> Result<T, Error> {
+    if !nbt.contains_key(key) {
+        return Ok(default);
+    }
+    let value = parse(nbt, key, nbt_path)?;
+    nbt.remove(key);
+    return Ok(value);
+}
+
+/// Like `remove_opt_or`, for booleans stored as byte
+pub fn remove_opt_bool_or(nbt: &mut HashMap<String, Value>, key: &str, nbt_path: &str, default: bool) -> Result<bool, Error> {
+    return Ok(remove_opt_or(nbt, key, nbt_path, default as i8, unwrap_opt_i8)? != 0);
+}
+
+/// Parse `key` with `parse` and remove it if it exists. Useful for optional tags that are written
+/// back only if they exist.
+pub fn remove_opt<T>(nbt: &mut HashMap<String, Value>, key: &str, nbt_path: &str, parse: TagParser<T>) -> Result<Option<T>, Error> {
+    if !nbt.contains_key(key) {
+        return Ok(None);
+    }
+    let value = parse(nbt, key, nbt_path)?;
+    nbt.remove(key);
+    return Ok(Some(value));
+}
+
+/// Like `remove_opt`, for booleans stored as byte
+pub fn remove_opt_bool(nbt: &mut HashMap<String, Value>, key: &str, nbt_path: &str) -> Result<Option<bool>, Error> {
+    return Ok(remove_opt(nbt, key, nbt_path, unwrap_opt_i8)?.map(|b| b != 0));
+}
+
 
 // pub struct NBTWithPath<'nbt> {
 //     pub nbt: Option<&'nbt Value>,
@@ -566,17 +656,15 @@ pub fn unwrap_opt_long_array_remove(nbt: &mut HashMap<String, Value>, key: &str,
 // }
 
 
-/// Not used now.
+/// Result of an `ErrorHandler` trying to fix an error
 #[repr(u8)]
 #[derive(Debug, Display)]
-#[allow(dead_code)]
 pub enum ErrorHandleResult<T> {
     HandledWithoutWarning(T),
     HandledWithWarning(T),
     NotHandled,
 }
 
-#[allow(dead_code)]
 impl<T> ErrorHandleResult<T> {
     pub fn has_value(&self) -> bool {
         return if let ErrorHandleResult::NotHandled = self {
@@ -599,44 +687,136 @@ impl<T> ErrorHandleResult<T> {
     }
 }
 
-/// Not used now.
-#[allow(dead_code)]
+/// How to fix a block entity or pending tick whose position is out of the region
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BlockPosOutOfRangeFixMethod {
+    /// Drop the block entity or pending tick
     IgnoreThisBlock,
+    /// Move it to this position, which must be in the region
     FixPos([i32; 3]),
 }
 
-/// Not used now.
-pub trait ErrorHandler {
-    // returns the fixed block index
-    fn fix_block_index_out_of_range(
-        _region: &mut Region,
-        _error: &Error) -> ErrorHandleResult<u16> {
+/// Decides whether errors in damaged schematics can be fixed while loading. Each `*LoadOption` has
+/// an error handler, which is `StrictErrorHandler` by default.
+///
+/// The default implementation of every method doesn't handle anything. If an error is fixed with
+/// `HandledWithWarning`, the error is passed to `report_warning`.
+pub trait ErrorHandler: Debug + Send + Sync {
+    /// Returns the block index to replace an out-of-range one, the palette of `region` is complete
+    /// and can be appended to.
+    fn fix_block_index_out_of_range(&self, _region: &mut Region, _error: &Error) -> ErrorHandleResult<u16> {
+        return ErrorHandleResult::NotHandled;
+    }
 
+    /// Fix block entities and pending ticks out of the region
+    fn fix_block_pos_out_of_range(&self, _region: &mut Region, _error: &Error) -> ErrorHandleResult<BlockPosOutOfRangeFixMethod> {
         return ErrorHandleResult::NotHandled;
     }
 
-    fn fix_block_pos_out_of_range(_region: &mut Region, _error: &Error) -> ErrorHandleResult<BlockPosOutOfRangeFixMethod> {
+    /// Returns the block to replace a palette entry with invalid id or number id
+    fn fix_invalid_block_id(&self, _error: &Error) -> ErrorHandleResult<Block> {
         return ErrorHandleResult::NotHandled;
     }
 
-    fn fix_invalid_block_id(_region: &mut Region, _error: &Error) -> ErrorHandleResult<Block> {
+    /// Returns the biome to replace an unknown one, like biomes from datapacks
+    fn fix_invalid_biome(&self, _error: &Error) -> ErrorHandleResult<Biome> {
         return ErrorHandleResult::NotHandled;
     }
+
+    /// Called with every error that is fixed with warning
+    fn report_warning(&self, _warning: Error) {}
 }
 
-/// Not used now.
+impl dyn ErrorHandler + '_ {
+    fn resolve<T>(&self, result: ErrorHandleResult<T>, error: Error) -> Result<T, Error> {
+        return match result {
+            ErrorHandleResult::NotHandled => Err(error),
+            ErrorHandleResult::HandledWithoutWarning(val) => Ok(val),
+            ErrorHandleResult::HandledWithWarning(val) => {
+                self.report_warning(error);
+                Ok(val)
+            }
+        };
+    }
+
+    pub(crate) fn handle_block_index_out_of_range(&self, region: &mut Region, error: Error) -> Result<u16, Error> {
+        let result = self.fix_block_index_out_of_range(region, &error);
+        return self.resolve(result, error);
+    }
+
+    /// Returns the fixed position, or `None` if the block entity or tick should be dropped
+    pub(crate) fn handle_block_pos_out_of_range(&self, region: &mut Region, error: Error) -> Result<Option<[i32; 3]>, Error> {
+        let result = self.fix_block_pos_out_of_range(region, &error);
+        return match self.resolve(result, error)? {
+            BlockPosOutOfRangeFixMethod::IgnoreThisBlock => Ok(None),
+            BlockPosOutOfRangeFixMethod::FixPos(pos) => Ok(Some(pos)),
+        };
+    }
+
+    /// Only `Error::InvalidBlockId` is passed to the handler, other errors are returned directly.
+    pub(crate) fn handle_invalid_block_id(&self, error: Error) -> Result<Block, Error> {
+        if let Error::InvalidBlockId { .. } | Error::InvalidBlockNumberId { .. } = error {
+            let result = self.fix_invalid_block_id(&error);
+            return self.resolve(result, error);
+        }
+        return Err(error);
+    }
+
+    pub(crate) fn handle_invalid_biome(&self, error: Error) -> Result<Biome, Error> {
+        if let Error::InvalidBiome { .. } = error {
+            let result = self.fix_invalid_biome(&error);
+            return self.resolve(result, error);
+        }
+        return Err(error);
+    }
+}
+
+/// Doesn't fix any error, so loading fails on the first one. Used by all load options by default.
+#[derive(Debug, Default)]
 pub struct StrictErrorHandler {}
 
+impl StrictErrorHandler {
+    pub fn new() -> StrictErrorHandler {
+        return StrictErrorHandler {};
+    }
+}
+
 impl ErrorHandler for StrictErrorHandler {}
 
-/// Not used now.
-pub struct DefaultErrorHandler {}
+/// Loads damaged schematics as much as possible: out-of-range block indices are replaced with air,
+/// blocks with invalid id or number id are replaced with `placeholder_block`, unknown biomes are
+/// replaced with the default biome, and block entities or pending ticks out of the region are
+/// dropped. Every fixed error is recorded as a warning.
+#[derive(Debug)]
+pub struct DefaultErrorHandler {
+    /// Replaces blocks with invalid id or number id, structure void by default.
+    pub placeholder_block: Block,
+    warnings: Mutex<Vec<Error>>,
+}
+
+impl Default for DefaultErrorHandler {
+    fn default() -> Self {
+        return DefaultErrorHandler::new();
+    }
+}
+
+impl DefaultErrorHandler {
+    pub fn new() -> DefaultErrorHandler {
+        return DefaultErrorHandler {
+            placeholder_block: Block::structure_void(),
+            warnings: Mutex::new(Vec::new()),
+        };
+    }
+
+    /// Errors fixed so far, in the order they were found
+    pub fn take_warnings(&self) -> Vec<Error> {
+        let mut warnings = self.warnings.lock().unwrap();
+        return std::mem::take(&mut *warnings);
+    }
+}
 
 impl ErrorHandler for DefaultErrorHandler {
-    fn fix_block_index_out_of_range(
-        region: &mut Region,
-        error: &Error) -> ErrorHandleResult<u16> {
+    fn fix_block_index_out_of_range(&self, region: &mut Region, error: &Error) -> ErrorHandleResult<u16> {
         if let Error::BlockIndexOutOfRange { .. } = error {
             let air_id = region.find_or_append_to_palette(&Block::air());
             return ErrorHandleResult::HandledWithWarning(air_id);
@@ -644,11 +824,28 @@ impl ErrorHandler for DefaultErrorHandler {
         return ErrorHandleResult::NotHandled;
     }
 
-    fn fix_block_pos_out_of_range(_region: &mut Region, _error: &Error) -> ErrorHandleResult<BlockPosOutOfRangeFixMethod> {
-        return ErrorHandleResult::HandledWithWarning(BlockPosOutOfRangeFixMethod::IgnoreThisBlock);
+    fn fix_block_pos_out_of_range(&self, _region: &mut Region, error: &Error) -> ErrorHandleResult<BlockPosOutOfRangeFixMethod> {
+        if let Error::BlockPosOutOfRange { .. } = error {
+            return ErrorHandleResult::HandledWithWarning(BlockPosOutOfRangeFixMethod::IgnoreThisBlock);
+        }
+        return ErrorHandleResult::NotHandled;
+    }
+
+    fn fix_invalid_block_id(&self, error: &Error) -> ErrorHandleResult<Block> {
+        if let Error::InvalidBlockId { .. } | Error::InvalidBlockNumberId { .. } = error {
+            return ErrorHandleResult::HandledWithWarning(self.placeholder_block.clone());
+        }
+        return ErrorHandleResult::NotHandled;
     }
 
-    // fn fix_invalid_block_id(_region: &mut Region, _error: &LoadError) -> ErrorHandleResult<Block> {
-    //     return ErrorHandleResult::NotHandled;
-    // }
-}
\ No newline at end of file
+    fn fix_invalid_biome(&self, error: &Error) -> ErrorHandleResult<Biome> {
+        if let Error::InvalidBiome { .. } = error {
+            return ErrorHandleResult::HandledWithWarning(Biome::default());
+        }
+        return ErrorHandleResult::NotHandled;
+    }
+
+    fn report_warning(&self, warning: Error) {
+        self.warnings.lock().unwrap().push(warning);
+    }
+}
@@ -2,33 +2,69 @@ use std::collections::{BTreeMap, HashMap};
 use fastnbt::Value;
 use serde::Deserialize;
 use crate::{Error, unwrap_tag};
-use crate::error::{unwrap_opt_i8, unwrap_opt_string};
+use crate::error::{unwrap_opt_i32, unwrap_opt_i8, unwrap_opt_string};
 use crate::schem::id_of_nbt_tag;
 //use crate::error::NBTWithPath;
 
 #[derive(Debug, Clone, Default, Deserialize)]
 pub struct Item {
     #[serde(rename = "Count")]
-    pub count: i8,
+    pub count: i32,
     pub id: String,
+    /// `tag` of items before 1.20.5
     #[serde(rename = "tag")]
     pub tags: HashMap<String, Value>,
+    /// `components` of items since 24w09a (1.20.5), `None` if the item is in the old format. An
+    /// item is written in the format it was read with.
+    #[serde(default)]
+    pub components: Option<HashMap<String, Value>>,
+    /// Tags that are not parsed, like `Damage` of items before 1.13. They are kept to be written
+    /// back.
+    #[serde(skip)]
+    pub unparsed_tags: HashMap<String, Value>,
 }
 
 impl Item {
+    /// Parse an item in either format. Items before 1.20.5 have a byte `Count` and `tag`, and
+    /// newer items have an int `count` (1 if missing) and `components`.
     pub fn from_nbt(nbt: &HashMap<String, Value>, tag_path: &str) -> Result<Item, Error> {
-        let count = unwrap_opt_i8(&nbt, "Count", tag_path)?;
         let id = unwrap_opt_string(&nbt, "id", tag_path)?.clone();
-        let tags = if let Some(t) = nbt.get("tag") {
-            unwrap_tag!(t,Compound,HashMap::new(),format!("{tag_path}/tag")).clone()
+        let mut unparsed_tags = nbt.clone();
+        for key in ["id", "Count", "tag", "count", "components"] {
+            unparsed_tags.remove(key);
+        }
+        if nbt.contains_key("Count") {
+            let count = unwrap_opt_i8(&nbt, "Count", tag_path)? as i32;
+            let tags = if let Some(t) = nbt.get("tag") {
+                unwrap_tag!(t,Compound,HashMap::new(),format!("{tag_path}/tag")).clone()
+            } else {
+                HashMap::new()
+            };
+            return Ok(Item {
+                count,
+                id,
+                tags,
+                components: None,
+                unparsed_tags,
+            });
+        }
+
+        let count = if nbt.contains_key("count") {
+            unwrap_opt_i32(&nbt, "count", tag_path)?
+        } else {
+            1
+        };
+        let components = if let Some(c) = nbt.get("components") {
+            unwrap_tag!(c,Compound,HashMap::new(),format!("{tag_path}/components")).clone()
         } else {
             HashMap::new()
         };
-
         return Ok(Item {
             count,
             id,
-            tags,
+            tags: HashMap::new(),
+            components: Some(components),
+            unparsed_tags,
         });
 
         // let nbt = Value::Compound(nbt);
@@ -39,12 +75,28 @@ impl Item {
         //
         // return Item::deserialize(deserializer);
     }
+
+    pub fn to_nbt(&self) -> HashMap<String, Value> {
+        let mut nbt = self.unparsed_tags.clone();
+        nbt.insert("id".to_string(), Value::String(self.id.clone()));
+        if let Some(components) = &self.components {
+            nbt.insert("count".to_string(), Value::Int(self.count));
+            if !components.is_empty() {
+                nbt.insert("components".to_string(), Value::Compound(components.clone()));
+            }
+            return nbt;
+        }
+        nbt.insert("Count".to_string(), Value::Byte(self.count as i8));
+        if !self.tags.is_empty() {
+            nbt.insert("tag".to_string(), Value::Compound(self.tags.clone()));
+        }
+        return nbt;
+    }
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct Inventory(pub BTreeMap<i8, Item>);
 
-#[allow(dead_code)]
 impl Inventory {
     pub fn from_nbt(nbt: &[Value], tag_path: &str) -> Result<Inventory, Error> {
         let mut result = BTreeMap::new();
@@ -52,8 +104,9 @@ impl Inventory {
         for (idx, nbt) in nbt.iter().enumerate() {
             let tag_path = format!("{tag_path}/[{idx}]");
             let nbt = unwrap_tag!(nbt,Compound,HashMap::new(),tag_path);
-            let item = Item::from_nbt(nbt, &tag_path)?;
+            let mut item = Item::from_nbt(nbt, &tag_path)?;
             let slot = unwrap_opt_i8(nbt, "Slot", &tag_path)?;
+            item.unparsed_tags.remove("Slot");
             if result.contains_key(&slot) {
                 return Err(Error::MultipleItemsInOneSlot {
                     slot,
@@ -66,4 +119,15 @@ impl Inventory {
         }
         return Ok(Self(result));
     }
+
+    /// Items with `Slot`, like `Inventory` of players and `Items` of chests
+    pub fn to_nbt(&self) -> Vec<Value> {
+        let mut result = Vec::with_capacity(self.0.len());
+        for (slot, item) in &self.0 {
+            let mut nbt = item.to_nbt();
+            nbt.insert("Slot".to_string(), Value::Byte(*slot));
+            result.push(Value::Compound(nbt));
+        }
+        return result;
+    }
 }
\ No newline at end of file
@@ -53,19 +53,28 @@ pub mod region;
 pub mod schem;
 
 pub mod world;
+/// Render schematics to images on CPU
+pub mod render;
+/// Filled maps stored in `map_<id>.dat`
+pub mod map;
 
 mod c_ffi;
-mod biome;
+/// Biomes in Minecraft
+pub mod biome;
 mod raid;
-mod player;
-mod item;
+/// Players, and the entity and mob fields they share
+pub mod player;
+/// Items and inventories
+pub mod item;
+/// Typed entities, like item frames, armor stands and mobs
+pub mod entity;
 
 /// `Block` is a type of block with namespace and properties(aka attributes) in MC.
 pub type Block = block::Block;
 /// Enumerate common blocks
 pub type CommonBlock = block::CommonBlock;
-/// An entity in MC, like zombie, minecart, etc.
-pub type Entity = region::Entity;
+/// An entity in MC, like zombie, minecart, etc. Types are in `entity`.
+pub type Entity = player::EntityBox;
 /// Block entity(also known as tile entity) in MC, like chest, furnace, etc.
 pub type BlockEntity = region::BlockEntity;
 /// A tick waiting to be processed
@@ -94,6 +103,12 @@ pub type WorldEdit13LoadOption = schem::WorldEdit13LoadOption;
 pub type WorldEdit13SaveOption = schem::WorldEdit13SaveOption;
 /// Options to load litematica
 pub type WorldEdit12LoadOption = schem::WorldEdit12LoadOption;
+/// Options to save world edit 1.12-
+pub type WorldEdit12SaveOption = schem::WorldEdit12SaveOption;
+/// Options to convert schematic to another data version
+pub type DataVersionConvertOption = schem::DataVersionConvertOption;
+/// Options to generate material list
+pub type MaterialListOption = schem::MaterialListOption;
 /// Minecraft data versions.
 pub type DataVersion = schem::DataVersion;
 /// Errors when loading and saving schematic
@@ -132,7 +147,7 @@ impl SchemFormat {
     /// Return all savable formats
     pub fn savable_formats() -> &'static [SchemFormat] {
         use SchemFormat::*;
-        return &[Litematica, VanillaStructure, WorldEdit13];
+        return &[Litematica, VanillaStructure, WorldEdit13, WorldEdit12];
     }
     /// Return if the format can be loaded
     pub fn loadable(&self) -> bool {
@@ -0,0 +1,377 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use fastnbt::Value;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use crate::error::{Error, unwrap_opt_i32, unwrap_opt_i8};
+use crate::render::{Image, MapArt};
+use crate::render::map_art::MAP_SIZE;
+use crate::{unwrap_opt_tag, unwrap_tag};
+use crate::schem::{common, DataVersion, id_of_nbt_tag};
+
+/// A banner marked on a map
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapBanner {
+    /// Dye color of the banner, like `white`
+    pub color: String,
+    /// Custom name of the banner as a json text component
+    pub name: Option<String>,
+    pub pos: [i32; 3],
+}
+
+/// An item frame that holds the map
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapFrame {
+    pub entity_id: i32,
+    pub rotation: i32,
+    pub pos: [i32; 3],
+}
+
+/// Content of `data/map_<id>.dat` in a world
+#[derive(Debug, Clone)]
+pub struct MapData {
+    /// Data version of the game that saved this map
+    pub data_version: i32,
+    /// 0 for 1 block per pixel, up to 4 for 16 blocks per pixel
+    pub scale: i8,
+    /// Like `minecraft:overworld`. Maps before 1.16 store dimension as number, they are converted
+    /// to namespaced ids.
+    pub dimension: String,
+    pub x_center: i32,
+    pub z_center: i32,
+    /// Locked maps are not updated when players hold them
+    pub locked: bool,
+    pub tracking_position: bool,
+    pub unlimited_tracking: bool,
+    pub colors: MapArt,
+    pub banners: Vec<MapBanner>,
+    pub frames: Vec<MapFrame>,
+    /// Tags in `data` that are not parsed. They are kept to be written back.
+    pub unparsed_tags: HashMap<String, Value>,
+}
+
+impl Default for MapData {
+    fn default() -> Self {
+        return MapData {
+            data_version: 3578,
+            scale: 0,
+            dimension: "minecraft:overworld".to_string(),
+            x_center: 0,
+            z_center: 0,
+            locked: false,
+            tracking_position: true,
+            unlimited_tracking: false,
+            colors: MapArt::new(),
+            banners: Vec::new(),
+            frames: Vec::new(),
+            unparsed_tags: HashMap::new(),
+        };
+    }
+}
+
+fn parse_xyz(nbt: &HashMap<String, Value>, tag_path: &str) -> Result<[i32; 3], Error> {
+    let pos_path = format!("{tag_path}/Pos");
+    let pos = unwrap_opt_tag!(nbt.get("Pos"),Compound,HashMap::new(),pos_path);
+    return Ok([unwrap_opt_i32(pos, "X", &pos_path)?,
+        unwrap_opt_i32(pos, "Y", &pos_path)?,
+        unwrap_opt_i32(pos, "Z", &pos_path)?]);
+}
+
+/// `pos` of banners and frames since 24w09a (1.20.5)
+fn parse_pos_array(nbt: &HashMap<String, Value>, tag_path: &str) -> Result<[i32; 3], Error> {
+    let pos_path = format!("{tag_path}/pos");
+    let pos = unwrap_opt_tag!(nbt.get("pos"),IntArray,fastnbt::IntArray::new(vec![]),pos_path);
+    return common::parse_size_list(pos, &pos_path, true);
+}
+
+fn xyz_to_compound(pos: &[i32; 3]) -> Value {
+    let mut nbt = HashMap::new();
+    nbt.insert("X".to_string(), Value::Int(pos[0]));
+    nbt.insert("Y".to_string(), Value::Int(pos[1]));
+    nbt.insert("Z".to_string(), Value::Int(pos[2]));
+    return Value::Compound(nbt);
+}
+
+fn parse_dimension(tag: Option<Value>, tag_path: &str) -> Result<String, Error> {
+    let number = match tag {
+        Some(Value::String(s)) => return Ok(s),
+        Some(Value::Byte(b)) => b as i32,
+        Some(Value::Int(i)) => i,
+        Some(t) => return Err(Error::TagTypeMismatch {
+            tag_path: tag_path.to_string(),
+            expected_type: id_of_nbt_tag(&Value::String(String::new())),
+            found_type: id_of_nbt_tag(&t),
+        }),
+        None => return Err(Error::TagMissing(tag_path.to_string())),
+    };
+    return match number {
+        0 => Ok("minecraft:overworld".to_string()),
+        -1 => Ok("minecraft:the_nether".to_string()),
+        1 => Ok("minecraft:the_end".to_string()),
+        _ => Err(Error::InvalidValue {
+            tag_path: tag_path.to_string(),
+            error: format!("Invalid dimension number {number}"),
+        }),
+    };
+}
+
+/// Maps before 20w21a store dimension as number, which is byte before 17w47a. Dimensions without
+/// number are written as string.
+fn dimension_to_nbt(dimension: &str, data_version: i32) -> Value {
+    if data_version >= DataVersion::Snapshot_20w21a as i32 {
+        return Value::String(dimension.to_string());
+    }
+    let number = match dimension {
+        "minecraft:overworld" => 0,
+        "minecraft:the_nether" => -1,
+        "minecraft:the_end" => 1,
+        _ => return Value::String(dimension.to_string()),
+    };
+    if data_version < DataVersion::Snapshot_17w47a as i32 {
+        return Value::Byte(number);
+    }
+    return Value::Int(number as i32);
+}
+
+impl MapBanner {
+    /// Banners since 24w09a (1.20.5) have lowercase keys, `pos` is an int array, and `color` is
+    /// white if missing.
+    pub fn from_nbt(nbt: &HashMap<String, Value>, tag_path: &str, data_version: i32) -> Result<MapBanner, Error> {
+        let lowercase = data_version >= DataVersion::Snapshot_24w09a as i32;
+        let (color_key, name_key) = if lowercase { ("color", "name") } else { ("Color", "Name") };
+        let color = match nbt.get(color_key) {
+            Some(tag) => unwrap_tag!(tag,String,"".to_string(),format!("{tag_path}/{color_key}")).clone(),
+            None if lowercase => "white".to_string(),
+            None => return Err(Error::TagMissing(format!("{tag_path}/{color_key}"))),
+        };
+        let name = match nbt.get(name_key) {
+            Some(tag) => Some(unwrap_tag!(tag,String,"".to_string(),format!("{tag_path}/{name_key}")).clone()),
+            None => None,
+        };
+        let pos = if lowercase { parse_pos_array(nbt, tag_path)? } else { parse_xyz(nbt, tag_path)? };
+        return Ok(MapBanner { color, name, pos });
+    }
+
+    pub fn to_nbt(&self, data_version: i32) -> HashMap<String, Value> {
+        let mut nbt = HashMap::new();
+        if data_version >= DataVersion::Snapshot_24w09a as i32 {
+            if self.color != "white" {
+                nbt.insert("color".to_string(), Value::String(self.color.clone()));
+            }
+            if let Some(name) = &self.name {
+                nbt.insert("name".to_string(), Value::String(name.clone()));
+            }
+            nbt.insert("pos".to_string(), Value::IntArray(fastnbt::IntArray::new(self.pos.to_vec())));
+            return nbt;
+        }
+        nbt.insert("Color".to_string(), Value::String(self.color.clone()));
+        if let Some(name) = &self.name {
+            nbt.insert("Name".to_string(), Value::String(name.clone()));
+        }
+        nbt.insert("Pos".to_string(), xyz_to_compound(&self.pos));
+        return nbt;
+    }
+}
+
+impl MapFrame {
+    /// Frames since 24w09a (1.20.5) have lowercase keys, and `pos` is an int array.
+    pub fn from_nbt(nbt: &HashMap<String, Value>, tag_path: &str, data_version: i32) -> Result<MapFrame, Error> {
+        if data_version >= DataVersion::Snapshot_24w09a as i32 {
+            return Ok(MapFrame {
+                entity_id: unwrap_opt_i32(nbt, "entity_id", tag_path)?,
+                rotation: unwrap_opt_i32(nbt, "rotation", tag_path)?,
+                pos: parse_pos_array(nbt, tag_path)?,
+            });
+        }
+        return Ok(MapFrame {
+            entity_id: unwrap_opt_i32(nbt, "EntityId", tag_path)?,
+            rotation: unwrap_opt_i32(nbt, "Rotation", tag_path)?,
+            pos: parse_xyz(nbt, tag_path)?,
+        });
+    }
+
+    pub fn to_nbt(&self, data_version: i32) -> HashMap<String, Value> {
+        let mut nbt = HashMap::new();
+        if data_version >= DataVersion::Snapshot_24w09a as i32 {
+            nbt.insert("entity_id".to_string(), Value::Int(self.entity_id));
+            nbt.insert("rotation".to_string(), Value::Int(self.rotation));
+            nbt.insert("pos".to_string(), Value::IntArray(fastnbt::IntArray::new(self.pos.to_vec())));
+            return nbt;
+        }
+        nbt.insert("EntityId".to_string(), Value::Int(self.entity_id));
+        nbt.insert("Rotation".to_string(), Value::Int(self.rotation));
+        nbt.insert("Pos".to_string(), xyz_to_compound(&self.pos));
+        return nbt;
+    }
+}
+
+impl MapData {
+    pub fn new() -> MapData {
+        return MapData::default();
+    }
+
+    /// Parse the root compound of `map_<id>.dat`. Maps before 15w32a have no `DataVersion`, they
+    /// are regarded as 1.8.9.
+    pub fn from_nbt(mut nbt: HashMap<String, Value>) -> Result<MapData, Error> {
+        let data_version = if nbt.contains_key("DataVersion") {
+            unwrap_opt_i32(&nbt, "DataVersion", "")?
+        } else {
+            DataVersion::Java_1_8_9 as i32
+        };
+        let tag_path = "/data";
+        let mut data = unwrap_opt_tag!(nbt.remove("data"),Compound,HashMap::new(),tag_path.to_string());
+
+        let mut result = MapData {
+            data_version,
+            scale: unwrap_opt_i8(&data, "scale", tag_path)?,
+            dimension: parse_dimension(data.remove("dimension"), &format!("{tag_path}/dimension"))?,
+            x_center: unwrap_opt_i32(&data, "xCenter", tag_path)?,
+            z_center: unwrap_opt_i32(&data, "zCenter", tag_path)?,
+            ..MapData::default()
+        };
+        // these flags are missing in old maps
+        for (key, dest) in [("locked", &mut result.locked),
+            ("trackingPosition", &mut result.tracking_position),
+            ("unlimitedTracking", &mut result.unlimited_tracking)] {
+            if data.contains_key(key) {
+                *dest = unwrap_opt_i8(&data, key, tag_path)? != 0;
+            }
+        }
+        {
+            let colors_path = format!("{tag_path}/colors");
+            let colors = unwrap_opt_tag!(data.get("colors"),ByteArray,fastnbt::ByteArray::new(vec![]),colors_path);
+            result.colors = match MapArt::from_byte_array(colors) {
+                Some(c) => c,
+                None => return Err(Error::InvalidValue {
+                    tag_path: colors_path,
+                    error: format!("Expected {} colors, but found {}", MAP_SIZE * MAP_SIZE, colors.len()),
+                }),
+            };
+        }
+        if let Some(tag) = data.get("banners") {
+            let path = format!("{tag_path}/banners");
+            for (idx, banner) in unwrap_tag!(tag,List,vec![],path).iter().enumerate() {
+                let path = format!("{path}/[{idx}]");
+                let banner = unwrap_tag!(banner,Compound,HashMap::new(),path);
+                result.banners.push(MapBanner::from_nbt(banner, &path, data_version)?);
+            }
+        }
+        if let Some(tag) = data.get("frames") {
+            let path = format!("{tag_path}/frames");
+            for (idx, frame) in unwrap_tag!(tag,List,vec![],path).iter().enumerate() {
+                let path = format!("{path}/[{idx}]");
+                let frame = unwrap_tag!(frame,Compound,HashMap::new(),path);
+                result.frames.push(MapFrame::from_nbt(frame, &path, data_version)?);
+            }
+        }
+
+        for key in ["scale", "xCenter", "zCenter", "locked", "trackingPosition", "unlimitedTracking",
+            "colors", "banners", "frames"] {
+            data.remove(key);
+        }
+        result.unparsed_tags = data;
+        return Ok(result);
+    }
+
+    /// Root compound of `map_<id>.dat`
+    pub fn to_nbt(&self) -> HashMap<String, Value> {
+        let mut data = self.unparsed_tags.clone();
+        data.insert("scale".to_string(), Value::Byte(self.scale));
+        data.insert("dimension".to_string(), dimension_to_nbt(&self.dimension, self.data_version));
+        data.insert("xCenter".to_string(), Value::Int(self.x_center));
+        data.insert("zCenter".to_string(), Value::Int(self.z_center));
+        data.insert("locked".to_string(), Value::Byte(self.locked as i8));
+        data.insert("trackingPosition".to_string(), Value::Byte(self.tracking_position as i8));
+        data.insert("unlimitedTracking".to_string(), Value::Byte(self.unlimited_tracking as i8));
+        data.insert("colors".to_string(), Value::ByteArray(fastnbt::ByteArray::new(self.colors.to_byte_array())));
+        data.insert("banners".to_string(),
+                    Value::List(self.banners.iter().map(|b| Value::Compound(b.to_nbt(self.data_version))).collect()));
+        data.insert("frames".to_string(),
+                    Value::List(self.frames.iter().map(|f| Value::Compound(f.to_nbt(self.data_version))).collect()));
+
+        let mut nbt = HashMap::new();
+        nbt.insert("data".to_string(), Value::Compound(data));
+        if self.data_version >= DataVersion::Snapshot_15w32a as i32 {
+            nbt.insert("DataVersion".to_string(), Value::Int(self.data_version));
+        }
+        return nbt;
+    }
+
+    /// Load from gzip-compressed nbt
+    pub fn from_reader(src: &mut dyn Read) -> Result<MapData, Error> {
+        let decoder = GzDecoder::new(src);
+        let nbt: HashMap<String, Value> = match fastnbt::from_reader(decoder) {
+            Ok(n) => n,
+            Err(e) => return Err(Error::NBTReadError(e)),
+        };
+        return Self::from_nbt(nbt);
+    }
+
+    /// Load `map_<id>.dat`
+    pub fn from_file(filename: &str) -> Result<MapData, Error> {
+        let mut file = match File::open(filename) {
+            Ok(f) => f,
+            Err(e) => return Err(Error::FileOpenError(e)),
+        };
+        return Self::from_reader(&mut file);
+    }
+
+    /// Write as gzip-compressed nbt
+    pub fn to_writer(&self, dest: &mut dyn Write) -> Result<(), Error> {
+        let mut encoder = GzEncoder::new(dest, Compression::default());
+        if let Err(e) = fastnbt::to_writer(&mut encoder, &self.to_nbt()) {
+            return Err(Error::NBTWriteError(e));
+        }
+        if let Err(e) = encoder.finish() {
+            return Err(Error::IOWriteError(e));
+        }
+        return Ok(());
+    }
+
+    /// Save as `map_<id>.dat`
+    pub fn save_file(&self, filename: &str) -> Result<(), Error> {
+        let mut file = match File::create(filename) {
+            Ok(f) => f,
+            Err(e) => return Err(Error::FileCreateError(e)),
+        };
+        return self.to_writer(&mut file);
+    }
+
+    /// Render colors as a 128x128 image
+    pub fn to_image(&self) -> Image {
+        return self.colors.to_image();
+    }
+
+    /// Replace colors with the nearest map colors of a 128x128 image. The colors are kept if the
+    /// image is not 128x128.
+    pub fn set_image(&mut self, image: &Image) -> Result<(), Error> {
+        let Some(colors) = MapArt::from_image(image) else {
+            return Err(Error::ImageSizeMismatch {
+                size: [image.width, image.height],
+                expected_size: [MAP_SIZE as u32, MAP_SIZE as u32],
+            });
+        };
+        self.colors = colors;
+        return Ok(());
+    }
+}
@@ -16,6 +16,8 @@ You should have received a copy of the GNU General Public License
 along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use std::collections::HashMap;
+use std::sync::OnceLock;
 use strum::Display;
 use crate::block::Block;
 use crate::region::BlockEntity;
@@ -1227,6 +1229,44 @@ impl Block {
         return Err(OldBlockParseError::NotImplemented { id, damage, version });
     }
 
+    /// Convert `Block` to number id and damage, this is the inverse of `from_old`. If no legacy
+    /// block equals to `self`, the legacy block with the same id and most matching properties is
+    /// selected, so that properties computed in game (like connections of fences) don't matter.
+    /// Returns `None` if the block has no legacy equivalent.
+    pub fn to_old(&self) -> Option<(u8, u8)> {
+        if !self.namespace.is_empty() && self.namespace != "minecraft" {
+            return None;
+        }
+        let lut = old_block_reverse_lut();
+        if let Some(id_damage) = lut.exact.get(self) {
+            return Some(*id_damage);
+        }
+
+        let candidates = lut.by_id.get(&self.id)?;
+        let mut best: Option<(usize, (u8, u8))> = None;
+        for (blk, id_damage) in candidates {
+            let mut matched = 0;
+            for (key, value) in &self.attributes {
+                if blk.attributes.get(key) == Some(value) {
+                    matched += 1;
+                }
+            }
+            if best.is_none() || matched > best.unwrap().0 {
+                best = Some((matched, *id_damage));
+            }
+        }
+        return best.map(|(_, id_damage)| id_damage);
+    }
+
+    /// Convert the block from `data_version` to 1.12.2 and returns its number id and damage.
+    /// Returns `None` if the block doesn't exist in 1.12.2.
+    pub fn to_old_from_version(&self, data_version: i32) -> Option<(u8, u8)> {
+        if data_version <= DataVersion::Java_1_12_2 as i32 {
+            return self.to_old();
+        }
+        return self.convert_data_version(data_version, DataVersion::Java_1_12_2 as i32)?.to_old();
+    }
+
     /// Returns the fixed block. `None` means that the block don't need to be fixed
     pub fn fix_block_property_with_block_entity(&self, _id: u8, _damage: u8, _be: &BlockEntity) -> Result<Option<Block>, OldBlockParseError> {
         let mut _block = self.clone();
@@ -1234,4 +1274,56 @@ impl Block {
 
         return Ok(None);
     }
-}
\ No newline at end of file
+}
+struct OldBlockReverseLUT {
+    exact: HashMap<Block, (u8, u8)>,
+    by_id: HashMap<String, Vec<(Block, (u8, u8))>>,
+}
+
+/// Build the look-up table from `Block` to number id by enumerating all valid id and damages.
+fn old_block_reverse_lut() -> &'static OldBlockReverseLUT {
+    static LUT: OnceLock<OldBlockReverseLUT> = OnceLock::new();
+    return LUT.get_or_init(|| {
+        let mut lut = OldBlockReverseLUT {
+            exact: HashMap::with_capacity(2048),
+            by_id: HashMap::with_capacity(256),
+        };
+        let mut damages = Vec::with_capacity(16);
+        for id in 0..=255u8 {
+            get_valid_damage_values(id, &mut damages);
+            for damage in &damages {
+                let block = match Block::from_old(id, *damage, DataVersion::Java_1_12_2) {
+                    Ok(b) => b,
+                    Err(_) => continue,
+                };
+                if !lut.exact.contains_key(&block) {
+                    lut.exact.insert(block.clone(), (id, *damage));
+                }
+                lut.by_id.entry(block.id.clone()).or_default().push((block, (id, *damage)));
+            }
+        }
+        return lut;
+    });
+}
+
+#[test]
+fn test_old_block_round_trip() {
+    let mut damages = Vec::new();
+    for id in 0..=255u8 {
+        get_valid_damage_values(id, &mut damages);
+        for damage in &damages {
+            let block = match Block::from_old(id, *damage, DataVersion::Java_1_12_2) {
+                Ok(b) => b,
+                Err(_) => continue,
+            };
+            let (new_id, new_damage) = block.to_old().unwrap();
+            let reverted = Block::from_old(new_id, new_damage, DataVersion::Java_1_12_2).unwrap();
+            assert_eq!(block, reverted);
+        }
+    }
+    let version = DataVersion::Java_1_20_4 as i32;
+    for (id, number_id) in [("granite", (1, 1)), ("oak_planks", (5, 0)), ("white_wool", (35, 0)), ("red_wool", (35, 14))] {
+        assert_eq!(Block::from_id(id).unwrap().to_old_from_version(version), Some(number_id));
+    }
+    assert_eq!(Block::from_id("tinted_glass").unwrap().to_old_from_version(version), None);
+}
@@ -1,10 +1,21 @@
 use std::any::Any;
-use std::collections::{BTreeMap, HashMap};
-use std::fmt::{Debug, Formatter};
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::ops::{Deref, DerefMut};
+use std::fs::File;
+use std::io::{Read, Write};
+use fastnbt::Value;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
 use strum::FromRepr;
+use crate::error::{Error, remove_opt, remove_opt_bool, remove_opt_bool_or, remove_opt_or, unwrap_opt_f32, unwrap_opt_f64, unwrap_opt_i16, unwrap_opt_i32, unwrap_opt_i64, unwrap_opt_i8};
 use crate::item::{Inventory, Item};
+use crate::schem::{DataVersion, id_of_nbt_tag};
+use crate::{unwrap_opt_tag, unwrap_tag};
 
-#[derive(Debug, Clone)]
+/// Namespaced id of a dimension, like `minecraft:overworld`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct DimensionId(String);
 
 impl Default for DimensionId {
@@ -13,22 +24,32 @@ impl Default for DimensionId {
     }
 }
 
-#[allow(dead_code)]
 impl DimensionId {
+    pub fn new(id: &str) -> Self {
+        return DimensionId(id.to_string());
+    }
+    pub fn as_str(&self) -> &str {
+        return &self.0;
+    }
     pub fn overworld() -> Self {
         return DimensionId("minecraft:overworld".to_string());
     }
     pub fn nether() -> Self {
-        return DimensionId("minecraft:nether".to_string());
+        return DimensionId("minecraft:the_nether".to_string());
     }
     pub fn the_end() -> Self {
         return DimensionId("minecraft:the_end".to_string());
     }
 }
 
+impl Display for DimensionId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 #[repr(i32)]
-#[derive(Debug, Clone, Copy, FromRepr)]
-#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
 pub enum AttributeOperation {
     Add = 0,
     MultiplyBase = 1,
@@ -37,111 +58,77 @@ pub enum AttributeOperation {
 
 #[derive(Debug, Clone)]
 pub struct AttributeModifier {
+    /// `Name` of the modifier, or `id` since 1.21 like `minecraft:armor.boots`
     pub name: String,
     pub amount: f64,
     pub operation: AttributeOperation,
-    pub uuid: [i32; 4],
+    /// Modifiers since 1.21 have no uuid, they are identified by `id`
+    pub uuid: Option<[i32; 4]>,
 }
 
 #[derive(Debug, Clone)]
 pub struct EntityAttribute {
+    /// Like `minecraft:generic.attack_damage`
+    pub name: String,
     pub base: f64,
     pub modifiers: Vec<AttributeModifier>,
 }
 
-#[derive(Debug, Clone)]
+/// Common tags of entities. Optional tags are `None` if they don't exist, and they are written
+/// back only if they exist.
+#[derive(Debug, Clone, Default)]
 pub struct EntityFields {
-    pub air: i16,
+    pub air: Option<i16>,
     pub custom_name: Option<String>,
-    pub custom_name_visible: bool,
-    pub fall_distance: f32,
-    pub fire: i16,
-    pub glowing: bool,
-    pub has_visual_fire: bool,
-    pub invulnerable: bool,
-    pub motion: [f64; 3],
-    pub no_gravity: bool,
-    pub on_ground: bool,
+    pub custom_name_visible: Option<bool>,
+    pub fall_distance: Option<f32>,
+    pub fire: Option<i16>,
+    pub glowing: Option<bool>,
+    pub has_visual_fire: Option<bool>,
+    pub invulnerable: Option<bool>,
+    pub motion: Option<[f64; 3]>,
+    pub no_gravity: Option<bool>,
+    pub on_ground: Option<bool>,
     pub passenger: Vec<EntityBox>,
-    pub portal_cool_down: i32,
+    pub portal_cool_down: Option<i32>,
     pub pos: [f64; 3],
-    pub rotation: [f32; 2],
-    pub silent: bool,
-    pub tags: HashMap<String, ScoreboardTag>,
-    pub ticks_frozen: i32,
-    pub uuid: [i32; 4],
-}
-
-#[derive(Debug, Clone)]
-pub struct ScoreboardTag {
-    pub score: i32,
-    pub locked: bool,
+    pub rotation: Option<[f32; 2]>,
+    pub silent: Option<bool>,
+    /// Scoreboard tags added by `/tag`
+    pub tags: Vec<String>,
+    pub ticks_frozen: Option<i32>,
+    /// Entities before 1.16 use `UUIDMost` and `UUIDLeast` instead, which are kept in unparsed tags
+    pub uuid: Option<[i32; 4]>,
 }
 
-impl Default for EntityFields {
-    fn default() -> Self {
-        return Self {
-            air: 0,
-            custom_name: None,
-            custom_name_visible: false,
-            fall_distance: 0.0,
-            fire: 0,
-            glowing: false,
-            has_visual_fire: false,
-            invulnerable: false,
-            motion: [0.0, 0.0, 0.0],
-            no_gravity: false,
-            on_ground: false,
-            passenger: vec![],
-            portal_cool_down: 0,
-            pos: [0.0, 0.0, 0.0],
-            rotation: [0.0, 0.0],
-            silent: false,
-            tags: Default::default(),
-            ticks_frozen: 0,
-            uuid: [0; 4],
-        };
-    }
-}
-
-#[derive(Debug, Clone)]
+/// Common tags of living entities, optional tags are handled like `EntityFields`
+#[derive(Debug, Clone, Default)]
 pub struct MobFields {
-    pub absorption_amount: f32,
+    pub absorption_amount: Option<f32>,
     pub active_effects: Vec<PotionEffect>,
-    pub attributes: BTreeMap<String, EntityAttribute>,
-    pub brain: HashMap<String, MobMemory>,
-    pub death_time: i16,
-    pub fall_flying: bool,
-    pub hurt_by_time_stamp: i32,
-    pub hurt_time: i16,
-    pub sleeping_pos: [i32; 3],
-}
-
-impl Default for MobFields {
-    fn default() -> Self {
-        return Self {
-            absorption_amount: 0.0,
-            active_effects: vec![],
-            attributes: Default::default(),
-            brain: Default::default(),
-            death_time: 0,
-            fall_flying: false,
-            hurt_by_time_stamp: 0,
-            hurt_time: 0,
-            sleeping_pos: [0, 0, 0],
-        };
-    }
+    /// Attributes in the order they are stored
+    pub attributes: Vec<EntityAttribute>,
+    /// Attributes are written with the keys before 24w09a (1.20.5), like `Attributes` and `Name`
+    pub legacy_attributes: bool,
+    /// Memories in `Brain`, `None` if there is no `Brain`
+    pub brain: Option<HashMap<String, MobMemory>>,
+    pub death_time: Option<i16>,
+    pub fall_flying: Option<bool>,
+    pub health: Option<f32>,
+    pub hurt_by_time_stamp: Option<i32>,
+    pub hurt_time: Option<i16>,
+    /// Position of the bed, `None` if not sleeping
+    pub sleeping_pos: Option<[i32; 3]>,
 }
 
 #[derive(Debug, Clone, Default)]
-#[allow(dead_code)]
 pub struct MobMemory {
-    ttl: i64,
-    value: MemoryValue,
+    /// Ticks before the memory expires, `None` if it never expires
+    pub ttl: Option<i64>,
+    pub value: MemoryValue,
 }
 
 #[derive(Debug, Clone)]
-#[allow(dead_code)]
 pub enum MemoryValue {
     Boolean(bool),
     Integer(i32),
@@ -154,6 +141,8 @@ pub enum MemoryValue {
     BlockPosList(Vec<[i32; 3]>),
     /// No tags, only the existence of memory is required
     Unit,
+    /// Values of other types, kept as is
+    Other(Value),
 }
 
 impl Default for MemoryValue {
@@ -172,7 +161,10 @@ pub struct PotionEffect {
     pub id: String,
     pub show_icon: bool,
     pub show_particles: bool,
-    pub factor_calculation_data: PotionEffectFactorCalculationData,
+    /// Only used by effects that fade in and out, like darkness
+    pub factor_calculation_data: Option<PotionEffectFactorCalculationData>,
+    /// The weaker effect with longer duration that is restored when this effect ends. There is at
+    /// most one hidden effect, which may have its own hidden effect.
     pub hidden_effects: Vec<PotionEffect>,
 }
 
@@ -214,8 +206,7 @@ impl Default for PlayerAbilities {
 }
 
 #[repr(i32)]
-#[derive(Debug, Clone, Copy, FromRepr)]
-#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
 pub enum PlayerGameType {
     Survival = 0,
     Creative = 1,
@@ -244,7 +235,9 @@ impl Default for WardenSpawnTracker {
 #[derive(Debug, Clone)]
 pub struct XpInfo {
     pub level: i32,
+    /// Progress to the next level, in [0, 1)
     pub xp_p: f32,
+    /// Seed of enchanting table, stored as `XpSeed`
     pub speed: i32,
     pub total: i32,
 }
@@ -271,9 +264,9 @@ pub struct PlayerFields {
     pub food_saturation_level: f32,
     pub food_level: i32,
     pub food_tick_timer: i32,
-    pub last_death_location: (DimensionId, [i32; 3]),
+    pub last_death_location: Option<(DimensionId, [i32; 3])>,
     pub game_type: PlayerGameType,
-    pub previous_game_type: PlayerGameType,
+    pub previous_game_type: Option<PlayerGameType>,
     // recipe book
     pub root_vehicle: Option<[i32; 4]>,
     pub score: i32,
@@ -281,6 +274,8 @@ pub struct PlayerFields {
     pub selected_item_slot: i32,
     pub sleep_timer: i16,
 
+    /// Parrots on shoulders. Shoulder entities are not parsed yet, they are kept in
+    /// `Player::unparsed_tags`.
     pub should_entity_left: Option<EntityBox>,
     pub should_entity_right: Option<EntityBox>,
     pub spawn_angle: f32,
@@ -303,9 +298,9 @@ impl Default for PlayerFields {
             food_saturation_level: 0.0,
             food_level: 0,
             food_tick_timer: 0,
-            last_death_location: (Default::default(), [0, 0, 0]),
+            last_death_location: None,
             game_type: PlayerGameType::Survival,
-            previous_game_type: PlayerGameType::Survival,
+            previous_game_type: None,
             root_vehicle: None,
             score: 0,
             seen_credits: false,
@@ -323,19 +318,21 @@ impl Default for PlayerFields {
     }
 }
 
-#[allow(dead_code)]
 impl PlayerFields {
-    fn selected_item(&self) -> Option<&Item> {
+    /// Item in the hotbar slot selected by player
+    pub fn selected_item(&self) -> Option<&Item> {
         let slot = self.selected_item_slot as i8;
         return self.inventory.0.get(&slot);
     }
-    fn selected_item_mut(&mut self) -> Option<&mut Item> {
+    pub fn selected_item_mut(&mut self) -> Option<&mut Item> {
         let slot = self.selected_item_slot as i8;
         return self.inventory.0.get_mut(&slot);
     }
 }
 
-pub trait GetEntity {
+pub trait GetEntity: Debug + Send + Sync {
+    /// Namespaced id, like `minecraft:item_frame`
+    fn id(&self) -> &str;
     fn entity_fields(&self) -> &EntityFields;
     fn pos(&self) -> [f64; 3] {
         return self.entity_fields().pos;
@@ -348,6 +345,15 @@ pub trait GetEntity {
     }
 
     fn clone_as_entity(&self) -> Box<dyn GetEntityMut>;
+
+    /// All nbt tags of the entity, including unparsed ones
+    fn to_nbt(&self) -> HashMap<String, Value>;
+
+    /// Tags that are not parsed into fields, they are written back as is
+    fn unparsed_tags(&self) -> &HashMap<String, Value>;
+
+    /// Used to downcast to the concrete type
+    fn as_any(&self) -> &dyn Any;
 }
 
 pub trait GetEntityMut: GetEntity {
@@ -355,6 +361,9 @@ pub trait GetEntityMut: GetEntity {
     fn to_mob_mut(&mut self) -> Option<&mut dyn GetMobMut> {
         return None;
     }
+    fn unparsed_tags_mut(&mut self) -> &mut HashMap<String, Value>;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
 }
 
 pub trait GetMob: GetEntity {
@@ -386,25 +395,61 @@ pub trait GetPlayerMut: GetPlayer + GetMobMut {
 
 #[derive(Debug, Clone, Default)]
 pub struct Player {
+    /// Data version of the game that saved this player
+    pub data_version: i32,
     pub entity_fields: EntityFields,
     pub mob_fields: MobFields,
     pub player_fields: PlayerFields,
+    /// Tags that are not parsed, like recipe book. They are kept to be written back.
+    pub unparsed_tags: HashMap<String, Value>,
 }
 
 impl GetEntity for Player {
+    fn id(&self) -> &str {
+        return "minecraft:player";
+    }
+
     fn entity_fields(&self) -> &EntityFields {
         return &self.entity_fields;
     }
 
+    fn to_mob_fields(&self) -> Option<&dyn GetMob> {
+        return Some(self);
+    }
+
     fn clone_as_entity(&self) -> Box<dyn GetEntityMut> {
         return Box::new(self.clone());
     }
+
+    fn to_nbt(&self) -> HashMap<String, Value> {
+        return Player::to_nbt(self);
+    }
+
+    fn unparsed_tags(&self) -> &HashMap<String, Value> {
+        return &self.unparsed_tags;
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self;
+    }
 }
 
 impl GetEntityMut for Player {
     fn entity_fields_mut(&mut self) -> &mut EntityFields {
         return &mut self.entity_fields;
     }
+
+    fn to_mob_mut(&mut self) -> Option<&mut dyn GetMobMut> {
+        return Some(self);
+    }
+
+    fn unparsed_tags_mut(&mut self) -> &mut HashMap<String, Value> {
+        return &mut self.unparsed_tags;
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        return self;
+    }
 }
 
 impl GetMob for Player {
@@ -447,12 +492,12 @@ impl GetPlayerMut for Player {
     }
 }
 
+/// An entity of any type, like `Player` and the types in `crate::entity`
 pub struct EntityBox(Box<dyn GetEntityMut>);
 
 impl Debug for EntityBox {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let ptr = self.0.as_ref() as *const dyn GetEntityMut;
-        return write!(f, "EntityBox, type: {:?}, address: {:?}", self.0.type_id(), ptr);
+        return self.0.fmt(f);
     }
 }
 
@@ -460,4 +505,838 @@ impl Clone for EntityBox {
     fn clone(&self) -> Self {
         return EntityBox(self.0.clone_as_entity());
     }
-}
\ No newline at end of file
+}
+
+impl Deref for EntityBox {
+    type Target = dyn GetEntityMut;
+    fn deref(&self) -> &Self::Target {
+        return self.0.as_ref();
+    }
+}
+
+impl DerefMut for EntityBox {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        return self.0.as_mut();
+    }
+}
+
+impl EntityBox {
+    pub fn new(entity: Box<dyn GetEntityMut>) -> EntityBox {
+        return EntityBox(entity);
+    }
+
+    pub fn downcast_ref<T: 'static>(&self) -> Option<&T> {
+        return self.0.as_any().downcast_ref();
+    }
+
+    pub fn downcast_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        return self.0.as_any_mut().downcast_mut();
+    }
+}
+fn parse_double_list<const N: usize>(list: &[Value], tag_path: &str) -> Result<[f64; N], Error> {
+    if list.len() != N {
+        return Err(Error::InvalidValue {
+            tag_path: tag_path.to_string(),
+            error: format!("Expected {N} doubles, but found {}", list.len()),
+        });
+    }
+    let mut result = [0.0; N];
+    for (idx, tag) in list.iter().enumerate() {
+        result[idx] = *unwrap_tag!(tag,Double,0.0,format!("{tag_path}/[{idx}]"));
+    }
+    return Ok(result);
+}
+
+fn parse_float_list<const N: usize>(list: &[Value], tag_path: &str) -> Result<[f32; N], Error> {
+    if list.len() != N {
+        return Err(Error::InvalidValue {
+            tag_path: tag_path.to_string(),
+            error: format!("Expected {N} floats, but found {}", list.len()),
+        });
+    }
+    let mut result = [0.0; N];
+    for (idx, tag) in list.iter().enumerate() {
+        result[idx] = *unwrap_tag!(tag,Float,0.0,format!("{tag_path}/[{idx}]"));
+    }
+    return Ok(result);
+}
+
+fn parse_int_array<const N: usize>(tag: &Value, tag_path: &str) -> Result<[i32; N], Error> {
+    let array = unwrap_tag!(tag,IntArray,fastnbt::IntArray::new(vec![]),tag_path);
+    if array.len() != N {
+        return Err(Error::InvalidValue {
+            tag_path: tag_path.to_string(),
+            error: format!("Expected {N} ints, but found {}", array.len()),
+        });
+    }
+    let mut result = [0; N];
+    result.copy_from_slice(array);
+    return Ok(result);
+}
+
+fn int_array(data: &[i32]) -> Value {
+    return Value::IntArray(fastnbt::IntArray::new(data.to_vec()));
+}
+
+/// Remove a compound tag if it exists
+pub(crate) fn remove_compound(nbt: &mut HashMap<String, Value>, key: &str, tag_path: &str) -> Result<Option<HashMap<String, Value>>, Error> {
+    return match nbt.remove(key) {
+        Some(tag) => Ok(Some(unwrap_tag!(tag,Compound,HashMap::new(),format!("{tag_path}/{key}")))),
+        None => Ok(None),
+    };
+}
+
+/// Remove a list tag if it exists, otherwise returns an empty list
+pub(crate) fn remove_list(nbt: &mut HashMap<String, Value>, key: &str, tag_path: &str) -> Result<Vec<Value>, Error> {
+    return match nbt.remove(key) {
+        Some(tag) => Ok(unwrap_tag!(tag,List,vec![],format!("{tag_path}/{key}"))),
+        None => Ok(vec![]),
+    };
+}
+
+pub(crate) fn remove_string(nbt: &mut HashMap<String, Value>, key: &str, tag_path: &str) -> Result<Option<String>, Error> {
+    return match nbt.remove(key) {
+        Some(tag) => Ok(Some(unwrap_tag!(tag,String,"".to_string(),format!("{tag_path}/{key}")))),
+        None => Ok(None),
+    };
+}
+
+fn compound_of_pairs<const N: usize>(pairs: [(&str, Value); N]) -> HashMap<String, Value> {
+    return pairs.into_iter().map(|(k, v)| (k.to_string(), v)).collect();
+}
+
+impl DimensionId {
+    /// Parse dimension stored as namespaced id, or as number before 1.16
+    pub fn from_nbt(tag: &Value, tag_path: &str) -> Result<DimensionId, Error> {
+        let number = match tag {
+            Value::String(s) => return Ok(DimensionId(s.clone())),
+            Value::Byte(b) => *b as i32,
+            Value::Int(i) => *i,
+            _ => return Err(Error::TagTypeMismatch {
+                tag_path: tag_path.to_string(),
+                expected_type: id_of_nbt_tag(&Value::String(String::new())),
+                found_type: id_of_nbt_tag(tag),
+            }),
+        };
+        return match number {
+            0 => Ok(Self::overworld()),
+            -1 => Ok(Self::nether()),
+            1 => Ok(Self::the_end()),
+            _ => Err(Error::InvalidValue {
+                tag_path: tag_path.to_string(),
+                error: format!("Invalid dimension number {number}"),
+            }),
+        };
+    }
+
+    /// Dimension as number before 20w21a (1.16), or as namespaced id. Dimensions without number are
+    /// always written as namespaced id.
+    pub fn to_nbt(&self, data_version: i32) -> Value {
+        if data_version < DataVersion::Snapshot_20w21a as i32 {
+            match self.0.as_str() {
+                "minecraft:overworld" => return Value::Int(0),
+                "minecraft:the_nether" => return Value::Int(-1),
+                "minecraft:the_end" => return Value::Int(1),
+                _ => {}
+            }
+        }
+        return Value::String(self.0.clone());
+    }
+}
+
+impl AttributeOperation {
+    /// Name of the operation since 24w09a (1.20.5)
+    pub fn name(&self) -> &'static str {
+        return match self {
+            AttributeOperation::Add => "add_value",
+            AttributeOperation::MultiplyBase => "add_multiplied_base",
+            AttributeOperation::Multiply => "add_multiplied_total",
+        };
+    }
+
+    pub fn from_name(name: &str) -> Option<AttributeOperation> {
+        return match name {
+            "add_value" => Some(AttributeOperation::Add),
+            "add_multiplied_base" => Some(AttributeOperation::MultiplyBase),
+            "add_multiplied_total" => Some(AttributeOperation::Multiply),
+            _ => None,
+        };
+    }
+}
+
+impl AttributeModifier {
+    /// Parse a modifier since 24w09a (1.20.5), which has lowercase keys and the operation as
+    /// string. Modifiers since 1.21 have `id` instead of `name` and `uuid`.
+    pub fn from_nbt(nbt: &HashMap<String, Value>, tag_path: &str) -> Result<AttributeModifier, Error> {
+        let op_path = format!("{tag_path}/operation");
+        let op = unwrap_opt_tag!(nbt.get("operation"),String,"".to_string(),op_path);
+        let Some(operation) = AttributeOperation::from_name(op) else {
+            return Err(Error::InvalidValue { tag_path: op_path, error: format!("Invalid attribute operation {op}") });
+        };
+        let amount = unwrap_opt_f64(nbt, "amount", tag_path)?;
+        if let Some(id) = nbt.get("id") {
+            return Ok(AttributeModifier {
+                name: unwrap_tag!(id,String,"".to_string(),format!("{tag_path}/id")).clone(),
+                amount,
+                operation,
+                uuid: None,
+            });
+        }
+        let uuid_path = format!("{tag_path}/uuid");
+        let Some(uuid) = nbt.get("uuid") else {
+            return Err(Error::TagMissing(uuid_path));
+        };
+        return Ok(AttributeModifier {
+            name: unwrap_opt_tag!(nbt.get("name"),String,"".to_string(),format!("{tag_path}/name")).clone(),
+            amount,
+            operation,
+            uuid: Some(parse_int_array(uuid, &uuid_path)?),
+        });
+    }
+
+    pub fn to_nbt(&self) -> HashMap<String, Value> {
+        let mut nbt = compound_of_pairs([
+            ("amount", Value::Double(self.amount)),
+            ("operation", Value::String(self.operation.name().to_string())),
+        ]);
+        match &self.uuid {
+            Some(uuid) => {
+                nbt.insert("name".to_string(), Value::String(self.name.clone()));
+                nbt.insert("uuid".to_string(), int_array(uuid));
+            }
+            None => {
+                nbt.insert("id".to_string(), Value::String(self.name.clone()));
+            }
+        }
+        return nbt;
+    }
+
+    /// Parse a modifier before 24w09a (1.20.5)
+    pub fn from_nbt_legacy(nbt: &HashMap<String, Value>, tag_path: &str) -> Result<AttributeModifier, Error> {
+        let op_path = format!("{tag_path}/Operation");
+        let op = unwrap_opt_i32(nbt, "Operation", tag_path)?;
+        let Some(operation) = AttributeOperation::from_repr(op) else {
+            return Err(Error::InvalidValue { tag_path: op_path, error: format!("Invalid attribute operation {op}") });
+        };
+        let uuid_path = format!("{tag_path}/UUID");
+        let Some(uuid) = nbt.get("UUID") else {
+            return Err(Error::TagMissing(uuid_path));
+        };
+        return Ok(AttributeModifier {
+            name: unwrap_opt_tag!(nbt.get("Name"),String,"".to_string(),format!("{tag_path}/Name")).clone(),
+            amount: unwrap_opt_f64(nbt, "Amount", tag_path)?,
+            operation,
+            uuid: Some(parse_int_array(uuid, &uuid_path)?),
+        });
+    }
+
+    /// Modifier before 24w09a (1.20.5), `UUID` is written only if it exists
+    pub fn to_nbt_legacy(&self) -> HashMap<String, Value> {
+        let mut nbt = compound_of_pairs([
+            ("Name", Value::String(self.name.clone())),
+            ("Amount", Value::Double(self.amount)),
+            ("Operation", Value::Int(self.operation as i32)),
+        ]);
+        if let Some(uuid) = &self.uuid {
+            nbt.insert("UUID".to_string(), int_array(uuid));
+        }
+        return nbt;
+    }
+}
+
+/// Keys of attributes, attribute names, bases and modifiers
+fn attribute_keys(legacy: bool) -> [&'static str; 4] {
+    return if legacy { ["Attributes", "Name", "Base", "Modifiers"] } else { ["attributes", "id", "base", "modifiers"] };
+}
+
+/// Parse and remove attributes of mobs in the order they are stored. Attributes since 24w09a
+/// (1.20.5) have lowercase keys.
+fn remove_attributes(nbt: &mut HashMap<String, Value>, tag_path: &str, legacy: bool) -> Result<Vec<EntityAttribute>, Error> {
+    let [list_key, name_key, base_key, modifiers_key] = attribute_keys(legacy);
+    let list = remove_list(nbt, list_key, tag_path)?;
+    let tag_path = format!("{tag_path}/{list_key}");
+    let mut result = Vec::with_capacity(list.len());
+    for (idx, tag) in list.iter().enumerate() {
+        let path = format!("{tag_path}/[{idx}]");
+        let nbt = unwrap_tag!(tag,Compound,HashMap::new(),path);
+        let name = unwrap_opt_tag!(nbt.get(name_key),String,"".to_string(),format!("{path}/{name_key}")).clone();
+        let mut modifiers = Vec::new();
+        if let Some(tag) = nbt.get(modifiers_key) {
+            let mod_path = format!("{path}/{modifiers_key}");
+            for (idx, tag) in unwrap_tag!(tag,List,vec![],mod_path).iter().enumerate() {
+                let path = format!("{mod_path}/[{idx}]");
+                let tag = unwrap_tag!(tag,Compound,HashMap::new(),path);
+                modifiers.push(if legacy {
+                    AttributeModifier::from_nbt_legacy(tag, &path)?
+                } else {
+                    AttributeModifier::from_nbt(tag, &path)?
+                });
+            }
+        }
+        result.push(EntityAttribute { name, base: unwrap_opt_f64(nbt, base_key, &path)?, modifiers });
+    }
+    return Ok(result);
+}
+
+fn insert_attributes(nbt: &mut HashMap<String, Value>, attributes: &[EntityAttribute], legacy: bool) {
+    let [list_key, name_key, base_key, modifiers_key] = attribute_keys(legacy);
+    let mut result = Vec::with_capacity(attributes.len());
+    for attr in attributes {
+        let mut attr_nbt = compound_of_pairs([(name_key, Value::String(attr.name.clone())), (base_key, Value::Double(attr.base))]);
+        if !attr.modifiers.is_empty() {
+            let modifiers = attr.modifiers.iter()
+                .map(|m| Value::Compound(if legacy { m.to_nbt_legacy() } else { m.to_nbt() }))
+                .collect();
+            attr_nbt.insert(modifiers_key.to_string(), Value::List(modifiers));
+        }
+        result.push(Value::Compound(attr_nbt));
+    }
+    nbt.insert(list_key.to_string(), Value::List(result));
+}
+
+/// Insert `value` into `nbt` if it is not `None`
+pub(crate) fn insert_opt<T>(nbt: &mut HashMap<String, Value>, key: &str, value: Option<T>, to_value: fn(T) -> Value) {
+    if let Some(value) = value {
+        nbt.insert(key.to_string(), to_value(value));
+    }
+}
+
+pub(crate) fn bool_to_byte(value: bool) -> Value {
+    return Value::Byte(value as i8);
+}
+
+fn double_list(values: [f64; 3]) -> Value {
+    return Value::List(values.iter().map(|v| Value::Double(*v)).collect());
+}
+
+fn float_list(values: [f32; 2]) -> Value {
+    return Value::List(values.iter().map(|v| Value::Float(*v)).collect());
+}
+
+impl MemoryValue {
+    pub fn from_nbt(tag: &Value) -> MemoryValue {
+        return match tag {
+            Value::Byte(b) => MemoryValue::Boolean(*b != 0),
+            Value::Int(i) => MemoryValue::Integer(*i),
+            Value::Long(l) => MemoryValue::Long(*l),
+            Value::IntArray(arr) if arr.len() == 4 => MemoryValue::UUID([arr[0], arr[1], arr[2], arr[3]]),
+            Value::Compound(nbt) => match (nbt.get("dimension"), nbt.get("pos")) {
+                (Some(Value::String(dim)), Some(Value::IntArray(pos))) if nbt.len() == 2 && pos.len() == 3 =>
+                    MemoryValue::GlobalPos { dimension: DimensionId(dim.clone()), pos: [pos[0], pos[1], pos[2]] },
+                _ => MemoryValue::Other(tag.clone()),
+            },
+            Value::List(list) if !list.is_empty() && list.iter().all(|t| matches!(t, Value::IntArray(a) if a.len() == 3)) => {
+                let positions = list.iter().map(|t| match t {
+                    Value::IntArray(a) => [a[0], a[1], a[2]],
+                    _ => unreachable!(),
+                });
+                MemoryValue::BlockPosList(positions.collect())
+            }
+            _ => MemoryValue::Other(tag.clone()),
+        };
+    }
+
+    /// Returns `None` for `Unit`
+    pub fn to_nbt(&self) -> Option<Value> {
+        return match self {
+            MemoryValue::Boolean(b) => Some(Value::Byte(*b as i8)),
+            MemoryValue::Integer(i) => Some(Value::Int(*i)),
+            MemoryValue::Long(l) => Some(Value::Long(*l)),
+            MemoryValue::UUID(uuid) => Some(int_array(uuid)),
+            MemoryValue::GlobalPos { dimension, pos } => Some(Value::Compound(compound_of_pairs([
+                ("dimension", Value::String(dimension.0.clone())),
+                ("pos", int_array(pos)),
+            ]))),
+            MemoryValue::BlockPosList(list) => Some(Value::List(list.iter().map(|p| int_array(p)).collect())),
+            MemoryValue::Unit => None,
+            MemoryValue::Other(v) => Some(v.clone()),
+        };
+    }
+}
+
+impl MobMemory {
+    pub fn from_nbt(nbt: &HashMap<String, Value>, tag_path: &str) -> Result<MobMemory, Error> {
+        let ttl = if nbt.contains_key("ttl") { Some(unwrap_opt_i64(nbt, "ttl", tag_path)?) } else { None };
+        let value = nbt.get("value").map_or(MemoryValue::Unit, MemoryValue::from_nbt);
+        return Ok(MobMemory { ttl, value });
+    }
+
+    pub fn to_nbt(&self) -> HashMap<String, Value> {
+        let mut nbt = HashMap::new();
+        if let Some(ttl) = self.ttl {
+            nbt.insert("ttl".to_string(), Value::Long(ttl));
+        }
+        if let Some(value) = self.value.to_nbt() {
+            nbt.insert("value".to_string(), value);
+        }
+        return nbt;
+    }
+}
+
+impl PotionEffectFactorCalculationData {
+    pub fn from_nbt(nbt: &HashMap<String, Value>, tag_path: &str) -> Result<Self, Error> {
+        return Ok(Self {
+            effect_changed_timestamp: unwrap_opt_i32(nbt, "effect_changed_timestamp", tag_path)?,
+            factor_current: unwrap_opt_f32(nbt, "factor_current", tag_path)?,
+            factor_previous_frame: unwrap_opt_f32(nbt, "factor_previous_frame", tag_path)?,
+            factor_start: unwrap_opt_f32(nbt, "factor_start", tag_path)?,
+            factor_target: unwrap_opt_f32(nbt, "factor_target", tag_path)?,
+            had_effect_last_tick: unwrap_opt_i8(nbt, "had_effect_last_tick", tag_path)? != 0,
+            padding_duration: unwrap_opt_i32(nbt, "padding_duration", tag_path)?,
+        });
+    }
+
+    pub fn to_nbt(&self) -> HashMap<String, Value> {
+        return compound_of_pairs([
+            ("effect_changed_timestamp", Value::Int(self.effect_changed_timestamp)),
+            ("factor_current", Value::Float(self.factor_current)),
+            ("factor_previous_frame", Value::Float(self.factor_previous_frame)),
+            ("factor_start", Value::Float(self.factor_start)),
+            ("factor_target", Value::Float(self.factor_target)),
+            ("had_effect_last_tick", Value::Byte(self.had_effect_last_tick as i8)),
+            ("padding_duration", Value::Int(self.padding_duration)),
+        ]);
+    }
+}
+
+impl PotionEffect {
+    /// Parse an element of `active_effects`(1.20.2+)
+    pub fn from_nbt(nbt: &HashMap<String, Value>, tag_path: &str) -> Result<PotionEffect, Error> {
+        let mut nbt = nbt.clone();
+        let nbt = &mut nbt;
+        let mut hidden_effects = Vec::new();
+        if let Some(hidden) = remove_compound(nbt, "hidden_effect", tag_path)? {
+            hidden_effects.push(PotionEffect::from_nbt(&hidden, &format!("{tag_path}/hidden_effect"))?);
+        }
+        let factor_calculation_data = match remove_compound(nbt, "factor_calculation_data", tag_path)? {
+            Some(data) => Some(PotionEffectFactorCalculationData::from_nbt(&data, &format!("{tag_path}/factor_calculation_data"))?),
+            None => None,
+        };
+        return Ok(PotionEffect {
+            ambient: remove_opt_bool_or(nbt, "ambient", tag_path, false)?,
+            amplifier: remove_opt_or(nbt, "amplifier", tag_path, 0, unwrap_opt_i8)?,
+            duration: remove_opt_or(nbt, "duration", tag_path, 0, unwrap_opt_i32)?,
+            id: unwrap_opt_tag!(nbt.get("id"),String,"".to_string(),format!("{tag_path}/id")).clone(),
+            show_icon: remove_opt_bool_or(nbt, "show_icon", tag_path, true)?,
+            show_particles: remove_opt_bool_or(nbt, "show_particles", tag_path, true)?,
+            factor_calculation_data,
+            hidden_effects,
+        });
+    }
+
+    pub fn to_nbt(&self) -> HashMap<String, Value> {
+        let mut nbt = compound_of_pairs([
+            ("id", Value::String(self.id.clone())),
+            ("ambient", Value::Byte(self.ambient as i8)),
+            ("amplifier", Value::Byte(self.amplifier)),
+            ("duration", Value::Int(self.duration)),
+            ("show_icon", Value::Byte(self.show_icon as i8)),
+            ("show_particles", Value::Byte(self.show_particles as i8)),
+        ]);
+        if let Some(hidden) = self.hidden_effects.first() {
+            nbt.insert("hidden_effect".to_string(), Value::Compound(hidden.to_nbt()));
+        }
+        if let Some(data) = &self.factor_calculation_data {
+            nbt.insert("factor_calculation_data".to_string(), Value::Compound(data.to_nbt()));
+        }
+        return nbt;
+    }
+}
+
+impl EntityFields {
+    /// Parse and remove common tags of entities from `nbt`
+    pub fn from_nbt(nbt: &mut HashMap<String, Value>, tag_path: &str) -> Result<EntityFields, Error> {
+        let mut result = EntityFields {
+            air: remove_opt(nbt, "Air", tag_path, unwrap_opt_i16)?,
+            custom_name: remove_string(nbt, "CustomName", tag_path)?,
+            custom_name_visible: remove_opt_bool(nbt, "CustomNameVisible", tag_path)?,
+            fall_distance: remove_opt(nbt, "FallDistance", tag_path, unwrap_opt_f32)?,
+            fire: remove_opt(nbt, "Fire", tag_path, unwrap_opt_i16)?,
+            glowing: remove_opt_bool(nbt, "Glowing", tag_path)?,
+            has_visual_fire: remove_opt_bool(nbt, "HasVisualFire", tag_path)?,
+            invulnerable: remove_opt_bool(nbt, "Invulnerable", tag_path)?,
+            no_gravity: remove_opt_bool(nbt, "NoGravity", tag_path)?,
+            on_ground: remove_opt_bool(nbt, "OnGround", tag_path)?,
+            portal_cool_down: remove_opt(nbt, "PortalCooldown", tag_path, unwrap_opt_i32)?,
+            silent: remove_opt_bool(nbt, "Silent", tag_path)?,
+            ticks_frozen: remove_opt(nbt, "TicksFrozen", tag_path, unwrap_opt_i32)?,
+            ..EntityFields::default()
+        };
+        if nbt.contains_key("Motion") {
+            result.motion = Some(parse_double_list(&remove_list(nbt, "Motion", tag_path)?, &format!("{tag_path}/Motion"))?);
+        }
+        if nbt.contains_key("Rotation") {
+            result.rotation = Some(parse_float_list(&remove_list(nbt, "Rotation", tag_path)?, &format!("{tag_path}/Rotation"))?);
+        }
+        let pos_path = format!("{tag_path}/Pos");
+        if !nbt.contains_key("Pos") {
+            return Err(Error::TagMissing(pos_path));
+        }
+        result.pos = parse_double_list(&remove_list(nbt, "Pos", tag_path)?, &pos_path)?;
+
+        let tags_path = format!("{tag_path}/Tags");
+        for (idx, tag) in remove_list(nbt, "Tags", tag_path)?.into_iter().enumerate() {
+            result.tags.push(unwrap_tag!(tag,String,"".to_string(),format!("{tags_path}/[{idx}]")));
+        }
+        if let Some(uuid) = nbt.remove("UUID") {
+            result.uuid = Some(parse_int_array(&uuid, &format!("{tag_path}/UUID"))?);
+        }
+        let passengers_path = format!("{tag_path}/Passengers");
+        for (idx, tag) in remove_list(nbt, "Passengers", tag_path)?.into_iter().enumerate() {
+            let path = format!("{passengers_path}/[{idx}]");
+            result.passenger.push(EntityBox::from_nbt(unwrap_tag!(tag,Compound,HashMap::new(),path), &path)?);
+        }
+        return Ok(result);
+    }
+
+    /// Write common tags of entities into `nbt`, including passengers
+    pub fn to_nbt(&self, nbt: &mut HashMap<String, Value>) {
+        if !self.passenger.is_empty() {
+            nbt.insert("Passengers".to_string(),
+                       Value::List(self.passenger.iter().map(|p| Value::Compound(p.to_nbt())).collect()));
+        }
+        insert_opt(nbt, "Air", self.air, Value::Short);
+        insert_opt(nbt, "CustomName", self.custom_name.clone(), Value::String);
+        insert_opt(nbt, "CustomNameVisible", self.custom_name_visible, bool_to_byte);
+        insert_opt(nbt, "FallDistance", self.fall_distance, Value::Float);
+        insert_opt(nbt, "Fire", self.fire, Value::Short);
+        insert_opt(nbt, "Glowing", self.glowing, bool_to_byte);
+        insert_opt(nbt, "HasVisualFire", self.has_visual_fire, bool_to_byte);
+        insert_opt(nbt, "Invulnerable", self.invulnerable, bool_to_byte);
+        insert_opt(nbt, "NoGravity", self.no_gravity, bool_to_byte);
+        insert_opt(nbt, "OnGround", self.on_ground, bool_to_byte);
+        insert_opt(nbt, "PortalCooldown", self.portal_cool_down, Value::Int);
+        insert_opt(nbt, "Silent", self.silent, bool_to_byte);
+        insert_opt(nbt, "TicksFrozen", self.ticks_frozen, Value::Int);
+        if !self.tags.is_empty() {
+            nbt.insert("Tags".to_string(), Value::List(self.tags.iter().map(|t| Value::String(t.clone())).collect()));
+        }
+        insert_opt(nbt, "Motion", self.motion, double_list);
+        nbt.insert("Pos".to_string(), double_list(self.pos));
+        insert_opt(nbt, "Rotation", self.rotation, float_list);
+        insert_opt(nbt, "UUID", self.uuid, |uuid| int_array(&uuid));
+    }
+}
+
+impl MobFields {
+    /// Parse and remove common tags of mobs from `nbt`. `data_version` decides the keys of
+    /// attributes.
+    pub fn from_nbt(nbt: &mut HashMap<String, Value>, tag_path: &str, data_version: i32) -> Result<MobFields, Error> {
+        let mut result = MobFields {
+            legacy_attributes: data_version < DataVersion::Snapshot_24w09a as i32,
+            absorption_amount: remove_opt(nbt, "AbsorptionAmount", tag_path, unwrap_opt_f32)?,
+            death_time: remove_opt(nbt, "DeathTime", tag_path, unwrap_opt_i16)?,
+            fall_flying: remove_opt_bool(nbt, "FallFlying", tag_path)?,
+            health: remove_opt(nbt, "Health", tag_path, unwrap_opt_f32)?,
+            hurt_by_time_stamp: remove_opt(nbt, "HurtByTimestamp", tag_path, unwrap_opt_i32)?,
+            hurt_time: remove_opt(nbt, "HurtTime", tag_path, unwrap_opt_i16)?,
+            ..MobFields::default()
+        };
+
+        let effects_path = format!("{tag_path}/active_effects");
+        for (idx, tag) in remove_list(nbt, "active_effects", tag_path)?.iter().enumerate() {
+            let path = format!("{effects_path}/[{idx}]");
+            result.active_effects.push(PotionEffect::from_nbt(unwrap_tag!(tag,Compound,HashMap::new(),path), &path)?);
+        }
+
+        result.attributes = remove_attributes(nbt, tag_path, result.legacy_attributes)?;
+
+        if let Some(mut brain) = remove_compound(nbt, "Brain", tag_path)? {
+            let brain_path = format!("{tag_path}/Brain");
+            let mut parsed = HashMap::new();
+            if let Some(memories) = remove_compound(&mut brain, "memories", &brain_path)? {
+                for (key, memory) in memories {
+                    let path = format!("{brain_path}/memories/{key}");
+                    let memory = MobMemory::from_nbt(unwrap_tag!(&memory,Compound,HashMap::new(),path), &path)?;
+                    parsed.insert(key, memory);
+                }
+            }
+            result.brain = Some(parsed);
+        }
+
+        if nbt.contains_key("SleepingX") {
+            result.sleeping_pos = Some([unwrap_opt_i32(nbt, "SleepingX", tag_path)?,
+                unwrap_opt_i32(nbt, "SleepingY", tag_path)?,
+                unwrap_opt_i32(nbt, "SleepingZ", tag_path)?]);
+            for key in ["SleepingX", "SleepingY", "SleepingZ"] {
+                nbt.remove(key);
+            }
+        }
+        return Ok(result);
+    }
+
+    /// Write common tags of mobs into `nbt`
+    pub fn to_nbt(&self, nbt: &mut HashMap<String, Value>) {
+        insert_opt(nbt, "AbsorptionAmount", self.absorption_amount, Value::Float);
+        insert_opt(nbt, "DeathTime", self.death_time, Value::Short);
+        insert_opt(nbt, "FallFlying", self.fall_flying, bool_to_byte);
+        insert_opt(nbt, "Health", self.health, Value::Float);
+        insert_opt(nbt, "HurtByTimestamp", self.hurt_by_time_stamp, Value::Int);
+        insert_opt(nbt, "HurtTime", self.hurt_time, Value::Short);
+        if !self.active_effects.is_empty() {
+            nbt.insert("active_effects".to_string(),
+                       Value::List(self.active_effects.iter().map(|e| Value::Compound(e.to_nbt())).collect()));
+        }
+        if !self.attributes.is_empty() {
+            insert_attributes(nbt, &self.attributes, self.legacy_attributes);
+        }
+        if let Some(brain) = &self.brain {
+            let memories = brain.iter()
+                .map(|(key, memory)| (key.clone(), Value::Compound(memory.to_nbt())))
+                .collect();
+            nbt.insert("Brain".to_string(), Value::Compound(compound_of_pairs([("memories", Value::Compound(memories))])));
+        }
+        if let Some(pos) = self.sleeping_pos {
+            for (key, value) in ["SleepingX", "SleepingY", "SleepingZ"].iter().zip(pos) {
+                nbt.insert(key.to_string(), Value::Int(value));
+            }
+        }
+    }
+
+    /// Find an attribute by name, like `minecraft:generic.attack_damage`
+    pub fn attribute(&self, name: &str) -> Option<&EntityAttribute> {
+        return self.attributes.iter().find(|a| a.name == name);
+    }
+}
+
+impl PlayerAbilities {
+    pub fn from_nbt(nbt: &HashMap<String, Value>, tag_path: &str) -> Result<PlayerAbilities, Error> {
+        return Ok(PlayerAbilities {
+            flying: unwrap_opt_i8(nbt, "flying", tag_path)? != 0,
+            instant_build: unwrap_opt_i8(nbt, "instabuild", tag_path)? != 0,
+            invulnerable: unwrap_opt_i8(nbt, "invulnerable", tag_path)? != 0,
+            may_build: unwrap_opt_i8(nbt, "mayBuild", tag_path)? != 0,
+            may_fly: unwrap_opt_i8(nbt, "mayfly", tag_path)? != 0,
+            walk_speed: unwrap_opt_f32(nbt, "walkSpeed", tag_path)?,
+            fly_speed: unwrap_opt_f32(nbt, "flySpeed", tag_path)?,
+        });
+    }
+
+    pub fn to_nbt(&self) -> HashMap<String, Value> {
+        return compound_of_pairs([
+            ("flying", Value::Byte(self.flying as i8)),
+            ("instabuild", Value::Byte(self.instant_build as i8)),
+            ("invulnerable", Value::Byte(self.invulnerable as i8)),
+            ("mayBuild", Value::Byte(self.may_build as i8)),
+            ("mayfly", Value::Byte(self.may_fly as i8)),
+            ("walkSpeed", Value::Float(self.walk_speed)),
+            ("flySpeed", Value::Float(self.fly_speed)),
+        ]);
+    }
+}
+
+fn parse_game_type(value: i32, tag_path: &str) -> Result<PlayerGameType, Error> {
+    return match PlayerGameType::from_repr(value) {
+        Some(t) => Ok(t),
+        None => Err(Error::InvalidValue {
+            tag_path: tag_path.to_string(),
+            error: format!("Invalid game type {value}"),
+        }),
+    };
+}
+
+impl PlayerFields {
+    /// Parse and remove tags of players from `nbt`
+    pub fn from_nbt(nbt: &mut HashMap<String, Value>, tag_path: &str) -> Result<PlayerFields, Error> {
+        let mut result = PlayerFields {
+            food_exhaust_level: remove_opt_or(nbt, "foodExhaustionLevel", tag_path, 0.0, unwrap_opt_f32)?,
+            food_saturation_level: remove_opt_or(nbt, "foodSaturationLevel", tag_path, 0.0, unwrap_opt_f32)?,
+            food_level: remove_opt_or(nbt, "foodLevel", tag_path, 0, unwrap_opt_i32)?,
+            food_tick_timer: remove_opt_or(nbt, "foodTickTimer", tag_path, 0, unwrap_opt_i32)?,
+            game_type: parse_game_type(remove_opt_or(nbt, "playerGameType", tag_path, 0, unwrap_opt_i32)?,
+                                       &format!("{tag_path}/playerGameType"))?,
+            score: remove_opt_or(nbt, "Score", tag_path, 0, unwrap_opt_i32)?,
+            seen_credits: remove_opt_bool_or(nbt, "seenCredits", tag_path, false)?,
+            selected_item_slot: remove_opt_or(nbt, "SelectedItemSlot", tag_path, 0, unwrap_opt_i32)?,
+            sleep_timer: remove_opt_or(nbt, "SleepTimer", tag_path, 0, unwrap_opt_i16)?,
+            spawn_angle: remove_opt_or(nbt, "SpawnAngle", tag_path, 0.0, unwrap_opt_f32)?,
+            spawn_forced: remove_opt_bool_or(nbt, "SpawnForced", tag_path, false)?,
+            xp_info: XpInfo {
+                level: remove_opt_or(nbt, "XpLevel", tag_path, 0, unwrap_opt_i32)?,
+                xp_p: remove_opt_or(nbt, "XpP", tag_path, 0.0, unwrap_opt_f32)?,
+                speed: remove_opt_or(nbt, "XpSeed", tag_path, 0, unwrap_opt_i32)?,
+                total: remove_opt_or(nbt, "XpTotal", tag_path, 0, unwrap_opt_i32)?,
+            },
+            ..PlayerFields::default()
+        };
+
+        if let Some(abilities) = remove_compound(nbt, "abilities", tag_path)? {
+            result.abilities = PlayerAbilities::from_nbt(&abilities, &format!("{tag_path}/abilities"))?;
+        }
+        if let Some(dim) = nbt.remove("Dimension") {
+            result.dimension = DimensionId::from_nbt(&dim, &format!("{tag_path}/Dimension"))?;
+        }
+        result.inventory = Inventory::from_nbt(&remove_list(nbt, "Inventory", tag_path)?, &format!("{tag_path}/Inventory"))?;
+        result.ender_items = Inventory::from_nbt(&remove_list(nbt, "EnderItems", tag_path)?, &format!("{tag_path}/EnderItems"))?;
+
+        if let Some(pos) = remove_compound(nbt, "enteredNetherPosition", tag_path)? {
+            let path = format!("{tag_path}/enteredNetherPosition");
+            result.entered_nether_position = Some([unwrap_opt_f64(&pos, "x", &path)?,
+                unwrap_opt_f64(&pos, "y", &path)?,
+                unwrap_opt_f64(&pos, "z", &path)?]);
+        }
+        if let Some(location) = remove_compound(nbt, "LastDeathLocation", tag_path)? {
+            let path = format!("{tag_path}/LastDeathLocation");
+            let Some(dim) = location.get("dimension") else {
+                return Err(Error::TagMissing(format!("{path}/dimension")));
+            };
+            let Some(pos) = location.get("pos") else {
+                return Err(Error::TagMissing(format!("{path}/pos")));
+            };
+            result.last_death_location = Some((DimensionId::from_nbt(dim, &format!("{path}/dimension"))?,
+                                               parse_int_array(pos, &format!("{path}/pos"))?));
+        }
+        let previous = remove_opt_or(nbt, "previousPlayerGameType", tag_path, -1, unwrap_opt_i32)?;
+        if previous >= 0 {
+            result.previous_game_type = Some(parse_game_type(previous, &format!("{tag_path}/previousPlayerGameType"))?);
+        }
+        // the vehicle entity is kept in unparsed tags
+        if let Some(Value::Compound(vehicle)) = nbt.get("RootVehicle") {
+            if let Some(attach) = vehicle.get("Attach") {
+                result.root_vehicle = Some(parse_int_array(attach, &format!("{tag_path}/RootVehicle/Attach"))?);
+            }
+        }
+        if let Some(dim) = nbt.remove("SpawnDimension") {
+            result.spawn_dimension = DimensionId::from_nbt(&dim, &format!("{tag_path}/SpawnDimension"))?;
+        }
+        if nbt.contains_key("SpawnX") {
+            result.spawn_pos = Some([unwrap_opt_i32(nbt, "SpawnX", tag_path)?,
+                unwrap_opt_i32(nbt, "SpawnY", tag_path)?,
+                unwrap_opt_i32(nbt, "SpawnZ", tag_path)?]);
+            for key in ["SpawnX", "SpawnY", "SpawnZ"] {
+                nbt.remove(key);
+            }
+        }
+        if let Some(tracker) = remove_compound(nbt, "warden_spawn_tracker", tag_path)? {
+            let path = format!("{tag_path}/warden_spawn_tracker");
+            result.warden_spawn_tracker = WardenSpawnTracker {
+                cooldown_ticks: unwrap_opt_i32(&tracker, "cooldown_ticks", &path)?,
+                ticks_since_last_warning: unwrap_opt_i32(&tracker, "ticks_since_last_warning", &path)?,
+                warning_level: unwrap_opt_i32(&tracker, "warning_level", &path)?,
+            };
+        }
+        return Ok(result);
+    }
+
+    /// Write tags of players into `nbt`. `RootVehicle` in `nbt` is updated or removed according
+    /// to `root_vehicle`. `Dimension` is written as number before 20w21a (1.16).
+    pub fn to_nbt(&self, nbt: &mut HashMap<String, Value>, data_version: i32) {
+        nbt.insert("abilities".to_string(), Value::Compound(self.abilities.to_nbt()));
+        nbt.insert("Dimension".to_string(), self.dimension.to_nbt(data_version));
+        nbt.insert("Inventory".to_string(), Value::List(self.inventory.to_nbt()));
+        nbt.insert("EnderItems".to_string(), Value::List(self.ender_items.to_nbt()));
+        if let Some(pos) = self.entered_nether_position {
+            nbt.insert("enteredNetherPosition".to_string(), Value::Compound(compound_of_pairs([
+                ("x", Value::Double(pos[0])), ("y", Value::Double(pos[1])), ("z", Value::Double(pos[2]))])));
+        }
+        nbt.insert("foodExhaustionLevel".to_string(), Value::Float(self.food_exhaust_level));
+        nbt.insert("foodSaturationLevel".to_string(), Value::Float(self.food_saturation_level));
+        nbt.insert("foodLevel".to_string(), Value::Int(self.food_level));
+        nbt.insert("foodTickTimer".to_string(), Value::Int(self.food_tick_timer));
+        if let Some((dim, pos)) = &self.last_death_location {
+            nbt.insert("LastDeathLocation".to_string(), Value::Compound(compound_of_pairs([
+                ("dimension", Value::String(dim.0.clone())), ("pos", int_array(pos))])));
+        }
+        nbt.insert("playerGameType".to_string(), Value::Int(self.game_type as i32));
+        if let Some(previous) = self.previous_game_type {
+            nbt.insert("previousPlayerGameType".to_string(), Value::Int(previous as i32));
+        }
+        match self.root_vehicle {
+            Some(attach) => {
+                let vehicle = nbt.entry("RootVehicle".to_string()).or_insert_with(|| Value::Compound(HashMap::new()));
+                if let Value::Compound(vehicle) = vehicle {
+                    vehicle.insert("Attach".to_string(), int_array(&attach));
+                }
+            }
+            None => { nbt.remove("RootVehicle"); }
+        }
+        nbt.insert("Score".to_string(), Value::Int(self.score));
+        nbt.insert("seenCredits".to_string(), Value::Byte(self.seen_credits as i8));
+        nbt.insert("SelectedItemSlot".to_string(), Value::Int(self.selected_item_slot));
+        nbt.insert("SleepTimer".to_string(), Value::Short(self.sleep_timer));
+        if let Some(pos) = self.spawn_pos {
+            for (key, value) in ["SpawnX", "SpawnY", "SpawnZ"].iter().zip(pos) {
+                nbt.insert(key.to_string(), Value::Int(value));
+            }
+            nbt.insert("SpawnAngle".to_string(), Value::Float(self.spawn_angle));
+            nbt.insert("SpawnDimension".to_string(), Value::String(self.spawn_dimension.0.clone()));
+            nbt.insert("SpawnForced".to_string(), Value::Byte(self.spawn_forced as i8));
+        }
+        nbt.insert("warden_spawn_tracker".to_string(), Value::Compound(compound_of_pairs([
+            ("cooldown_ticks", Value::Int(self.warden_spawn_tracker.cooldown_ticks)),
+            ("ticks_since_last_warning", Value::Int(self.warden_spawn_tracker.ticks_since_last_warning)),
+            ("warning_level", Value::Int(self.warden_spawn_tracker.warning_level)),
+        ])));
+        nbt.insert("XpLevel".to_string(), Value::Int(self.xp_info.level));
+        nbt.insert("XpP".to_string(), Value::Float(self.xp_info.xp_p));
+        nbt.insert("XpSeed".to_string(), Value::Int(self.xp_info.speed));
+        nbt.insert("XpTotal".to_string(), Value::Int(self.xp_info.total));
+    }
+}
+
+impl Player {
+    /// Parse `playerdata/<uuid>.dat` or `Player` in `level.dat`
+    pub fn from_nbt(mut nbt: HashMap<String, Value>, tag_path: &str) -> Result<Player, Error> {
+        let nbt = &mut nbt;
+        let data_version = remove_opt_or(nbt, "DataVersion", tag_path, 0, unwrap_opt_i32)?;
+        let entity_fields = EntityFields::from_nbt(nbt, tag_path)?;
+        let mob_fields = MobFields::from_nbt(nbt, tag_path, data_version)?;
+        let player_fields = PlayerFields::from_nbt(nbt, tag_path)?;
+        return Ok(Player {
+            data_version,
+            entity_fields,
+            mob_fields,
+            player_fields,
+            unparsed_tags: std::mem::take(nbt),
+        });
+    }
+
+    pub fn to_nbt(&self) -> HashMap<String, Value> {
+        let mut nbt = self.unparsed_tags.clone();
+        if self.data_version != 0 {
+            nbt.insert("DataVersion".to_string(), Value::Int(self.data_version));
+        }
+        self.entity_fields.to_nbt(&mut nbt);
+        self.mob_fields.to_nbt(&mut nbt);
+        self.player_fields.to_nbt(&mut nbt, self.data_version);
+        return nbt;
+    }
+
+    /// Load from gzip-compressed nbt, like `playerdata/<uuid>.dat`
+    pub fn from_reader(src: &mut dyn Read) -> Result<Player, Error> {
+        let decoder = GzDecoder::new(src);
+        let nbt: HashMap<String, Value> = match fastnbt::from_reader(decoder) {
+            Ok(n) => n,
+            Err(e) => return Err(Error::NBTReadError(e)),
+        };
+        return Self::from_nbt(nbt, "");
+    }
+
+    pub fn from_file(filename: &str) -> Result<Player, Error> {
+        let mut file = match File::open(filename) {
+            Ok(f) => f,
+            Err(e) => return Err(Error::FileOpenError(e)),
+        };
+        return Self::from_reader(&mut file);
+    }
+
+    /// Write as gzip-compressed nbt
+    pub fn to_writer(&self, dest: &mut dyn Write) -> Result<(), Error> {
+        let mut encoder = GzEncoder::new(dest, Compression::default());
+        if let Err(e) = fastnbt::to_writer(&mut encoder, &self.to_nbt()) {
+            return Err(Error::NBTWriteError(e));
+        }
+        if let Err(e) = encoder.finish() {
+            return Err(Error::IOWriteError(e));
+        }
+        return Ok(());
+    }
+
+    pub fn save_file(&self, filename: &str) -> Result<(), Error> {
+        let mut file = match File::create(filename) {
+            Ok(f) => f,
+            Err(e) => return Err(Error::FileCreateError(e)),
+        };
+        return self.to_writer(&mut file);
+    }
+}
@@ -17,25 +17,16 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
 use std::collections::HashMap;
-use ndarray::{Array3};
+use ndarray::{Array2, Array3};
+use crate::biome::Biome;
 use crate::block::Block;
 use crate::error::Error;
+use crate::player::EntityBox;
 
 /// Sky light and block light
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Light(u8);
 
-/// An entity in MC, like zombie, minecart, etc.
-#[derive(Debug, Clone)]
-pub struct Entity {
-    /// nbt tags of entity
-    pub tags: HashMap<String, fastnbt::Value>,
-    /// Position in double precision float
-    pub position: [f64; 3],
-    /// Position in integer
-    pub block_pos: [i32; 3],
-}
-
 /// Block entity(also known as tile entity) in MC, like chest, furnace, etc.
 #[derive(Debug, Clone)]
 pub struct BlockEntity {
@@ -155,6 +146,16 @@ pub trait HasOffset {
 // }
 
 
+/// Biomes of a region. Different formats store biomes in different resolutions, so both are kept
+/// as they are loaded.
+#[derive(Debug, Clone)]
+pub enum RegionBiomes {
+    /// One biome per column, stored in z,x, like `.schem` v2
+    Columns(Array2<Biome>),
+    /// One biome per block, stored in y,z,x, like `.schem` v3
+    Blocks(Array3<Biome>),
+}
+
 /// Region is a 3d area in Minecraft, containing blocks and entities. \
 /// Litematica files can have multiple regions, but vanilla structure, world edit schematics can have only one. \
 /// Blocks in a region are stored as continuous 3d index array. A palette(Vec of blocks) records all
@@ -174,10 +175,14 @@ pub struct Region {
     /// All pending ticks. The key is position (xyz)
     pub pending_ticks: HashMap<[i32; 3], Vec<PendingTick>>,
     /// All entities
-    pub entities: Vec<Entity>,
+    pub entities: Vec<EntityBox>,
     /// Offset of this region
     pub offset: [i32; 3],
-
+    /// Biomes of this region, `None` if the source format doesn't store biomes
+    pub biomes: Option<RegionBiomes>,
+    /// Sky light and block light, stored in y,z,x. `None` if light is unknown, like regions loaded
+    /// from schematics
+    pub light: Option<Array3<Light>>,
 
     //pub array_number_id_damage: Option<Array3<(u8, u8)>>
 }
@@ -201,24 +206,6 @@ impl Light {
     }
 }
 
-impl Entity {
-    pub fn new() -> Entity {
-        return Entity {
-            tags: HashMap::new(),
-            position: [0.0, 0.0, 0.0],
-            block_pos: [0, 0, 0],
-        };
-    }
-
-    /// Add adder to position and block_pos
-    pub fn pos_shift(&mut self, adder: [i32; 3]) {
-        for dim in 0..3 {
-            self.block_pos[dim] += adder[dim];
-            self.position[dim] += adder[dim] as f64;
-        }
-    }
-}
-
 impl BlockEntity {
     pub fn new() -> BlockEntity {
         return BlockEntity {
@@ -360,6 +347,8 @@ impl Region {
             pending_ticks: HashMap::new(),
             entities: Vec::new(),
             offset: [0, 0, 0],
+            biomes: None,
+            light: None,
         };
         result.find_or_append_to_palette(&Block::air());
         return result;
@@ -439,7 +428,7 @@ impl Region {
         return Ok(());
     }
 
-    /// Reshape the region and fill `array_yzx` with 0
+    /// Reshape the region and fill `array_yzx` with 0. Biomes and light are removed.
     pub fn reshape(&mut self, shape_xyz: &[i32; 3]) {
         let mut usz: [usize; 3] = [0, 0, 0];
         for idx in 0..3 {
@@ -451,6 +440,8 @@ impl Region {
         }
         let shape_yzx = Self::pos_xyz_to_yzx(&usz);
         self.array_yzx = Array3::zeros(shape_yzx);
+        self.biomes = None;
+        self.light = None;
         //let shape_zx = [shape_xyz[2], shape_xyz[1]];
         // self.sky_block_light = Array3::default(shape_yzx);
         // self.sky_block_light.fill(Light::default());
@@ -591,4 +582,53 @@ impl Region {
             &mut []
         };
     }
+
+    /// Get biome at `r_pos`. Returns `None` if the region has no biomes or `r_pos` is outside the
+    /// region. For per-column biomes, y is ignored.
+    pub fn biome_at(&self, r_pos: [i32; 3]) -> Option<Biome> {
+        if !self.contains_coord(r_pos) {
+            return None;
+        }
+        let [x, y, z] = Self::i32_to_usize(&r_pos);
+        return match &self.biomes {
+            None => None,
+            Some(RegionBiomes::Columns(arr)) => arr.get([z, x]).copied(),
+            Some(RegionBiomes::Blocks(arr)) => arr.get([y, z, x]).copied(),
+        };
+    }
+
+    /// Get sky light and block light at `r_pos`. Returns `None` if the region has no light or
+    /// `r_pos` is outside the region.
+    pub fn light_at(&self, r_pos: [i32; 3]) -> Option<Light> {
+        if !self.contains_coord(r_pos) {
+            return None;
+        }
+        let [x, y, z] = Self::i32_to_usize(&r_pos);
+        return self.light.as_ref()?.get([y, z, x]).copied();
+    }
+
+    /// Fill the region with `biome` in given resolution, replacing existing biomes
+    pub fn fill_biome(&mut self, biome: Biome, per_block: bool) {
+        let (y, z, x) = self.array_yzx.dim();
+        self.biomes = Some(if per_block {
+            RegionBiomes::Blocks(Array3::from_elem([y, z, x], biome))
+        } else {
+            RegionBiomes::Columns(Array2::from_elem([z, x], biome))
+        });
+    }
+
+    /// Set biome at `r_pos`. For per-column biomes, the whole column is set. Returns `Err` if the
+    /// region has no biomes or `r_pos` is outside the region.
+    pub fn set_biome(&mut self, r_pos: [i32; 3], biome: Biome) -> Result<(), ()> {
+        if !self.contains_coord(r_pos) {
+            return Err(());
+        }
+        let [x, y, z] = Self::i32_to_usize(&r_pos);
+        match &mut self.biomes {
+            None => return Err(()),
+            Some(RegionBiomes::Columns(arr)) => arr[[z, x]] = biome,
+            Some(RegionBiomes::Blocks(arr)) => arr[[y, z, x]] = biome,
+        }
+        return Ok(());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,172 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::block::Block;
+use crate::region::{Region, WorldSlice};
+use crate::render::{Image, MapColor};
+use crate::render::map_color::MapShade;
+
+/// Width and height of a map in pixels
+pub const MAP_SIZE: usize = 128;
+
+/// Blocks that are always filled with water
+const WATER_FILLED_IDS: &[&str] = &["water", "bubble_column", "kelp", "kelp_plant", "seagrass", "tall_seagrass"];
+
+/// Options to compute map art
+#[derive(Debug, Clone)]
+pub struct MapArtOption {
+    /// Relative x and z of the north-west corner of the map in region. The row right to the north
+    /// of the map is used to shade the first row, like vanilla maps.
+    pub origin: [i32; 2],
+}
+
+impl Default for MapArtOption {
+    fn default() -> Self {
+        return MapArtOption {
+            origin: [0, 0],
+        };
+    }
+}
+
+/// Colors of a 128x128 map
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapArt {
+    /// `base color id * 4 + shade` of each pixel, indexed by `z * 128 + x`. Same as `colors` in
+    /// `map_<id>.dat`.
+    pub colors: Vec<u8>,
+}
+
+fn is_water_filled(blk: &Block) -> bool {
+    if !(blk.namespace.is_empty() || blk.namespace == "minecraft") {
+        return false;
+    }
+    return WATER_FILLED_IDS.contains(&blk.id.as_str())
+        || blk.attributes.get("waterlogged").is_some_and(|v| v == "true");
+}
+
+/// The topmost visible block in a column
+struct ColumnTop {
+    color: MapColor,
+    y: i32,
+    /// Count of water filled blocks from the top, 0 if the top isn't water
+    water_depth: i32,
+}
+
+impl MapArt {
+    /// A map of transparent pixels
+    pub fn new() -> MapArt {
+        return MapArt { colors: vec![0; MAP_SIZE * MAP_SIZE] };
+    }
+
+    /// Load from `colors` of `map_<id>.dat`, returns `None` if the length is not 128*128
+    pub fn from_byte_array(colors: &[i8]) -> Option<MapArt> {
+        if colors.len() != MAP_SIZE * MAP_SIZE {
+            return None;
+        }
+        return Some(MapArt { colors: colors.iter().map(|c| *c as u8).collect() });
+    }
+
+    /// Same as `colors` of `map_<id>.dat`
+    pub fn to_byte_array(&self) -> Vec<i8> {
+        return self.colors.iter().map(|c| *c as i8).collect();
+    }
+
+    /// Base color and shade at `x` and `z`, returns `None` for invalid color bytes
+    pub fn color_at(&self, x: usize, z: usize) -> Option<(MapColor, MapShade)> {
+        return MapColor::from_color_byte(self.colors[z * MAP_SIZE + x]);
+    }
+
+    /// Render as a 128x128 image, transparent pixels are kept transparent
+    pub fn to_image(&self) -> Image {
+        let mut image = Image::new(MAP_SIZE as u32, MAP_SIZE as u32);
+        for (pixel, color) in image.pixels.iter_mut().zip(self.colors.iter()) {
+            *pixel = MapColor::rgba_of_color_byte(*color);
+        }
+        return image;
+    }
+
+    /// Convert a 128x128 image to map colors, each pixel takes the nearest shaded color. Returns
+    /// `None` if the size of image is not 128x128.
+    pub fn from_image(image: &Image) -> Option<MapArt> {
+        if image.width as usize != MAP_SIZE || image.height as usize != MAP_SIZE {
+            return None;
+        }
+        return Some(MapArt { colors: image.pixels.iter().map(|p| MapColor::nearest_color_byte(*p)).collect() });
+    }
+}
+
+impl Default for MapArt {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+impl Region {
+    fn map_column_top(&self, x: i32, z: i32) -> Option<ColumnTop> {
+        let shape = self.shape();
+        if x < 0 || z < 0 || x >= shape[0] || z >= shape[2] {
+            return None;
+        }
+        for y in (0..shape[1]).rev() {
+            let blk = self.block_at([x, y, z]).unwrap();
+            let color = blk.map_color();
+            if color.is_transparent() {
+                continue;
+            }
+            if !is_water_filled(blk) {
+                return Some(ColumnTop { color, y, water_depth: 0 });
+            }
+            let depth = (0..=y).rev()
+                .take_while(|wy| is_water_filled(self.block_at([x, *wy, z]).unwrap()))
+                .count() as i32;
+            return Some(ColumnTop { color: MapColor::Water, y, water_depth: depth });
+        }
+        return None;
+    }
+
+    /// Compute colors of a map covering 128x128 columns from `option.origin`, like a vanilla map
+    /// of scale 0. Each column shows its topmost visible block. Blocks higher than the block to
+    /// the north are light and lower ones are dark. Water is shaded by depth instead, with a
+    /// checkerboard pattern in the middle depths. Columns outside the region are transparent.
+    pub fn map_art(&self, option: &MapArtOption) -> MapArt {
+        let mut result = MapArt::new();
+        let [x0, z0] = option.origin;
+        for x in 0..MAP_SIZE {
+            let mut north_y = self.map_column_top(x0 + x as i32, z0 - 1).map_or(-1, |top| top.y);
+            for z in 0..MAP_SIZE {
+                let Some(top) = self.map_column_top(x0 + x as i32, z0 + z as i32) else {
+                    north_y = -1;
+                    continue;
+                };
+                let shade = if top.color == MapColor::Water {
+                    let f = top.water_depth as f64 * 0.1 + ((x + z) & 1) as f64 * 0.2;
+                    if f < 0.5 { MapShade::Light } else if f > 0.9 { MapShade::Dark } else { MapShade::Normal }
+                } else if top.y > north_y {
+                    MapShade::Light
+                } else if top.y < north_y {
+                    MapShade::Dark
+                } else {
+                    MapShade::Normal
+                };
+                result.colors[z * MAP_SIZE + x] = top.color.to_color_byte(shade);
+                north_y = top.y;
+            }
+        }
+        return result;
+    }
+}
@@ -0,0 +1,681 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use strum::{Display, EnumIter, IntoEnumIterator};
+use crate::block::Block;
+
+/// Base colors of blocks on maps, same as `MapColor` in vanilla.
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, EnumIter, Display)]
+pub enum MapColor {
+    None = 0,
+    Grass = 1,
+    Sand = 2,
+    Wool = 3,
+    Fire = 4,
+    Ice = 5,
+    Metal = 6,
+    Plant = 7,
+    Snow = 8,
+    Clay = 9,
+    Dirt = 10,
+    Stone = 11,
+    Water = 12,
+    Wood = 13,
+    Quartz = 14,
+    ColorOrange = 15,
+    ColorMagenta = 16,
+    ColorLightBlue = 17,
+    ColorYellow = 18,
+    ColorLightGreen = 19,
+    ColorPink = 20,
+    ColorGray = 21,
+    ColorLightGray = 22,
+    ColorCyan = 23,
+    ColorPurple = 24,
+    ColorBlue = 25,
+    ColorBrown = 26,
+    ColorGreen = 27,
+    ColorRed = 28,
+    ColorBlack = 29,
+    Gold = 30,
+    Diamond = 31,
+    Lapis = 32,
+    Emerald = 33,
+    Podzol = 34,
+    Nether = 35,
+    TerracottaWhite = 36,
+    TerracottaOrange = 37,
+    TerracottaMagenta = 38,
+    TerracottaLightBlue = 39,
+    TerracottaYellow = 40,
+    TerracottaLightGreen = 41,
+    TerracottaPink = 42,
+    TerracottaGray = 43,
+    TerracottaLightGray = 44,
+    TerracottaCyan = 45,
+    TerracottaPurple = 46,
+    TerracottaBlue = 47,
+    TerracottaBrown = 48,
+    TerracottaGreen = 49,
+    TerracottaRed = 50,
+    TerracottaBlack = 51,
+    CrimsonNylium = 52,
+    CrimsonStem = 53,
+    CrimsonHyphae = 54,
+    WarpedNylium = 55,
+    WarpedStem = 56,
+    WarpedHyphae = 57,
+    WarpedWartBlock = 58,
+    Deepslate = 59,
+    RawIron = 60,
+    GlowLichen = 61,
+}
+
+/// Brightness of map colors. A byte in `colors` of maps is `base color id * 4 + shade`.
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, EnumIter, Display)]
+pub enum MapShade {
+    /// The block is lower than its north neighbor
+    Dark = 0,
+    /// The block is as high as its north neighbor
+    Normal = 1,
+    /// The block is higher than its north neighbor
+    Light = 2,
+    /// Not used by vanilla maps, but can be stored in map files
+    Darkest = 3,
+}
+
+/// Rgb of each map color, indexed by id
+const MAP_COLOR_RGB: [u32; 62] = [
+    0x000000, 0x7FB238, 0xF7E9A3, 0xC7C7C7, 0xFF0000, 0xA0A0FF, 0xA7A7A7, 0x007C00,
+    0xFFFFFF, 0xA4A8B8, 0x976D4D, 0x707070, 0x4040FF, 0x8F7748, 0xFFFCF5, 0xD87F33,
+    0xB24CD8, 0x6699D8, 0xE5E533, 0x7FCC19, 0xF27FA5, 0x4C4C4C, 0x999999, 0x4C7F99,
+    0x7F3FB2, 0x334CB2, 0x664C33, 0x667F33, 0x993333, 0x191919, 0xFAEE4D, 0x5CDBD5,
+    0x4A80FF, 0x00D93A, 0x815631, 0x700200, 0xD1B1A1, 0x9F5224, 0x95576C, 0x706C8A,
+    0xBA8524, 0x677535, 0xA04D4E, 0x392923, 0x876B62, 0x575C5C, 0x7A4958, 0x4C3E5C,
+    0x4C3223, 0x4C522A, 0x8E3C2E, 0x251610, 0xBD3031, 0x943F61, 0x5C191D, 0x167E86,
+    0x3A8E8C, 0x562C3E, 0x14B485, 0x646464, 0xD8AF93, 0x7FA796,
+];
+
+/// Map colors of vanilla blocks, same as the `mapColor` of each block in vanilla. Logs are in
+/// `LOG_COLORS`, since their colors depend on the axis.
+const BLOCK_COLORS: &[(MapColor, &[&str])] = &[
+    (MapColor::None, &[
+        "air", "cave_air", "void_air", "structure_void", "barrier", "light", "glass", "glass_pane", "iron_bars",
+        "chain", "lever", "ladder", "tripwire", "tripwire_hook", "redstone_wire", "repeater", "comparator",
+        "flower_pot", "end_rod", "moving_piston", "nether_portal", "torch", "wall_torch", "soul_torch",
+        "soul_wall_torch", "redstone_torch", "redstone_wall_torch", "rail", "powered_rail", "detector_rail",
+        "activator_rail", "stone_button", "polished_blackstone_button", "skeleton_skull", "skeleton_wall_skull",
+        "wither_skeleton_skull", "wither_skeleton_wall_skull", "zombie_head", "zombie_wall_head", "player_head",
+        "player_wall_head", "creeper_head", "creeper_wall_head", "dragon_head", "dragon_wall_head", "piglin_head",
+        "piglin_wall_head", "oak_button", "spruce_button", "birch_button", "jungle_button", "acacia_button",
+        "dark_oak_button", "mangrove_button", "cherry_button", "bamboo_button", "crimson_button", "warped_button",
+        "white_stained_glass_pane", "orange_stained_glass_pane", "magenta_stained_glass_pane",
+        "light_blue_stained_glass_pane", "yellow_stained_glass_pane", "lime_stained_glass_pane",
+        "pink_stained_glass_pane", "gray_stained_glass_pane", "light_gray_stained_glass_pane",
+        "cyan_stained_glass_pane", "purple_stained_glass_pane", "blue_stained_glass_pane", "brown_stained_glass_pane",
+        "green_stained_glass_pane", "red_stained_glass_pane", "black_stained_glass_pane",
+    ]),
+    (MapColor::Grass, &[
+        "grass_block", "slime_block",
+    ]),
+    (MapColor::Sand, &[
+        "sand", "suspicious_sand", "chiseled_sandstone", "cut_sandstone", "cut_sandstone_slab", "sandstone",
+        "sandstone_slab", "sandstone_stairs", "sandstone_wall", "smooth_sandstone", "smooth_sandstone_slab",
+        "smooth_sandstone_stairs", "birch_planks", "birch_slab", "birch_stairs", "birch_fence", "birch_fence_gate",
+        "birch_door", "birch_trapdoor", "birch_pressure_plate", "birch_sign", "birch_wall_sign", "birch_hanging_sign",
+        "birch_wall_hanging_sign", "stripped_birch_log", "stripped_birch_wood", "birch_wood", "glowstone",
+        "bone_block", "turtle_egg", "scaffolding", "candle", "ochre_froglight", "end_stone", "end_stone_bricks",
+        "end_stone_brick_slab", "end_stone_brick_stairs", "end_stone_brick_wall",
+    ]),
+    (MapColor::Wool, &[
+        "mushroom_stem", "cobweb",
+    ]),
+    (MapColor::Fire, &[
+        "lava", "fire", "tnt", "redstone_block",
+    ]),
+    (MapColor::Ice, &[
+        "ice", "packed_ice", "blue_ice", "frosted_ice",
+    ]),
+    (MapColor::Metal, &[
+        "iron_block", "anvil", "chipped_anvil", "damaged_anvil", "iron_door", "iron_trapdoor",
+        "heavy_weighted_pressure_plate", "brewing_stand", "grindstone", "lantern", "soul_lantern", "lodestone",
+    ]),
+    (MapColor::Plant, &[
+        "short_grass", "grass", "fern", "tall_grass", "large_fern", "dandelion", "poppy", "blue_orchid", "allium",
+        "azure_bluet", "red_tulip", "orange_tulip", "white_tulip", "pink_tulip", "oxeye_daisy", "cornflower",
+        "lily_of_the_valley", "wither_rose", "torchflower", "torchflower_crop", "sunflower", "lilac", "rose_bush",
+        "peony", "pitcher_plant", "pitcher_crop", "wheat", "carrots", "potatoes", "beetroots", "melon_stem",
+        "pumpkin_stem", "attached_melon_stem", "attached_pumpkin_stem", "sweet_berry_bush", "cactus", "sugar_cane",
+        "bamboo", "lily_pad", "vine", "cocoa", "azalea", "flowering_azalea", "azalea_leaves",
+        "flowering_azalea_leaves", "cave_vines", "cave_vines_plant", "big_dripleaf", "big_dripleaf_stem",
+        "small_dripleaf", "spore_blossom", "pink_petals", "mangrove_propagule", "oak_leaves", "spruce_leaves",
+        "birch_leaves", "jungle_leaves", "acacia_leaves", "dark_oak_leaves", "mangrove_leaves", "oak_sapling",
+        "spruce_sapling", "birch_sapling", "jungle_sapling", "acacia_sapling", "dark_oak_sapling",
+    ]),
+    (MapColor::Snow, &[
+        "snow", "snow_block", "powder_snow", "white_wool", "white_carpet", "white_bed", "white_concrete",
+        "white_concrete_powder", "white_glazed_terracotta", "white_shulker_box", "white_stained_glass", "white_candle",
+    ]),
+    (MapColor::Clay, &[
+        "infested_stone", "infested_cobblestone", "infested_stone_bricks", "infested_mossy_stone_bricks",
+        "infested_cracked_stone_bricks", "infested_chiseled_stone_bricks", "clay",
+    ]),
+    (MapColor::Dirt, &[
+        "granite", "granite_slab", "granite_stairs", "granite_wall", "polished_granite", "polished_granite_slab",
+        "polished_granite_stairs", "packed_mud", "dirt", "coarse_dirt", "rooted_dirt", "farmland", "dirt_path",
+        "grass_path", "jukebox", "brown_mushroom_block", "hanging_roots", "jungle_planks", "jungle_slab",
+        "jungle_stairs", "jungle_fence", "jungle_fence_gate", "jungle_door", "jungle_trapdoor",
+        "jungle_pressure_plate", "jungle_sign", "jungle_wall_sign", "jungle_hanging_sign", "jungle_wall_hanging_sign",
+        "stripped_jungle_log", "stripped_jungle_wood", "jungle_wood",
+    ]),
+    (MapColor::Stone, &[
+        "stone", "stone_slab", "stone_stairs", "stone_pressure_plate", "smooth_stone", "smooth_stone_slab",
+        "cobblestone", "cobblestone_slab", "cobblestone_stairs", "cobblestone_wall", "mossy_cobblestone",
+        "mossy_cobblestone_slab", "mossy_cobblestone_stairs", "mossy_cobblestone_wall", "stone_bricks",
+        "stone_brick_slab", "stone_brick_stairs", "stone_brick_wall", "mossy_stone_bricks", "mossy_stone_brick_slab",
+        "mossy_stone_brick_stairs", "mossy_stone_brick_wall", "cracked_stone_bricks", "chiseled_stone_bricks",
+        "andesite", "andesite_slab", "andesite_stairs", "andesite_wall", "polished_andesite", "polished_andesite_slab",
+        "polished_andesite_stairs", "bedrock", "gravel", "suspicious_gravel", "coal_ore", "iron_ore", "copper_ore",
+        "gold_ore", "redstone_ore", "emerald_ore", "lapis_ore", "diamond_ore", "spawner", "furnace", "smoker",
+        "blast_furnace", "dispenser", "dropper", "observer", "piston", "sticky_piston", "piston_head", "hopper",
+        "cauldron", "water_cauldron", "lava_cauldron", "powder_snow_cauldron", "stonecutter", "ender_chest",
+    ]),
+    (MapColor::Water, &[
+        "water", "bubble_column", "kelp", "kelp_plant", "seagrass", "tall_seagrass", "frogspawn",
+    ]),
+    (MapColor::Wood, &[
+        "dead_bush", "bamboo_sapling", "oak_planks", "oak_slab", "oak_stairs", "oak_fence", "oak_fence_gate",
+        "oak_door", "oak_trapdoor", "oak_pressure_plate", "oak_sign", "oak_wall_sign", "oak_hanging_sign",
+        "oak_wall_hanging_sign", "stripped_oak_log", "stripped_oak_wood", "oak_wood", "petrified_oak_slab", "chest",
+        "trapped_chest", "barrel", "crafting_table", "cartography_table", "fletching_table", "smithing_table", "loom",
+        "lectern", "composter", "beehive", "note_block", "bookshelf", "chiseled_bookshelf", "daylight_detector",
+        "white_banner", "white_wall_banner", "orange_banner", "orange_wall_banner", "magenta_banner",
+        "magenta_wall_banner", "light_blue_banner", "light_blue_wall_banner", "yellow_banner", "yellow_wall_banner",
+        "lime_banner", "lime_wall_banner", "pink_banner", "pink_wall_banner", "gray_banner", "gray_wall_banner",
+        "light_gray_banner", "light_gray_wall_banner", "cyan_banner", "cyan_wall_banner", "purple_banner",
+        "purple_wall_banner", "blue_banner", "blue_wall_banner", "brown_banner", "brown_wall_banner", "green_banner",
+        "green_wall_banner", "red_banner", "red_wall_banner", "black_banner", "black_wall_banner",
+    ]),
+    (MapColor::Quartz, &[
+        "diorite", "diorite_slab", "diorite_stairs", "diorite_wall", "polished_diorite", "polished_diorite_slab",
+        "polished_diorite_stairs", "quartz_block", "quartz_slab", "quartz_stairs", "smooth_quartz",
+        "smooth_quartz_slab", "smooth_quartz_stairs", "chiseled_quartz_block", "quartz_pillar", "quartz_bricks",
+        "sea_lantern", "target",
+    ]),
+    (MapColor::ColorOrange, &[
+        "red_sand", "chiseled_red_sandstone", "cut_red_sandstone", "cut_red_sandstone_slab", "red_sandstone",
+        "red_sandstone_slab", "red_sandstone_stairs", "red_sandstone_wall", "smooth_red_sandstone",
+        "smooth_red_sandstone_slab", "smooth_red_sandstone_stairs", "pumpkin", "carved_pumpkin", "jack_o_lantern",
+        "honey_block", "honeycomb_block", "terracotta", "raw_copper_block", "lightning_rod", "acacia_planks",
+        "acacia_slab", "acacia_stairs", "acacia_fence", "acacia_fence_gate", "acacia_door", "acacia_trapdoor",
+        "acacia_pressure_plate", "acacia_sign", "acacia_wall_sign", "acacia_hanging_sign", "acacia_wall_hanging_sign",
+        "stripped_acacia_log", "stripped_acacia_wood", "copper_block", "cut_copper", "cut_copper_slab",
+        "cut_copper_stairs", "waxed_copper_block", "waxed_cut_copper", "waxed_cut_copper_slab",
+        "waxed_cut_copper_stairs", "orange_wool", "orange_carpet", "orange_bed", "orange_concrete",
+        "orange_concrete_powder", "orange_glazed_terracotta", "orange_shulker_box", "orange_stained_glass",
+        "orange_candle",
+    ]),
+    (MapColor::ColorMagenta, &[
+        "purpur_block", "purpur_slab", "purpur_stairs", "purpur_pillar", "magenta_wool", "magenta_carpet",
+        "magenta_bed", "magenta_concrete", "magenta_concrete_powder", "magenta_glazed_terracotta",
+        "magenta_shulker_box", "magenta_stained_glass", "magenta_candle",
+    ]),
+    (MapColor::ColorLightBlue, &[
+        "soul_fire", "light_blue_wool", "light_blue_carpet", "light_blue_bed", "light_blue_concrete",
+        "light_blue_concrete_powder", "light_blue_glazed_terracotta", "light_blue_shulker_box",
+        "light_blue_stained_glass", "light_blue_candle",
+    ]),
+    (MapColor::ColorYellow, &[
+        "hay_block", "sponge", "wet_sponge", "bee_nest", "bamboo_mosaic", "bamboo_mosaic_slab", "bamboo_mosaic_stairs",
+        "stripped_bamboo_block", "bamboo_planks", "bamboo_slab", "bamboo_stairs", "bamboo_fence", "bamboo_fence_gate",
+        "bamboo_door", "bamboo_trapdoor", "bamboo_pressure_plate", "bamboo_sign", "bamboo_wall_sign",
+        "bamboo_hanging_sign", "bamboo_wall_hanging_sign", "horn_coral_block", "horn_coral", "horn_coral_fan",
+        "horn_coral_wall_fan", "yellow_wool", "yellow_carpet", "yellow_bed", "yellow_concrete",
+        "yellow_concrete_powder", "yellow_glazed_terracotta", "yellow_shulker_box", "yellow_stained_glass",
+        "yellow_candle",
+    ]),
+    (MapColor::ColorLightGreen, &[
+        "melon", "lime_wool", "lime_carpet", "lime_bed", "lime_concrete", "lime_concrete_powder",
+        "lime_glazed_terracotta", "lime_shulker_box", "lime_stained_glass", "lime_candle",
+    ]),
+    (MapColor::ColorPink, &[
+        "cherry_leaves", "cherry_sapling", "pearlescent_froglight", "brain_coral_block", "brain_coral",
+        "brain_coral_fan", "brain_coral_wall_fan", "pink_wool", "pink_carpet", "pink_bed", "pink_concrete",
+        "pink_concrete_powder", "pink_glazed_terracotta", "pink_shulker_box", "pink_stained_glass", "pink_candle",
+    ]),
+    (MapColor::ColorGray, &[
+        "acacia_wood", "tinted_glass", "dead_tube_coral_block", "dead_tube_coral", "dead_tube_coral_fan",
+        "dead_tube_coral_wall_fan", "dead_brain_coral_block", "dead_brain_coral", "dead_brain_coral_fan",
+        "dead_brain_coral_wall_fan", "dead_bubble_coral_block", "dead_bubble_coral", "dead_bubble_coral_fan",
+        "dead_bubble_coral_wall_fan", "dead_fire_coral_block", "dead_fire_coral", "dead_fire_coral_fan",
+        "dead_fire_coral_wall_fan", "dead_horn_coral_block", "dead_horn_coral", "dead_horn_coral_fan",
+        "dead_horn_coral_wall_fan", "gray_wool", "gray_carpet", "gray_bed", "gray_concrete", "gray_concrete_powder",
+        "gray_glazed_terracotta", "gray_shulker_box", "gray_stained_glass", "gray_candle",
+    ]),
+    (MapColor::ColorLightGray, &[
+        "structure_block", "jigsaw", "light_gray_wool", "light_gray_carpet", "light_gray_bed", "light_gray_concrete",
+        "light_gray_concrete_powder", "light_gray_glazed_terracotta", "light_gray_shulker_box",
+        "light_gray_stained_glass", "light_gray_candle",
+    ]),
+    (MapColor::ColorCyan, &[
+        "warped_fungus", "warped_roots", "nether_sprouts", "twisting_vines", "twisting_vines_plant", "sculk_sensor",
+        "calibrated_sculk_sensor", "prismarine", "prismarine_slab", "prismarine_stairs", "prismarine_wall",
+        "cyan_wool", "cyan_carpet", "cyan_bed", "cyan_concrete", "cyan_concrete_powder", "cyan_glazed_terracotta",
+        "cyan_shulker_box", "cyan_stained_glass", "cyan_candle",
+    ]),
+    (MapColor::ColorPurple, &[
+        "mycelium", "chorus_plant", "chorus_flower", "amethyst_block", "budding_amethyst", "amethyst_cluster",
+        "large_amethyst_bud", "medium_amethyst_bud", "small_amethyst_bud", "shulker_box", "repeating_command_block",
+        "bubble_coral_block", "bubble_coral", "bubble_coral_fan", "bubble_coral_wall_fan", "purple_wool",
+        "purple_carpet", "purple_bed", "purple_concrete", "purple_concrete_powder", "purple_glazed_terracotta",
+        "purple_shulker_box", "purple_stained_glass", "purple_candle",
+    ]),
+    (MapColor::ColorBlue, &[
+        "tube_coral_block", "tube_coral", "tube_coral_fan", "tube_coral_wall_fan", "blue_wool", "blue_carpet",
+        "blue_bed", "blue_concrete", "blue_concrete_powder", "blue_glazed_terracotta", "blue_shulker_box",
+        "blue_stained_glass", "blue_candle",
+    ]),
+    (MapColor::ColorBrown, &[
+        "brown_mushroom", "soul_sand", "soul_soil", "command_block", "dark_oak_planks", "dark_oak_slab",
+        "dark_oak_stairs", "dark_oak_fence", "dark_oak_fence_gate", "dark_oak_door", "dark_oak_trapdoor",
+        "dark_oak_pressure_plate", "dark_oak_sign", "dark_oak_wall_sign", "dark_oak_hanging_sign",
+        "dark_oak_wall_hanging_sign", "stripped_dark_oak_log", "stripped_dark_oak_wood", "dark_oak_wood", "brown_wool",
+        "brown_carpet", "brown_bed", "brown_concrete", "brown_concrete_powder", "brown_glazed_terracotta",
+        "brown_shulker_box", "brown_stained_glass", "brown_candle",
+    ]),
+    (MapColor::ColorGreen, &[
+        "moss_block", "moss_carpet", "dried_kelp_block", "sea_pickle", "end_portal_frame", "chain_command_block",
+        "green_wool", "green_carpet", "green_bed", "green_concrete", "green_concrete_powder",
+        "green_glazed_terracotta", "green_shulker_box", "green_stained_glass", "green_candle",
+    ]),
+    (MapColor::ColorRed, &[
+        "bricks", "brick_slab", "brick_stairs", "brick_wall", "red_mushroom", "red_mushroom_block", "nether_wart",
+        "nether_wart_block", "shroomlight", "enchanting_table", "sniffer_egg", "mangrove_planks", "mangrove_slab",
+        "mangrove_stairs", "mangrove_fence", "mangrove_fence_gate", "mangrove_door", "mangrove_trapdoor",
+        "mangrove_pressure_plate", "mangrove_sign", "mangrove_wall_sign", "mangrove_hanging_sign",
+        "mangrove_wall_hanging_sign", "stripped_mangrove_log", "stripped_mangrove_wood", "mangrove_wood",
+        "fire_coral_block", "fire_coral", "fire_coral_fan", "fire_coral_wall_fan", "red_wool", "red_carpet", "red_bed",
+        "red_concrete", "red_concrete_powder", "red_glazed_terracotta", "red_shulker_box", "red_stained_glass",
+        "red_candle",
+    ]),
+    (MapColor::ColorBlack, &[
+        "obsidian", "crying_obsidian", "coal_block", "netherite_block", "ancient_debris", "respawn_anchor", "basalt",
+        "polished_basalt", "smooth_basalt", "gilded_blackstone", "chiseled_polished_blackstone",
+        "cracked_polished_blackstone_bricks", "polished_blackstone_pressure_plate", "dragon_egg", "end_portal",
+        "end_gateway", "sculk", "sculk_vein", "sculk_catalyst", "sculk_shrieker", "blackstone", "blackstone_slab",
+        "blackstone_stairs", "blackstone_wall", "polished_blackstone", "polished_blackstone_slab",
+        "polished_blackstone_stairs", "polished_blackstone_wall", "polished_blackstone_bricks",
+        "polished_blackstone_brick_slab", "polished_blackstone_brick_stairs", "polished_blackstone_brick_wall",
+        "black_wool", "black_carpet", "black_bed", "black_concrete", "black_concrete_powder",
+        "black_glazed_terracotta", "black_shulker_box", "black_stained_glass", "black_candle",
+    ]),
+    (MapColor::Gold, &[
+        "gold_block", "raw_gold_block", "bell", "light_weighted_pressure_plate",
+    ]),
+    (MapColor::Diamond, &[
+        "prismarine_bricks", "prismarine_brick_slab", "prismarine_brick_stairs", "dark_prismarine",
+        "dark_prismarine_slab", "dark_prismarine_stairs", "diamond_block", "beacon", "conduit",
+    ]),
+    (MapColor::Lapis, &[
+        "lapis_block",
+    ]),
+    (MapColor::Emerald, &[
+        "emerald_block",
+    ]),
+    (MapColor::Podzol, &[
+        "podzol", "mangrove_roots", "muddy_mangrove_roots", "campfire", "soul_campfire", "spruce_planks",
+        "spruce_slab", "spruce_stairs", "spruce_fence", "spruce_fence_gate", "spruce_door", "spruce_trapdoor",
+        "spruce_pressure_plate", "spruce_sign", "spruce_wall_sign", "spruce_hanging_sign", "spruce_wall_hanging_sign",
+        "stripped_spruce_log", "stripped_spruce_wood", "spruce_wood",
+    ]),
+    (MapColor::Nether, &[
+        "crimson_fungus", "crimson_roots", "weeping_vines", "weeping_vines_plant", "netherrack", "nether_gold_ore",
+        "nether_quartz_ore", "magma_block", "nether_brick_fence", "nether_bricks", "nether_brick_slab",
+        "nether_brick_stairs", "nether_brick_wall", "red_nether_bricks", "red_nether_brick_slab",
+        "red_nether_brick_stairs", "red_nether_brick_wall", "chiseled_nether_bricks", "cracked_nether_bricks",
+    ]),
+    (MapColor::TerracottaWhite, &[
+        "calcite", "cherry_planks", "cherry_slab", "cherry_stairs", "cherry_fence", "cherry_fence_gate", "cherry_door",
+        "cherry_trapdoor", "cherry_pressure_plate", "cherry_sign", "cherry_wall_sign", "cherry_hanging_sign",
+        "cherry_wall_hanging_sign", "stripped_cherry_log", "stripped_cherry_wood", "white_terracotta",
+    ]),
+    (MapColor::TerracottaOrange, &[
+        "orange_terracotta",
+    ]),
+    (MapColor::TerracottaMagenta, &[
+        "magenta_terracotta",
+    ]),
+    (MapColor::TerracottaLightBlue, &[
+        "light_blue_terracotta",
+    ]),
+    (MapColor::TerracottaYellow, &[
+        "yellow_terracotta",
+    ]),
+    (MapColor::TerracottaLightGreen, &[
+        "lime_terracotta",
+    ]),
+    (MapColor::TerracottaPink, &[
+        "pink_terracotta",
+    ]),
+    (MapColor::TerracottaGray, &[
+        "tuff", "tuff_slab", "tuff_stairs", "tuff_wall", "polished_tuff", "polished_tuff_slab", "polished_tuff_stairs",
+        "polished_tuff_wall", "tuff_bricks", "tuff_brick_slab", "tuff_brick_stairs", "tuff_brick_wall",
+        "chiseled_tuff", "chiseled_tuff_bricks", "cherry_wood", "gray_terracotta",
+    ]),
+    (MapColor::TerracottaLightGray, &[
+        "mud_bricks", "mud_brick_slab", "mud_brick_stairs", "mud_brick_wall", "exposed_copper", "exposed_cut_copper",
+        "exposed_cut_copper_slab", "exposed_cut_copper_stairs", "waxed_exposed_copper", "waxed_exposed_cut_copper",
+        "waxed_exposed_cut_copper_slab", "waxed_exposed_cut_copper_stairs", "light_gray_terracotta",
+    ]),
+    (MapColor::TerracottaCyan, &[
+        "mud", "cyan_terracotta",
+    ]),
+    (MapColor::TerracottaPurple, &[
+        "purple_terracotta",
+    ]),
+    (MapColor::TerracottaBlue, &[
+        "blue_terracotta",
+    ]),
+    (MapColor::TerracottaBrown, &[
+        "dripstone_block", "pointed_dripstone", "brown_terracotta",
+    ]),
+    (MapColor::TerracottaGreen, &[
+        "green_terracotta",
+    ]),
+    (MapColor::TerracottaRed, &[
+        "decorated_pot", "red_terracotta",
+    ]),
+    (MapColor::TerracottaBlack, &[
+        "black_terracotta",
+    ]),
+    (MapColor::CrimsonNylium, &[
+        "crimson_nylium",
+    ]),
+    (MapColor::CrimsonStem, &[
+        "crimson_planks", "crimson_slab", "crimson_stairs", "crimson_fence", "crimson_fence_gate", "crimson_door",
+        "crimson_trapdoor", "crimson_pressure_plate", "crimson_sign", "crimson_wall_sign", "crimson_hanging_sign",
+        "crimson_wall_hanging_sign", "crimson_stem", "stripped_crimson_stem",
+    ]),
+    (MapColor::CrimsonHyphae, &[
+        "crimson_hyphae", "stripped_crimson_hyphae",
+    ]),
+    (MapColor::WarpedNylium, &[
+        "warped_nylium", "oxidized_copper", "oxidized_cut_copper", "oxidized_cut_copper_slab",
+        "oxidized_cut_copper_stairs", "waxed_oxidized_copper", "waxed_oxidized_cut_copper",
+        "waxed_oxidized_cut_copper_slab", "waxed_oxidized_cut_copper_stairs",
+    ]),
+    (MapColor::WarpedStem, &[
+        "warped_planks", "warped_slab", "warped_stairs", "warped_fence", "warped_fence_gate", "warped_door",
+        "warped_trapdoor", "warped_pressure_plate", "warped_sign", "warped_wall_sign", "warped_hanging_sign",
+        "warped_wall_hanging_sign", "warped_stem", "stripped_warped_stem", "weathered_copper", "weathered_cut_copper",
+        "weathered_cut_copper_slab", "weathered_cut_copper_stairs", "waxed_weathered_copper",
+        "waxed_weathered_cut_copper", "waxed_weathered_cut_copper_slab", "waxed_weathered_cut_copper_stairs",
+    ]),
+    (MapColor::WarpedHyphae, &[
+        "warped_hyphae", "stripped_warped_hyphae",
+    ]),
+    (MapColor::WarpedWartBlock, &[
+        "warped_wart_block",
+    ]),
+    (MapColor::Deepslate, &[
+        "deepslate", "chiseled_deepslate", "cracked_deepslate_bricks", "cracked_deepslate_tiles",
+        "reinforced_deepslate", "infested_deepslate", "cobbled_deepslate", "cobbled_deepslate_slab",
+        "cobbled_deepslate_stairs", "cobbled_deepslate_wall", "polished_deepslate", "polished_deepslate_slab",
+        "polished_deepslate_stairs", "polished_deepslate_wall", "deepslate_bricks", "deepslate_brick_slab",
+        "deepslate_brick_stairs", "deepslate_brick_wall", "deepslate_tiles", "deepslate_tile_slab",
+        "deepslate_tile_stairs", "deepslate_tile_wall", "deepslate_coal_ore", "deepslate_iron_ore",
+        "deepslate_copper_ore", "deepslate_gold_ore", "deepslate_redstone_ore", "deepslate_emerald_ore",
+        "deepslate_lapis_ore", "deepslate_diamond_ore",
+    ]),
+    (MapColor::RawIron, &[
+        "raw_iron_block",
+    ]),
+    (MapColor::GlowLichen, &[
+        "glow_lichen", "verdant_froglight",
+    ]),
+];
+
+/// Logs with the map color of their top and side
+const LOG_COLORS: &[(&str, MapColor, MapColor)] = &[
+    ("oak_log", MapColor::Wood, MapColor::Podzol),
+    ("spruce_log", MapColor::Podzol, MapColor::ColorBrown),
+    ("birch_log", MapColor::Sand, MapColor::Quartz),
+    ("jungle_log", MapColor::Dirt, MapColor::Podzol),
+    ("acacia_log", MapColor::ColorOrange, MapColor::Stone),
+    ("dark_oak_log", MapColor::ColorBrown, MapColor::ColorBrown),
+    ("mangrove_log", MapColor::ColorRed, MapColor::Podzol),
+    ("cherry_log", MapColor::TerracottaWhite, MapColor::TerracottaGray),
+    ("bamboo_block", MapColor::ColorYellow, MapColor::Plant),
+];
+
+fn block_color_lut() -> &'static HashMap<&'static str, MapColor> {
+    static LUT: OnceLock<HashMap<&'static str, MapColor>> = OnceLock::new();
+    return LUT.get_or_init(|| {
+        let mut lut = HashMap::new();
+        for (color, ids) in BLOCK_COLORS {
+            for id in *ids {
+                lut.insert(*id, *color);
+            }
+        }
+        return lut;
+    });
+}
+
+/// Dye colors, with map colors of dyed blocks and dyed terracotta
+const DYE_COLORS: &[(&str, MapColor, MapColor)] = &[
+    ("light_blue", MapColor::ColorLightBlue, MapColor::TerracottaLightBlue),
+    ("light_gray", MapColor::ColorLightGray, MapColor::TerracottaLightGray),
+    ("white", MapColor::Snow, MapColor::TerracottaWhite),
+    ("orange", MapColor::ColorOrange, MapColor::TerracottaOrange),
+    ("magenta", MapColor::ColorMagenta, MapColor::TerracottaMagenta),
+    ("yellow", MapColor::ColorYellow, MapColor::TerracottaYellow),
+    ("lime", MapColor::ColorLightGreen, MapColor::TerracottaLightGreen),
+    ("pink", MapColor::ColorPink, MapColor::TerracottaPink),
+    ("gray", MapColor::ColorGray, MapColor::TerracottaGray),
+    ("cyan", MapColor::ColorCyan, MapColor::TerracottaCyan),
+    ("purple", MapColor::ColorPurple, MapColor::TerracottaPurple),
+    ("blue", MapColor::ColorBlue, MapColor::TerracottaBlue),
+    ("brown", MapColor::ColorBrown, MapColor::TerracottaBrown),
+    ("green", MapColor::ColorGreen, MapColor::TerracottaGreen),
+    ("red", MapColor::ColorRed, MapColor::TerracottaRed),
+    ("black", MapColor::ColorBlack, MapColor::TerracottaBlack),
+];
+
+/// Blocks dyed by dye colors, like `red_wool`
+const DYED_SUFFIXES: &[&str] = &["_wool", "_carpet", "_bed", "_concrete", "_concrete_powder", "_glazed_terracotta", "_shulker_box", "_stained_glass"];
+
+/// Blocks that are invisible on maps
+const INVISIBLE_IDS: &[&str] = &[
+    "air", "cave_air", "void_air", "structure_void", "barrier", "light", "glass", "glass_pane",
+    "iron_bars", "chain", "lever", "ladder", "tripwire", "tripwire_hook",
+    "redstone_wire", "repeater", "comparator", "flower_pot", "end_rod", "lightning_rod",
+    "scaffolding", "moving_piston", "nether_portal", "end_gateway",
+];
+
+/// Blocks whose id ends with these suffixes are invisible on maps
+const INVISIBLE_SUFFIXES: &[&str] = &["torch", "rail", "_button", "_head", "_skull", "_sign", "_banner", "_candle", "candle"];
+
+/// Blocks missing from `BLOCK_COLORS` are matched by keywords in their ids, checked in order
+const KEYWORD_COLORS: &[(&str, MapColor)] = &[
+    ("water", MapColor::Water), ("bubble_column", MapColor::Water), ("kelp", MapColor::Water), ("seagrass", MapColor::Water),
+    ("lava", MapColor::Fire), ("fire", MapColor::Fire), ("tnt", MapColor::Fire), ("redstone_block", MapColor::Fire),
+    ("packed_ice", MapColor::Ice), ("blue_ice", MapColor::Ice), ("ice", MapColor::Ice),
+    ("snow", MapColor::Snow),
+    ("grass_block", MapColor::Grass), ("slime", MapColor::Grass),
+    ("leaves", MapColor::Plant), ("sapling", MapColor::Plant), ("grass", MapColor::Plant), ("fern", MapColor::Plant),
+    ("vine", MapColor::Plant), ("lily_pad", MapColor::Plant), ("cactus", MapColor::Plant), ("sugar_cane", MapColor::Plant),
+    ("bamboo_block", MapColor::ColorYellow), ("bamboo", MapColor::Plant), ("azalea", MapColor::Plant), ("moss", MapColor::ColorGreen),
+    ("crimson_nylium", MapColor::CrimsonNylium), ("crimson_hyphae", MapColor::CrimsonHyphae), ("crimson", MapColor::CrimsonStem),
+    ("warped_nylium", MapColor::WarpedNylium), ("warped_hyphae", MapColor::WarpedHyphae), ("warped_wart", MapColor::WarpedWartBlock),
+    ("warped", MapColor::WarpedStem),
+    ("nether_wart", MapColor::ColorRed), ("netherrack", MapColor::Nether), ("nether_brick", MapColor::Nether), ("nether_gold_ore", MapColor::Nether),
+    ("nether_quartz_ore", MapColor::Nether), ("magma", MapColor::Nether),
+    ("quartz", MapColor::Quartz), ("diorite", MapColor::Quartz), ("sea_lantern", MapColor::Quartz),
+    ("deepslate", MapColor::Deepslate), ("raw_iron", MapColor::RawIron), ("glow_lichen", MapColor::GlowLichen),
+    ("iron_block", MapColor::Metal), ("anvil", MapColor::Metal), ("iron_door", MapColor::Metal), ("iron_trapdoor", MapColor::Metal),
+    ("cauldron", MapColor::Stone), ("hopper", MapColor::Stone),
+    ("raw_gold", MapColor::Gold), ("gold_block", MapColor::Gold), ("bell", MapColor::Gold),
+    ("diamond_block", MapColor::Diamond), ("beacon", MapColor::Diamond), ("dark_prismarine", MapColor::Diamond),
+    ("prismarine_brick", MapColor::Diamond), ("prismarine", MapColor::ColorCyan),
+    ("lapis_block", MapColor::Lapis), ("emerald_block", MapColor::Emerald),
+    ("tinted_glass", MapColor::ColorGray), ("obsidian", MapColor::ColorBlack), ("coal_block", MapColor::ColorBlack), ("blackstone", MapColor::ColorBlack),
+    ("basalt", MapColor::ColorBlack),
+    ("pumpkin", MapColor::ColorOrange), ("copper", MapColor::ColorOrange), ("red_sand", MapColor::ColorOrange),
+    ("honey", MapColor::ColorOrange), ("acacia", MapColor::ColorOrange), ("terracotta", MapColor::ColorOrange),
+    ("melon", MapColor::ColorLightGreen), ("hay_block", MapColor::ColorYellow), ("sponge", MapColor::ColorYellow),
+    ("red_mushroom", MapColor::ColorRed), ("brown_mushroom", MapColor::Dirt), ("mycelium", MapColor::ColorPurple), ("purpur", MapColor::ColorMagenta), ("amethyst", MapColor::ColorPurple),
+    ("shulker_box", MapColor::ColorPurple), ("soul_sand", MapColor::ColorBrown), ("soul_soil", MapColor::ColorBrown),
+    ("dark_oak", MapColor::ColorBrown), ("brick", MapColor::ColorRed), ("mangrove", MapColor::ColorRed),
+    ("cherry", MapColor::TerracottaWhite), ("mud", MapColor::TerracottaCyan), ("calcite", MapColor::TerracottaWhite),
+    ("dripstone", MapColor::TerracottaBrown),
+    ("spruce", MapColor::Podzol), ("podzol", MapColor::Podzol),
+    ("birch", MapColor::Sand), ("sand", MapColor::Sand), ("end_stone", MapColor::Sand), ("bone_block", MapColor::Sand),
+    ("glowstone", MapColor::Sand),
+    ("jungle", MapColor::Dirt), ("dirt", MapColor::Dirt), ("farmland", MapColor::Dirt), ("granite", MapColor::Dirt),
+    ("clay", MapColor::Clay), ("cobweb", MapColor::Wool), ("mushroom_stem", MapColor::Wool),
+    ("oak", MapColor::Wood), ("planks", MapColor::Wood), ("log", MapColor::Wood), ("chest", MapColor::Wood),
+    ("crafting_table", MapColor::Wood), ("bookshelf", MapColor::Wood), ("barrel", MapColor::Wood), ("note_block", MapColor::Wood),
+];
+
+impl MapColor {
+    /// Rgb of this color, like `0x7FB238` for grass
+    pub fn rgb(&self) -> [u8; 3] {
+        let rgb = MAP_COLOR_RGB[*self as usize];
+        return [(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8];
+    }
+
+    /// Get map color from its id, returns `None` if id is out of range
+    pub fn from_id(id: u8) -> Option<MapColor> {
+        return MapColor::iter().nth(id as usize);
+    }
+
+    /// Returns true if blocks of this color are not drawn on maps
+    pub fn is_transparent(&self) -> bool {
+        return *self == MapColor::None;
+    }
+
+    /// Rgb of this color with `shade`
+    pub fn shaded_rgb(&self, shade: MapShade) -> [u8; 3] {
+        let factor = shade.multiplier() as u32;
+        return self.rgb().map(|c| (c as u32 * factor / 255) as u8);
+    }
+
+    /// Byte stored in `colors` of maps
+    pub fn to_color_byte(&self, shade: MapShade) -> u8 {
+        return *self as u8 * 4 + shade as u8;
+    }
+
+    /// Parse a byte in `colors` of maps, returns `None` if the base color is out of range
+    pub fn from_color_byte(byte: u8) -> Option<(MapColor, MapShade)> {
+        let color = MapColor::from_id(byte / 4)?;
+        return Some((color, MapShade::from_id(byte % 4)));
+    }
+
+    /// RGBA of a byte in `colors` of maps. Transparent and invalid bytes are fully transparent.
+    pub fn rgba_of_color_byte(byte: u8) -> [u8; 4] {
+        return match Self::from_color_byte(byte) {
+            Some((color, shade)) if !color.is_transparent() => {
+                let [r, g, b] = color.shaded_rgb(shade);
+                [r, g, b, 255]
+            }
+            _ => [0, 0, 0, 0],
+        };
+    }
+
+    /// Byte of the shaded map color closest to `rgba`. Pixels with alpha below 128 are transparent.
+    pub fn nearest_color_byte(rgba: [u8; 4]) -> u8 {
+        if rgba[3] < 128 {
+            return 0;
+        }
+        let mut best = (u32::MAX, 0u8);
+        for color in MapColor::iter().filter(|c| !c.is_transparent()) {
+            for shade in MapShade::iter() {
+                let rgb = color.shaded_rgb(shade);
+                let dist: u32 = (0..3).map(|i| (rgb[i] as i32 - rgba[i] as i32).pow(2) as u32).sum();
+                if dist < best.0 {
+                    best = (dist, color.to_color_byte(shade));
+                }
+            }
+        }
+        return best.1;
+    }
+}
+
+impl MapShade {
+    /// `id` is taken modulo 4
+    pub fn from_id(id: u8) -> MapShade {
+        return match id % 4 {
+            0 => MapShade::Dark,
+            1 => MapShade::Normal,
+            2 => MapShade::Light,
+            _ => MapShade::Darkest,
+        };
+    }
+
+    /// Rgb of base colors are multiplied by `multiplier / 255`
+    pub fn multiplier(&self) -> u8 {
+        return match self {
+            MapShade::Dark => 180,
+            MapShade::Normal => 220,
+            MapShade::Light => 255,
+            MapShade::Darkest => 135,
+        };
+    }
+}
+
+impl Block {
+    /// Map color of this block, same as vanilla. Vanilla blocks missing from the table are guessed
+    /// from their ids, and blocks from other namespaces are treated as stone.
+    pub fn map_color(&self) -> MapColor {
+        if !(self.namespace.is_empty() || self.namespace == "minecraft") {
+            return MapColor::Stone;
+        }
+        let id = self.id.as_str();
+        if let Some((_, top, side)) = LOG_COLORS.iter().find(|(log, _, _)| *log == id) {
+            return if self.attributes.get("axis").map_or(true, |axis| axis == "y") { *top } else { *side };
+        }
+        if let Some(color) = block_color_lut().get(id) {
+            // the head of beds is always white
+            if id.ends_with("_bed") && self.attributes.get("part").map(|p| p.as_str()) == Some("head") {
+                return MapColor::Wool;
+            }
+            return *color;
+        }
+        if INVISIBLE_IDS.contains(&id) || INVISIBLE_SUFFIXES.iter().any(|s| id.ends_with(s))
+            || id.ends_with("_glass_pane") {
+            return MapColor::None;
+        }
+        for (color, dyed, terracotta) in DYE_COLORS {
+            match id.strip_prefix(color) {
+                Some("_terracotta") => return *terracotta,
+                Some(rest) if DYED_SUFFIXES.contains(&rest) => return *dyed,
+                _ => {}
+            }
+        }
+        for (keyword, color) in KEYWORD_COLORS {
+            if id.contains(keyword) {
+                return *color;
+            }
+        }
+        return MapColor::Stone;
+    }
+}
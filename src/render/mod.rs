@@ -0,0 +1,342 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::cmp::max;
+use ndarray::{Array3, ArrayView2, Axis};
+use crate::block::Block;
+use crate::schem::Schematic;
+
+/// Map colors of blocks
+pub mod map_color;
+/// Compute map colors and shades of map arts
+pub mod map_art;
+/// Encode and decode png images
+pub mod png;
+/// Block colors from resource packs
+pub mod texture_pack;
+
+pub use map_color::{MapColor, MapShade};
+pub use map_art::{MapArt, MapArtOption};
+pub use texture_pack::TexturePack;
+
+/// An image in RGBA, stored row by row
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<[u8; 4]>,
+}
+
+/// How to look at a schematic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderView {
+    /// Look down from the top
+    TopDown,
+    /// Look from the top of +x and +z
+    Isometric,
+}
+
+/// Options to render schematics
+#[derive(Debug, Clone)]
+pub struct RenderOption {
+    /// Pixels per block edge. The isometric view rounds it down to an even number no less than 2.
+    pub block_size: u32,
+    /// Brightness of top, +z and +x faces in the isometric view
+    pub face_shades: [f32; 3],
+    /// Rotate the schematic clockwise around y axis by 90 degrees for this many times before
+    /// rendering
+    pub rotation: u8,
+    /// Color of pixels without blocks
+    pub background: [u8; 4],
+    /// Use average colors of textures instead of map colors. Blocks without texture still use map
+    /// colors.
+    pub texture_pack: Option<TexturePack>,
+}
+
+impl Default for RenderOption {
+    fn default() -> Self {
+        return RenderOption {
+            block_size: 4,
+            face_shades: [1.0, 0.8, 0.6],
+            rotation: 0,
+            background: [0, 0, 0, 0],
+            texture_pack: None,
+        };
+    }
+}
+
+/// Options to generate preview images
+#[derive(Debug, Clone)]
+pub struct PreviewOption {
+    /// Width and height of the preview. Litematica uses 140.
+    pub size: u32,
+    pub view: RenderView,
+}
+
+impl Default for PreviewOption {
+    fn default() -> Self {
+        return PreviewOption {
+            size: 140,
+            view: RenderView::Isometric,
+        };
+    }
+}
+
+impl Image {
+    /// Create a transparent image
+    pub fn new(width: u32, height: u32) -> Image {
+        return Self::filled(width, height, [0; 4]);
+    }
+
+    /// Create an image filled with `rgba`
+    pub fn filled(width: u32, height: u32, rgba: [u8; 4]) -> Image {
+        return Image {
+            width,
+            height,
+            pixels: vec![rgba; width as usize * height as usize],
+        };
+    }
+
+    pub fn pixel_at(&self, x: u32, y: u32) -> [u8; 4] {
+        return self.pixels[(y * self.width + x) as usize];
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, rgba: [u8; 4]) {
+        self.pixels[(y * self.width + x) as usize] = rgba;
+    }
+
+    /// Fill a `size`x`size` square whose top-left corner is at `x` and `y`
+    fn fill_square(&mut self, x: u32, y: u32, size: u32, rgba: [u8; 4]) {
+        for dy in 0..size {
+            for dx in 0..size {
+                self.set_pixel(x + dx, y + dy, rgba);
+            }
+        }
+    }
+
+    /// Scale the image to fit into a `size`x`size` square, keeping the aspect ratio. Pixels are
+    /// sampled by nearest neighbor, and the image is placed at the center.
+    pub fn fit_into_square(&self, size: u32) -> Image {
+        let mut result = Image::new(size, size);
+        if self.width == 0 || self.height == 0 {
+            return result;
+        }
+        let scale = f64::min(size as f64 / self.width as f64, size as f64 / self.height as f64);
+        let w = max(1, (self.width as f64 * scale) as u32).min(size);
+        let h = max(1, (self.height as f64 * scale) as u32).min(size);
+        let (x0, y0) = ((size - w) / 2, (size - h) / 2);
+        for y in 0..h {
+            let src_y = ((y as f64 + 0.5) / scale) as u32;
+            for x in 0..w {
+                let src_x = ((x as f64 + 0.5) / scale) as u32;
+                result.set_pixel(x0 + x, y0 + y, self.pixel_at(src_x.min(self.width - 1), src_y.min(self.height - 1)));
+            }
+        }
+        return result;
+    }
+
+    /// Pixels in ARGB, the format of `PreviewImageData` in litematica
+    pub fn to_argb(&self) -> Vec<i32> {
+        return self.pixels.iter().map(|[r, g, b, a]| {
+            return ((*a as u32) << 24 | (*r as u32) << 16 | (*g as u32) << 8 | *b as u32) as i32;
+        }).collect();
+    }
+
+    /// Load from ARGB pixels, returns `None` if the size doesn't match
+    pub fn from_argb(width: u32, height: u32, argb: &[i32]) -> Option<Image> {
+        if argb.len() != width as usize * height as usize {
+            return None;
+        }
+        let pixels = argb.iter().map(|p| {
+            let p = *p as u32;
+            return [(p >> 16) as u8, (p >> 8) as u8, p as u8, (p >> 24) as u8];
+        }).collect();
+        return Some(Image { width, height, pixels });
+    }
+}
+
+fn shade_rgb(rgb: [u8; 3], factor: f32) -> [u8; 4] {
+    let shade = |c: u8| (c as f32 * factor).clamp(0.0, 255.0) as u8;
+    return [shade(rgb[0]), shade(rgb[1]), shade(rgb[2]), 255];
+}
+
+/// Rotate a yzx array clockwise around y axis (looking down) by 90 degrees for `rotation` times
+fn rotate_yzx<T: Clone>(mut array: Array3<T>, rotation: u8) -> Array3<T> {
+    for _ in 0..rotation % 4 {
+        let mut rotated = array.permuted_axes([0, 2, 1]);
+        rotated.invert_axis(Axis(2));
+        array = rotated.as_standard_layout().into_owned();
+    }
+    return array;
+}
+
+/// Render a layer of colors in zx order, each block as a `block_size`x`block_size` square
+fn render_layer(layer: ArrayView2<Option<[u8; 3]>>, option: &RenderOption) -> Image {
+    let (size_z, size_x) = layer.dim();
+    let scale = max(1, option.block_size);
+    let mut image = Image::filled(size_x as u32 * scale, size_z as u32 * scale, option.background);
+    for ((z, x), color) in layer.indexed_iter() {
+        if let Some(rgb) = color {
+            image.fill_square(x as u32 * scale, z as u32 * scale, scale, shade_rgb(*rgb, 1.0));
+        }
+    }
+    return image;
+}
+
+impl Schematic {
+    /// Fill the enclosing box of all regions in yzx order. Each block is converted by `convert`,
+    /// and cells are left as `default` if `convert` returns `None`. If regions overlap, the first
+    /// region wins.
+    fn enclosing_volume<T: Clone>(&self, default: T, convert: impl Fn(&Block) -> Option<T>) -> Array3<T> {
+        let shape = self.shape();
+        let mut result = Array3::from_elem((shape[1] as usize, shape[2] as usize, shape[0] as usize), default);
+        if self.regions.is_empty() {
+            return result;
+        }
+        let min_offset: [i32; 3] = std::array::from_fn(|dim| {
+            return self.regions.iter().map(|reg| reg.offset[dim]).min().unwrap();
+        });
+        for reg in self.regions.iter().rev() {
+            let lut: Vec<Option<T>> = reg.palette.iter().map(&convert).collect();
+            let base: [usize; 3] = std::array::from_fn(|dim| (reg.offset[dim] - min_offset[dim]) as usize);
+            for ((y, z, x), blk_idx) in reg.array_yzx.indexed_iter() {
+                if let Some(value) = &lut[*blk_idx as usize] {
+                    result[[y + base[1], z + base[2], x + base[0]]] = value.clone();
+                }
+            }
+        }
+        return result;
+    }
+
+    /// Map colors of the enclosing box of all regions, in yzx order. If regions overlap, the first
+    /// visible block wins.
+    pub fn map_color_volume(&self) -> Array3<MapColor> {
+        return self.enclosing_volume(MapColor::None, |blk| {
+            let color = blk.map_color();
+            return if color.is_transparent() { None } else { Some(color) };
+        });
+    }
+
+    /// Colors of the enclosing box in yzx order after rotation, `None` for invisible blocks
+    fn render_colors(&self, option: &RenderOption) -> Array3<Option<[u8; 3]>> {
+        let colors = self.enclosing_volume(None, |blk| {
+            let color = blk.map_color();
+            if color.is_transparent() {
+                return None;
+            }
+            let texture = option.texture_pack.as_ref().and_then(|t| t.color_of(blk));
+            return Some(Some(texture.unwrap_or(color.rgb())));
+        });
+        return rotate_yzx(colors, option.rotation);
+    }
+
+    /// Render with `view`
+    pub fn render(&self, view: RenderView, option: &RenderOption) -> Image {
+        return match view {
+            RenderView::TopDown => self.render_top_down(option),
+            RenderView::Isometric => self.render_isometric(option),
+        };
+    }
+
+    /// Render the topmost visible block of each column as a `block_size`x`block_size` square.
+    /// Image x is the x axis and image y is the z axis.
+    pub fn render_top_down(&self, option: &RenderOption) -> Image {
+        let colors = self.render_colors(option);
+        let (size_y, size_z, size_x) = colors.dim();
+        let scale = max(1, option.block_size);
+        let mut image = Image::filled(size_x as u32 * scale, size_z as u32 * scale, option.background);
+        for z in 0..size_z {
+            for x in 0..size_x {
+                if let Some(rgb) = (0..size_y).rev().find_map(|y| colors[[y, z, x]]) {
+                    image.fill_square(x as u32 * scale, z as u32 * scale, scale, shade_rgb(rgb, 1.0));
+                }
+            }
+        }
+        return image;
+    }
+
+    /// Render blocks in layer `y`, which is relative to the enclosing box of all regions. Returns
+    /// `None` if `y` is out of range.
+    pub fn render_slice(&self, y: i32, option: &RenderOption) -> Option<Image> {
+        if y < 0 || y >= self.shape()[1] {
+            return None;
+        }
+        let colors = self.render_colors(option);
+        return Some(render_layer(colors.index_axis(Axis(0), y as usize), option));
+    }
+
+    /// Render every layer from bottom to top, see `render_slice`
+    pub fn render_slices(&self, option: &RenderOption) -> Vec<Image> {
+        let colors = self.render_colors(option);
+        return colors.axis_iter(Axis(0)).map(|layer| render_layer(layer, option)).collect();
+    }
+
+    /// Render an isometric image looking from the top of +x and +z. Top, +z and +x faces are
+    /// shaded by `face_shades`, and blocks covered on all 3 visible faces are skipped.
+    pub fn render_isometric(&self, option: &RenderOption) -> Image {
+        let colors = self.render_colors(option);
+        let (size_y, size_z, size_x) = colors.dim();
+        let s = max(2, option.block_size / 2 * 2) as usize;
+        let width = (size_x + size_z) * s;
+        let height = (size_x + size_z) * s / 2 + size_y * s;
+        let mut image = Image::filled(width as u32, height as u32, option.background);
+
+        let opaque = |x: usize, y: usize, z: usize| -> bool {
+            return x < size_x && y < size_y && z < size_z && colors[[y, z, x]].is_some();
+        };
+        let sf = s as f32;
+        // later blocks are drawn on top of earlier ones
+        for y in 0..size_y {
+            for z in 0..size_z {
+                for x in 0..size_x {
+                    let Some(rgb) = colors[[y, z, x]] else { continue };
+                    if opaque(x + 1, y, z) && opaque(x, y + 1, z) && opaque(x, y, z + 1) {
+                        continue;
+                    }
+                    let faces = option.face_shades.map(|f| shade_rgb(rgb, f));
+                    let bx = (x + size_z - 1 - z) * s;
+                    let by = (x + z) * s / 2 + (size_y - 1 - y) * s;
+                    for v in 0..2 * s {
+                        let vf = v as f32 + 0.5;
+                        for u in 0..2 * s {
+                            // distance to the vertical center line, 0 at center and 1 at both sides
+                            let d = (2 * u + 1).abs_diff(2 * s) as f32 / (2 * s) as f32;
+                            if vf < sf / 2.0 * (1.0 - d) || vf > 1.5 * sf + sf / 2.0 * (1.0 - d) {
+                                continue;
+                            }
+                            let face = if vf < sf - sf * d / 2.0 { 0 } else if u < s { 1 } else { 2 };
+                            image.set_pixel((bx + u) as u32, (by + v) as u32, faces[face]);
+                        }
+                    }
+                }
+            }
+        }
+        return image;
+    }
+
+    /// Render a square thumbnail
+    pub fn render_preview(&self, option: &PreviewOption) -> Image {
+        return self.render(option.view, &RenderOption::default()).fit_into_square(option.size);
+    }
+
+    /// Render a thumbnail and store it in metadata, it will be saved as `PreviewImageData` of
+    /// litematica.
+    pub fn update_litematica_preview(&mut self, option: &PreviewOption) {
+        self.metadata.litematica_preview = Some(self.render_preview(option).to_argb());
+    }
+}
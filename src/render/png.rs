@@ -0,0 +1,219 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::fs::File;
+use std::io::{Read, Write};
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use crate::error::Error;
+use crate::render::Image;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+fn crc32(chunks: &[&[u8]]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+    for data in chunks {
+        for byte in *data {
+            crc ^= *byte as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 { 0xEDB88320 ^ (crc >> 1) } else { crc >> 1 };
+            }
+        }
+    }
+    return !crc;
+}
+
+fn write_chunk(dest: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    dest.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    dest.extend_from_slice(chunk_type);
+    dest.extend_from_slice(data);
+    dest.extend_from_slice(&crc32(&[chunk_type, data]).to_be_bytes());
+}
+
+fn invalid_png(detail: &str) -> Error {
+    return Error::InvalidPNG { detail: detail.to_string() };
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    return if pa <= pb && pa <= pc { a } else if pb <= pc { b } else { c };
+}
+
+impl Image {
+    /// Encode as a non-interlaced 8-bit RGBA png
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut raw = Vec::with_capacity((self.width as usize * 4 + 1) * self.height as usize);
+        for row in self.pixels.chunks(self.width.max(1) as usize) {
+            // filter type 0, no filter
+            raw.push(0);
+            for pixel in row {
+                raw.extend_from_slice(pixel);
+            }
+        }
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&raw).expect("Writing to vec never fails");
+        let compressed = encoder.finish().expect("Writing to vec never fails");
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&self.width.to_be_bytes());
+        header.extend_from_slice(&self.height.to_be_bytes());
+        // 8 bits per channel, RGBA, deflate, adaptive filtering, no interlace
+        header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+        let mut result = Vec::with_capacity(compressed.len() + 64);
+        result.extend_from_slice(&PNG_SIGNATURE);
+        write_chunk(&mut result, b"IHDR", &header);
+        write_chunk(&mut result, b"IDAT", &compressed);
+        write_chunk(&mut result, b"IEND", &[]);
+        return result;
+    }
+
+    /// Save as png file
+    pub fn save_png(&self, filename: &str) -> Result<(), Error> {
+        let mut file = match File::create(filename) {
+            Ok(f) => f,
+            Err(e) => return Err(Error::FileCreateError(e)),
+        };
+        if let Err(e) = file.write_all(&self.to_png()) {
+            return Err(Error::IOWriteError(e));
+        }
+        return Ok(());
+    }
+
+    /// Decode a non-interlaced png. Gray, RGB, palette, gray-alpha and RGBA images with at most 8
+    /// bits per channel are supported.
+    pub fn from_png(data: &[u8]) -> Result<Image, Error> {
+        if data.len() < 8 || data[0..8] != PNG_SIGNATURE {
+            return Err(invalid_png("PNG signature is missing"));
+        }
+        let mut offset = 8;
+        let mut header: Option<(u32, u32, u8, u8)> = None;
+        let mut palette: Vec<[u8; 4]> = Vec::new();
+        let mut idat = Vec::new();
+        while offset + 8 <= data.len() {
+            let len = u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
+            let chunk_type = &data[offset + 4..offset + 8];
+            if offset + 12 + len > data.len() {
+                return Err(invalid_png("Chunk is incomplete"));
+            }
+            let content = &data[offset + 8..offset + 8 + len];
+            match chunk_type {
+                b"IHDR" => {
+                    if len != 13 {
+                        return Err(invalid_png("IHDR should have 13 bytes"));
+                    }
+                    if content[12] != 0 {
+                        return Err(invalid_png("Interlaced png is not supported"));
+                    }
+                    header = Some((u32::from_be_bytes(content[0..4].try_into().unwrap()),
+                                   u32::from_be_bytes(content[4..8].try_into().unwrap()),
+                                   content[8], content[9]));
+                }
+                b"PLTE" => palette = content.chunks_exact(3).map(|c| [c[0], c[1], c[2], 255]).collect(),
+                b"tRNS" => {
+                    for (idx, alpha) in content.iter().enumerate() {
+                        if let Some(p) = palette.get_mut(idx) {
+                            p[3] = *alpha;
+                        }
+                    }
+                }
+                b"IDAT" => idat.extend_from_slice(content),
+                b"IEND" => break,
+                _ => {}
+            }
+            offset += 12 + len;
+        }
+
+        let Some((width, height, bit_depth, color_type)) = header else {
+            return Err(invalid_png("IHDR is missing"));
+        };
+        let channels: usize = match color_type {
+            0 | 3 => 1,
+            2 => 3,
+            4 => 2,
+            6 => 4,
+            _ => return Err(invalid_png(&format!("Invalid color type {color_type}"))),
+        };
+        let bit_depth_supported = if channels == 1 { matches!(bit_depth, 1 | 2 | 4 | 8) } else { bit_depth == 8 };
+        if !bit_depth_supported {
+            return Err(invalid_png(&format!("Bit depth {bit_depth} of color type {color_type} is not supported")));
+        }
+        let bits_per_pixel = channels * bit_depth as usize;
+        let bpp = (bits_per_pixel / 8).max(1);
+        let stride = (width as usize * bits_per_pixel).div_ceil(8);
+
+        let mut raw = Vec::new();
+        if let Err(e) = ZlibDecoder::new(idat.as_slice()).read_to_end(&mut raw) {
+            return Err(Error::IOReadError(e));
+        }
+        if raw.len() < (stride + 1) * height as usize {
+            return Err(invalid_png("Image data is incomplete"));
+        }
+
+        let mut prev = vec![0u8; stride];
+        let mut cur = vec![0u8; stride];
+        let mut image = Image::new(width, height);
+        for y in 0..height as usize {
+            let line = &raw[y * (stride + 1)..(y + 1) * (stride + 1)];
+            for i in 0..stride {
+                let a = if i >= bpp { cur[i - bpp] } else { 0 };
+                let b = prev[i];
+                let c = if i >= bpp { prev[i - bpp] } else { 0 };
+                let predictor = match line[0] {
+                    0 => 0,
+                    1 => a,
+                    2 => b,
+                    3 => ((a as u16 + b as u16) / 2) as u8,
+                    4 => paeth(a, b, c),
+                    f => return Err(invalid_png(&format!("Invalid filter type {f}"))),
+                };
+                cur[i] = line[i + 1].wrapping_add(predictor);
+            }
+            for x in 0..width as usize {
+                let sample = |channel: usize| -> u8 {
+                    let bit = (x * channels + channel) * bit_depth as usize;
+                    let value = (cur[bit / 8] >> (8 - bit_depth as usize - bit % 8)) & ((1u16 << bit_depth) - 1) as u8;
+                    return value;
+                };
+                // scale gray samples to 8 bits
+                let gray = |value: u8| -> u8 { (value as u16 * 255 / ((1u16 << bit_depth) - 1)) as u8 };
+                let rgba = match color_type {
+                    0 => { let g = gray(sample(0)); [g, g, g, 255] }
+                    2 => [sample(0), sample(1), sample(2), 255],
+                    3 => palette.get(sample(0) as usize).copied().unwrap_or([0, 0, 0, 255]),
+                    4 => [sample(0), sample(0), sample(0), sample(1)],
+                    _ => [sample(0), sample(1), sample(2), sample(3)],
+                };
+                image.set_pixel(x as u32, y as u32, rgba);
+            }
+            std::mem::swap(&mut prev, &mut cur);
+        }
+        return Ok(image);
+    }
+
+    /// Load png file, see `from_png`
+    pub fn from_png_file(filename: &str) -> Result<Image, Error> {
+        let data = match std::fs::read(filename) {
+            Ok(d) => d,
+            Err(e) => return Err(Error::FileOpenError(e)),
+        };
+        return Self::from_png(&data);
+    }
+}
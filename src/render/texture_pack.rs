@@ -0,0 +1,124 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::HashMap;
+use std::path::Path;
+use crate::block::Block;
+use crate::error::Error;
+use crate::render::Image;
+
+/// Average colors of block textures, used to render blocks instead of map colors
+#[derive(Debug, Clone, Default)]
+pub struct TexturePack {
+    /// Texture name without extension, like `oak_log_top`
+    colors: HashMap<String, [u8; 3]>,
+}
+
+/// Suffixes of blocks that use textures of another block, like `oak_slab` and `stone_brick_wall`
+const DERIVED_SUFFIXES: &[&str] = &["_slab", "_stairs", "_wall", "_fence_gate", "_fence", "_pressure_plate", "_button"];
+
+impl TexturePack {
+    pub fn new() -> TexturePack {
+        return TexturePack::default();
+    }
+
+    /// Load textures from an unzipped resource pack. Block textures are searched in
+    /// `assets/minecraft/textures/block` (1.13+) and `assets/minecraft/textures/blocks`. Files that
+    /// can not be decoded are skipped.
+    pub fn from_dir(dir: &str) -> Result<TexturePack, Error> {
+        let mut result = TexturePack::new();
+        let mut found = false;
+        for sub_dir in ["assets/minecraft/textures/block", "assets/minecraft/textures/blocks"] {
+            let path = Path::new(dir).join(sub_dir);
+            let entries = match std::fs::read_dir(&path) {
+                Ok(e) => e,
+                Err(_) => continue,
+            };
+            found = true;
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().and_then(|ext| ext.to_str()) != Some("png") {
+                    continue;
+                }
+                let (Some(name), Some(filename)) = (path.file_stem().and_then(|s| s.to_str()), path.to_str()) else {
+                    continue;
+                };
+                if let Ok(image) = Image::from_png_file(filename) {
+                    if let Some(color) = image.average_color() {
+                        result.insert(name, color);
+                    }
+                }
+            }
+        }
+        if !found {
+            return Err(Error::NoSuchFile {
+                filename: "assets/minecraft/textures/block".to_string(),
+                expected_to_exist_in: dir.to_string(),
+            });
+        }
+        return Ok(result);
+    }
+
+    /// Set the color of a texture
+    pub fn insert(&mut self, texture_name: &str, rgb: [u8; 3]) {
+        self.colors.insert(texture_name.to_string(), rgb);
+    }
+
+    /// Number of textures
+    pub fn len(&self) -> usize {
+        return self.colors.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.colors.is_empty();
+    }
+
+    /// Color of the top face of `blk`, returns `None` if no texture matches
+    pub fn color_of(&self, blk: &Block) -> Option<[u8; 3]> {
+        if !(blk.namespace.is_empty() || blk.namespace == "minecraft") {
+            return None;
+        }
+        let id = blk.id.replace("_carpet", "_wool");
+        let mut candidates = vec![format!("{id}_top"), id.clone()];
+        if let Some(base) = DERIVED_SUFFIXES.iter().find_map(|s| id.strip_suffix(s)) {
+            for suffix in ["_top", "", "s", "_planks", "_block_top", "_block"] {
+                candidates.push(format!("{base}{suffix}"));
+            }
+        }
+        return candidates.iter().find_map(|name| self.colors.get(name).copied());
+    }
+}
+
+impl Image {
+    /// Average color of non-transparent pixels weighted by alpha, returns `None` if all pixels are
+    /// transparent
+    pub fn average_color(&self) -> Option<[u8; 3]> {
+        let mut sum = [0u64; 3];
+        let mut weight = 0u64;
+        for [r, g, b, a] in &self.pixels {
+            sum[0] += *r as u64 * *a as u64;
+            sum[1] += *g as u64 * *a as u64;
+            sum[2] += *b as u64 * *a as u64;
+            weight += *a as u64;
+        }
+        if weight == 0 {
+            return None;
+        }
+        return Some(sum.map(|s| (s / weight) as u8));
+    }
+}
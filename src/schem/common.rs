@@ -25,7 +25,7 @@ use fastnbt::Value;
 use crate::error::Error;
 use crate::{unwrap_opt_tag, schem::{id_of_nbt_tag}, unwrap_tag};
 use crate::block::Block;
-use crate::region::{BlockEntity, Entity};
+use crate::region::BlockEntity;
 
 pub fn size_to_compound<T>(size: &[T; 3]) -> HashMap<String, Value>
     where T: Copy, Value: From<T>
@@ -134,33 +134,6 @@ pub fn i64_ms_timestamp_to_system_time(timestamp: i64) -> SystemTime {
 }
 
 
-pub fn parse_entity_litematica(nbt: HashMap<String, Value>, tag_path: &str) -> Result<Entity, Error> {
-    let mut entity = Entity::new();
-    {
-        let tag_pos_path = format!("{}/Pos", tag_path);
-        let pos = unwrap_opt_tag!(nbt.get("Pos"),List,vec![],tag_pos_path);
-        if pos.len() != 3 {
-            return Err(Error::InvalidValue {
-                tag_path: tag_pos_path,
-                error: format!("Pos field for an entity should contain 3 doubles, but found {}", pos.len()),
-            });
-        }
-
-
-        let mut pos_d = [0.0, 0.0, 0.0];
-        for dim in 0..3 {
-            let cur_tag_path = format!("{}/Pos[{}]", tag_path, dim);
-            pos_d[dim] = unwrap_tag!(pos[dim],Double,0.0,cur_tag_path);
-            entity.block_pos[dim] = pos_d[dim] as i32;
-        }
-
-        entity.position = pos_d;
-    }
-
-    entity.tags = nbt;
-    return Ok(entity);
-}
-
 // Checks if pos >= lower_bound && pos <= upper_bound
 pub fn check_pos_in_range(pos: [i32; 3], lower_bound: [i32; 3], upper_bound: [i32; 3]) -> bool {
     for dim in 0..3 {
@@ -190,4 +163,109 @@ pub fn parse_block_entity_nocheck(mut nbt: HashMap<String, Value>, tag_path: &st
     be.tags = nbt;
 
     return Ok((pos, be));
-}
\ No newline at end of file
+}
+pub const BYTE_ARRAY_TOKEN: &str = "__fastnbt_byte_array";
+pub const LONG_ARRAY_TOKEN: &str = "__fastnbt_long_array";
+
+/// Visits an nbt array produced by fastnbt's deserializer, and passes the raw big-endian bytes to
+/// `decode`. Used by streaming loaders to decode huge arrays without building `Value`.
+struct NbtArrayVisitor<F> {
+    token: &'static str,
+    decode: F,
+}
+
+struct NbtArrayBytesSeed<F>(F);
+
+impl<'de, F, T> serde::de::Visitor<'de> for NbtArrayBytesSeed<F>
+    where F: FnOnce(&[u8]) -> T {
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        return write!(formatter, "bytes of nbt array");
+    }
+
+    fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<T, E> {
+        return Ok((self.0)(v));
+    }
+}
+
+impl<'de, F, T> serde::de::DeserializeSeed<'de> for NbtArrayBytesSeed<F>
+    where F: FnOnce(&[u8]) -> T {
+    type Value = T;
+
+    fn deserialize<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<T, D::Error> {
+        return deserializer.deserialize_bytes(self);
+    }
+}
+
+impl<'de, F, T> serde::de::Visitor<'de> for NbtArrayVisitor<F>
+    where F: FnOnce(&[u8]) -> T {
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        return write!(formatter, "nbt array ({})", self.token);
+    }
+
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<T, A::Error> {
+        let key: Option<String> = map.next_key()?;
+        if key.as_deref() != Some(self.token) {
+            return Err(serde::de::Error::custom(format!("expected nbt array ({}), but found a compound", self.token)));
+        }
+        return map.next_value_seed(NbtArrayBytesSeed(self.decode));
+    }
+}
+
+/// Deserialize an nbt array(`BYTE_ARRAY_TOKEN` or `LONG_ARRAY_TOKEN`) by decoding its raw
+/// big-endian bytes with `decode`.
+pub fn deserialize_nbt_array<'de, D, F, T>(deserializer: D, token: &'static str, decode: F) -> Result<T, D::Error>
+    where D: serde::Deserializer<'de>, F: FnOnce(&[u8]) -> T {
+    return deserializer.deserialize_map(NbtArrayVisitor { token, decode });
+}
+
+/// Decode a big-endian long array into `u64`
+pub fn decode_long_array(bytes: &[u8]) -> Vec<u64> {
+    return bytes.chunks_exact(8)
+        .map(|c| u64::from_be_bytes(c.try_into().unwrap()))
+        .collect();
+}
+
+/// Raw big-endian bytes of an nbt array, serialized without building `Value`
+pub struct NbtArrayBytes<'a> {
+    pub token: &'static str,
+    pub bytes: &'a [u8],
+}
+
+struct RawBytes<'a>(&'a [u8]);
+
+impl serde::Serialize for RawBytes<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        return serializer.serialize_bytes(self.0);
+    }
+}
+
+impl serde::Serialize for NbtArrayBytes<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(self.token, &RawBytes(self.bytes))?;
+        return map.end();
+    }
+}
+
+/// Encode `u64` as a big-endian long array
+pub fn encode_long_array(data: &[u64]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(data.len() * 8);
+    for val in data {
+        bytes.extend_from_slice(&val.to_be_bytes());
+    }
+    return bytes;
+}
+
+/// Long array decoded directly from fastnbt's deserializer, without building `Value`
+pub struct StreamedLongArray(pub Vec<u64>);
+
+impl<'de> serde::Deserialize<'de> for StreamedLongArray {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        return Ok(StreamedLongArray(deserialize_nbt_array(deserializer, LONG_ARRAY_TOKEN, decode_long_array)?));
+    }
+}
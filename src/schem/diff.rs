@@ -0,0 +1,298 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::BTreeMap;
+use crate::block::Block;
+use crate::error::Error;
+use crate::player::EntityBox;
+use crate::region::{BlockEntity, Region, WorldSlice};
+use crate::schem::{MetaDataIR, Schematic};
+
+/// Change of a single block position
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockDiff {
+    /// The position is only covered by the new schematic
+    Added(Block),
+    /// The position is only covered by the old schematic
+    Removed(Block),
+    /// Namespace or id of the block changed, like `stone` -> `dirt`
+    IdChanged { old: Block, new: Block },
+    /// Same block id, but different properties, like `facing=north` -> `facing=east`
+    StateChanged { old: Block, new: Block },
+}
+
+/// Change of block entity at a position
+#[derive(Debug, Clone)]
+pub enum BlockEntityDiff {
+    Added(BlockEntity),
+    Removed(BlockEntity),
+    /// Nbt tags of the block entity changed
+    Changed { old: BlockEntity, new: BlockEntity },
+}
+
+/// Differences from one schematic to another. All positions are global positions, and entities
+/// are shifted by their region offsets.
+#[derive(Debug, Clone)]
+pub struct SchematicDiff {
+    /// Changed blocks, ordered by position (xyz)
+    pub blocks: BTreeMap<[i32; 3], BlockDiff>,
+    /// Changed block entities, ordered by position (xyz)
+    pub block_entities: BTreeMap<[i32; 3], BlockEntityDiff>,
+    /// Entities only exist in the new schematic
+    pub added_entities: Vec<EntityBox>,
+    /// Entities only exist in the old schematic
+    pub removed_entities: Vec<EntityBox>,
+}
+
+impl BlockDiff {
+    /// The block after this change, `None` if it's removed
+    pub fn new_block(&self) -> Option<&Block> {
+        return match self {
+            BlockDiff::Added(new) => Some(new),
+            BlockDiff::Removed(_) => None,
+            BlockDiff::IdChanged { new, .. } => Some(new),
+            BlockDiff::StateChanged { new, .. } => Some(new),
+        };
+    }
+}
+
+impl BlockEntityDiff {
+    /// The block entity after this change, `None` if it's removed
+    pub fn new_block_entity(&self) -> Option<&BlockEntity> {
+        return match self {
+            BlockEntityDiff::Added(new) => Some(new),
+            BlockEntityDiff::Removed(_) => None,
+            BlockEntityDiff::Changed { new, .. } => Some(new),
+        };
+    }
+}
+
+/// Global positions are compared first, and then all tags
+fn same_entity(a: &EntityBox, b: &EntityBox) -> bool {
+    if a.pos() != b.pos() || a.block_pos() != b.block_pos() {
+        return false;
+    }
+    return a.to_nbt() == b.to_nbt();
+}
+
+fn global_entities(schem: &Schematic) -> Vec<EntityBox> {
+    let mut result = Vec::new();
+    for reg in &schem.regions {
+        for entity in &reg.entities {
+            let mut e = entity.clone();
+            e.pos_shift(reg.offset);
+            result.push(e);
+        }
+    }
+    return result;
+}
+
+/// Returns the lower bound and upper bound(exclusive) of all regions in global coordinates
+fn global_bounds(schem: &Schematic) -> Option<([i32; 3], [i32; 3])> {
+    let mut result: Option<([i32; 3], [i32; 3])> = None;
+    for reg in &schem.regions {
+        let lb = reg.offset;
+        let shape = reg.shape();
+        let ub: [i32; 3] = std::array::from_fn(|dim| lb[dim] + shape[dim]);
+        result = Some(match result {
+            None => (lb, ub),
+            Some((l, u)) => (std::array::from_fn(|dim| l[dim].min(lb[dim])),
+                             std::array::from_fn(|dim| u[dim].max(ub[dim]))),
+        });
+    }
+    return result;
+}
+
+impl Schematic {
+    /// Compare blocks, block entities and entities of this schematic with `new`. Positions are
+    /// compared in global coordinates, so regions may have different offsets and shapes; if
+    /// multiple regions overlap, the first region is used, like `first_block_info_at`.
+    ///
+    /// A position is added or removed only if it's outside all regions of the other schematic, so
+    /// air replacing stone is a change of block id.
+    pub fn diff(&self, new: &Schematic) -> SchematicDiff {
+        let mut result = SchematicDiff {
+            blocks: BTreeMap::new(),
+            block_entities: BTreeMap::new(),
+            added_entities: Vec::new(),
+            removed_entities: Vec::new(),
+        };
+
+        let bounds = match (global_bounds(self), global_bounds(new)) {
+            (None, None) => None,
+            (Some(b), None) | (None, Some(b)) => Some(b),
+            (Some((l1, u1)), Some((l2, u2))) => Some((std::array::from_fn(|dim| l1[dim].min(l2[dim])),
+                                                      std::array::from_fn(|dim| u1[dim].max(u2[dim])))),
+        };
+
+        if let Some((lb, ub)) = bounds {
+            for y in lb[1]..ub[1] {
+                for z in lb[2]..ub[2] {
+                    for x in lb[0]..ub[0] {
+                        let pos = [x, y, z];
+                        let old_info = self.first_block_info_at(pos);
+                        let new_info = new.first_block_info_at(pos);
+                        let (old_blk, old_be) = match old_info {
+                            Some((_, blk, be, _)) => (Some(blk), be),
+                            None => (None, None),
+                        };
+                        let (new_blk, new_be) = match new_info {
+                            Some((_, blk, be, _)) => (Some(blk), be),
+                            None => (None, None),
+                        };
+
+                        let block_diff = match (old_blk, new_blk) {
+                            (None, None) => None,
+                            (None, Some(n)) => Some(BlockDiff::Added(n.clone())),
+                            (Some(o), None) => Some(BlockDiff::Removed(o.clone())),
+                            (Some(o), Some(n)) => {
+                                if o == n {
+                                    None
+                                } else if o.namespace != n.namespace || o.id != n.id {
+                                    Some(BlockDiff::IdChanged { old: o.clone(), new: n.clone() })
+                                } else {
+                                    Some(BlockDiff::StateChanged { old: o.clone(), new: n.clone() })
+                                }
+                            }
+                        };
+                        if let Some(d) = block_diff {
+                            result.blocks.insert(pos, d);
+                        }
+
+                        let be_diff = match (old_be, new_be) {
+                            (None, None) => None,
+                            (None, Some(n)) => Some(BlockEntityDiff::Added(n.clone())),
+                            (Some(o), None) => Some(BlockEntityDiff::Removed(o.clone())),
+                            (Some(o), Some(n)) => {
+                                if o.tags == n.tags {
+                                    None
+                                } else {
+                                    Some(BlockEntityDiff::Changed { old: o.clone(), new: n.clone() })
+                                }
+                            }
+                        };
+                        if let Some(d) = be_diff {
+                            result.block_entities.insert(pos, d);
+                        }
+                    }
+                }
+            }
+        }
+
+        // entities are matched one to one, so duplicated entities are counted correctly
+        let mut new_entities: Vec<Option<EntityBox>> = global_entities(new).into_iter().map(Some).collect();
+        for old_entity in global_entities(self) {
+            let matched = new_entities.iter_mut()
+                .find(|e| e.as_ref().is_some_and(|e| same_entity(e, &old_entity)));
+            match matched {
+                Some(slot) => *slot = None,
+                None => result.removed_entities.push(old_entity),
+            }
+        }
+        result.added_entities = new_entities.into_iter().flatten().collect();
+
+        return result;
+    }
+}
+
+impl SchematicDiff {
+    /// Returns true if two schematics are identical
+    pub fn is_empty(&self) -> bool {
+        return self.blocks.is_empty()
+            && self.block_entities.is_empty()
+            && self.added_entities.is_empty()
+            && self.removed_entities.is_empty();
+    }
+
+    /// Count of block changes in `(added, removed, id changed, state changed)`
+    pub fn block_change_counts(&self) -> (usize, usize, usize, usize) {
+        let mut counts = (0, 0, 0, 0);
+        for d in self.blocks.values() {
+            match d {
+                BlockDiff::Added(_) => counts.0 += 1,
+                BlockDiff::Removed(_) => counts.1 += 1,
+                BlockDiff::IdChanged { .. } => counts.2 += 1,
+                BlockDiff::StateChanged { .. } => counts.3 += 1,
+            }
+        }
+        return counts;
+    }
+
+    /// Count of block entity changes in `(added, removed, changed)`
+    pub fn block_entity_change_counts(&self) -> (usize, usize, usize) {
+        let mut counts = (0, 0, 0);
+        for d in self.block_entities.values() {
+            match d {
+                BlockEntityDiff::Added(_) => counts.0 += 1,
+                BlockEntityDiff::Removed(_) => counts.1 += 1,
+                BlockEntityDiff::Changed { .. } => counts.2 += 1,
+            }
+        }
+        return counts;
+    }
+
+    /// Write changed cells into a new schematic with one region that encloses all changed blocks
+    /// and block entities. Changed positions are filled with the new block(removed positions are
+    /// filled with air), and others are filled with structure void. Added entities inside the region
+    /// are kept. The region offset is the global lower bound of changes.
+    pub fn to_schematic(&self, metadata: MetaDataIR) -> Result<Schematic, Error> {
+        let mut schem = Schematic::new();
+        schem.metadata = metadata;
+
+        let mut positions = self.blocks.keys().chain(self.block_entities.keys());
+        let first = match positions.next() {
+            Some(p) => *p,
+            None => return Ok(schem),
+        };
+        let (mut lb, mut ub) = (first, first);
+        for pos in positions {
+            for dim in 0..3 {
+                lb[dim] = lb[dim].min(pos[dim]);
+                ub[dim] = ub[dim].max(pos[dim]);
+            }
+        }
+        let shape: [i32; 3] = std::array::from_fn(|dim| ub[dim] - lb[dim] + 1);
+
+        let mut region = Region::with_shape(shape);
+        region.name = "Diff".to_string();
+        region.offset = lb;
+        region.fill_with(&Block::structure_void());
+        let air = Block::air();
+        for (pos, d) in &self.blocks {
+            let blk = d.new_block().unwrap_or(&air);
+            let r_pos = region.global_pos_to_relative_pos(*pos);
+            region.set_block(r_pos, blk).unwrap();
+        }
+        for (pos, d) in &self.block_entities {
+            if let Some(be) = d.new_block_entity() {
+                region.block_entities.insert(region.global_pos_to_relative_pos(*pos), be.clone());
+            }
+        }
+        for entity in &self.added_entities {
+            let r_pos = region.global_pos_to_relative_pos(entity.block_pos());
+            if region.contains_coord(r_pos) {
+                let mut e = entity.clone();
+                e.pos_shift([-lb[0], -lb[1], -lb[2]]);
+                region.entities.push(e);
+            }
+        }
+        region.shrink_palette()?;
+        schem.regions.push(region);
+        return Ok(schem);
+    }
+}
@@ -17,17 +17,22 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
 use std::cmp::max;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::From;
 use std::fs::File;
-use fastnbt::{LongArray, Value};
+use ndarray::Array3;
+use fastnbt::{IntArray, LongArray, Value};
 use flate2::{GzBuilder};
 use flate2::read::GzDecoder;
+use serde::de::{DeserializeSeed, MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use crate::schem::{LitematicaMetaData, Schematic, id_of_nbt_tag, MetaDataIR, Region, LitematicaLoadOption, BlockEntity, LitematicaSaveOption};
-use crate::error::{Error};
+use crate::error::{Error, ErrorHandler};
 use crate::{unwrap_opt_tag, unwrap_tag};
 use crate::schem::common;
 use crate::region::{PendingTick, PendingTickInfo, WorldSlice};
+use crate::player::EntityBox;
 use crate::schem::common::size_i32_abs;
 
 impl MetaDataIR {
@@ -43,6 +48,7 @@ impl MetaDataIR {
 
         result.litematica_version = src.version;
         result.litematica_subversion = src.sub_version;
+        result.litematica_preview = src.preview_image_data.clone();
         //result.date = Some(src.time_modified);
         return result;
     }
@@ -73,7 +79,7 @@ impl Schematic {
         return Self::from_litematica_nbt(parsed, option);
     }
 
-    pub fn from_litematica_nbt(mut nbt: HashMap<String, Value>, _option: &LitematicaLoadOption) -> Result<(Schematic, LitematicaMetaData), Error> {
+    pub fn from_litematica_nbt(mut nbt: HashMap<String, Value>, option: &LitematicaLoadOption) -> Result<(Schematic, LitematicaMetaData), Error> {
         let mut schem = Schematic::new();
         let raw_metadata;
         match parse_metadata(&nbt) {
@@ -88,7 +94,7 @@ impl Schematic {
         schem.regions.reserve(regions.len());
         for (key, val) in regions {
             let reg = unwrap_tag!(val,Compound,HashMap::new(),format!("/Regions/{}",key));
-            match Region::from_nbt_litematica(reg, &*format!("/Regions/{}", key)) {
+            match Region::from_nbt_litematica(reg, &*format!("/Regions/{}", key), option) {
                 Ok(mut reg) => {
                     reg.name = key.clone();
                     schem.regions.push(reg);
@@ -152,6 +158,11 @@ fn parse_metadata(root: &HashMap<String, Value>) -> Result<LitematicaMetaData, E
         unwrap_opt_tag!(md.get("EnclosingSize"),Compound,HashMap::new(),"/Metadata/EnclosingSize".to_string()),
         "/Metadata/EnclosingSize", false)?;
 
+    if let Some(value) = md.get("PreviewImageData") {
+        let data = unwrap_tag!(value,IntArray,IntArray::new(vec![]),"/Metadata/PreviewImageData");
+        result.preview_image_data = Some(data.to_vec());
+    }
+
     if let Some(value) = root.get("SubVersion") {
         result.sub_version = Some(*unwrap_tag!(value,Int,0,"/SubVersion"));
     }
@@ -171,7 +182,52 @@ pub fn block_required_bits(palette_size: usize) -> usize {
 
 impl Region {
     /// Load a region from nbt
-    pub fn from_nbt_litematica(nbt: &mut HashMap<String, Value>, tag_path: &str) -> Result<Region, Error> {
+    pub fn from_nbt_litematica(nbt: &mut HashMap<String, Value>, tag_path: &str, option: &LitematicaLoadOption) -> Result<Region, Error> {
+        let block_states = unwrap_opt_tag!(nbt.remove("BlockStates"),LongArray,LongArray::new(vec![]),format!("{}/BlockStates",tag_path));
+        let block_states: Vec<u64> = block_states.into_inner().into_iter().map(|val| val as u64).collect();
+        let array_yzx = Self::decode_block_states_litematica(nbt, block_states, tag_path)?;
+        return Self::from_nbt_litematica_with_block_array(nbt, array_yzx, tag_path, option);
+    }
+
+    /// Decode `BlockStates` into palette indices in yzx order. Indices out of range of the palette
+    /// are kept, and they are fixed when the region is built.
+    fn decode_block_states_litematica(nbt: &HashMap<String, Value>, block_states: Vec<u64>, tag_path: &str) -> Result<Array3<u16>, Error> {
+        let palette_len = unwrap_opt_tag!(nbt.get("BlockStatePalette"),List,vec![],format!("{}/BlockStatePalette",tag_path)).len();
+        let cur_tag_path = format!("{}/Size", tag_path);
+        let size = unwrap_opt_tag!(nbt.get("Size"),Compound,HashMap::new(),cur_tag_path);
+        let size = size_i32_abs(common::parse_size_compound(size, &cur_tag_path, true)?);
+        let shape = [size[1] as usize, size[2] as usize, size[0] as usize];
+
+        let mut array_yzx = Array3::zeros(shape);
+        // every block is the only one in palette
+        if palette_len <= 1 {
+            return Ok(array_yzx);
+        }
+        let volume: usize = shape.iter().product();
+        let bits = block_required_bits(palette_len);
+        let num_u64 = block_states.len();
+        let Some(mbs) = MultiBitSet::from_data_vec(block_states, volume, bits as u8) else {
+            return Err(Error::InvalidValue {
+                tag_path: format!("{tag_path}/BlockStates"),
+                error: format!("{volume} blocks of {bits} bits require {} longs, but found {num_u64}", (volume * bits).div_ceil(64)),
+            });
+        };
+        let mut idx = 0;
+        for y in 0..shape[0] {
+            for z in 0..shape[1] {
+                for x in 0..shape[2] {
+                    array_yzx[[y, z, x]] = mbs.get(idx).min(u16::MAX as u64) as u16;
+                    idx += 1;
+                }
+            }
+        }
+        return Ok(array_yzx);
+    }
+
+    /// Load a region from nbt, but `BlockStates` is decoded separately
+    fn from_nbt_litematica_with_block_array(nbt: &mut HashMap<String, Value>, array_yzx: Array3<u16>, tag_path: &str,
+                                            option: &LitematicaLoadOption) -> Result<Region, Error> {
+        let handler = &*option.error_handler;
         let mut region = Region::new();
 
         // parse position(offset)
@@ -192,58 +248,31 @@ impl Region {
             for (idx, blk_nbt) in palette.iter().enumerate() {
                 let cur_tag_path = format!("{}/BlockStatePalette[{}]", tag_path, idx);
                 let blk_nbt = unwrap_tag!(blk_nbt,Compound,HashMap::new(),&cur_tag_path);
-                let block = common::parse_block(blk_nbt, &cur_tag_path);
-                match block {
-                    Ok(blk) => region.palette.push(blk),
-                    Err(e) => return Err(e),
-                }
-            }
-        }
-
-        // parse size
-        let region_size;
-        {
-            let cur_tag_path = format!("{}/Size", tag_path);
-            let size = unwrap_opt_tag!(nbt.get("Size"),Compound,HashMap::new(),cur_tag_path);
-            match common::parse_size_compound(size, &cur_tag_path, true) {
-                Ok(size) => {
-                    let size = size_i32_abs(size);
-                    region.reshape(&size);
-                    region_size = size;
-                },
-                Err(e) => return Err(e),
+                let block = match common::parse_block(blk_nbt, &cur_tag_path) {
+                    Ok(blk) => blk,
+                    Err(e) => handler.handle_invalid_block_id(e)?,
+                };
+                region.palette.push(block);
             }
         }
 
-
-        let total_blocks = region_size[0] as isize * region_size[1] as isize * region_size[2] as isize;
-
         //parse 3d
         {
+            region.array_yzx = array_yzx;
             let palette_len = region.palette.len();
-            let array =
-                unwrap_opt_tag!(nbt.get("BlockStates"),LongArray,LongArray::new(vec![]),format!("{}/BlockStates",tag_path));
-            let mut array_u8_be: Vec<u64> = Vec::with_capacity(array.len());
-            for val in array.iter() {
-                array_u8_be.push(u64::from_ne_bytes(val.to_le_bytes()));
-            }
-            let mbs = MultiBitSet::from_data_vec(array_u8_be, total_blocks as usize, block_required_bits(palette_len) as u8);
-            assert!(mbs.is_some());
-            let mbs = mbs.unwrap();
-            let mut idx = 0;
-            for y in 0..region.shape()[1] {
-                for z in 0..region.shape()[2] {
-                    for x in 0..region.shape()[0] {
-                        let blk_id = mbs.get(idx);
-                        if blk_id >= palette_len as u64 {
-                            return Err(Error::BlockIndexOutOfRange {
+            let shape = region.array_yzx.dim();
+            for y in 0..shape.0 {
+                for z in 0..shape.1 {
+                    for x in 0..shape.2 {
+                        let blk_id = region.array_yzx[[y, z, x]];
+                        if blk_id as usize >= palette_len {
+                            let fixed = handler.handle_block_index_out_of_range(&mut region, Error::BlockIndexOutOfRange {
                                 tag_path: format!("{}/BlockStates", tag_path),
                                 index: blk_id as i32,
                                 range: [0, palette_len as i32],
-                            })
+                            })?;
+                            region.array_yzx[[y, z, x]] = fixed;
                         }
-                        idx += 1;
-                        region.array_yzx[[y as usize, z as usize, x as usize, ]] = blk_id as u16;
                     }
                 }
             }
@@ -259,7 +288,7 @@ impl Region {
                     unwrap_tag!(entity_comp,Compound,HashMap::new(),cur_tag_path);
                 let mut temp = HashMap::new();
                 std::mem::swap(&mut temp, entity_comp);
-                let parse_res = common::parse_entity_litematica(temp, &cur_tag_path);
+                let parse_res = EntityBox::from_nbt(temp, &cur_tag_path);
                 match parse_res {
                     Ok(entity) => region.entities.push(entity),
                     Err(e) => return Err(e),
@@ -276,15 +305,16 @@ impl Region {
                 let te_comp = unwrap_tag!(te_comp,Compound,HashMap::new(),cur_tag_path);
                 let mut temp = HashMap::new();
                 std::mem::swap(&mut temp, te_comp);
-                let te_res = parse_tile_entity(temp, tag_path, &region_size);
+                let te_res = parse_tile_entity(temp, tag_path, &mut region, handler);
 
                 let pos;
                 let te;
                 match te_res {
-                    Ok((pos_, te_)) => {
+                    Ok(Some((pos_, te_))) => {
                         pos = pos_;
                         te = te_;
                     }
+                    Ok(None) => continue,
                     Err(e) => return Err(e),
                 }
 
@@ -321,7 +351,9 @@ impl Region {
                 for (idx, tick) in ticks.iter().enumerate() {
                     let path = format!("{path}/[{idx}]");
                     let tick = unwrap_tag!(tick,Compound,HashMap::new(),path);
-                    let (pos, tick) = parse_pending_tick(tick, &path, &region.shape(), is_block)?;
+                    let Some((pos, tick)) = parse_pending_tick(tick, &path, &mut region, handler, is_block)? else {
+                        continue;
+                    };
 
                     // if region.pending_ticks.contains_key(&pos) {
                     //     debug_assert!(tick_tag_record.contains_key(&pos));
@@ -581,41 +613,55 @@ impl MultiBitSet {
 }
 
 
-fn parse_tile_entity(nbt: HashMap<String, Value>, tag_path: &str, region_size: &[i32; 3])
-    -> Result<([i32; 3], BlockEntity), Error> {
-    let (pos, be) = common::parse_block_entity_nocheck(nbt, tag_path, false)?;
+/// Returns `None` if the tile entity is out of the region and dropped by the error handler
+fn parse_tile_entity(nbt: HashMap<String, Value>, tag_path: &str, region: &mut Region, handler: &dyn ErrorHandler)
+    -> Result<Option<([i32; 3], BlockEntity)>, Error> {
+    let (mut pos, be) = common::parse_block_entity_nocheck(nbt, tag_path, false)?;
+    let region_size = region.shape();
 
     let tag_names = ['x', 'y', 'z'];
     for (dim, p) in pos.iter().enumerate() {
         if *p < 0 || *p > region_size[dim] {
-            return Err(Error::BlockPosOutOfRange {
+            let fixed = handler.handle_block_pos_out_of_range(region, Error::BlockPosOutOfRange {
                 tag_path: format!("{}/{}", tag_path, tag_names[dim]),
                 pos,
                 lower_bound: [0, 0, 0],
-                upper_bound: *region_size,
-            });
+                upper_bound: region_size,
+            })?;
+            match fixed {
+                Some(p) => pos = p,
+                None => return Ok(None),
+            }
+            break;
         }
     }
-    return Ok((pos, be));
+    return Ok(Some((pos, be)));
 }
 
 
-fn parse_pending_tick(nbt: &HashMap<String, Value>, tag_path: &str, region_size: &[i32; 3], is_block: bool)
-    -> Result<([i32; 3], PendingTick), Error> {
-    let pos;
+/// Returns `None` if the tick is out of the region and dropped by the error handler
+fn parse_pending_tick(nbt: &HashMap<String, Value>, tag_path: &str, region: &mut Region, handler: &dyn ErrorHandler, is_block: bool)
+    -> Result<Option<([i32; 3], PendingTick)>, Error> {
+    let mut pos;
     match common::parse_size_compound(nbt, tag_path, false) {
         Ok(p) => pos = p,
         Err(e) => return Err(e),
     }
+    let region_size = region.shape();
     let pos_keys = ['x', 'y', 'z'];
     for dim in 0..3 {
         if pos[dim] < 0 || pos[dim] >= region_size[dim] {
-            return Err(Error::BlockPosOutOfRange {
+            let fixed = handler.handle_block_pos_out_of_range(region, Error::BlockPosOutOfRange {
                 tag_path: format!("{}/{}", tag_path, pos_keys[dim]),
                 pos,
                 lower_bound: [0, 0, 0],
-                upper_bound: *region_size,
-            });
+                upper_bound: region_size,
+            })?;
+            match fixed {
+                Some(p) => pos = p,
+                None => return Ok(None),
+            }
+            break;
         }
     }
 
@@ -636,7 +682,7 @@ fn parse_pending_tick(nbt: &HashMap<String, Value>, tag_path: &str, region_size:
         };
     }
 
-    return Ok((pos, pending_tick));
+    return Ok(Some((pos, pending_tick)));
 }
 
 #[allow(dead_code)]
@@ -652,6 +698,7 @@ impl Schematic {
         md.description = self.metadata.description.clone();
         md.version = self.metadata.litematica_version;
         md.sub_version = self.metadata.litematica_subversion;
+        md.preview_image_data = self.metadata.litematica_preview.clone();
         md.total_volume = self.volume() as i32;
         md.region_count = self.regions.len() as i32;
         md.total_blocks = self.total_blocks(false) as i32;
@@ -660,68 +707,77 @@ impl Schematic {
         return Ok(md);
     }
 
-    fn find_non_duplicate_name<T>(saved_regions: &HashMap<String, T>, old_name: &str) -> String {
-        let idx = 1u64;
+    fn find_non_duplicate_name(used_names: &HashSet<String>, old_name: &str) -> String {
+        let mut idx = 1u64;
         loop {
             let cur_name = format!("{}({})", old_name, idx);
-            if saved_regions.contains_key(&cur_name) {
+            if used_names.contains(&cur_name) {
+                idx += 1;
                 continue;
             }
             return cur_name;
         }
     }
+
+    /// Names of regions to save, duplicated names are renamed or rejected according to `option`
+    fn litematica_region_names(&self, option: &LitematicaSaveOption) -> Result<Vec<String>, Error> {
+        let mut used_names = HashSet::with_capacity(self.regions.len());
+        let mut names = Vec::with_capacity(self.regions.len());
+        for reg in &self.regions {
+            let mut name = reg.name.clone();
+            if used_names.contains(&name) {
+                if !option.rename_duplicated_regions {
+                    return Err(Error::DuplicatedRegionName { name });
+                }
+                name = Self::find_non_duplicate_name(&used_names, &reg.name);
+            }
+            used_names.insert(name.clone());
+            names.push(name);
+        }
+        return Ok(names);
+    }
+
     /// Save into nbt format
     pub fn to_nbt_litematica(&self, option: &LitematicaSaveOption) -> Result<HashMap<String, Value>, Error> {
-        let mut nbt: HashMap<String, Value> = HashMap::new();
+        let mut nbt = self.to_nbt_litematica_without_regions()?;
 
         //Regions
         {
+            let names = self.litematica_region_names(option)?;
             let mut regions: HashMap<String, Value> = HashMap::with_capacity(self.regions.len());
-            for reg in &self.regions {
-                let nbt_region;
-                match reg.to_nbt_litematica() {
-                    Ok(nbt) => nbt_region = nbt,
-                    Err(e) => return Err(e),
-                }
-
-                if regions.contains_key(&reg.name) {
-                    if option.rename_duplicated_regions {
-                        let new_name = Self::find_non_duplicate_name(&regions, &reg.name);
-                        regions.insert(new_name, Value::Compound(nbt_region));
-                        continue;
-                    }
-                    return Err(Error::DuplicatedRegionName { name: reg.name.clone() });
-                }
-                regions.insert(reg.name.clone(), Value::Compound(nbt_region));
+            for (reg, name) in self.regions.iter().zip(names) {
+                regions.insert(name, Value::Compound(reg.to_nbt_litematica()?));
             }
             nbt.insert("Regions".to_string(), Value::Compound(regions));
         }
+        return Ok(nbt);
+    }
 
-        // meta data
+    /// Save metadata into nbt format
+    fn to_nbt_litematica_without_regions(&self) -> Result<HashMap<String, Value>, Error> {
+        let mut nbt: HashMap<String, Value> = HashMap::new();
+        let md = self.metadata_litematica()?;
+        nbt.insert("MinecraftDataVersion".to_string(), Value::Int(md.data_version));
+        nbt.insert("Version".to_string(), Value::Int(md.version));
+        if let Some(sv) = md.sub_version {
+            nbt.insert("SubVersion".to_string(), Value::Int(sv));
+        }
         {
-            let md = match self.metadata_litematica() {
-                Ok(md_) => md_,
-                Err(e) => return Err(e),
-            };
-            nbt.insert("MinecraftDataVersion".to_string(), Value::Int(md.data_version));
-            nbt.insert("Version".to_string(), Value::Int(md.version));
-            if let Some(sv) = md.sub_version {
-                nbt.insert("SubVersion".to_string(), Value::Int(sv));
-            }
-            {
-                let mut md_nbt = HashMap::new();
-                md_nbt.insert("Name".to_string(), Value::String(md.name));
-                md_nbt.insert("Author".to_string(), Value::String(md.author));
-                md_nbt.insert("Description".to_string(), Value::String(md.description));
-                md_nbt.insert("TimeCreated".to_string(), Value::Long(md.time_created));
-                md_nbt.insert("TimeModified".to_string(), Value::Long(md.time_modified));
-                md_nbt.insert("TotalVolume".to_string(), Value::Int(self.volume() as i32));
-                md_nbt.insert("TotalBlocks".to_string(), Value::Int(self.total_blocks(false) as i32));
-                md_nbt.insert("RegionCount".to_string(), Value::Int(self.regions.len() as i32));
-                md_nbt.insert("EnclosingSize".to_string(), Value::Compound(common::size_to_compound(&self.shape())));
-
-                nbt.insert("Metadata".to_string(), Value::Compound(md_nbt));
+            let mut md_nbt = HashMap::new();
+            md_nbt.insert("Name".to_string(), Value::String(md.name));
+            md_nbt.insert("Author".to_string(), Value::String(md.author));
+            md_nbt.insert("Description".to_string(), Value::String(md.description));
+            md_nbt.insert("TimeCreated".to_string(), Value::Long(md.time_created));
+            md_nbt.insert("TimeModified".to_string(), Value::Long(md.time_modified));
+            md_nbt.insert("TotalVolume".to_string(), Value::Int(self.volume() as i32));
+            md_nbt.insert("TotalBlocks".to_string(), Value::Int(self.total_blocks(false) as i32));
+            md_nbt.insert("RegionCount".to_string(), Value::Int(self.regions.len() as i32));
+            md_nbt.insert("EnclosingSize".to_string(), Value::Compound(common::size_to_compound(&self.shape())));
+            if let Some(preview) = md.preview_image_data {
+                md_nbt.insert("PreviewImageData".to_string(), Value::IntArray(IntArray::new(preview)));
             }
+
+            nbt.insert("Metadata".to_string(), Value::Compound(md_nbt));
         }
         return Ok(nbt);
     }
@@ -779,6 +835,31 @@ impl Schematic {
 impl Region {
     /// Save region to nbt
     pub fn to_nbt_litematica(&self) -> Result<HashMap<String, Value>, Error> {
+        let mut nbt = self.to_nbt_litematica_without_block_states()?;
+        let i64_rep: Vec<i64> = self.block_states_litematica().into_iter().map(|val| val as i64).collect();
+        nbt.insert("BlockStates".to_string(), Value::LongArray(LongArray::new(i64_rep)));
+        return Ok(nbt);
+    }
+
+    /// Encode blocks into `BlockStates` of litematica
+    pub(crate) fn block_states_litematica(&self) -> Vec<u64> {
+        let mut mbs = MultiBitSet::new();
+        mbs.reset(block_required_bits(self.palette.len()).max(1) as u8, self.volume() as usize);
+        let mut idx = 0usize;
+        for y in 0..self.shape()[1] as usize {
+            for z in 0..self.shape()[2] as usize {
+                for x in 0..self.shape()[0] as usize {
+                    let res = mbs.set(idx, self.array_yzx[[y, z, x]] as u64);
+                    assert!(res.is_ok());
+                    idx += 1;
+                }
+            }
+        }
+        return mbs.arr;
+    }
+
+    /// Save region to nbt except `BlockStates`, which is the only huge tag
+    pub(crate) fn to_nbt_litematica_without_block_states(&self) -> Result<HashMap<String, Value>, Error> {
         let mut nbt = HashMap::new();
         //Size
         nbt.insert("Size".to_string(), Value::Compound(common::size_to_compound(&self.shape())));
@@ -796,34 +877,10 @@ impl Region {
         {
             let mut entities = Vec::with_capacity(self.entities.len());
             for entity in &self.entities {
-                let mut e_nbt = entity.tags.clone();
-                e_nbt.insert("Pos".to_string(), Value::List(common::size_to_list(&entity.position)));
-                entities.push(Value::Compound(e_nbt));
+                entities.push(Value::Compound(entity.to_nbt()));
             }
             nbt.insert("Entities".to_string(), Value::List(entities));
         }
-        // BlockStates
-        {
-            let mut mbs = MultiBitSet::new();
-            mbs.reset(block_required_bits(self.palette.len()) as u8, self.volume() as usize);
-            let mut idx = 0usize;
-            for y in 0..self.shape()[1] as usize {
-                for z in 0..self.shape()[2] as usize {
-                    for x in 0..self.shape()[0] as usize {
-                        let res = mbs.set(idx, self.array_yzx[[y, z, x]] as u64);
-                        assert!(res.is_ok());
-                        idx += 1;
-                    }
-                }
-            }
-
-            let u64_slice = mbs.as_u64_slice();
-            let mut i64_rep = Vec::with_capacity(u64_slice.len());
-            for u_val in u64_slice {
-                i64_rep.push(i64::from_le_bytes(u_val.to_ne_bytes()));
-            }
-            nbt.insert("BlockStates".to_string(), Value::LongArray(LongArray::new(i64_rep)));
-        }
         //TileEntities
         {
             let mut te_list = Vec::with_capacity(self.block_entities.len());
@@ -859,6 +916,228 @@ impl Region {
 }
 
 
+/// A litematica region parsed as soon as it's read, so that only one `BlockStates` is kept in memory
+struct StreamedRegionSeed {
+    tag_path: String,
+}
+
+/// Tags of a region with `BlockStates` decoded. The region is built after the whole file is read,
+/// with the error handler in load option.
+struct StreamedRegion {
+    nbt: HashMap<String, Value>,
+    array_yzx: Array3<u16>,
+}
+
+impl<'de> DeserializeSeed<'de> for StreamedRegionSeed {
+    type Value = Result<StreamedRegion, Error>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        return deserializer.deserialize_map(self);
+    }
+}
+
+impl<'de> Visitor<'de> for StreamedRegionSeed {
+    type Value = Result<StreamedRegion, Error>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        return write!(formatter, "a litematica region");
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut nbt = HashMap::new();
+        let mut block_states = None;
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "BlockStates" {
+                block_states = Some(map.next_value::<common::StreamedLongArray>()?.0);
+            } else {
+                let value: Value = map.next_value()?;
+                nbt.insert(key, value);
+            }
+        }
+        let block_states = match block_states {
+            Some(bs) => bs,
+            None => return Ok(Err(Error::TagMissing(format!("{}/BlockStates", self.tag_path)))),
+        };
+        return Ok(Region::decode_block_states_litematica(&nbt, block_states, &self.tag_path)
+            .map(|array_yzx| StreamedRegion { nbt, array_yzx }));
+    }
+}
+
+/// Regions of litematica in the order of file
+struct StreamedRegions(Vec<(String, Result<StreamedRegion, Error>)>);
+
+impl<'de> Deserialize<'de> for StreamedRegions {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct RegionsVisitor;
+        impl<'de> Visitor<'de> for RegionsVisitor {
+            type Value = StreamedRegions;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                return write!(formatter, "a compound of litematica regions");
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut regions = Vec::new();
+                while let Some(name) = map.next_key::<String>()? {
+                    let tag_path = format!("/Regions/{}", name);
+                    let region = map.next_value_seed(StreamedRegionSeed { tag_path })?;
+                    regions.push((name, region));
+                }
+                return Ok(StreamedRegions(regions));
+            }
+        }
+        return deserializer.deserialize_map(RegionsVisitor);
+    }
+}
+
+/// Root of litematica, `Regions` is parsed while reading and other tags are kept as `Value`
+struct StreamedLitematica {
+    root: HashMap<String, Value>,
+    regions: Option<StreamedRegions>,
+}
+
+impl<'de> Deserialize<'de> for StreamedLitematica {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct RootVisitor;
+        impl<'de> Visitor<'de> for RootVisitor {
+            type Value = StreamedLitematica;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                return write!(formatter, "a litematica file");
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut result = StreamedLitematica { root: HashMap::new(), regions: None };
+                while let Some(key) = map.next_key::<String>()? {
+                    if key == "Regions" {
+                        result.regions = Some(map.next_value()?);
+                    } else {
+                        let value: Value = map.next_value()?;
+                        result.root.insert(key, value);
+                    }
+                }
+                return Ok(result);
+            }
+        }
+        return deserializer.deserialize_map(RootVisitor);
+    }
+}
+
+/// Serialize a region with `BlockStates` encoded only when it's written
+struct StreamedRegionSer<'a>(&'a Region);
+
+impl Serialize for StreamedRegionSer<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let tags = self.0.to_nbt_litematica_without_block_states().map_err(serde::ser::Error::custom)?;
+        let mut map = serializer.serialize_map(Some(tags.len() + 1))?;
+        for (key, value) in &tags {
+            map.serialize_entry(key, value)?;
+        }
+        drop(tags);
+        let bytes = common::encode_long_array(&self.0.block_states_litematica());
+        map.serialize_entry("BlockStates", &common::NbtArrayBytes { token: common::LONG_ARRAY_TOKEN, bytes: &bytes })?;
+        return map.end();
+    }
+}
+
+struct StreamedRegionsSer<'a>(Vec<(String, &'a Region)>);
+
+impl Serialize for StreamedRegionsSer<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (name, reg) in &self.0 {
+            map.serialize_entry(name, &StreamedRegionSer(reg))?;
+        }
+        return map.end();
+    }
+}
+
+struct StreamedLitematicaSer<'a> {
+    root: HashMap<String, Value>,
+    regions: StreamedRegionsSer<'a>,
+}
+
+impl Serialize for StreamedLitematicaSer<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.root.len() + 1))?;
+        for (key, value) in &self.root {
+            map.serialize_entry(key, value)?;
+        }
+        map.serialize_entry("Regions", &self.regions)?;
+        return map.end();
+    }
+}
+
+impl Schematic {
+    /// Load litematica from a reader without building the whole nbt tree. `BlockStates` of each
+    /// region is decoded right after it's read, so the peak memory is about the size of loaded
+    /// schematic plus `BlockStates` of a single region.
+    pub fn from_litematica_reader_streaming(src: &mut dyn std::io::Read, option: &LitematicaLoadOption) -> Result<(Schematic, LitematicaMetaData), Error> {
+        let parsed: Result<StreamedLitematica, _> = fastnbt::from_reader(src);
+        let parsed = match parsed {
+            Ok(p) => p,
+            Err(e) => return Err(Error::NBTReadError(e)),
+        };
+        let mut schem = Schematic::new();
+        let raw_metadata = parse_metadata(&parsed.root)?;
+        schem.metadata = MetaDataIR::from_litematica(&raw_metadata);
+
+        let regions = match parsed.regions {
+            Some(r) => r.0,
+            None => return Err(Error::TagMissing("/Regions".to_string())),
+        };
+        schem.regions.reserve(regions.len());
+        for (name, reg) in regions {
+            let StreamedRegion { mut nbt, array_yzx } = reg?;
+            let tag_path = format!("/Regions/{}", name);
+            let mut reg = Region::from_nbt_litematica_with_block_array(&mut nbt, array_yzx, &tag_path, option)?;
+            reg.name = name;
+            schem.regions.push(reg);
+        }
+        return Ok((schem, raw_metadata));
+    }
+
+    /// Load litematica from file, see `from_litematica_reader_streaming`
+    pub fn from_litematica_file_streaming(filename: &str, option: &LitematicaLoadOption) -> Result<(Schematic, LitematicaMetaData), Error> {
+        let mut file = match File::open(filename) {
+            Ok(f) => f,
+            Err(e) => return Err(Error::FileOpenError(e)),
+        };
+        let mut decoder = GzDecoder::new(&mut file);
+        return Self::from_litematica_reader_streaming(&mut decoder, option);
+    }
+
+    /// Save litematica to writer without building the whole nbt tree. `BlockStates` of each region
+    /// is encoded only when it's written.
+    pub fn save_litematica_writer_streaming(&self, dest: &mut dyn std::io::Write, option: &LitematicaSaveOption) -> Result<(), Error> {
+        let names = self.litematica_region_names(option)?;
+        let root = StreamedLitematicaSer {
+            root: self.to_nbt_litematica_without_regions()?,
+            regions: StreamedRegionsSer(names.into_iter().zip(self.regions.iter()).collect()),
+        };
+        let mut encoder = GzBuilder::new().comment("Generated by mc_schem").write(dest, option.compress_level);
+
+        let res: Result<(), fastnbt::error::Error> = fastnbt::to_writer(&mut encoder, &root);
+        if let Err(e) = res {
+            return Err(Error::NBTWriteError(e));
+        }
+        if let Err(e) = encoder.finish() {
+            return Err(Error::NBTWriteError(e.into()));
+        }
+        return Ok(());
+    }
+
+    /// Save litematica to file, see `save_litematica_writer_streaming`
+    pub fn save_litematica_file_streaming(&self, filename: &str, option: &LitematicaSaveOption) -> Result<(), Error> {
+        let mut file = match File::create(filename) {
+            Ok(f) => f,
+            Err(e) => return Err(Error::FileCreateError(e)),
+        };
+        return self.save_litematica_writer_streaming(&mut file, option);
+    }
+}
+
+
 impl PendingTick {
     /// Save a pending tick to nbt, in litematica format
     pub fn to_nbt(&self, pos: &[i32; 3]) -> HashMap<String, Value> {
@@ -0,0 +1,305 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::HashMap;
+use fastnbt::Value;
+use crate::block::Block;
+use crate::schem::{MaterialListOption, Schematic};
+
+/// Count of one kind of item in material list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaterialEntry {
+    /// Full item id, like `minecraft:torch`
+    pub item: String,
+    /// Total count of this item
+    pub count: u64,
+}
+
+/// Items required to build a schematic, sorted by count in descending order
+#[derive(Debug, Clone, Default)]
+pub struct MaterialList {
+    pub entries: Vec<MaterialEntry>,
+}
+
+/// Blocks that have no item, like fire and portals
+const NO_ITEM_IDS: &[&str] = &[
+    "air", "cave_air", "void_air", "structure_void", "fire", "soul_fire", "nether_portal",
+    "end_portal", "end_gateway", "moving_piston", "piston_head", "bubble_column", "frosted_ice",
+];
+
+/// Blocks placed by items of another id
+const RENAMED_IDS: &[(&str, &str)] = &[
+    ("wall_torch", "torch"),
+    ("soul_wall_torch", "soul_torch"),
+    ("redstone_wall_torch", "redstone_torch"),
+    ("wheat", "wheat_seeds"),
+    ("carrots", "carrot"),
+    ("potatoes", "potato"),
+    ("beetroots", "beetroot_seeds"),
+    ("melon_stem", "melon_seeds"),
+    ("attached_melon_stem", "melon_seeds"),
+    ("pumpkin_stem", "pumpkin_seeds"),
+    ("attached_pumpkin_stem", "pumpkin_seeds"),
+    ("torchflower_crop", "torchflower_seeds"),
+    ("pitcher_crop", "pitcher_pod"),
+    ("cocoa", "cocoa_beans"),
+    ("sweet_berry_bush", "sweet_berries"),
+    ("cave_vines", "glow_berries"),
+    ("cave_vines_plant", "glow_berries"),
+    ("tripwire", "string"),
+    ("redstone_wire", "redstone"),
+    ("kelp_plant", "kelp"),
+    ("bamboo_sapling", "bamboo"),
+    ("twisting_vines_plant", "twisting_vines"),
+    ("weeping_vines_plant", "weeping_vines"),
+    ("big_dripleaf_stem", "big_dripleaf"),
+    ("tall_seagrass", "seagrass"),
+    ("farmland", "dirt"),
+    ("dirt_path", "dirt"),
+    ("powder_snow", "powder_snow_bucket"),
+    ("water_cauldron", "cauldron"),
+    ("powder_snow_cauldron", "cauldron"),
+];
+
+/// Wall-mounted variants, `oak_wall_sign` is placed by `oak_sign`
+const WALL_SUFFIXES: &[&str] = &["_wall_sign", "_wall_hanging_sign", "_wall_banner", "_wall_head", "_wall_skull", "_wall_fan"];
+
+fn is_vanilla(blk: &Block) -> bool {
+    return blk.namespace.is_empty() || blk.namespace == "minecraft";
+}
+
+fn int_property(blk: &Block, key: &str, default: u64) -> u64 {
+    return blk.attributes.get(key).and_then(|v| v.parse().ok()).unwrap_or(default);
+}
+
+/// Items required to place one `blk`. Returns an empty vec if the block has no item, or it's
+/// counted by another part of a multi-block structure, like the head of bed.
+pub fn items_of_block(blk: &Block) -> Vec<(String, u64)> {
+    if !is_vanilla(blk) {
+        return vec![(format!("{}:{}", blk.namespace, blk.id), 1)];
+    }
+    let id = blk.id.as_str();
+    if NO_ITEM_IDS.contains(&id) {
+        return vec![];
+    }
+    // beds, doors and tall plants are counted once per two cells
+    if blk.attributes.get("part").is_some_and(|v| v == "head")
+        || blk.attributes.get("half").is_some_and(|v| v == "upper") {
+        return vec![];
+    }
+    let item = |id: &str, count: u64| -> Vec<(String, u64)> {
+        return vec![(format!("minecraft:{id}"), count)];
+    };
+    match id {
+        // only source blocks can be placed with buckets
+        "water" | "lava" => {
+            return if int_property(blk, "level", 0) == 0 { item(&format!("{id}_bucket"), 1) } else { vec![] };
+        }
+        "lava_cauldron" => return vec![("minecraft:cauldron".to_string(), 1), ("minecraft:lava_bucket".to_string(), 1)],
+        "candle_cake" => return vec![("minecraft:cake".to_string(), 1), ("minecraft:candle".to_string(), 1)],
+        "sea_pickle" => return item(id, int_property(blk, "pickles", 1)),
+        "turtle_egg" => return item(id, int_property(blk, "eggs", 1)),
+        "pink_petals" => return item(id, int_property(blk, "flower_amount", 1)),
+        "snow" => return item(id, int_property(blk, "layers", 1)),
+        _ => {}
+    }
+    if let Some(flower) = id.strip_prefix("potted_") {
+        return vec![("minecraft:flower_pot".to_string(), 1), (format!("minecraft:{flower}"), 1)];
+    }
+    if let Some(color) = id.strip_suffix("_candle_cake") {
+        return vec![("minecraft:cake".to_string(), 1), (format!("minecraft:{color}_candle"), 1)];
+    }
+    if id == "candle" || id.ends_with("_candle") {
+        return item(id, int_property(blk, "candles", 1));
+    }
+    if id.ends_with("_slab") && blk.attributes.get("type").is_some_and(|v| v == "double") {
+        return item(id, 2);
+    }
+    for (block_id, item_id) in RENAMED_IDS {
+        if id == *block_id {
+            return item(item_id, 1);
+        }
+    }
+    for suffix in WALL_SUFFIXES {
+        if let Some(prefix) = id.strip_suffix(suffix) {
+            return item(&format!("{prefix}{}", suffix.replacen("_wall", "", 1)), 1);
+        }
+    }
+    return item(id, 1);
+}
+
+/// Max count of item in one slot
+pub fn stack_size_of_item(item: &str) -> u64 {
+    let id = item.strip_prefix("minecraft:").unwrap_or(item);
+    const UNSTACKABLE_SUFFIXES: &[&str] = &[
+        "_sword", "_pickaxe", "_axe", "_shovel", "_hoe", "_helmet", "_chestplate", "_leggings",
+        "_boots", "_bed", "_bucket", "_boat", "_raft", "minecart", "potion", "shulker_box",
+        "_horse_armor", "music_disc", "_book", "_on_a_stick",
+    ];
+    const UNSTACKABLE_IDS: &[&str] = &[
+        "cake", "totem_of_undying", "elytra", "shield", "bow", "crossbow", "trident",
+        "fishing_rod", "flint_and_steel", "shears", "saddle", "brush", "spyglass", "mace",
+    ];
+    const STACK_16_IDS: &[&str] = &["ender_pearl", "snowball", "egg", "armor_stand", "honey_bottle", "bucket"];
+    if id == "book" || id == "bookshelf" {
+        return 64;
+    }
+    if UNSTACKABLE_IDS.contains(&id) || UNSTACKABLE_SUFFIXES.iter().any(|s| id.ends_with(s)) {
+        return 1;
+    }
+    if STACK_16_IDS.contains(&id) || id.ends_with("_sign") || id.ends_with("_banner") {
+        return 16;
+    }
+    return 64;
+}
+
+/// Add items in an inventory list, including items inside shulker box items
+fn count_items_in_list(items: &[Value], counter: &mut HashMap<String, u64>) {
+    for item in items {
+        let Value::Compound(item) = item else { continue };
+        // since 1.20.5, container components store items in `item`
+        let item = match item.get("item") {
+            Some(Value::Compound(inner)) => inner,
+            _ => item,
+        };
+        let id = match item.get("id") {
+            Some(Value::String(id)) => id,
+            _ => continue,
+        };
+        let count = match (item.get("Count"), item.get("count")) {
+            (Some(c), _) | (None, Some(c)) => c.as_i64().unwrap_or(1),
+            (None, None) => 1,
+        };
+        let id = if id.contains(':') { id.clone() } else { format!("minecraft:{id}") };
+        *counter.entry(id).or_insert(0) += count.max(0) as u64;
+
+        if let Some(Value::Compound(tag)) = item.get("tag") {
+            if let Some(Value::Compound(be_tag)) = tag.get("BlockEntityTag") {
+                if let Some(Value::List(nested)) = be_tag.get("Items") {
+                    count_items_in_list(nested, counter);
+                }
+            }
+        }
+        if let Some(Value::Compound(components)) = item.get("components") {
+            if let Some(Value::List(nested)) = components.get("minecraft:container") {
+                count_items_in_list(nested, counter);
+            }
+        }
+    }
+}
+
+impl Schematic {
+    /// Count items required to build this schematic. Blocks are converted to items that place
+    /// them, like `wall_torch` -> `torch`, double slabs count as 2, beds and doors count once, and
+    /// flowing fluids and fire are not counted. Air is never counted.
+    pub fn material_list(&self, option: &MaterialListOption) -> MaterialList {
+        let (full_palette, luts) = self.full_palette();
+        let mut block_counts = vec![0u64; full_palette.len()];
+        for (reg, lut) in self.regions.iter().zip(luts.iter()) {
+            for blk_idx in reg.array_yzx.iter() {
+                block_counts[lut[*blk_idx as usize]] += 1;
+            }
+        }
+
+        let mut counter: HashMap<String, u64> = HashMap::new();
+        for ((blk, _), count) in full_palette.iter().zip(block_counts.iter()) {
+            if *count == 0 {
+                continue;
+            }
+            for (item, num) in items_of_block(blk) {
+                *counter.entry(item).or_insert(0) += num * count;
+            }
+        }
+
+        if option.include_inventories {
+            for reg in &self.regions {
+                for be in reg.block_entities.values() {
+                    if let Some(Value::List(items)) = be.tags.get("Items") {
+                        count_items_in_list(items, &mut counter);
+                    }
+                }
+            }
+        }
+
+        let mut entries: Vec<MaterialEntry> = counter.into_iter()
+            .filter(|(_, count)| *count > 0)
+            .map(|(item, count)| MaterialEntry { item, count })
+            .collect();
+        entries.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.item.cmp(&b.item)));
+        return MaterialList { entries };
+    }
+}
+
+impl MaterialEntry {
+    /// Max count of this item in one slot
+    pub fn stack_size(&self) -> u64 {
+        return stack_size_of_item(&self.item);
+    }
+
+    /// Split count into `(shulker boxes, stacks, remaining items)`. A shulker box holds 27 stacks.
+    pub fn breakdown(&self) -> (u64, u64, u64) {
+        let stack = self.stack_size();
+        let box_size = stack * 27;
+        return (self.count / box_size, self.count % box_size / stack, self.count % stack);
+    }
+}
+
+fn escape_json(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    return result;
+}
+
+impl MaterialList {
+    /// Count of `item`, 0 if it's not in the list
+    pub fn count_of(&self, item: &str) -> u64 {
+        return self.entries.iter().find(|e| e.item == item).map_or(0, |e| e.count);
+    }
+
+    /// Format as csv with header `item,count,stack_size,shulker_boxes,stacks,remainder`
+    pub fn to_csv(&self) -> String {
+        let mut result = String::from("item,count,stack_size,shulker_boxes,stacks,remainder\n");
+        for e in &self.entries {
+            let (boxes, stacks, remainder) = e.breakdown();
+            result.push_str(&format!("{},{},{},{boxes},{stacks},{remainder}\n", e.item, e.count, e.stack_size()));
+        }
+        return result;
+    }
+
+    /// Format as a json array of objects, with the same fields as csv
+    pub fn to_json(&self) -> String {
+        let mut result = String::from("[");
+        for (idx, e) in self.entries.iter().enumerate() {
+            let (boxes, stacks, remainder) = e.breakdown();
+            if idx > 0 {
+                result.push(',');
+            }
+            result.push_str(&format!("\n  {{\"item\": \"{}\", \"count\": {}, \"stack_size\": {}, \"shulker_boxes\": {boxes}, \"stacks\": {stacks}, \"remainder\": {remainder}}}",
+                                     escape_json(&e.item), e.count, e.stack_size()));
+        }
+        result.push_str("\n]\n");
+        return result;
+    }
+}
@@ -27,6 +27,29 @@ use strum::Display;
 #[repr(i32)]
 #[allow(dead_code)]
 pub enum DataVersion {
+    Java_1_20_6 = 3839,
+    Java_1_20_6_rc1 = 3838,
+    Java_1_20_5 = 3837,
+    Java_1_20_5_rc3 = 3836,
+    Java_1_20_5_rc2 = 3835,
+    Java_1_20_5_rc1 = 3834,
+    Java_1_20_5_pre4 = 3832,
+    Java_1_20_5_pre3 = 3831,
+    Java_1_20_5_pre2 = 3830,
+    Java_1_20_5_pre1 = 3829,
+    Snapshot_24w14a = 3827,
+    Snapshot_24w13a = 3826,
+    Snapshot_24w12a = 3824,
+    Snapshot_24w11a = 3823,
+    Snapshot_24w10a = 3821,
+    Snapshot_24w09a = 3819,
+    Snapshot_24w07a = 3817,
+    Snapshot_24w06a = 3815,
+    Snapshot_24w05b = 3811,
+    Snapshot_24w05a = 3809,
+    Snapshot_24w04a = 3806,
+    Snapshot_24w03b = 3805,
+    Snapshot_24w03a = 3804,
     Snapshot_23w51b = 3802,
     Snapshot_23w51a = 3801,
     Java_1_20_4 = 3700,
@@ -1055,7 +1078,7 @@ pub enum DataVersion {
 impl DataVersion {
     /// The latest data version
     pub fn latest() -> DataVersion {
-        return DataVersion::Snapshot_23w51b;
+        return DataVersion::Java_1_20_6;
     }
 
     pub fn new() -> DataVersion {
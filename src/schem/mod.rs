@@ -24,18 +24,27 @@ pub mod vanilla_structure;
 pub mod mc_version;
 pub mod common;
 pub mod schem_slice;
+pub mod version_convert;
+pub mod transform;
+pub mod diff;
+pub mod material_list;
 
 
 use std::cmp::max;
 use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
+use std::sync::Arc;
 use crate::block::{Block, CommonBlock};
+use crate::biome::Biome;
+use std::io::Read;
 use fastnbt;
+use fastnbt::Value;
 use flate2::Compression;
-use crate::error::{Error};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use crate::error::{DefaultErrorHandler, Error, ErrorHandler, StrictErrorHandler};
 //use schem::mc_version;
-use crate::{PendingTick, schem};
+use crate::{PendingTick, schem, SchemFormat};
 use crate::region::{BlockEntity, Region, WorldSlice};
 
 /// Minecraft data versions.
@@ -57,6 +66,8 @@ pub struct LitematicaMetaData {
     pub region_count: i32,
     pub total_blocks: i32,
     pub enclosing_size: [i32; 3],
+    /// `Metadata/PreviewImageData`, ARGB pixels of a square thumbnail
+    pub preview_image_data: Option<Vec<i32>>,
 }
 
 #[allow(dead_code)]
@@ -110,7 +121,8 @@ impl LitematicaMetaData {
             total_volume: 0,
             region_count: 0,
             total_blocks: 0,
-            enclosing_size: [0; 3]
+            enclosing_size: [0; 3],
+            preview_image_data: None,
         };
         return Ok(result);
     }
@@ -280,6 +292,8 @@ pub struct MetaDataIR {
 
     pub litematica_version: i32,
     pub litematica_subversion: Option<i32>,
+    /// ARGB pixels of a square thumbnail, shown in the menu of litematica
+    pub litematica_preview: Option<Vec<i32>>,
 
     pub schem_version: i32,
     pub schem_offset: [i32; 3],
@@ -318,6 +332,7 @@ impl MetaDataIR {
             description: String::from("Default metadata generated by mc_schem"),
             litematica_version: LitematicaMetaData::default().version,
             litematica_subversion: LitematicaMetaData::default().sub_version,
+            litematica_preview: None,
             schem_version: WE13MetaData::default().version,
             schem_offset: [0, 0, 0],
             schem_we_offset: None,
@@ -460,6 +475,21 @@ impl Schematic {
         return None;
     }
 
+    /// Get biome at `pos` of the first region that covers `pos` and has biomes
+    pub fn first_biome_at(&self, pos: [i32; 3]) -> Option<Biome> {
+        for reg in &self.regions {
+            if let Some(b) = reg.biome_at(reg.global_pos_to_relative_pos(pos)) {
+                return Some(b);
+            }
+        }
+        return None;
+    }
+
+    /// Returns true if any region has biomes
+    pub fn has_biomes(&self) -> bool {
+        return self.regions.iter().any(|reg| reg.biomes.is_some());
+    }
+
     pub fn first_pending_tick_at(&self, pos: [i32; 3]) -> &[PendingTick] {
         for reg in &self.regions {
             if let Some(b) = reg.pending_ticks.get(&reg.global_pos_to_relative_pos(pos)) {
@@ -567,30 +597,128 @@ impl Schematic {
         return (palette, lut_lut);
     }
 
-    /// Load schematic from file.
+    /// Load schematic from file. The format is deduced from the filename extension, or detected by
+    /// content if the extension is unknown.
     pub fn from_file(filename: &str) -> Result<(Schematic, RawMetaData), Error> {
+        return Self::from_file_with_error_handler(filename, Arc::new(StrictErrorHandler::new()));
+    }
+
+    /// Load damaged schematic from file with `DefaultErrorHandler`, see `from_file`. Errors that
+    /// are fixed while loading are returned as warnings.
+    pub fn from_file_lenient(filename: &str) -> Result<(Schematic, RawMetaData, Vec<Error>), Error> {
+        let handler = Arc::new(DefaultErrorHandler::new());
+        let (schem, raw) = Self::from_file_with_error_handler(filename, handler.clone())?;
+        return Ok((schem, raw, handler.take_warnings()));
+    }
+
+    /// Load schematic from file like `from_file`, errors are passed to `error_handler`.
+    pub fn from_file_with_error_handler(filename: &str, error_handler: Arc<dyn ErrorHandler>) -> Result<(Schematic, RawMetaData), Error> {
         if filename.ends_with(".litematic") {
-            let (schem, raw) = Self::from_litematica_file(filename, &LitematicaLoadOption::default())?;
+            let option = LitematicaLoadOption { error_handler };
+            let (schem, raw) = Self::from_litematica_file(filename, &option)?;
             return Ok((schem, RawMetaData::Litematica(raw)));
         }
         if filename.ends_with(".nbt") {
-            let (schem, raw) = Self::from_vanilla_structure_file(filename, &VanillaStructureLoadOption::default())?;
+            let option = VanillaStructureLoadOption { error_handler, ..VanillaStructureLoadOption::default() };
+            let (schem, raw) = Self::from_vanilla_structure_file(filename, &option)?;
             return Ok((schem, RawMetaData::VanillaStructure(raw)));
         }
         if filename.ends_with(".schem") {
-            let (schem, raw) = Self::from_world_edit_13_file(filename, &WorldEdit13LoadOption::default())?;
+            let option = WorldEdit13LoadOption { error_handler };
+            let (schem, raw) = Self::from_world_edit_13_file(filename, &option)?;
             return Ok((schem, RawMetaData::WE13(raw)));
         }
         if filename.ends_with(".schematic") {
-            let (schem, raw, ..) = Self::from_world_edit_12_file(filename, &WorldEdit12LoadOption::default())?;
+            let option = WorldEdit12LoadOption { error_handler, ..WorldEdit12LoadOption::default() };
+            let (schem, raw, ..) = Self::from_world_edit_12_file(filename, &option)?;
             return Ok((schem, RawMetaData::WE12(raw)));
         }
 
-        let split = filename.split(".");
-        let extension = split.last().unwrap_or_else(|| "");
+        let mut file = match std::fs::File::open(filename) {
+            Ok(f) => f,
+            Err(e) => return Err(Error::FileOpenError(e)),
+        };
+        let (schem, raw, _) = Self::from_reader_auto_with_error_handler(&mut file, error_handler)?;
+        return Ok((schem, raw));
+    }
 
+    /// Load schematic from a reader, and detect the format by content. The reader can be gzip,
+    /// zlib compressed or uncompressed nbt. Returns the loaded schematic, raw metadata and the
+    /// detected format.
+    pub fn from_reader_auto(src: &mut dyn Read) -> Result<(Schematic, RawMetaData, SchemFormat), Error> {
+        return Self::from_reader_auto_with_error_handler(src, Arc::new(StrictErrorHandler::new()));
+    }
 
-        return Err(Error::UnrecognisedExtension { extension: extension.to_string() });
+    /// Load schematic from a reader like `from_reader_auto`, errors are passed to `error_handler`.
+    pub fn from_reader_auto_with_error_handler(src: &mut dyn Read, error_handler: Arc<dyn ErrorHandler>) -> Result<(Schematic, RawMetaData, SchemFormat), Error> {
+        let mut header = [0u8; 2];
+        if let Err(e) = src.read_exact(&mut header) {
+            return Err(Error::NBTReadError(e.into()));
+        }
+        let mut chained = std::io::Cursor::new(header).chain(src);
+        let parse_res: Result<HashMap<String, Value>, fastnbt::error::Error> = match header {
+            [0x1f, 0x8b] => fastnbt::from_reader(GzDecoder::new(chained)),
+            [cmf, flg] if cmf & 0x0f == 8 && (cmf as u16 * 256 + flg as u16) % 31 == 0
+            => fastnbt::from_reader(ZlibDecoder::new(chained)),
+            [10, _] => fastnbt::from_reader(&mut chained),
+            _ => return Err(Error::UnrecognisedFormat {
+                detail: format!("the content is neither gzip, zlib nor nbt, first bytes are {:#04x} {:#04x}", header[0], header[1]),
+            }),
+        };
+        let nbt = match parse_res {
+            Ok(nbt) => nbt,
+            Err(e) => return Err(Error::NBTReadError(e)),
+        };
+
+        let format = match Self::detect_format_nbt(&nbt) {
+            Some(f) => f,
+            None => {
+                let mut keys: Vec<&String> = nbt.keys().collect();
+                keys.sort();
+                return Err(Error::UnrecognisedFormat { detail: format!("unknown root tags {:?}", keys) });
+            }
+        };
+        let (schem, raw) = match format {
+            SchemFormat::Litematica => {
+                let (schem, raw) = Self::from_litematica_nbt(nbt, &LitematicaLoadOption { error_handler })?;
+                (schem, RawMetaData::Litematica(raw))
+            }
+            SchemFormat::VanillaStructure => {
+                let (schem, raw) = Self::from_vanilla_structure_nbt(nbt, &VanillaStructureLoadOption { error_handler, ..VanillaStructureLoadOption::default() })?;
+                (schem, RawMetaData::VanillaStructure(raw))
+            }
+            SchemFormat::WorldEdit13 => {
+                let (schem, raw) = Self::from_world_edit_13_nbt(nbt, &WorldEdit13LoadOption { error_handler })?;
+                (schem, RawMetaData::WE13(raw))
+            }
+            SchemFormat::WorldEdit12 => {
+                let (schem, raw, ..) = Self::from_world_edit_12_nbt(nbt, &WorldEdit12LoadOption { error_handler, ..WorldEdit12LoadOption::default() })?;
+                (schem, RawMetaData::WE12(raw))
+            }
+        };
+        return Ok((schem, raw, format));
+    }
+
+    /// Detect schematic format by tags in root compound.
+    pub fn detect_format_nbt(root: &HashMap<String, Value>) -> Option<SchemFormat> {
+        if let Some(Value::Compound(_)) = root.get("Regions") {
+            return Some(SchemFormat::Litematica);
+        }
+        if let Some(Value::Compound(tag_schem)) = root.get("Schematic") {
+            if tag_schem.contains_key("Version") {
+                return Some(SchemFormat::WorldEdit13);
+            }
+        }
+        if root.contains_key("Version") && root.contains_key("Palette") && root.contains_key("BlockData") {
+            return Some(SchemFormat::WorldEdit13);
+        }
+        if (root.contains_key("palette") || root.contains_key("palettes")) && root.contains_key("blocks") {
+            return Some(SchemFormat::VanillaStructure);
+        }
+        if root.contains_key("Materials") || (root.contains_key("Blocks") && root.contains_key("Data")) {
+            return Some(SchemFormat::WorldEdit12);
+        }
+        return None;
     }
 
     /// Save schematic to file.
@@ -604,6 +732,10 @@ impl Schematic {
         if filename.ends_with(".schem") {
             return self.save_world_edit_13_file(filename, &WorldEdit13SaveOption::default());
         }
+        if filename.ends_with(".schematic") {
+            self.save_world_edit_12_file(filename, &WorldEdit12SaveOption::default())?;
+            return Ok(());
+        }
 
         let split = filename.split(".");
         let extension = split.last().unwrap_or_else(|| "");
@@ -753,12 +885,15 @@ pub fn id_of_nbt_tag(tag: &fastnbt::Value) -> u8 {
 pub struct VanillaStructureLoadOption {
     /// Background block of the schematic. vanilla structure will not store structure void.
     pub background_block: CommonBlock,
+    /// Fixes errors in damaged files, `StrictErrorHandler` by default.
+    pub error_handler: Arc<dyn ErrorHandler>,
 }
 
 impl VanillaStructureLoadOption {
     pub fn default() -> VanillaStructureLoadOption {
         return VanillaStructureLoadOption {
-            background_block: CommonBlock::StructureVoid
+            background_block: CommonBlock::StructureVoid,
+            error_handler: Arc::new(StrictErrorHandler::new()),
         }
     }
 }
@@ -781,14 +916,17 @@ impl Default for VanillaStructureSaveOption {
     }
 }
 
-//#[derive(Debug)]
 /// Options to load litematica
+#[derive(Debug)]
 pub struct LitematicaLoadOption {
+    /// Fixes errors in damaged files, `StrictErrorHandler` by default.
+    pub error_handler: Arc<dyn ErrorHandler>,
 }
 
 impl LitematicaLoadOption {
     pub fn default() -> LitematicaLoadOption {
         return LitematicaLoadOption {
+            error_handler: Arc::new(StrictErrorHandler::new()),
         };
     }
 }
@@ -813,14 +951,19 @@ impl Default for LitematicaSaveOption {
 }
 
 
-/// Options to load litematica
+/// Options to load world edit 1.13+
 #[derive(Debug)]
-pub struct WorldEdit13LoadOption {}
+pub struct WorldEdit13LoadOption {
+    /// Fixes errors in damaged files, `StrictErrorHandler` by default.
+    pub error_handler: Arc<dyn ErrorHandler>,
+}
 
 #[allow(dead_code)]
 impl WorldEdit13LoadOption {
     pub fn default() -> WorldEdit13LoadOption {
-        return WorldEdit13LoadOption {};
+        return WorldEdit13LoadOption {
+            error_handler: Arc::new(StrictErrorHandler::new()),
+        };
     }
 }
 
@@ -845,18 +988,86 @@ impl Default for WorldEdit13SaveOption {
     }
 }
 
-/// Options to load litematica
+/// Options to load world edit 1.12-
 #[derive(Debug)]
 pub struct WorldEdit12LoadOption {
     /// Data version of this schematic. Data version is not stored in `.schematic`, so we should assign it.
     pub data_version: DataVersion,
+    /// Fixes errors in damaged files, `StrictErrorHandler` by default.
+    pub error_handler: Arc<dyn ErrorHandler>,
 }
 
 impl Default for WorldEdit12LoadOption {
     fn default() -> Self {
         return WorldEdit12LoadOption {
             data_version: DataVersion::Java_1_12_2,
+            error_handler: Arc::new(StrictErrorHandler::new()),
         }
     }
 }
 
+
+/// How to handle blocks without number id when saving `.schematic`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NumberIdFallback {
+    /// Returns `Error::BlockWithoutNumberId`
+    ReturnError,
+    /// Replace the block with this number id and damage. Number ids greater than 255 are stored in
+    /// `AddBlocks`, so `id` should be less than 4096, and `damage` should be less than 16.
+    ReplaceWith { id: u16, damage: u8 },
+}
+
+/// Options to save world edit 1.12-
+#[derive(Debug)]
+pub struct WorldEdit12SaveOption {
+    /// Level of gzip compression, 0<= level <=9.
+    pub compress_level: Compression,
+    /// If the schematic contains multiple regions, some positions may not be covered by any region,
+    /// but `.schematic` can have only one region, so we must define a block for these positions.
+    /// Air by default.
+    pub background_block: CommonBlock,
+    /// What to do if a block has no equivalent number id, like blocks added after 1.13.
+    pub number_id_fallback: NumberIdFallback,
+}
+
+impl Default for WorldEdit12SaveOption {
+    fn default() -> Self {
+        return WorldEdit12SaveOption {
+            compress_level: Compression::best(),
+            background_block: CommonBlock::Air,
+            number_id_fallback: NumberIdFallback::ReturnError,
+        }
+    }
+}
+
+/// Options to convert a schematic to another data version
+#[derive(Debug)]
+pub struct DataVersionConvertOption {
+    /// Blocks that can not be represented in the target version are replaced with this block. If
+    /// `None`, these blocks are kept unchanged. They are listed in the report in both cases.
+    pub unrepresentable_replacement: Option<CommonBlock>,
+}
+
+impl Default for DataVersionConvertOption {
+    fn default() -> Self {
+        return DataVersionConvertOption {
+            unrepresentable_replacement: None,
+        }
+    }
+}
+
+/// Options to generate material list
+#[derive(Debug)]
+pub struct MaterialListOption {
+    /// Add items stored in block entities, like chests, furnaces and shulker boxes. Items inside
+    /// shulker box items are counted too.
+    pub include_inventories: bool,
+}
+
+impl Default for MaterialListOption {
+    fn default() -> Self {
+        return MaterialListOption {
+            include_inventories: false,
+        }
+    }
+}
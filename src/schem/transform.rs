@@ -0,0 +1,439 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::HashMap;
+use fastnbt::Value;
+use ndarray::{Array2, Array3};
+use crate::biome::Biome;
+use crate::block::Block;
+use crate::entity::ItemFrame;
+use crate::player::EntityBox;
+use crate::region::{Light, Region, RegionBiomes, WorldSlice};
+use crate::schem::Schematic;
+
+/// Rotation around Y axis, clockwise when viewed from above
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    Clockwise90,
+    Clockwise180,
+    CounterClockwise90,
+}
+
+/// Mirror a region by flipping one horizontal axis
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mirror {
+    /// Flip x, east and west are swapped
+    X,
+    /// Flip z, north and south are swapped
+    Z,
+}
+
+/// Linear transform on xz plane, `[x', z'] = matrix * [x, z]`. Every element is -1, 0 or 1, so
+/// it can only be rotations and mirrors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct XZTransform {
+    matrix: [[i32; 2]; 2],
+}
+
+const HORIZONTAL_DIRECTIONS: [(&str, [i32; 2]); 4] = [
+    ("north", [0, -1]),
+    ("south", [0, 1]),
+    ("west", [-1, 0]),
+    ("east", [1, 0]),
+];
+
+impl XZTransform {
+    fn from_rotation(rotation: Rotation) -> Self {
+        let matrix = match rotation {
+            Rotation::Clockwise90 => [[0, -1], [1, 0]],
+            Rotation::Clockwise180 => [[-1, 0], [0, -1]],
+            Rotation::CounterClockwise90 => [[0, 1], [-1, 0]],
+        };
+        return Self { matrix };
+    }
+
+    fn from_mirror(mirror: Mirror) -> Self {
+        let matrix = match mirror {
+            Mirror::X => [[-1, 0], [0, 1]],
+            Mirror::Z => [[1, 0], [0, -1]],
+        };
+        return Self { matrix };
+    }
+
+    fn transpose() -> Self {
+        return Self { matrix: [[0, 1], [1, 0]] };
+    }
+
+    fn apply<T>(&self, xz: [T; 2]) -> [T; 2]
+        where T: Copy + std::ops::Mul<Output=T> + std::ops::Add<Output=T> + From<i8> {
+        let m = |row: usize, col: usize| T::from(self.matrix[row][col] as i8);
+        return [m(0, 0) * xz[0] + m(0, 1) * xz[1], m(1, 0) * xz[0] + m(1, 1) * xz[1]];
+    }
+
+    /// If x and z are swapped
+    fn swaps_axes(&self) -> bool {
+        return self.matrix[0][0] == 0;
+    }
+
+    /// Mirrors change handedness, so that left and right are swapped
+    fn is_mirror(&self) -> bool {
+        let m = &self.matrix;
+        return m[0][0] * m[1][1] - m[0][1] * m[1][0] < 0;
+    }
+
+    /// Transform shape(xyz) of a region
+    fn apply_shape(&self, shape: [i32; 3]) -> [i32; 3] {
+        return if self.swaps_axes() {
+            [shape[2], shape[1], shape[0]]
+        } else {
+            shape
+        };
+    }
+
+    /// Transform a block position in a region of `shape`, the result is also inside the transformed region.
+    fn apply_block_pos(&self, pos: [i32; 3], shape: [i32; 3]) -> [i32; 3] {
+        let xz = self.apply([pos[0], pos[2]]);
+        // the transformed region starts at the transformed corner with minimum coordinates
+        let max = self.apply([shape[0] - 1, shape[2] - 1]);
+        return [xz[0] - max[0].min(0), pos[1], xz[1] - max[1].min(0)];
+    }
+
+    /// Transform an entity position in a region of `shape`
+    fn apply_entity_pos(&self, pos: [f64; 3], shape: [i32; 3]) -> [f64; 3] {
+        let xz = self.apply([pos[0], pos[2]]);
+        let max = self.apply([shape[0] as f64, shape[2] as f64]);
+        return [xz[0] - max[0].min(0.0), pos[1], xz[1] - max[1].min(0.0)];
+    }
+
+    /// Transform a horizontal direction, vertical directions are kept
+    fn apply_direction<'a>(&self, direction: &'a str) -> &'a str {
+        for (name, vec) in HORIZONTAL_DIRECTIONS {
+            if name == direction {
+                let transformed = self.apply(vec);
+                for (new_name, new_vec) in HORIZONTAL_DIRECTIONS {
+                    if new_vec == transformed {
+                        return new_name;
+                    }
+                }
+            }
+        }
+        return direction;
+    }
+
+    /// Transform yaw in degrees. 0 is south, and the angle goes clockwise.
+    fn apply_yaw(&self, yaw: f64) -> f64 {
+        let rad = yaw.to_radians();
+        let xz = self.apply([-rad.sin(), rad.cos()]);
+        let result = (-xz[0]).atan2(xz[1]).to_degrees();
+        return result.rem_euclid(360.0);
+    }
+}
+
+fn swap_values(value: &str, a: &str, b: &str) -> String {
+    return if value == a {
+        b.to_string()
+    } else if value == b {
+        a.to_string()
+    } else {
+        value.to_string()
+    };
+}
+
+/// Rename a rail shape like `north_east` or `ascending_west`
+fn transform_rail_shape(shape: &str, t: &XZTransform) -> String {
+    if let Some(dir) = shape.strip_prefix("ascending_") {
+        return format!("ascending_{}", t.apply_direction(dir));
+    }
+    if shape == "north_south" || shape == "east_west" {
+        return if t.swaps_axes() {
+            swap_values(shape, "north_south", "east_west")
+        } else {
+            shape.to_string()
+        };
+    }
+    if let Some((a, b)) = shape.split_once('_') {
+        let (a, b) = (t.apply_direction(a), t.apply_direction(b));
+        // corners are named as north/south first, then east/west
+        return if a == "north" || a == "south" {
+            format!("{a}_{b}")
+        } else {
+            format!("{b}_{a}")
+        };
+    }
+    return shape.to_string();
+}
+
+impl Block {
+    fn transformed(&self, t: &XZTransform) -> Block {
+        let mut result = self.clone();
+        result.attributes.clear();
+        for (key, value) in &self.attributes {
+            let new_key = t.apply_direction(key).to_string();
+            let new_value = match key.as_str() {
+                "facing" | "horizontal_facing" => t.apply_direction(value).to_string(),
+                "axis" if t.swaps_axes() => swap_values(value, "x", "z"),
+                "rotation" => {
+                    // 16 directions, same as yaw
+                    if let Ok(r) = value.parse::<i32>() {
+                        let yaw = t.apply_yaw(r as f64 * 22.5);
+                        ((yaw / 22.5).round() as i32 % 16).to_string()
+                    } else {
+                        value.clone()
+                    }
+                }
+                "shape" => {
+                    if self.id.ends_with("stairs") {
+                        if t.is_mirror() {
+                            let v = swap_values(value, "inner_left", "inner_right");
+                            swap_values(&v, "outer_left", "outer_right")
+                        } else {
+                            value.clone()
+                        }
+                    } else {
+                        transform_rail_shape(value, t)
+                    }
+                }
+                "hinge" if t.is_mirror() => swap_values(value, "left", "right"),
+                "type" if t.is_mirror() && self.id.ends_with("chest") => swap_values(value, "left", "right"),
+                "orientation" => {
+                    // jigsaw and crafter, like `north_up` and `up_east`
+                    match value.split_once('_') {
+                        Some((front, top)) => format!("{}_{}", t.apply_direction(front), t.apply_direction(top)),
+                        None => value.clone(),
+                    }
+                }
+                _ => value.clone(),
+            };
+            result.attributes.insert(new_key, new_value);
+        }
+        return result;
+    }
+
+    /// Rotate the block around Y axis, properties like `facing`, `axis`, `rotation`, `shape` and
+    /// connections of fences and walls are changed.
+    pub fn rotated(&self, rotation: Rotation) -> Block {
+        return self.transformed(&XZTransform::from_rotation(rotation));
+    }
+
+    /// Mirror the block, properties like `facing`, `shape`, `hinge` and connections are changed.
+    pub fn mirrored(&self, mirror: Mirror) -> Block {
+        return self.transformed(&XZTransform::from_mirror(mirror));
+    }
+}
+
+/// 2 dimensional data value of paintings, 0 is south and goes clockwise
+const FACING_2D: [&str; 4] = ["south", "west", "north", "east"];
+/// 3 dimensional data value of item frames
+const FACING_3D: [&str; 6] = ["down", "up", "north", "south", "west", "east"];
+
+fn transform_facing(value: i8, names: &[&str], t: &XZTransform) -> i8 {
+    if value < 0 || value as usize >= names.len() {
+        return value;
+    }
+    let new_dir = t.apply_direction(names[value as usize]);
+    return names.iter().position(|n| *n == new_dir).unwrap() as i8;
+}
+
+impl EntityBox {
+    fn transform(&mut self, t: &XZTransform, shape: [i32; 3]) {
+        let fields = self.entity_fields_mut();
+        fields.pos = t.apply_entity_pos(fields.pos, shape);
+        if let Some(rotation) = &mut fields.rotation {
+            rotation[0] = t.apply_yaw(rotation[0] as f64) as f32;
+        }
+        if let Some(motion) = &mut fields.motion {
+            let motion_xz = t.apply([motion[0], motion[2]]);
+            motion[0] = motion_xz[0];
+            motion[2] = motion_xz[1];
+        }
+
+        // hanging entities
+        let facing_names: &[&str] = if self.downcast_ref::<ItemFrame>().is_some() { &FACING_3D } else { &FACING_2D };
+        if let Some(hanging) = self.hanging_fields_mut() {
+            hanging.facing = transform_facing(hanging.facing, facing_names, t);
+            hanging.tile_pos = t.apply_block_pos(hanging.tile_pos, shape);
+        }
+        // leash knots
+        let tags = self.unparsed_tags_mut();
+        if let (Some(Value::Int(x)), Some(Value::Int(y)), Some(Value::Int(z)))
+            = (tags.get("TileX"), tags.get("TileY"), tags.get("TileZ")) {
+            let tile = t.apply_block_pos([*x, *y, *z], shape);
+            tags.insert("TileX".to_string(), Value::Int(tile[0]));
+            tags.insert("TileZ".to_string(), Value::Int(tile[2]));
+        }
+    }
+}
+
+impl Region {
+    fn transform(&mut self, t: &XZTransform) {
+        let shape = self.shape();
+        let new_shape = t.apply_shape(shape);
+        let mut array = Array3::zeros(Self::pos_xyz_to_yzx(&Self::i32_to_usize(&new_shape)));
+        for y in 0..shape[1] {
+            for z in 0..shape[2] {
+                for x in 0..shape[0] {
+                    let new_pos = Self::i32_to_usize(&t.apply_block_pos([x, y, z], shape));
+                    array[Self::pos_xyz_to_yzx(&new_pos)] = self.array_yzx[[y as usize, z as usize, x as usize]];
+                }
+            }
+        }
+        self.array_yzx = array;
+
+        if let Some(biomes) = &self.biomes {
+            let biomes = match biomes {
+                RegionBiomes::Columns(src) => {
+                    let mut dst = Array2::from_elem([new_shape[2] as usize, new_shape[0] as usize], Biome::default());
+                    for z in 0..shape[2] {
+                        for x in 0..shape[0] {
+                            let new_pos = Self::i32_to_usize(&t.apply_block_pos([x, 0, z], shape));
+                            dst[[new_pos[2], new_pos[0]]] = src[[z as usize, x as usize]];
+                        }
+                    }
+                    RegionBiomes::Columns(dst)
+                }
+                RegionBiomes::Blocks(src) => {
+                    let mut dst = Array3::from_elem(Self::pos_xyz_to_yzx(&Self::i32_to_usize(&new_shape)), Biome::default());
+                    for y in 0..shape[1] {
+                        for z in 0..shape[2] {
+                            for x in 0..shape[0] {
+                                let new_pos = Self::i32_to_usize(&t.apply_block_pos([x, y, z], shape));
+                                dst[Self::pos_xyz_to_yzx(&new_pos)] = src[[y as usize, z as usize, x as usize]];
+                            }
+                        }
+                    }
+                    RegionBiomes::Blocks(dst)
+                }
+            };
+            self.biomes = Some(biomes);
+        }
+        if let Some(src) = &self.light {
+            let mut dst = Array3::from_elem(Self::pos_xyz_to_yzx(&Self::i32_to_usize(&new_shape)), Light::default());
+            for y in 0..shape[1] {
+                for z in 0..shape[2] {
+                    for x in 0..shape[0] {
+                        let new_pos = Self::i32_to_usize(&t.apply_block_pos([x, y, z], shape));
+                        dst[Self::pos_xyz_to_yzx(&new_pos)] = src[[y as usize, z as usize, x as usize]];
+                    }
+                }
+            }
+            self.light = Some(dst);
+        }
+
+        for blk in &mut self.palette {
+            *blk = blk.transformed(t);
+        }
+
+        let block_entities: HashMap<_, _> = self.block_entities.drain()
+            .map(|(pos, be)| (t.apply_block_pos(pos, shape), be)).collect();
+        self.block_entities = block_entities;
+        let pending_ticks: HashMap<_, _> = self.pending_ticks.drain()
+            .map(|(pos, ticks)| (t.apply_block_pos(pos, shape), ticks)).collect();
+        self.pending_ticks = pending_ticks;
+
+        for entity in &mut self.entities {
+            entity.transform(t, shape);
+        }
+    }
+
+    /// Rotate the region around Y axis, the offset is not changed.
+    pub fn rotate(&mut self, rotation: Rotation) {
+        self.transform(&XZTransform::from_rotation(rotation));
+    }
+
+    /// Mirror the region, the offset is not changed.
+    pub fn mirror(&mut self, mirror: Mirror) {
+        self.transform(&XZTransform::from_mirror(mirror));
+    }
+
+    /// Swap x and z of the region, which equals to rotating clockwise by 90 degrees and then mirroring x.
+    pub fn transpose_xz(&mut self) {
+        self.transform(&XZTransform::transpose());
+    }
+}
+
+impl Schematic {
+    fn transform(&mut self, t: &XZTransform) {
+        if self.regions.is_empty() {
+            return;
+        }
+        // regions are transformed inside the enclosing box, whose lower bound is kept
+        let lb: [i32; 3] = std::array::from_fn(|dim| self.regions.iter().map(|reg| reg.offset[dim]).min().unwrap());
+        let shape = self.shape();
+        for reg in &mut self.regions {
+            let reg_shape = reg.shape();
+            let r_lb = [reg.offset[0] - lb[0], reg.offset[1], reg.offset[2] - lb[2]];
+            let r_ub = [r_lb[0] + reg_shape[0] - 1, r_lb[1], r_lb[2] + reg_shape[2] - 1];
+            let a = t.apply_block_pos(r_lb, shape);
+            let b = t.apply_block_pos(r_ub, shape);
+            reg.offset = [a[0].min(b[0]) + lb[0], reg.offset[1], a[2].min(b[2]) + lb[2]];
+            reg.transform(t);
+        }
+    }
+
+    /// Rotate the whole schematic around Y axis. Regions are moved inside the enclosing box.
+    pub fn rotate(&mut self, rotation: Rotation) {
+        self.transform(&XZTransform::from_rotation(rotation));
+    }
+
+    /// Mirror the whole schematic. Regions are moved inside the enclosing box.
+    pub fn mirror(&mut self, mirror: Mirror) {
+        self.transform(&XZTransform::from_mirror(mirror));
+    }
+
+    /// Swap x and z of the whole schematic
+    pub fn transpose_xz(&mut self) {
+        self.transform(&XZTransform::transpose());
+    }
+}
+
+#[test]
+fn test_transform_block() {
+    let stairs = Block::from_id("minecraft:oak_stairs[facing=north,half=bottom,shape=inner_left,waterlogged=false]").unwrap();
+    let rotated = stairs.rotated(Rotation::Clockwise90);
+    assert_eq!(rotated.attributes["facing"], "east");
+    assert_eq!(rotated.attributes["shape"], "inner_left");
+    let mirrored = stairs.mirrored(Mirror::Z);
+    assert_eq!(mirrored.attributes["facing"], "south");
+    assert_eq!(mirrored.attributes["shape"], "inner_right");
+
+    let rail = Block::from_id("minecraft:rail[shape=north_east]").unwrap();
+    assert_eq!(rail.rotated(Rotation::Clockwise90).attributes["shape"], "south_east");
+    assert_eq!(rail.mirrored(Mirror::X).attributes["shape"], "north_west");
+
+    let wall = Block::from_id("minecraft:cobblestone_wall[east=low,north=tall,south=none,up=true,west=none]").unwrap();
+    let rotated = wall.rotated(Rotation::CounterClockwise90);
+    assert_eq!(rotated.attributes["west"], "tall");
+    assert_eq!(rotated.attributes["north"], "low");
+    assert_eq!(rotated.attributes["up"], "true");
+
+    let sign = Block::from_id("minecraft:oak_sign[rotation=1]").unwrap();
+    assert_eq!(sign.rotated(Rotation::Clockwise90).attributes["rotation"], "5");
+    assert_eq!(sign.mirrored(Mirror::X).attributes["rotation"], "15");
+
+    let log = Block::from_id("minecraft:oak_log[axis=x]").unwrap();
+    assert_eq!(log.rotated(Rotation::Clockwise90).attributes["axis"], "z");
+    assert_eq!(log.mirrored(Mirror::X), log);
+
+    for rot in [Rotation::Clockwise90, Rotation::Clockwise180, Rotation::CounterClockwise90] {
+        let mut blk = stairs.clone();
+        for _ in 0..4 {
+            blk = blk.rotated(rot);
+        }
+        assert_eq!(blk, stairs);
+    }
+}
@@ -21,12 +21,13 @@ use std::fs::File;
 use crate::schem::{common, id_of_nbt_tag, MetaDataIR, VanillaStructureLoadOption, VanillaStructureSaveOption};
 //use compress::zlib;
 use crate::schem::schem::{BlockEntity, Schematic, VanillaStructureMetaData};
-use crate::region::{Entity, Region, WorldSlice};
+use crate::region::{Region, WorldSlice};
+use crate::player::EntityBox;
 use fastnbt;
 use fastnbt::{Value};
 use flate2::{GzBuilder};
 use flate2::read::GzDecoder;
-use crate::error::{Error};
+use crate::error::{Error, ErrorHandler};
 use crate::{unwrap_tag, unwrap_opt_tag};
 use crate::error::Error::FileOpenError;
 
@@ -72,18 +73,24 @@ fn parse_size_tag(nbt: &HashMap<String, Value>) -> Result<[i32; 3], Error> {
 }
 
 
-fn parse_array_item(item: &Value, tag_path: &str, palette_size: i32, region_size: [i32; 3]) -> Result<(i32, [i32; 3], Option<BlockEntity>), Error> {
+/// Returns `None` if the block is out of the region and dropped by the error handler
+fn parse_array_item(item: &Value, tag_path: &str, region: &mut Region, handler: &dyn ErrorHandler)
+    -> Result<Option<(u16, [i32; 3], Option<BlockEntity>)>, Error> {
     let map = unwrap_tag!(item,Compound,HashMap::new(),tag_path);
+    let palette_size = region.palette.len() as i32;
+    let region_size = region.shape();
 
     // parse state
     let state: i32 = *unwrap_opt_tag!(map.get("state"),Int,0,&*format!("{}/state", tag_path));
-    if state < 0 || state >= palette_size {
-        return Err(Error::BlockIndexOutOfRange {
+    let state = if state < 0 || state >= palette_size {
+        handler.handle_block_index_out_of_range(region, Error::BlockIndexOutOfRange {
             tag_path: format!("{}/state", tag_path),
             index: state,
             range: [0, palette_size],
-        });
-    }
+        })?
+    } else {
+        state as u16
+    };
 
     let pos_list = unwrap_opt_tag!(map.get("pos"),List,vec![],&*format!("{}/pos", tag_path));
 
@@ -100,19 +107,24 @@ fn parse_array_item(item: &Value, tag_path: &str, palette_size: i32, region_size
     }
     for idx in 0..3 {
         if pos[idx] < 0 || pos[idx] >= region_size[idx] {
-            return Err(Error::BlockPosOutOfRange {
+            let fixed = handler.handle_block_pos_out_of_range(region, Error::BlockPosOutOfRange {
                 tag_path: format!("{}/pos[{}]", tag_path, idx),
                 pos,
                 lower_bound: [0, 0, 0],
                 upper_bound: region_size,
-            });
+            })?;
+            match fixed {
+                Some(p) => pos = p,
+                None => return Ok(None),
+            }
+            break;
         }
     }
 
     let nbt_comp;
     match map.get("nbt") {
         Some(nbt_comp_tmp) => nbt_comp = nbt_comp_tmp,
-        None => return Ok((state, pos, None)),
+        None => return Ok(Some((state, pos, None))),
     }
 
     let nbt_comp = unwrap_tag!(nbt_comp,Compound,HashMap::new(),&*format!("{}/nbt",tag_path));
@@ -120,54 +132,52 @@ fn parse_array_item(item: &Value, tag_path: &str, palette_size: i32, region_size
         tags: nbt_comp.clone(),
     };
 
-    return Ok((state, pos, Some(block_entity)));
+    return Ok(Some((state, pos, Some(block_entity))));
 }
 
-fn parse_entity(tag: &mut Value, tag_path: &str) -> Result<Entity, Error> {
+/// The entity is positioned by `pos`, and hanging entities are attached to `blockPos`. `Pos` and
+/// `TileX/Y/Z` in `nbt` are world coordinates when the structure was saved, so they are replaced.
+fn parse_entity(tag: &mut Value, tag_path: &str) -> Result<EntityBox, Error> {
     let compound = unwrap_tag!(tag,Compound,HashMap::new(),tag_path);
 
-    let mut entity = Entity::new();
+    let mut block_pos = [0; 3];
     // parse blockPos
     {
-        let block_pos = unwrap_opt_tag!(compound.get("blockPos"),List,vec![],&*format!("{}/blockPos",tag_path));
-        if block_pos.len() != 3 {
+        let block_pos_list = unwrap_opt_tag!(compound.get("blockPos"),List,vec![],&*format!("{}/blockPos",tag_path));
+        if block_pos_list.len() != 3 {
             return Err(Error::InvalidValue {
                 tag_path: format!("{}/blockPos", tag_path),
-                error: format!("blockPos should have 3 elements, but found {}", block_pos.len()),
+                error: format!("blockPos should have 3 elements, but found {}", block_pos_list.len()),
             }
             );
         }
 
         for idx in 0..3 {
-            entity.block_pos[idx] = *unwrap_opt_tag!(block_pos.get(idx),
+            block_pos[idx] = *unwrap_opt_tag!(block_pos_list.get(idx),
                 Int,0,
                 &*format!("{}/blockPos[{}]",tag_path,idx));
         }
     }
     // parse pos
-    {
-        let pos = unwrap_opt_tag!(compound.get("pos"),List,vec![],&*format!("{}/pos",tag_path));
-        if pos.len() != 3 {
-            return Err(Error::InvalidValue {
-                tag_path: format!("{}/pos", tag_path),
-                error: format!("blockPos should have 3 elements, but found {}", pos.len()),
-            });
-        }
-
-        for idx in 0..3 {
-            entity.position[idx] = *unwrap_opt_tag!(pos.get(idx),
-                Double,0.0,
-                &*format!("{}/pos[{}]",tag_path,idx));
-        }
+    let pos = unwrap_opt_tag!(compound.get("pos"),List,vec![],&*format!("{}/pos",tag_path)).clone();
+    if pos.len() != 3 {
+        return Err(Error::InvalidValue {
+            tag_path: format!("{}/pos", tag_path),
+            error: format!("blockPos should have 3 elements, but found {}", pos.len()),
+        });
     }
 
     // parse nbt
-    {
-        let nbt = unwrap_opt_tag!(compound.remove("nbt"),
-            Compound,HashMap::new(),&*format!("{}/nbt",tag_path));
-        entity.tags = nbt;
+    let nbt_path = format!("{}/nbt", tag_path);
+    let mut nbt = unwrap_opt_tag!(compound.remove("nbt"),
+            Compound,HashMap::new(),&*nbt_path);
+    nbt.insert("Pos".to_string(), Value::List(pos));
+    for (key, dim) in [("TileX", 0), ("TileY", 1), ("TileZ", 2)] {
+        if nbt.contains_key(key) {
+            nbt.insert(key.to_string(), Value::Int(block_pos[dim]));
+        }
     }
-    return Ok(entity);
+    return EntityBox::from_nbt(nbt, &nbt_path);
 }
 
 
@@ -231,11 +241,11 @@ impl Schematic {
                 let tag_path = format!("/palette[{}]", idx);
 
                 let blk_comp = unwrap_tag!(blk_tag,Compound,HashMap::new(),&tag_path);
-                let blk = common::parse_block(blk_comp, &tag_path);
-                match blk {
-                    Err(err) => return Err(err),
-                    Ok(blk) => region.palette.push(blk),
-                }
+                let blk = match common::parse_block(blk_comp, &tag_path) {
+                    Err(err) => option.error_handler.handle_invalid_block_id(err)?,
+                    Ok(blk) => blk,
+                };
+                region.palette.push(blk);
             }
         }
 
@@ -268,18 +278,19 @@ impl Schematic {
             for (idx, blk_item) in blocks_list.iter().enumerate() {
                 let blk_item = parse_array_item(blk_item,
                                                 &*format!("/blocks[{}]", idx),
-                                                region.palette.len() as i32,
-                                                [region_size[0], region_size[1], region_size[2]]);
+                                                &mut region,
+                                                &*option.error_handler);
                 let state;
                 let pos;
                 let block_entity_opt;
                 match blk_item {
-                    Ok(unwrapped_tmp) => (state, pos, block_entity_opt) = unwrapped_tmp,
+                    Ok(Some(unwrapped_tmp)) => (state, pos, block_entity_opt) = unwrapped_tmp,
+                    Ok(None) => continue,
                     Err(e) => return Err(e),
                 }
 
                 let pos_ndarr = [pos[0] as usize, pos[1] as usize, pos[2] as usize];
-                region.array_yzx[Region::pos_xyz_to_yzx(&pos_ndarr)] = state as u16;
+                region.array_yzx[Region::pos_xyz_to_yzx(&pos_ndarr)] = state;
 
                 if let Some(block_entity) = block_entity_opt {
                     region.block_entities.insert([pos[0], pos[1], pos[2]], block_entity);
@@ -421,15 +432,13 @@ impl Schematic {
             for reg in &self.regions {
                 for entity in &reg.entities {
                     let mut nbt = HashMap::new();
-                    let mut block_pos = Vec::with_capacity(3);
-                    let mut pos = Vec::with_capacity(3);
-                    for dim in 0..3 {
-                        block_pos.push(Value::Int(entity.block_pos[dim]));
-                        pos.push(Value::Double(entity.position[dim]));
-                    }
+                    let block_pos = entity.block_pos();
+                    let pos = entity.pos();
+                    let block_pos = (0..3).map(|dim| Value::Int(block_pos[dim])).collect();
+                    let pos = (0..3).map(|dim| Value::Double(pos[dim])).collect();
                     nbt.insert(String::from("blockPos"), Value::List(block_pos));
                     nbt.insert(String::from("pos"), Value::List(pos));
-                    nbt.insert(String::from("nbt"), Value::Compound(entity.tags.clone()));
+                    nbt.insert(String::from("nbt"), Value::Compound(entity.to_nbt()));
 
                     entities.push(Value::Compound(nbt));
                 }
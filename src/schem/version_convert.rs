@@ -0,0 +1,818 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use crate::block::Block;
+use crate::old_block::get_valid_damage_values;
+use crate::region::Region;
+use crate::schem::{DataVersion, DataVersionConvertOption, Schematic};
+
+/// Result of converting a schematic to another data version
+#[derive(Debug, Clone)]
+pub struct DataVersionConvertReport {
+    pub source_version: i32,
+    pub target_version: i32,
+    /// Blocks whose id or properties are changed, `(before, after)`
+    pub converted_blocks: Vec<(Block, Block)>,
+    /// Blocks that can not be represented in the target version
+    pub unrepresentable_blocks: Vec<Block>,
+}
+
+/// A change of blocks introduced in some data version.
+struct BlockChange {
+    /// The first data version with this change
+    version: DataVersion,
+    /// Convert a block from `version - 1` to `version`.
+    upgrade: fn(&Block) -> Block,
+    /// Convert a block from `version` to `version - 1`, returns `None` if it can't be represented.
+    downgrade: fn(&Block) -> Option<Block>,
+}
+
+const BLOCK_CHANGES: [BlockChange; 10] = [
+    BlockChange { version: DataVersion::Snapshot_17w47a, upgrade: flatten, downgrade: unflatten },
+    BlockChange { version: DataVersion::Snapshot_18w43a, upgrade: upgrade_18w43a, downgrade: downgrade_18w43a },
+    BlockChange { version: DataVersion::Snapshot_19w34a, upgrade: Block::clone, downgrade: downgrade_19w34a },
+    BlockChange { version: DataVersion::Snapshot_20w06a, upgrade: upgrade_20w06a, downgrade: downgrade_20w06a },
+    BlockChange { version: DataVersion::Snapshot_20w45a, upgrade: upgrade_20w45a, downgrade: downgrade_20w45a },
+    BlockChange { version: DataVersion::Snapshot_20w46a, upgrade: upgrade_20w46a, downgrade: downgrade_20w46a },
+    BlockChange { version: DataVersion::Java_1_19, upgrade: Block::clone, downgrade: downgrade_1_19 },
+    BlockChange { version: DataVersion::Snapshot_22w42a, upgrade: Block::clone, downgrade: downgrade_22w42a },
+    BlockChange { version: DataVersion::Java_1_20, upgrade: Block::clone, downgrade: downgrade_1_20 },
+    BlockChange { version: DataVersion::Java_1_20_3_rc1, upgrade: upgrade_1_20_3, downgrade: downgrade_1_20_3 },
+];
+
+fn renamed(src: &Block, id: &str) -> Block {
+    let mut blk = src.clone();
+    blk.id = id.to_string();
+    return blk;
+}
+
+fn is_vanilla(blk: &Block) -> bool {
+    return blk.namespace.is_empty() || blk.namespace == "minecraft";
+}
+
+/// Returns `None` if `blk` is a vanilla block listed in `added` (exact ids) or whose id contains
+/// any pattern in `added_patterns`.
+fn reject_added(blk: &Block, added: &[&str], added_patterns: &[&str]) -> Option<Block> {
+    if !is_vanilla(blk) {
+        return Some(blk.clone());
+    }
+    if added.contains(&blk.id.as_str()) {
+        return None;
+    }
+    for pattern in added_patterns {
+        if blk.id.contains(pattern) {
+            return None;
+        }
+    }
+    return Some(blk.clone());
+}
+
+fn color_1_13(color_1_12: &str) -> &str {
+    return if color_1_12 == "silver" {
+        "light_gray"
+    } else {
+        color_1_12
+    };
+}
+
+fn take(blk: &mut Block, key: &str) -> String {
+    return blk.attributes.remove(key).unwrap_or_default();
+}
+
+/// Convert a block with 1.12 string id and properties (like the output of `Block::from_old`) to
+/// 1.13.
+fn flatten(src: &Block) -> Block {
+    if !is_vanilla(src) {
+        return src.clone();
+    }
+    let mut blk = src.clone();
+    let id = src.id.as_str();
+    let new_id: String = match id {
+        "stone" => match take(&mut blk, "variant").as_str() {
+            "granite" => "granite",
+            "smooth_granite" => "polished_granite",
+            "diorite" => "diorite",
+            "smooth_diorite" => "polished_diorite",
+            "andesite" => "andesite",
+            "smooth_andesite" => "polished_andesite",
+            _ => "stone",
+        }.to_string(),
+        "grass" => "grass_block".to_string(),
+        "dirt" => {
+            let variant = take(&mut blk, "variant");
+            if variant != "podzol" {
+                blk.attributes.remove("snowy");
+            }
+            if variant.is_empty() { "dirt".to_string() } else { variant }
+        }
+        "planks" | "double_wooden_slab" | "wooden_slab" | "fence" | "fence_gate" | "wooden_door"
+        | "wooden_button" | "wooden_pressure_plate" | "trapdoor" => {
+            let variant = take(&mut blk, "variant");
+            let wood = if variant.is_empty() { "oak".to_string() } else { variant };
+            match id {
+                "planks" => format!("{wood}_planks"),
+                "double_wooden_slab" => {
+                    blk.set_property("type", "double");
+                    format!("{wood}_slab")
+                }
+                "wooden_slab" => {
+                    let half = take(&mut blk, "half");
+                    blk.set_property("type", if half.is_empty() { "bottom" } else { half.as_str() });
+                    format!("{wood}_slab")
+                }
+                "fence" => "oak_fence".to_string(),
+                "fence_gate" => "oak_fence_gate".to_string(),
+                "wooden_door" => "oak_door".to_string(),
+                "wooden_button" => "oak_button".to_string(),
+                "wooden_pressure_plate" => "oak_pressure_plate".to_string(),
+                _ => "oak_trapdoor".to_string(),
+            }
+        }
+        "sapling" => format!("{}_sapling", take(&mut blk, "type")),
+        "flowing_water" => "water".to_string(),
+        "flowing_lava" => "lava".to_string(),
+        "sand" => {
+            let variant = take(&mut blk, "variant");
+            if variant.is_empty() { "sand".to_string() } else { variant }
+        }
+        "log" | "log2" => {
+            let variant = take(&mut blk, "variant");
+            if blk.attributes.get("axis").map(|s| s.as_str()) == Some("none") {
+                blk.set_property("axis", "y");
+                format!("{variant}_wood")
+            } else {
+                format!("{variant}_log")
+            }
+        }
+        "leaves" | "leaves2" => {
+            let variant = take(&mut blk, "variant");
+            let decayable = take(&mut blk, "decayable");
+            blk.attributes.remove("check_decay");
+            blk.set_property("persistent", &(decayable == "false"));
+            blk.set_property("distance", &7);
+            format!("{variant}_leaves")
+        }
+        "sponge" => if take(&mut blk, "wet") == "true" { "wet_sponge" } else { "sponge" }.to_string(),
+        "sandstone" | "red_sandstone" => match take(&mut blk, "type").as_str() {
+            "chiseled_sandstone" => "chiseled_sandstone",
+            "smooth_sandstone" => "cut_sandstone",
+            "chiseled_red_sandstone" => "chiseled_red_sandstone",
+            "smooth_red_sandstone" => "cut_red_sandstone",
+            _ => id,
+        }.to_string(),
+        "noteblock" => "note_block".to_string(),
+        "bed" => "red_bed".to_string(),
+        "golden_rail" => "powered_rail".to_string(),
+        "piston_head" => {
+            blk.set_property("short", &false);
+            "piston_head".to_string()
+        }
+        "piston_extension" => "moving_piston".to_string(),
+        "web" => "cobweb".to_string(),
+        "tallgrass" => match take(&mut blk, "type").as_str() {
+            "dead_bush" => "dead_bush",
+            "fern" => "fern",
+            _ => "grass",
+        }.to_string(),
+        "deadbush" => "dead_bush".to_string(),
+        "wool" | "stained_hardened_clay" | "stained_glass" | "carpet" | "concrete" | "concrete_powder"
+        | "stained_glass_pane" => {
+            let color = take(&mut blk, "color");
+            let suffix = match id {
+                "wool" => "wool",
+                "stained_hardened_clay" => "terracotta",
+                "stained_glass" => "stained_glass",
+                "carpet" => "carpet",
+                "concrete" => "concrete",
+                "concrete_powder" => "concrete_powder",
+                _ => "stained_glass_pane",
+            };
+            format!("{}_{suffix}", color_1_13(&color))
+        }
+        "yellow_flower" => {
+            blk.attributes.remove("type");
+            "dandelion".to_string()
+        }
+        "red_flower" => match take(&mut blk, "type").as_str() {
+            "houstonia" => "azure_bluet".to_string(),
+            "" => "poppy".to_string(),
+            t => t.to_string(),
+        },
+        "double_stone_slab" | "stone_slab" | "double_stone_slab2" | "stone_slab2" => {
+            let variant = take(&mut blk, "variant");
+            let seamless = take(&mut blk, "seamless") == "true";
+            let is_double = id.starts_with("double");
+            if is_double {
+                blk.set_property("type", "double");
+            } else {
+                let half = take(&mut blk, "half");
+                blk.set_property("type", if half.is_empty() { "bottom" } else { half.as_str() });
+            }
+            if is_double && seamless {
+                blk.attributes.remove("type");
+                match variant.as_str() {
+                    "sandstone" => "smooth_sandstone",
+                    "red_sandstone" => "smooth_red_sandstone",
+                    "quartz" => "smooth_quartz",
+                    _ => "smooth_stone",
+                }.to_string()
+            } else {
+                match variant.as_str() {
+                    "wood_old" => "petrified_oak_slab".to_string(),
+                    "" => "stone_slab".to_string(),
+                    v => format!("{v}_slab"),
+                }
+            }
+        }
+        "purpur_double_slab" | "purpur_slab" => {
+            blk.attributes.remove("variant");
+            if id == "purpur_double_slab" {
+                blk.set_property("type", "double");
+            } else {
+                let half = take(&mut blk, "half");
+                blk.set_property("type", if half.is_empty() { "bottom" } else { half.as_str() });
+            }
+            "purpur_slab".to_string()
+        }
+        "brick_block" => "bricks".to_string(),
+        "tnt" => {
+            let explode = take(&mut blk, "explode");
+            blk.set_property("unstable", if explode.is_empty() { "false" } else { explode.as_str() });
+            "tnt".to_string()
+        }
+        "torch" | "redstone_torch" | "unlit_redstone_torch" => {
+            let facing = take(&mut blk, "facing");
+            if id != "torch" {
+                blk.set_property("lit", &(id == "redstone_torch"));
+            }
+            let prefix = if id == "torch" { "" } else { "redstone_" };
+            if facing == "up" || facing.is_empty() {
+                format!("{prefix}torch")
+            } else {
+                blk.set_property("facing", &facing);
+                format!("{prefix}wall_torch")
+            }
+        }
+        "fire" => {
+            for dir in ["east", "north", "south", "west", "up"] {
+                blk.set_property(dir, &false);
+            }
+            "fire".to_string()
+        }
+        "mob_spawner" => "spawner".to_string(),
+        "stone_stairs" => "cobblestone_stairs".to_string(),
+        "chest" | "trapped_chest" => {
+            blk.set_property("type", "single");
+            id.to_string()
+        }
+        "furnace" | "lit_furnace" | "redstone_ore" | "lit_redstone_ore" | "redstone_lamp" | "lit_redstone_lamp" => {
+            blk.set_property("lit", &id.starts_with("lit_"));
+            id.trim_start_matches("lit_").to_string()
+        }
+        "standing_sign" => "sign".to_string(),
+        "standing_banner" => "white_banner".to_string(),
+        "wall_banner" => "white_wall_banner".to_string(),
+        "iron_door" | "spruce_door" | "birch_door" | "jungle_door" | "acacia_door" | "dark_oak_door" => id.to_string(),
+        "lever" => {
+            let (face, facing) = match take(&mut blk, "facing").as_str() {
+                "down_x" => ("ceiling", "west".to_string()),
+                "down_z" => ("ceiling", "north".to_string()),
+                "up_x" => ("floor", "west".to_string()),
+                "up_z" => ("floor", "north".to_string()),
+                f => ("wall", f.to_string()),
+            };
+            blk.set_property("face", face);
+            blk.set_property("facing", &facing);
+            "lever".to_string()
+        }
+        "stone_button" => "stone_button".to_string(),
+        "snow_layer" => "snow".to_string(),
+        "snow" => "snow_block".to_string(),
+        "reeds" => "sugar_cane".to_string(),
+        "pumpkin" => "carved_pumpkin".to_string(),
+        "lit_pumpkin" => "jack_o_lantern".to_string(),
+        "portal" => "nether_portal".to_string(),
+        "unpowered_repeater" | "powered_repeater" => {
+            blk.set_property("powered", &(id == "powered_repeater"));
+            blk.set_property("locked", &false);
+            "repeater".to_string()
+        }
+        "monster_egg" => match take(&mut blk, "variant").as_str() {
+            "cobblestone" => "infested_cobblestone",
+            "stone_brick" => "infested_stone_bricks",
+            "mossy_brick" => "infested_mossy_stone_bricks",
+            "cracked_brick" => "infested_cracked_stone_bricks",
+            "chiseled_brick" => "infested_chiseled_stone_bricks",
+            _ => "infested_stone",
+        }.to_string(),
+        "stonebrick" => match take(&mut blk, "variant").as_str() {
+            "mossy_stonebrick" => "mossy_stone_bricks",
+            "cracked_stonebrick" => "cracked_stone_bricks",
+            "chiseled_stonebrick" => "chiseled_stone_bricks",
+            _ => "stone_bricks",
+        }.to_string(),
+        "brown_mushroom_block" | "red_mushroom_block" => {
+            let variant = take(&mut blk, "variant");
+            let sides: &[&str] = match variant.as_str() {
+                "north_west" => &["up", "north", "west"],
+                "north" => &["up", "north"],
+                "north_east" => &["up", "north", "east"],
+                "west" => &["up", "west"],
+                "center" => &["up"],
+                "east" => &["up", "east"],
+                "south_west" => &["up", "south", "west"],
+                "south" => &["up", "south"],
+                "south_east" => &["up", "south", "east"],
+                "stem" => &["north", "south", "east", "west"],
+                "all_outside" | "all_stem" => &["up", "down", "north", "south", "east", "west"],
+                _ => &[],
+            };
+            for dir in ["up", "down", "north", "south", "east", "west"] {
+                blk.set_property(dir, &sides.contains(&dir));
+            }
+            if variant == "stem" || variant == "all_stem" {
+                "mushroom_stem".to_string()
+            } else {
+                id.to_string()
+            }
+        }
+        "melon_block" => "melon".to_string(),
+        "pumpkin_stem" | "melon_stem" => {
+            blk.attributes.remove("facing");
+            id.to_string()
+        }
+        "waterlily" => "lily_pad".to_string(),
+        "nether_brick" => "nether_bricks".to_string(),
+        "cobblestone_wall" => {
+            let variant = take(&mut blk, "variant");
+            if variant == "mossy_cobblestone" { "mossy_cobblestone_wall" } else { "cobblestone_wall" }.to_string()
+        }
+        "flower_pot" => {
+            let contents = take(&mut blk, "contents");
+            blk.attributes.remove("legacy_data");
+            match contents.as_str() {
+                "" | "empty" => "flower_pot".to_string(),
+                "rose" => "potted_poppy".to_string(),
+                "mushroom_red" => "potted_red_mushroom".to_string(),
+                "mushroom_brown" => "potted_brown_mushroom".to_string(),
+                c => format!("potted_{c}"),
+            }
+        }
+        "skull" => {
+            let facing = take(&mut blk, "facing");
+            blk.attributes.remove("nodrop");
+            if facing == "up" || facing == "down" || facing.is_empty() {
+                blk.set_property("rotation", &0);
+                "skeleton_skull".to_string()
+            } else {
+                blk.set_property("facing", &facing);
+                "skeleton_wall_skull".to_string()
+            }
+        }
+        "anvil" => match take(&mut blk, "damage").as_str() {
+            "1" => "chipped_anvil",
+            "2" => "damaged_anvil",
+            _ => "anvil",
+        }.to_string(),
+        "unpowered_comparator" | "powered_comparator" => "comparator".to_string(),
+        "daylight_detector" | "daylight_detector_inverted" => {
+            blk.set_property("inverted", &(id == "daylight_detector_inverted"));
+            "daylight_detector".to_string()
+        }
+        "quartz_ore" => "nether_quartz_ore".to_string(),
+        "quartz_block" => match take(&mut blk, "variant").as_str() {
+            "chiseled" => "chiseled_quartz_block".to_string(),
+            "lines_x" | "lines_y" | "lines_z" => {
+                let axis = &src.attributes["variant"][6..];
+                blk.set_property("axis", axis);
+                "quartz_pillar".to_string()
+            }
+            _ => "quartz_block".to_string(),
+        },
+        "hardened_clay" => "terracotta".to_string(),
+        "slime" => "slime_block".to_string(),
+        "prismarine" => {
+            let variant = take(&mut blk, "variant");
+            if variant.is_empty() { "prismarine".to_string() } else { variant }
+        }
+        "double_plant" => {
+            let variant = take(&mut blk, "variant");
+            blk.attributes.remove("facing");
+            match variant.as_str() {
+                "syringa" => "lilac",
+                "double_grass" => "tall_grass",
+                "fern" => "large_fern",
+                "rose" => "rose_bush",
+                "paeonia" => "peony",
+                _ => "sunflower",
+            }.to_string()
+        }
+        "end_bricks" => "end_stone_bricks".to_string(),
+        "magma" => "magma_block".to_string(),
+        "red_nether_brick" => "red_nether_bricks".to_string(),
+        "silver_shulker_box" => "light_gray_shulker_box".to_string(),
+        "silver_glazed_terracotta" => "light_gray_glazed_terracotta".to_string(),
+        _ => id.to_string(),
+    };
+    blk.id = new_id;
+
+    // doors store different properties in the upper and lower half in 1.12
+    if blk.id.ends_with("_door") {
+        let hinge = take(&mut blk, "hing");
+        for (key, default) in [("facing", "north"), ("open", "false"), ("powered", "false"), ("hinge", "left")] {
+            if !blk.attributes.contains_key(key) {
+                blk.set_property(key, if key == "hinge" && !hinge.is_empty() { hinge.as_str() } else { default });
+            }
+        }
+    }
+    // 1.12 buttons use `up` and `down` as facing
+    if blk.id.ends_with("_button") {
+        match blk.attributes.get("facing").map(|s| s.as_str()) {
+            Some("up") => {
+                blk.set_property("face", "floor");
+                blk.set_property("facing", "north");
+            }
+            Some("down") => {
+                blk.set_property("face", "ceiling");
+                blk.set_property("facing", "north");
+            }
+            _ => blk.set_property("face", "wall"),
+        }
+    }
+    if blk.id == "note_block" {
+        blk.set_property("instrument", "harp");
+        blk.set_property("note", &0);
+        blk.set_property("powered", &false);
+    }
+    if can_be_waterlogged_1_13(&blk) {
+        blk.set_property("waterlogged", &false);
+    }
+    if blk.id.ends_with("_fence_gate") {
+        blk.set_property("in_wall", &false);
+    }
+    if blk.id.ends_with("_trapdoor") {
+        blk.set_property("powered", &false);
+    }
+    return blk;
+}
+
+/// Returns true if the block converted from 1.12 has the `waterlogged` property in 1.13.
+fn can_be_waterlogged_1_13(blk: &Block) -> bool {
+    let id = blk.id.as_str();
+    if ["chest", "trapped_chest", "ender_chest", "ladder", "sign", "wall_sign", "iron_bars", "glass_pane"].contains(&id) {
+        return true;
+    }
+    for suffix in ["_slab", "_stairs", "_fence", "_stained_glass_pane", "_trapdoor", "_wall"] {
+        if id.ends_with(suffix) {
+            return true;
+        }
+    }
+    return false;
+}
+
+struct UnflattenLUT {
+    exact: HashMap<Block, Block>,
+    by_id: HashMap<String, Vec<(Block, Block)>>,
+}
+
+/// Build the look-up table from 1.13 blocks to 1.12 blocks by flattening all 1.12 blocks
+fn unflatten_lut() -> &'static UnflattenLUT {
+    static LUT: OnceLock<UnflattenLUT> = OnceLock::new();
+    return LUT.get_or_init(|| {
+        let mut lut = UnflattenLUT {
+            exact: HashMap::with_capacity(2048),
+            by_id: HashMap::with_capacity(1024),
+        };
+        let mut damages = Vec::with_capacity(16);
+        for id in 0..=255u8 {
+            get_valid_damage_values(id, &mut damages);
+            for damage in &damages {
+                let old = match Block::from_old(id, *damage, DataVersion::Java_1_12_2) {
+                    Ok(b) => b,
+                    Err(_) => continue,
+                };
+                let new = flatten(&old);
+                if !lut.exact.contains_key(&new) {
+                    lut.exact.insert(new.clone(), old.clone());
+                }
+                lut.by_id.entry(new.id.clone()).or_default().push((new, old));
+            }
+        }
+        return lut;
+    });
+}
+
+/// Convert a 1.13 block to 1.12, the inverse of `flatten`
+fn unflatten(src: &Block) -> Option<Block> {
+    if !is_vanilla(src) {
+        return Some(src.clone());
+    }
+    let lut = unflatten_lut();
+    if let Some(old) = lut.exact.get(src) {
+        return Some(old.clone());
+    }
+    let candidates = lut.by_id.get(&src.id)?;
+    let mut best: Option<(usize, &Block)> = None;
+    for (new, old) in candidates {
+        let mut matched = 0;
+        for (key, value) in &src.attributes {
+            if new.attributes.get(key) == Some(value) {
+                matched += 1;
+            }
+        }
+        if best.is_none() || matched > best.unwrap().0 {
+            best = Some((matched, old));
+        }
+    }
+    return best.map(|(_, old)| old.clone());
+}
+
+const ADDED_IN_1_14: &[&str] = &["barrel", "smoker", "blast_furnace", "cartography_table",
+    "fletching_table", "grindstone", "lectern", "smithing_table", "stonecutter", "bell", "lantern",
+    "campfire", "scaffolding", "sweet_berry_bush", "composter", "jigsaw", "cornflower",
+    "lily_of_the_valley", "wither_rose", "bamboo", "bamboo_sapling", "potted_bamboo",
+    "potted_cornflower", "potted_lily_of_the_valley", "potted_wither_rose", "smooth_stone_slab",
+    "stone_stairs", "cut_sandstone_slab", "cut_red_sandstone_slab",
+    "mossy_cobblestone_slab", "mossy_cobblestone_stairs", "mossy_stone_brick_slab",
+    "mossy_stone_brick_stairs", "mossy_stone_brick_wall", "smooth_quartz_slab",
+    "smooth_quartz_stairs"];
+const ADDED_PATTERNS_IN_1_14: &[&str] = &["_sign", "granite_", "diorite_", "andesite_", "end_stone_brick_",
+    "red_nether_brick_", "smooth_sandstone_", "smooth_red_sandstone_", "prismarine_wall", "brick_wall",
+    "sandstone_wall", "nether_brick_wall"];
+
+fn upgrade_18w43a(src: &Block) -> Block {
+    if !is_vanilla(src) {
+        return src.clone();
+    }
+    return match src.id.as_str() {
+        "sign" => renamed(src, "oak_sign"),
+        "wall_sign" => renamed(src, "oak_wall_sign"),
+        "stone_slab" => renamed(src, "smooth_stone_slab"),
+        _ => src.clone(),
+    };
+}
+
+fn downgrade_18w43a(src: &Block) -> Option<Block> {
+    if !is_vanilla(src) {
+        return Some(src.clone());
+    }
+    return match src.id.as_str() {
+        "oak_sign" => Some(renamed(src, "sign")),
+        "oak_wall_sign" => Some(renamed(src, "wall_sign")),
+        "smooth_stone_slab" => Some(renamed(src, "stone_slab")),
+        "stone_slab" => None,
+        _ => reject_added(src, ADDED_IN_1_14, ADDED_PATTERNS_IN_1_14),
+    };
+}
+
+fn downgrade_19w34a(src: &Block) -> Option<Block> {
+    return reject_added(src, &["bee_nest", "beehive", "honey_block", "honeycomb_block"], &[]);
+}
+
+/// Walls have the `up` property, and walls are the only blocks whose id ends with `_wall`.
+fn is_wall(blk: &Block) -> bool {
+    return is_vanilla(blk) && blk.id.ends_with("_wall") && blk.attributes.contains_key("up");
+}
+
+fn upgrade_20w06a(src: &Block) -> Block {
+    let mut blk = src.clone();
+    if !is_wall(src) {
+        return blk;
+    }
+    for dir in ["east", "north", "south", "west"] {
+        if let Some(value) = blk.attributes.get_mut(dir) {
+            *value = if value == "true" { "low" } else { "none" }.to_string();
+        }
+    }
+    return blk;
+}
+
+const ADDED_IN_1_16: &[&str] = &["netherite_block", "ancient_debris", "basalt", "polished_basalt",
+    "soul_fire", "soul_torch", "soul_wall_torch", "soul_lantern", "soul_campfire", "soul_soil",
+    "chain", "respawn_anchor", "lodestone", "target", "crying_obsidian", "shroomlight",
+    "nether_gold_ore", "quartz_bricks", "chiseled_nether_bricks", "cracked_nether_bricks",
+    "twisting_vines", "twisting_vines_plant", "weeping_vines", "weeping_vines_plant",
+    "nether_sprouts", "warped_wart_block", "polished_blackstone_bricks", "gilded_blackstone"];
+const ADDED_PATTERNS_IN_1_16: &[&str] = &["crimson_", "warped_", "blackstone"];
+
+fn downgrade_20w06a(src: &Block) -> Option<Block> {
+    let mut blk = reject_added(src, ADDED_IN_1_16, ADDED_PATTERNS_IN_1_16)?;
+    if !is_wall(src) {
+        return Some(blk);
+    }
+    for dir in ["east", "north", "south", "west"] {
+        if let Some(value) = blk.attributes.get_mut(dir) {
+            *value = (value != "none").to_string();
+        }
+    }
+    return Some(blk);
+}
+
+fn upgrade_20w45a(src: &Block) -> Block {
+    if is_vanilla(src) && src.id == "grass_path" {
+        return renamed(src, "dirt_path");
+    }
+    return src.clone();
+}
+
+fn downgrade_20w45a(src: &Block) -> Option<Block> {
+    if is_vanilla(src) && src.id == "dirt_path" {
+        return Some(renamed(src, "grass_path"));
+    }
+    return Some(src.clone());
+}
+
+const ADDED_IN_1_17: &[&str] = &["amethyst_block", "budding_amethyst", "amethyst_cluster", "tuff",
+    "calcite", "dripstone_block", "pointed_dripstone", "moss_block", "moss_carpet", "azalea",
+    "flowering_azalea", "azalea_leaves", "flowering_azalea_leaves", "cave_vines", "cave_vines_plant",
+    "big_dripleaf", "big_dripleaf_stem", "small_dripleaf", "glow_lichen", "hanging_roots",
+    "rooted_dirt", "spore_blossom", "lightning_rod", "powder_snow", "sculk_sensor", "tinted_glass",
+    "smooth_basalt", "light", "lava_cauldron", "powder_snow_cauldron", "potted_azalea_bush",
+    "potted_flowering_azalea_bush"];
+const ADDED_PATTERNS_IN_1_17: &[&str] = &["copper", "deepslate", "amethyst_bud", "candle", "raw_"];
+
+fn upgrade_20w46a(src: &Block) -> Block {
+    if !is_vanilla(src) || src.id != "cauldron" {
+        return src.clone();
+    }
+    let mut blk = src.clone();
+    let level = take(&mut blk, "level");
+    match level.as_str() {
+        "" | "0" => {}
+        // levels of water cauldron are in [1, 3]
+        _ => {
+            blk.id = "water_cauldron".to_string();
+            blk.set_property("level", &level);
+        }
+    }
+    return blk;
+}
+
+fn downgrade_20w46a(src: &Block) -> Option<Block> {
+    if is_vanilla(src) {
+        if src.id == "cauldron" {
+            let mut blk = src.clone();
+            blk.set_property("level", &0);
+            return Some(blk);
+        }
+        if src.id == "water_cauldron" {
+            return Some(renamed(src, "cauldron"));
+        }
+    }
+    return reject_added(src, ADDED_IN_1_17, ADDED_PATTERNS_IN_1_17);
+}
+
+fn downgrade_1_19(src: &Block) -> Option<Block> {
+    return reject_added(src, &["mud", "packed_mud", "muddy_mangrove_roots", "sculk", "sculk_vein",
+        "sculk_catalyst", "sculk_shrieker", "reinforced_deepslate", "frogspawn", "ochre_froglight",
+        "verdant_froglight", "pearlescent_froglight"], &["mangrove", "mud_brick"]);
+}
+
+fn downgrade_22w42a(src: &Block) -> Option<Block> {
+    return reject_added(src, &["bamboo_block", "stripped_bamboo_block", "chiseled_bookshelf",
+        "piglin_head", "piglin_wall_head"], &["bamboo_", "hanging_sign"]);
+}
+
+fn downgrade_1_20(src: &Block) -> Option<Block> {
+    return reject_added(src, &["pink_petals", "suspicious_sand", "suspicious_gravel", "torchflower",
+        "torchflower_crop", "potted_torchflower", "pitcher_plant", "pitcher_crop", "sniffer_egg",
+        "decorated_pot", "calibrated_sculk_sensor"], &["cherry_"]);
+}
+
+fn upgrade_1_20_3(src: &Block) -> Block {
+    if is_vanilla(src) && src.id == "grass" {
+        return renamed(src, "short_grass");
+    }
+    return src.clone();
+}
+
+fn downgrade_1_20_3(src: &Block) -> Option<Block> {
+    if is_vanilla(src) && src.id == "short_grass" {
+        return Some(renamed(src, "grass"));
+    }
+    return reject_added(src, &["crafter", "trial_spawner", "tuff_bricks", "chiseled_tuff",
+        "polished_tuff"], &["tuff_", "copper_door", "copper_trapdoor", "copper_grate", "copper_bulb",
+        "chiseled_copper"]);
+}
+
+impl Block {
+    /// Convert block from data version `from` to data version `to`. Returns `None` if the block
+    /// can not be represented in `to`. Blocks with a namespace other than `minecraft` are never
+    /// changed.
+    pub fn convert_data_version(&self, from: i32, to: i32) -> Option<Block> {
+        let mut blk = self.clone();
+        if from < to {
+            for change in &BLOCK_CHANGES {
+                let version = change.version as i32;
+                if from < version && version <= to {
+                    blk = (change.upgrade)(&blk);
+                }
+            }
+        } else {
+            for change in BLOCK_CHANGES.iter().rev() {
+                let version = change.version as i32;
+                if to < version && version <= from {
+                    blk = (change.downgrade)(&blk)?;
+                }
+            }
+        }
+        return Some(blk);
+    }
+}
+
+impl Region {
+    /// Convert all blocks in palette, blocks that become identical are merged.
+    fn convert_data_version(&mut self, from: i32, to: i32, option: &DataVersionConvertOption,
+                            report: &mut DataVersionConvertReport) {
+        let mut new_palette: Vec<Block> = Vec::with_capacity(self.palette.len());
+        let mut index_lut: Vec<u16> = Vec::with_capacity(self.palette.len());
+        for blk in &self.palette {
+            let converted = match blk.convert_data_version(from, to) {
+                Some(b) => b,
+                None => {
+                    if !report.unrepresentable_blocks.contains(blk) {
+                        report.unrepresentable_blocks.push(blk.clone());
+                    }
+                    match option.unrepresentable_replacement {
+                        Some(replacement) => replacement.to_block(),
+                        None => blk.clone(),
+                    }
+                }
+            };
+            if converted != *blk && !report.converted_blocks.iter().any(|(b, _)| b == blk) {
+                report.converted_blocks.push((blk.clone(), converted.clone()));
+            }
+            let idx = match new_palette.iter().position(|b| *b == converted) {
+                Some(idx) => idx,
+                None => {
+                    new_palette.push(converted);
+                    new_palette.len() - 1
+                }
+            };
+            index_lut.push(idx as u16);
+        }
+        if new_palette.len() != self.palette.len() {
+            for idx in &mut self.array_yzx {
+                *idx = index_lut[*idx as usize];
+            }
+        }
+        self.palette = new_palette;
+    }
+}
+
+impl Schematic {
+    /// Convert blocks of all regions to `target` data version, and set the data version in
+    /// metadata. Block entities and entities are not converted.
+    pub fn convert_data_version(&mut self, target: DataVersion, option: &DataVersionConvertOption) -> DataVersionConvertReport {
+        let source = self.metadata.mc_data_version;
+        let target = target as i32;
+        let mut report = DataVersionConvertReport {
+            source_version: source,
+            target_version: target,
+            converted_blocks: Vec::new(),
+            unrepresentable_blocks: Vec::new(),
+        };
+        for reg in &mut self.regions {
+            reg.convert_data_version(source, target, option, &mut report);
+        }
+        self.metadata.mc_data_version = target;
+        return report;
+    }
+}
+
+#[test]
+fn test_flatten_all_old_blocks() {
+    let mut damages = Vec::new();
+    for id in 0..=255u8 {
+        get_valid_damage_values(id, &mut damages);
+        for damage in &damages {
+            let old = match Block::from_old(id, *damage, DataVersion::Java_1_12_2) {
+                Ok(b) => b,
+                Err(_) => continue,
+            };
+            let new = flatten(&old);
+            for key in ["variant", "color", "decayable", "check_decay", "hing", "legacy_data", "nodrop"] {
+                assert!(!new.attributes.contains_key(key), "{old} -> {new}");
+            }
+            let reverted = unflatten(&new).unwrap();
+            assert_eq!(flatten(&reverted), new);
+        }
+    }
+}
@@ -16,17 +16,19 @@ You should have received a copy of the GNU General Public License
 along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::mem;
 use fastnbt::Value;
+use flate2::GzBuilder;
 use flate2::read::GzDecoder;
 use ndarray::Array3;
 use crate::block::Block;
 use crate::error::Error;
 use crate::old_block::OldBlockParseError;
-use crate::region::{BlockEntity, Region};
-use crate::schem::{common, id_of_nbt_tag, MetaDataIR, Schematic, WE12MetaData, WorldEdit12LoadOption};
+use crate::region::{BlockEntity, Region, WorldSlice};
+use crate::player::EntityBox;
+use crate::schem::{common, id_of_nbt_tag, MetaDataIR, NumberIdFallback, Schematic, WE12MetaData, WorldEdit12LoadOption, WorldEdit12SaveOption};
 use crate::{unwrap_opt_tag, unwrap_tag};
 
 
@@ -186,12 +188,12 @@ impl Region {
         let mut region = Region::new();
 
         let mut id_damage_counter = [[BlockStats::default(); 16]; 256];
+        // (id, damage) with damage >= 16 -> (first occur index, block index)
+        let mut invalid_damages: BTreeMap<(u8, u8), (u32, u16)> = BTreeMap::new();
         for (idx, (id, damage)) in id_damage_array.iter().enumerate() {
             if *damage >= 16 {
-                return Err(Error::InvalidBlockNumberId {
-                    tag_path: format!("/Data[{idx}]"),
-                    detail: OldBlockParseError::DamageMoreThan15 { damage: *damage },
-                });
+                invalid_damages.entry((*id, *damage)).or_insert((idx as u32, u16::MAX));
+                continue;
             }
             let stat = &mut id_damage_counter[*id as usize][*damage as usize];
             stat.count += 1;
@@ -208,17 +210,28 @@ impl Region {
                 if stat.count <= 0 {
                     continue;
                 }
-                let block = match Block::from_old(id as u8, damage, data_version) {
-                    Ok(b) => b,
-                    Err(detail) => return Err(Error::InvalidBlockNumberId {
-                        tag_path: format!("/Data[{}]", stat.first_occur_index),
-                        detail,
-                    }),
+                match Block::from_old(id as u8, damage, data_version) {
+                    Ok(block) => {
+                        stat.id = region.palette.len() as u16;
+                        region.palette.push(block);
+                    },
+                    Err(detail) => {
+                        let block = option.error_handler.handle_invalid_block_id(Error::InvalidBlockNumberId {
+                            tag_path: format!("/Data[{}]", stat.first_occur_index),
+                            detail,
+                        })?;
+                        stat.id = region.find_or_append_to_palette(&block);
+                    },
                 };
-                stat.id = region.palette.len() as u16;
-                region.palette.push(block);
             }
         }
+        for ((_, damage), (first_occur_index, block_index)) in invalid_damages.iter_mut() {
+            let block = option.error_handler.handle_invalid_block_id(Error::InvalidBlockNumberId {
+                tag_path: format!("/Data[{first_occur_index}]"),
+                detail: OldBlockParseError::DamageMoreThan15 { damage: *damage },
+            })?;
+            *block_index = region.find_or_append_to_palette(&block);
+        }
 
         let shape_usize = id_damage_array.shape();
         let shape_yzx: [i32; 3] = [shape_usize[0] as i32, shape_usize[1] as i32, shape_usize[2] as i32];
@@ -230,9 +243,13 @@ impl Region {
                 for x in 0..shape_yzx[2] {
                     let pos = [y as usize, z as usize, x as usize];
                     let (id, damage) = id_damage_array[pos];
-                    let stat = &id_damage_counter[id as usize][damage as usize];
-                    debug_assert!((stat.id as usize) < region.palette.len());
-                    region.array_yzx[pos] = stat.id;
+                    let block_index = if damage >= 16 {
+                        invalid_damages[&(id, damage)].1
+                    } else {
+                        id_damage_counter[id as usize][damage as usize].id
+                    };
+                    debug_assert!((block_index as usize) < region.palette.len());
+                    region.array_yzx[pos] = block_index;
                 }
             }
         }
@@ -243,18 +260,27 @@ impl Region {
         for (idx, te) in tile_entities.iter_mut().enumerate() {
             let tag_path = format!("/TileEntities[{idx}]");
             let te = unwrap_tag!(te,Compound,HashMap::new(),&tag_path);
-            let pos_xyz = common::parse_size_compound(te, &tag_path, false)?;
+            let mut pos_xyz = common::parse_size_compound(te, &tag_path, false)?;
             //check pos
+            let mut dropped = false;
             for dim in 0..3 {
                 if pos_xyz[dim] < 0 || pos_xyz[dim] >= shape_xyz[dim] {
-                    return Err(Error::BlockPosOutOfRange {
+                    let fixed = option.error_handler.handle_block_pos_out_of_range(&mut region, Error::BlockPosOutOfRange {
                         tag_path,
                         pos: pos_xyz,
                         lower_bound: [0, 0, 0],
                         upper_bound: shape_xyz,
-                    });
+                    })?;
+                    match fixed {
+                        Some(p) => pos_xyz = p,
+                        None => dropped = true,
+                    }
+                    break;
                 }
             }
+            if dropped {
+                continue;
+            }
             let mut block_entity = BlockEntity::new();
             mem::swap(&mut block_entity.tags, te);
             for key in ["x", "y", "z"] {
@@ -265,6 +291,19 @@ impl Region {
             region.block_entities.insert(pos_xyz, block_entity);
         }
 
+        //entities
+        if let Some(entities) = nbt.get_mut("Entities") {
+            let entities = unwrap_tag!(entities,List,vec![],"/Entities");
+            region.entities.reserve(entities.len());
+            for (idx, entity) in entities.iter_mut().enumerate() {
+                let tag_path = format!("/Entities[{idx}]");
+                let entity = unwrap_tag!(entity,Compound,HashMap::new(),&tag_path);
+                let mut temp = HashMap::new();
+                mem::swap(&mut temp, entity);
+                region.entities.push(EntityBox::from_nbt(temp, &tag_path)?);
+            }
+        }
+
         // if option.fix_string_id_with_block_entity_data {
         //     let mut block_to_index: HashMap<Block, u16> = HashMap::new();
         //     block_to_index.reserve(region.palette.len() + region.block_entities.len());
@@ -326,4 +365,183 @@ impl Region {
 
         return Ok((region, id_damage_array));
     }
-}
\ No newline at end of file
+}
+
+#[allow(dead_code)]
+impl Schematic {
+    pub fn metadata_world_edit_12(&self) -> Result<WE12MetaData, Error> {
+        let shape = self.shape();
+        for sz in shape {
+            if sz < 0 {
+                return Err(Error::NegativeSize { size: shape, region_name: "all regions".to_string() });
+            }
+            if sz > i16::MAX as i32 {
+                return Err(Error::SizeTooLarge {
+                    size: [shape[0] as u64, shape[1] as u64, shape[2] as u64],
+                    max_size: [i16::MAX as u64; 3],
+                });
+            }
+        }
+
+        let mut result = WE12MetaData::default();
+        if !self.metadata.schem_material.is_empty() {
+            result.materials = self.metadata.schem_material.clone();
+        }
+        result.we_offset = self.metadata.schem_offset;
+        result.we_origin = self.metadata.schem_origin.unwrap_or([0, 0, 0]);
+        result.width = shape[0] as i16;//x
+        result.height = shape[1] as i16;//y
+        result.length = shape[2] as i16;//z
+        return Ok(result);
+    }
+
+    fn write_metadata_we12(dest: &mut HashMap<String, Value>, md: &WE12MetaData) {
+        dest.insert("Materials".to_string(), Value::String(md.materials.clone()));
+        let pos_letter = ['X', 'Y', 'Z'];
+        for dim in 0..3 {
+            dest.insert(format!("WEOffset{}", pos_letter[dim]), Value::Int(md.we_offset[dim]));
+            dest.insert(format!("WEOrigin{}", pos_letter[dim]), Value::Int(md.we_origin[dim]));
+        }
+        dest.insert("Width".to_string(), Value::Short(md.width));
+        dest.insert("Height".to_string(), Value::Short(md.height));
+        dest.insert("Length".to_string(), Value::Short(md.length));
+    }
+
+    /// Convert the palette of `region` from `data_version` to 1.12.2 number id and damage. Blocks
+    /// without number id are replaced according to `option`, and appended to `replaced`.
+    fn palette_to_number_id(region: &Region, data_version: i32, option: &WorldEdit12SaveOption, replaced: &mut Vec<Block>)
+        -> Result<Vec<(u16, u8)>, Error> {
+        let mut result = Vec::with_capacity(region.palette.len());
+        for blk in &region.palette {
+            if let Some((id, damage)) = blk.to_old_from_version(data_version) {
+                result.push((id as u16, damage));
+                continue;
+            }
+            match option.number_id_fallback {
+                NumberIdFallback::ReturnError => return Err(Error::BlockWithoutNumberId { block: blk.full_id() }),
+                NumberIdFallback::ReplaceWith { id, damage } => {
+                    if id >= 4096 || damage >= 16 {
+                        return Err(Error::InvalidValue {
+                            tag_path: "/Blocks".to_string(),
+                            error: format!("Fallback number id {id}:{damage} can not be stored, id should be less than 4096 and damage should be less than 16."),
+                        });
+                    }
+                    replaced.push(blk.clone());
+                    result.push((id, damage));
+                }
+            }
+        }
+        return Ok(result);
+    }
+
+    /// Save `.schematic` to nbt. Blocks replaced by `option.number_id_fallback` are also returned.
+    pub fn to_nbt_world_edit_12(&self, option: &WorldEdit12SaveOption) -> Result<(HashMap<String, Value>, Vec<Block>), Error> {
+        let md = self.metadata_world_edit_12()?;
+        let mut root = HashMap::new();
+        Self::write_metadata_we12(&mut root, &md);
+
+        let region = self.to_single_region(&option.background_block.to_block());
+        let mut replaced = Vec::new();
+        let number_id_lut = Self::palette_to_number_id(&region, self.metadata.mc_data_version, option, &mut replaced)?;
+
+        // blocks
+        {
+            let volume = region.volume() as usize;
+            let mut blocks = Vec::with_capacity(volume);
+            let mut data = Vec::with_capacity(volume);
+            let mut add_blocks = vec![0u8; volume.div_ceil(2)];
+            let mut has_add_blocks = false;
+            // array_yzx is iterated in yzx order, which is the same as the order in `.schematic`
+            for (idx, blk_idx) in region.array_yzx.iter().enumerate() {
+                let (id, damage) = number_id_lut[*blk_idx as usize];
+                blocks.push((id & 0xFF) as u8 as i8);
+                data.push(damage as i8);
+                let add = (id >> 8) as u8;
+                if add != 0 {
+                    has_add_blocks = true;
+                    if idx % 2 == 0 {
+                        add_blocks[idx / 2] |= add;
+                    } else {
+                        add_blocks[idx / 2] |= add << 4;
+                    }
+                }
+            }
+            root.insert("Blocks".to_string(), Value::ByteArray(fastnbt::ByteArray::new(blocks)));
+            root.insert("Data".to_string(), Value::ByteArray(fastnbt::ByteArray::new(data)));
+            if has_add_blocks {
+                let add_blocks = add_blocks.into_iter().map(|b| b as i8).collect();
+                root.insert("AddBlocks".to_string(), Value::ByteArray(fastnbt::ByteArray::new(add_blocks)));
+            }
+        }
+
+        // tile entities
+        {
+            let mut positions: Vec<&[i32; 3]> = region.block_entities.keys().collect();
+            positions.sort_by_key(|pos| Region::pos_xyz_to_yzx(*pos));
+            let mut te_list = Vec::with_capacity(positions.len());
+            for pos in positions {
+                let be = &region.block_entities[pos];
+                let mut nbt = be.tags.clone();
+                for (dim, key) in ["x", "y", "z"].iter().enumerate() {
+                    nbt.insert(key.to_string(), Value::Int(pos[dim]));
+                }
+                te_list.push(Value::Compound(nbt));
+            }
+            root.insert("TileEntities".to_string(), Value::List(te_list));
+        }
+
+        // entities
+        {
+            let mut entities = Vec::with_capacity(region.entities.len());
+            for entity in &region.entities {
+                entities.push(Value::Compound(entity.to_nbt()));
+            }
+            root.insert("Entities".to_string(), Value::List(entities));
+        }
+
+        return Ok((root, replaced));
+    }
+
+    /// Save `.schematic` to writer. Blocks replaced by `option.number_id_fallback` are returned.
+    pub fn save_world_edit_12_writer(&self, dest: &mut dyn std::io::Write, option: &WorldEdit12SaveOption) -> Result<Vec<Block>, Error> {
+        let (nbt, replaced) = self.to_nbt_world_edit_12(option)?;
+        let mut encoder = GzBuilder::new()
+            .comment("Generated by mc_schem")
+            .write(dest, option.compress_level);
+
+        let res: Result<(), fastnbt::error::Error> = fastnbt::to_writer(&mut encoder, &nbt);
+        if let Err(e) = res {
+            return Err(Error::NBTWriteError(e));
+        }
+        if let Err(e) = encoder.finish() {
+            return Err(Error::NBTWriteError(e.into()));
+        }
+
+        return Ok(replaced);
+    }
+
+    /// Save `.schematic` to file. Blocks replaced by `option.number_id_fallback` are returned.
+    pub fn save_world_edit_12_file(&self, filename: &str, option: &WorldEdit12SaveOption) -> Result<Vec<Block>, Error> {
+        let (nbt, replaced) = self.to_nbt_world_edit_12(option)?;
+
+        let file = match File::create(filename) {
+            Ok(f) => f,
+            Err(e) => return Err(Error::FileCreateError(e)),
+        };
+
+        let mut encoder = GzBuilder::new()
+            .filename(filename)
+            .comment("Generated by mc_schem")
+            .write(file, option.compress_level);
+
+        let res: Result<(), fastnbt::error::Error> = fastnbt::to_writer(&mut encoder, &nbt);
+        if let Err(e) = res {
+            return Err(Error::NBTWriteError(e));
+        }
+        if let Err(e) = encoder.finish() {
+            return Err(Error::NBTWriteError(e.into()));
+        }
+
+        return Ok(replaced);
+    }
+}
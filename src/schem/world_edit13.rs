@@ -21,10 +21,14 @@ use std::fs::File;
 use fastnbt::Value;
 use flate2::{GzBuilder};
 use flate2::read::GzDecoder;
-use ndarray::Array3;
+use ndarray::{Array2, Array3};
+use serde::de::{DeserializeSeed, MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::biome::Biome;
 use crate::block::Block;
-use crate::error::{Error};
-use crate::region::{BlockEntity, Region, WorldSlice};
+use crate::error::{Error, ErrorHandler};
+use crate::region::{BlockEntity, Region, RegionBiomes, WorldSlice};
 use crate::schem::{common, MetaDataIR, Schematic, WE13MetaData, WE13MetaDataV3Extra, WorldEdit13LoadOption, WorldEdit13SaveOption};
 use crate::{unwrap_opt_tag, unwrap_tag};
 use crate::schem::id_of_nbt_tag;
@@ -180,35 +184,9 @@ fn parse_metadata(nbt: &HashMap<String, Value>, tag_path: &str, _option: &WorldE
     return Ok(we13);
 }
 
-fn parse_single_block(src: &[i8]) -> i32 {
-    debug_assert!(src.len() > 0);
-    if src.len() == 1 {
-        debug_assert!(src[0] >= 0);
-        return src[0] as i32;
-    }
-
-    let mut result = 0;
-    for (idx, value) in src.iter().enumerate() {
-        if idx == src.len() - 1 {
-            debug_assert!(*value >= 1);
-        } else {
-            debug_assert!(*value < 0);
-        }
-        let value_fixed;
-        if idx != 0 {
-            value_fixed = *value as i32 + 1;
-        } else {
-            value_fixed = *value as i32;
-        }
-        result += value_fixed * (1 << (idx * 7));
-    }
-
-    return result;
-}
-
 #[allow(dead_code)]
 impl Region {
-    fn parse_palette_v2(nbt: &HashMap<String, Value, >, tag_path: &str, _option: &WorldEdit13LoadOption) -> Result<Vec<Block>, Error> {
+    fn parse_palette_v2(nbt: &HashMap<String, Value, >, tag_path: &str, option: &WorldEdit13LoadOption) -> Result<Vec<Block>, Error> {
         let palette_max = *unwrap_opt_tag!(nbt.get("PaletteMax"),Int,0,format!("{tag_path}/PaletteMax"));
         let palette_comp = unwrap_opt_tag!(nbt.get("Palette"),Compound,HashMap::new(),format!("{tag_path}/Palette"));
         if palette_max != palette_comp.len() as i32 {
@@ -218,7 +196,7 @@ impl Region {
             });
         }
 
-        return parse_palette(palette_comp, tag_path);
+        return parse_palette(palette_comp, tag_path, &*option.error_handler);
     }
 
     fn parse_size_v2(nbt: &HashMap<String, Value, >, tag_path: &str, _option: &WorldEdit13LoadOption) -> Result<[i32; 3], Error> {
@@ -238,61 +216,100 @@ impl Region {
         return Ok(sz);
     }
 
-    fn parse_3d_array_v2(block_data: &[i8], tag_path: &str, _option: &WorldEdit13LoadOption, size: [i32; 3], palette: &[Block]) -> Result<Array3<u16>, Error> {
-        let mut array: Array3<u16> = Array3::default([size[1] as usize, size[2] as usize, size[0] as usize]);
-
-        let total_blocks = size[1] as usize * size[2] as usize * size[0] as usize;
-        let mut decoded_blocks = 0;
-        let mut idx = 0;
-        for y in 0..size[1] as usize {
-            for z in 0..size[2] as usize {
-                for x in 0..size[0] as usize {
-                    if idx >= block_data.len() {
-                        return Err(Error::BlockDataIncomplete {
-                            tag_path: tag_path.to_string(),
-                            index: idx,
-                            detail: format!("{} blocks decoded, {} blocks missing, {} blocks in total.", decoded_blocks, total_blocks - decoded_blocks, total_blocks),
-                        });
-                    }
+    /// Decode varint-encoded block indices in `BlockData`, without checking the size
+    fn decode_block_data(block_data: impl ExactSizeIterator<Item=u8>, tag_path: &str) -> Result<Vec<u16>, Error> {
+        let mut result = Vec::with_capacity(block_data.len());
+        let mut value: u32 = 0;
+        let mut shift = 0;
+        let mut first_byte_index = 0;
+        for (idx, byte) in block_data.enumerate() {
+            if shift == 0 {
+                first_byte_index = idx;
+            }
+            value |= ((byte & 0x7F) as u32) << shift;
+            if byte & 0x80 != 0 {
+                shift += 7;
+                if shift > 14 {
+                    return Err(Error::InvalidValue {
+                        tag_path: format!("{tag_path}[{}]", first_byte_index),
+                        error: "A block index is encoded in more than 3 bytes".to_string(),
+                    });
+                }
+                continue;
+            }
+            if value > u16::MAX as u32 {
+                return Err(Error::BlockIndexOutOfRange {
+                    tag_path: format!("{tag_path}[{}]", first_byte_index),
+                    index: value as i32,
+                    range: [0, 65536],
+                });
+            }
+            result.push(value as u16);
+            value = 0;
+            shift = 0;
+        }
+        if shift != 0 {
+            return Err(Error::BlockDataIncomplete {
+                tag_path: tag_path.to_string(),
+                index: first_byte_index,
+                detail: format!("BlockData[{}] expects one or more elements to represent a block, but the data ends; {} blocks decoded.", first_byte_index, result.len()),
+            });
+        }
+        return Ok(result);
+    }
 
-                    let cur_block_first_byte_index = idx;
+    /// Reshape decoded block indices into a 3d array, and check their range with the palette of
+    /// `region`
+    fn block_data_to_array(mut block_data: Vec<u16>, tag_path: &str, size: [i32; 3], region: &mut Region, handler: &dyn ErrorHandler) -> Result<Array3<u16>, Error> {
+        let shape_yzx = [size[1] as usize, size[2] as usize, size[0] as usize];
+        let total_blocks = shape_yzx[0] * shape_yzx[1] * shape_yzx[2];
+        if block_data.len() < total_blocks {
+            return Err(Error::BlockDataIncomplete {
+                tag_path: tag_path.to_string(),
+                index: block_data.len(),
+                detail: format!("{} blocks decoded, {} blocks missing, {} blocks in total.", block_data.len(), total_blocks - block_data.len(), total_blocks),
+            });
+        }
+        block_data.truncate(total_blocks);
+        let palette_len = region.palette.len();
+        for (idx, blk) in block_data.iter_mut().enumerate() {
+            if *blk as usize >= palette_len {
+                *blk = handler.handle_block_index_out_of_range(region, Error::BlockIndexOutOfRange {
+                    tag_path: format!("{tag_path}[{}]", idx),
+                    index: *blk as i32,
+                    range: [0, palette_len as i32],
+                })?;
+            }
+        }
+        return Ok(Array3::from_shape_vec(shape_yzx, block_data).unwrap());
+    }
 
-                    let mut cur_block_bytes = usize::MAX;
-                    for iidx in cur_block_first_byte_index..block_data.len() {
-                        if block_data[iidx] >= 0 {
-                            cur_block_bytes = iidx - cur_block_first_byte_index + 1;
-                            break;
-                        }
-                    }
-                    if cur_block_bytes >= block_data.len() {
-                        let first_byte = block_data[cur_block_first_byte_index];
-                        return Err(Error::BlockDataIncomplete {
-                            tag_path: tag_path.to_string(),
-                            index: idx,
-                            detail: format!("BlockData[{}] is {}, which expects one or more elements to represent a block, but the data ends; {} blocks decoded, {} blocks missing, {} blocks in total.", idx - 1, first_byte, decoded_blocks, total_blocks - decoded_blocks, total_blocks),
-                        });
-                    }
-                    idx += cur_block_bytes;
-                    let decoded_block_index = parse_single_block(&block_data[cur_block_first_byte_index..(cur_block_first_byte_index + cur_block_bytes)]);
-
-                    assert!(decoded_block_index >= 0);
-                    if decoded_block_index as usize >= palette.len() {
-                        return Err(Error::BlockIndexOutOfRange {
-                            tag_path: format!("{tag_path}[{}]", cur_block_first_byte_index),
-                            index: decoded_block_index,
-                            range: [0, palette.len() as i32],
-                        });
-                    }
-                    decoded_blocks += 1;
-                    array[[y, z, x]] = decoded_block_index as u16;
-                }
+    /// Decode varint-encoded biome indices in `BiomeData`(v2) or `Biomes/Data`(v3), and look them
+    /// up in the biome palette
+    fn decode_biome_data(biome_data: &[i8], tag_path: &str, total: usize, palette: &[Biome]) -> Result<Vec<Biome>, Error> {
+        let indices = Self::decode_block_data(biome_data.iter().map(|b| *b as u8), tag_path)?;
+        if indices.len() < total {
+            return Err(Error::BlockDataIncomplete {
+                tag_path: tag_path.to_string(),
+                index: indices.len(),
+                detail: format!("{} biomes decoded, {} biomes missing, {} biomes in total.", indices.len(), total - indices.len(), total),
+            });
+        }
+        let mut result = Vec::with_capacity(total);
+        for (idx, biome_idx) in indices.iter().take(total).enumerate() {
+            match palette.get(*biome_idx as usize) {
+                Some(biome) => result.push(*biome),
+                None => return Err(Error::BlockIndexOutOfRange {
+                    tag_path: format!("{tag_path}[{}]", idx),
+                    index: *biome_idx as i32,
+                    range: [0, palette.len() as i32],
+                }),
             }
         }
-        debug_assert!(idx == block_data.len());
-        return Ok(array);
+        return Ok(result);
     }
 
-    fn parse_block_entities_v2(block_entities: &mut [Value], tag_path: &str, _option: &WorldEdit13LoadOption, size: [i32; 3])
+    fn parse_block_entities_v2(block_entities: &mut [Value], tag_path: &str, option: &WorldEdit13LoadOption, region: &mut Region)
         -> Result<HashMap<[i32; 3], BlockEntity>, Error> {
         let mut result = HashMap::with_capacity(block_entities.len());
         for (idx, nbt) in block_entities.iter_mut().enumerate() {
@@ -300,7 +317,9 @@ impl Region {
             let nbt = unwrap_tag!(nbt,Compound,HashMap::new(),cur_tag_path);
             let mut nbt_temp = HashMap::new();
             std::mem::swap(&mut nbt_temp, nbt);
-            let (be, pos) = parse_block_entity(nbt_temp, &cur_tag_path, &size)?;
+            let Some((be, pos)) = parse_block_entity(nbt_temp, &cur_tag_path, region, &*option.error_handler)? else {
+                continue;
+            };
 
             if result.contains_key(&pos) {
                 return Err(Error::MultipleBlockEntityInOnePos {
@@ -315,6 +334,14 @@ impl Region {
 
     /// Load region from nbt, for `.schem` v2
     pub fn from_world_edit_13_v2(mut root: HashMap<String, Value>, option: &WorldEdit13LoadOption) -> Result<Region, Error> {
+        let block_data_tag_path = "/BlockData";
+        let block_data = unwrap_opt_tag!(root.remove("BlockData"),ByteArray,fastnbt::ByteArray::new(vec![]),block_data_tag_path);
+        let block_data = Self::decode_block_data(block_data.iter().map(|b| *b as u8), block_data_tag_path)?;
+        return Self::from_world_edit_13_v2_with_block_data(root, block_data, option);
+    }
+
+    /// Load region from nbt for `.schem` v2, but `BlockData` is decoded separately
+    fn from_world_edit_13_v2_with_block_data(mut root: HashMap<String, Value>, block_data: Vec<u16>, option: &WorldEdit13LoadOption) -> Result<Region, Error> {
         let mut region = Region::new();
         let tag_path = "";
         // palette
@@ -326,24 +353,48 @@ impl Region {
         let size: [i32; 3] = Self::parse_size_v2(&root, tag_path, option)?;
 
         // parse 3d array
-        {
-            let block_data_tag_path = format!("{tag_path}/BlockData");
-            let block_data = unwrap_opt_tag!(root.get("BlockData"),ByteArray,fastnbt::ByteArray::new(vec![]),block_data_tag_path);
-            region.array_yzx = Self::parse_3d_array_v2(block_data.as_ref(), &block_data_tag_path, option, size, &region.palette)?;
+        region.array_yzx = Self::block_data_to_array(block_data, &format!("{tag_path}/BlockData"), size, &mut region, &*option.error_handler)?;
+
+        // biomes, one per column
+        if let Some(biome_data) = root.get("BiomeData") {
+            let biome_data_path = format!("{tag_path}/BiomeData");
+            let biome_data = unwrap_tag!(biome_data,ByteArray,fastnbt::ByteArray::new(vec![]),biome_data_path);
+            let biome_palette_path = format!("{tag_path}/BiomePalette");
+            let biome_palette = unwrap_opt_tag!(root.get("BiomePalette"),Compound,HashMap::new(),biome_palette_path);
+            let biome_palette = parse_biome_palette(biome_palette, &biome_palette_path, &*option.error_handler)?;
+            if let Some(Value::Int(palette_max)) = root.get("BiomePaletteMax") {
+                if *palette_max as usize != biome_palette.len() {
+                    return Err(Error::InvalidValue {
+                        tag_path: biome_palette_path,
+                        error: format!("BiomePaletteMax should equal to the size of BiomePalette ({}), but found {}", biome_palette.len(), palette_max),
+                    });
+                }
+            }
+            let shape_zx = [size[2] as usize, size[0] as usize];
+            let biomes = Self::decode_biome_data(biome_data, &biome_data_path, shape_zx[0] * shape_zx[1], &biome_palette)?;
+            region.biomes = Some(RegionBiomes::Columns(Array2::from_shape_vec(shape_zx, biomes).unwrap()));
         }
 
-
         // parse block entities
         {
             let be_tag_path = format!("{tag_path}/BlockEntities");
             let block_entities = unwrap_opt_tag!(root.get_mut("BlockEntities"),List,vec![],be_tag_path);
-            region.block_entities = Self::parse_block_entities_v2(block_entities, &be_tag_path, option, size)?;
+            region.block_entities = Self::parse_block_entities_v2(block_entities, &be_tag_path, option, &mut region)?;
         }
         return Ok(region);
     }
 
     /// Load region from nbt, for `.schem` v3
     pub fn from_world_edit_13_v3(mut tag_schem: HashMap<String, Value>, option: &WorldEdit13LoadOption) -> Result<Region, Error> {
+        let tag_blocks = unwrap_opt_tag!(tag_schem.get_mut("Blocks"),Compound,HashMap::new(),"/Schematic/Blocks");
+        let tag_data_path = "/Schematic/Blocks/Data";
+        let tag_data = unwrap_opt_tag!(tag_blocks.remove("Data"),ByteArray,fastnbt::ByteArray::new(vec![]),tag_data_path);
+        let block_data = Self::decode_block_data(tag_data.iter().map(|b| *b as u8), tag_data_path)?;
+        return Self::from_world_edit_13_v3_with_block_data(tag_schem, block_data, option);
+    }
+
+    /// Load region from nbt for `.schem` v3, but `Blocks/Data` is decoded separately
+    fn from_world_edit_13_v3_with_block_data(mut tag_schem: HashMap<String, Value>, block_data: Vec<u16>, option: &WorldEdit13LoadOption) -> Result<Region, Error> {
         let tag_schem_path = "/Schematic";
         let mut region = Region::new();
         //size
@@ -355,21 +406,29 @@ impl Region {
         {
             let tag_palette_path = "/Schematic/Blocks/Palette";
             let tag_palette = unwrap_opt_tag!(tag_blocks.get("Palette"),Compound,HashMap::new(),tag_palette_path);
-            region.palette = parse_palette(tag_palette, tag_palette_path)?;
+            region.palette = parse_palette(tag_palette, tag_palette_path, &*option.error_handler)?;
         }
         //3d array
-        {
-            let tag_data_path = "/Schematic/Blocks/Data";
-            let tag_data = unwrap_opt_tag!(tag_blocks.get("Data"),ByteArray,fastnbt::ByteArray::new(vec![]),tag_data_path);
-            region.array_yzx = Self::parse_3d_array_v2(&tag_data, tag_data_path, option, size, &region.palette)?;
-        }
+        region.array_yzx = Self::block_data_to_array(block_data, "/Schematic/Blocks/Data", size, &mut region, &*option.error_handler)?;
         //block entities
         {
             let tag_be_path = "/Schematic/Blocks/BlockEntities";
             let tag_be = unwrap_opt_tag!(tag_blocks.get_mut("BlockEntities"),List,vec![],tag_be_path);
-            region.block_entities = Self::parse_block_entities_v2(tag_be, tag_be_path, option, size)?;
+            region.block_entities = Self::parse_block_entities_v2(tag_be, tag_be_path, option, &mut region)?;
+        }
+        //biomes, one per block
+        if let Some(tag_biomes) = tag_schem.get("Biomes") {
+            let tag_biomes_path = "/Schematic/Biomes";
+            let tag_biomes = unwrap_tag!(tag_biomes,Compound,HashMap::new(),tag_biomes_path);
+            let tag_palette_path = "/Schematic/Biomes/Palette";
+            let tag_palette = unwrap_opt_tag!(tag_biomes.get("Palette"),Compound,HashMap::new(),tag_palette_path);
+            let biome_palette = parse_biome_palette(tag_palette, tag_palette_path, &*option.error_handler)?;
+            let tag_data_path = "/Schematic/Biomes/Data";
+            let tag_data = unwrap_opt_tag!(tag_biomes.get("Data"),ByteArray,fastnbt::ByteArray::new(vec![]),tag_data_path);
+            let shape_yzx = [size[1] as usize, size[2] as usize, size[0] as usize];
+            let biomes = Self::decode_biome_data(tag_data, tag_data_path, shape_yzx.iter().product(), &biome_palette)?;
+            region.biomes = Some(RegionBiomes::Blocks(Array3::from_shape_vec(shape_yzx, biomes).unwrap()));
         }
-
 
         return Ok(region);
     }
@@ -377,7 +436,7 @@ impl Region {
 }
 
 
-fn parse_palette(pal: &HashMap<String, Value>, tag_path: &str) -> Result<Vec<Block>, Error> {
+fn parse_palette(pal: &HashMap<String, Value>, tag_path: &str, handler: &dyn ErrorHandler) -> Result<Vec<Block>, Error> {
     if pal.len() >= 65536 {
         return Err(Error::PaletteTooLong(pal.len()));
     }
@@ -391,7 +450,7 @@ fn parse_palette(pal: &HashMap<String, Value>, tag_path: &str) -> Result<Vec<Blo
         let block;
         match Block::from_id(key) {
             Ok(blk) => block = blk,
-            Err(e) => return Err(Error::InvalidBlockId { id: key.clone(), reason: e }),
+            Err(e) => block = handler.handle_invalid_block_id(Error::InvalidBlockId { id: key.clone(), reason: e })?,
         }
 
         let cur_tag_path = format!("{tag_path}/Palette/{}", key);
@@ -416,9 +475,41 @@ fn parse_palette(pal: &HashMap<String, Value>, tag_path: &str) -> Result<Vec<Blo
     return Ok(result);
 }
 
-fn parse_block_entity(mut nbt: HashMap<String, Value>, tag_path: &str, region_size: &[i32; 3])
-    -> Result<(BlockEntity, [i32; 3]), Error> {
-    let pos;
+/// Unknown biomes (e.g. from datapacks) are fixed by `handler`
+fn parse_biome_palette(pal: &HashMap<String, Value>, tag_path: &str, handler: &dyn ErrorHandler) -> Result<Vec<Biome>, Error> {
+    let mut result: Vec<Option<Biome>> = vec![None; pal.len()];
+    for (key, val) in pal {
+        let cur_tag_path = format!("{tag_path}/{}", key);
+        let biome = match Biome::from_str(key) {
+            Some(b) => b,
+            None => handler.handle_invalid_biome(Error::InvalidBiome {
+                tag_path: cur_tag_path.clone(),
+                biome: key.clone(),
+            })?,
+        };
+        let idx = *unwrap_tag!(val,Int,0,cur_tag_path);
+        if idx < 0 || idx >= pal.len() as i32 {
+            return Err(Error::InvalidValue {
+                tag_path: cur_tag_path,
+                error: format!("Biome index {} in palette is out of range [0,{})", idx, pal.len()),
+            });
+        }
+        if let Some(prev) = result[idx as usize] {
+            return Err(Error::InvalidValue {
+                tag_path: cur_tag_path,
+                error: format!("Biome index {} is used by both {} and {}", idx, prev, biome),
+            });
+        }
+        result[idx as usize] = Some(biome);
+    }
+    return Ok(result.into_iter().flatten().collect());
+}
+
+/// Returns `None` if the block entity is out of the region and dropped by the error handler
+fn parse_block_entity(mut nbt: HashMap<String, Value>, tag_path: &str, region: &mut Region, handler: &dyn ErrorHandler)
+    -> Result<Option<(BlockEntity, [i32; 3])>, Error> {
+    let region_size = region.shape();
+    let mut pos;
     let pos_tag_path = format!("{}/Pos", tag_path);
     // parse pos
     {
@@ -430,12 +521,17 @@ fn parse_block_entity(mut nbt: HashMap<String, Value>, tag_path: &str, region_si
     }
     for dim in 0..3 {
         if pos[dim] < 0 || pos[dim] >= region_size[dim] {
-            return Err(Error::BlockPosOutOfRange {
+            let fixed = handler.handle_block_pos_out_of_range(region, Error::BlockPosOutOfRange {
                 tag_path: pos_tag_path,
                 pos,
                 lower_bound: [0, 0, 0],
-                upper_bound: *region_size,
-            });
+                upper_bound: region_size,
+            })?;
+            match fixed {
+                Some(p) => pos = p,
+                None => return Ok(None),
+            }
+            break;
         }
     }
 
@@ -443,7 +539,7 @@ fn parse_block_entity(mut nbt: HashMap<String, Value>, tag_path: &str, region_si
     nbt.remove("Pos");
     be.tags = nbt;
 
-    return Ok((be, pos));
+    return Ok(Some((be, pos)));
 }
 
 #[allow(dead_code)]
@@ -578,6 +674,50 @@ impl Schematic {
         return Ok(block_data);
     }
 
+    /// Encode biomes of all regions into a biome palette and varint-encoded data. For per-column
+    /// biomes(v2), the lowest biome in each column is used. Positions without biome are filled
+    /// with the default biome.
+    fn encode_biomes(&self, shape: [i32; 3], per_block: bool) -> (HashMap<String, Value>, Vec<i8>) {
+        let mut lut: [Option<u16>; 256] = [None; 256];
+        let mut pal = HashMap::new();
+        let mut data = Vec::with_capacity(if per_block { self.volume() as usize } else { (shape[0] * shape[2]) as usize });
+        let mut encode = |biome: Biome| {
+            let index = match lut[biome as usize] {
+                Some(idx) => idx,
+                None => {
+                    let idx = pal.len() as u16;
+                    pal.insert(format!("minecraft:{}", biome), Value::Int(idx as i32));
+                    lut[biome as usize] = Some(idx);
+                    idx
+                }
+            };
+            for value in &encode_single_block(index) {
+                data.push(*value);
+                if *value >= 0 {
+                    break;
+                }
+            }
+        };
+
+        if per_block {
+            for y in 0..shape[1] {
+                for z in 0..shape[2] {
+                    for x in 0..shape[0] {
+                        encode(self.first_biome_at([x, y, z]).unwrap_or_default());
+                    }
+                }
+            }
+        } else {
+            for z in 0..shape[2] {
+                for x in 0..shape[0] {
+                    let biome = (0..shape[1]).find_map(|y| self.first_biome_at([x, y, z]));
+                    encode(biome.unwrap_or_default());
+                }
+            }
+        }
+        return (pal, data);
+    }
+
     fn save_block_entities_v2(&self, shape: [i32; 3]) -> Result<Vec<Value>, Error> {
         let mut be_list;
         {
@@ -617,6 +757,12 @@ impl Schematic {
 
     /// Save `.schem` v2 to nbt
     pub fn to_nbt_world_edit_13_v2(&self, md: WE13MetaData, option: &WorldEdit13SaveOption) -> Result<HashMap<String, Value>, Error> {
+        let (mut root, block_data) = self.to_nbt_world_edit_13_v2_without_block_data(md, option)?;
+        root.insert("BlockData".to_string(), Value::ByteArray(fastnbt::ByteArray::new(block_data.encode()?)));
+        return Ok(root);
+    }
+
+    fn to_nbt_world_edit_13_v2_without_block_data(&self, md: WE13MetaData, option: &WorldEdit13SaveOption) -> Result<(HashMap<String, Value>, BlockDataEncoder), Error> {
         let mut root = HashMap::new();
         // metadata
         Self::write_metadata_v2(&mut root, &md);
@@ -637,23 +783,41 @@ impl Schematic {
         let shape = self.shape();
         Self::write_shape_v2(&mut root, shape)?;
 
-        // block data
-        {
-            let block_data = self.save_block_data_v2(shape, &luts_of_block_idx, background_blk_index)?;
-            root.insert("BlockData".to_string(), Value::ByteArray(fastnbt::ByteArray::new(block_data)));
-        }
-
         // block entities
         {
             let be_list = self.save_block_entities_v2(shape)?;
             root.insert("BlockEntities".to_string(), Value::List(be_list));
         }
 
-        return Ok(root);
+        // biomes
+        if self.has_biomes() {
+            let (pal, data) = self.encode_biomes(shape, false);
+            root.insert("BiomePaletteMax".to_string(), Value::Int(pal.len() as i32));
+            root.insert("BiomePalette".to_string(), Value::Compound(pal));
+            root.insert("BiomeData".to_string(), Value::ByteArray(fastnbt::ByteArray::new(data)));
+        }
+
+        let block_data = BlockDataEncoder {
+            schem: self,
+            shape,
+            luts_of_block_idx,
+            background_blk_index,
+        };
+        return Ok((root, block_data));
     }
 
     /// Save `.schem` v3 to nbt
     pub fn to_nbt_world_edit_13_v3(&self, md: WE13MetaData, option: &WorldEdit13SaveOption) -> Result<HashMap<String, Value>, Error> {
+        let (mut root, block_data) = self.to_nbt_world_edit_13_v3_without_block_data(md, option)?;
+        if let Some(Value::Compound(tag_schem)) = root.get_mut("Schematic") {
+            if let Some(Value::Compound(tag_blocks)) = tag_schem.get_mut("Blocks") {
+                tag_blocks.insert("Data".to_string(), Value::ByteArray(fastnbt::ByteArray::new(block_data.encode()?)));
+            }
+        }
+        return Ok(root);
+    }
+
+    fn to_nbt_world_edit_13_v3_without_block_data(&self, md: WE13MetaData, option: &WorldEdit13SaveOption) -> Result<(HashMap<String, Value>, BlockDataEncoder), Error> {
         let mut tag_schem = HashMap::new();
         // metadata
         Self::write_metadata_v3(&mut tag_schem, &md);
@@ -673,22 +837,31 @@ impl Schematic {
         let shape = self.shape();
         Self::write_shape_v2(&mut tag_schem, shape)?;
 
-        // block data
-        {
-            let block_data = self.save_block_data_v2(shape, &luts_of_block_idx, background_blk_index)?;
-            tag_blocks.insert("Data".to_string(), Value::ByteArray(fastnbt::ByteArray::new(block_data)));
-        }
-
         // block entities
         {
             let be_list = self.save_block_entities_v2(shape)?;
             tag_blocks.insert("BlockEntities".to_string(), Value::List(be_list));
         }
         tag_schem.insert("Blocks".to_string(), Value::Compound(tag_blocks));
+
+        // biomes
+        if self.has_biomes() {
+            let (pal, data) = self.encode_biomes(shape, true);
+            let mut tag_biomes = HashMap::new();
+            tag_biomes.insert("Palette".to_string(), Value::Compound(pal));
+            tag_biomes.insert("Data".to_string(), Value::ByteArray(fastnbt::ByteArray::new(data)));
+            tag_schem.insert("Biomes".to_string(), Value::Compound(tag_biomes));
+        }
         let mut root = HashMap::new();
         root.insert("Schematic".to_string(), Value::Compound(tag_schem));
 
-        return Ok(root);
+        let block_data = BlockDataEncoder {
+            schem: self,
+            shape,
+            luts_of_block_idx,
+            background_blk_index,
+        };
+        return Ok((root, block_data));
     }
 
     /// Save `.schem` to nbt
@@ -760,6 +933,221 @@ impl Schematic {
 }
 
 
+/// Everything needed to encode `BlockData`, so that it can be encoded only when it's written
+struct BlockDataEncoder<'a> {
+    schem: &'a Schematic,
+    shape: [i32; 3],
+    luts_of_block_idx: Vec<Vec<usize>>,
+    background_blk_index: u16,
+}
+
+impl BlockDataEncoder<'_> {
+    fn encode(&self) -> Result<Vec<i8>, Error> {
+        return self.schem.save_block_data_v2(self.shape, &self.luts_of_block_idx, self.background_blk_index);
+    }
+}
+
+/// Compound of `.schem` that is read with `BlockData`(v2) or `Schematic/Blocks/Data`(v3)
+/// decoded while reading, other tags are kept as `Value`.
+struct StreamedCompound {
+    tags: HashMap<String, Value>,
+    block_data: Option<Result<Vec<u16>, Error>>,
+    children: HashMap<String, StreamedCompound>,
+}
+
+struct StreamedCompoundSeed {
+    tag_path: String,
+}
+
+impl<'de> Deserialize<'de> for StreamedCompound {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        return StreamedCompoundSeed { tag_path: String::new() }.deserialize(deserializer);
+    }
+}
+
+impl StreamedCompoundSeed {
+    fn is_block_data(&self, key: &str) -> bool {
+        return (self.tag_path.is_empty() && key == "BlockData")
+            || (self.tag_path == "/Schematic/Blocks" && key == "Data");
+    }
+
+    fn is_child(&self, key: &str) -> bool {
+        return (self.tag_path.is_empty() && key == "Schematic")
+            || (self.tag_path == "/Schematic" && key == "Blocks");
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for StreamedCompoundSeed {
+    type Value = StreamedCompound;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        return deserializer.deserialize_map(self);
+    }
+}
+
+impl<'de> Visitor<'de> for StreamedCompoundSeed {
+    type Value = StreamedCompound;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        return write!(formatter, "a compound at {}", self.tag_path);
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut result = StreamedCompound {
+            tags: HashMap::new(),
+            block_data: None,
+            children: HashMap::new(),
+        };
+        while let Some(key) = map.next_key::<String>()? {
+            let tag_path = format!("{}/{}", self.tag_path, key);
+            if self.is_block_data(&key) {
+                let decoded = map.next_value_seed(BlockDataSeed { tag_path })?;
+                result.block_data = Some(decoded);
+            } else if self.is_child(&key) {
+                let child = map.next_value_seed(StreamedCompoundSeed { tag_path })?;
+                result.children.insert(key, child);
+            } else {
+                let value: Value = map.next_value()?;
+                result.tags.insert(key, value);
+            }
+        }
+        return Ok(result);
+    }
+}
+
+struct BlockDataSeed {
+    tag_path: String,
+}
+
+impl<'de> DeserializeSeed<'de> for BlockDataSeed {
+    type Value = Result<Vec<u16>, Error>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        let tag_path = self.tag_path;
+        return common::deserialize_nbt_array(deserializer, common::BYTE_ARRAY_TOKEN,
+                                             |bytes| Region::decode_block_data(bytes.iter().copied(), &tag_path));
+    }
+}
+
+/// Serialize a compound, and insert `BlockData` encoded only when it's written at `path`
+struct CompoundWithBlockData<'a> {
+    tags: &'a HashMap<String, Value>,
+    path: &'a [&'static str],
+    block_data: &'a BlockDataEncoder<'a>,
+}
+
+impl Serialize for CompoundWithBlockData<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.tags.len() + 1))?;
+        for (key, value) in self.tags {
+            match value {
+                Value::Compound(child) if self.path.len() > 1 && key == self.path[0] => {
+                    map.serialize_entry(key, &CompoundWithBlockData {
+                        tags: child,
+                        path: &self.path[1..],
+                        block_data: self.block_data,
+                    })?;
+                }
+                _ => map.serialize_entry(key, value)?,
+            }
+        }
+        if self.path.len() == 1 {
+            let bytes: Vec<u8> = self.block_data.encode().map_err(serde::ser::Error::custom)?
+                .into_iter().map(|b| b as u8).collect();
+            map.serialize_entry(self.path[0], &common::NbtArrayBytes { token: common::BYTE_ARRAY_TOKEN, bytes: &bytes })?;
+        }
+        return map.end();
+    }
+}
+
+impl Schematic {
+    /// Load `.schem` from reader without building the whole nbt tree. The 3d block array is
+    /// decoded while reading.
+    pub fn from_world_edit_13_reader_streaming(src: &mut dyn std::io::Read, option: &WorldEdit13LoadOption) -> Result<(Schematic, WE13MetaData), Error> {
+        let mut root = match fastnbt::from_reader::<_, StreamedCompound>(src) {
+            Ok(r) => r,
+            Err(e) => return Err(Error::NBTReadError(e)),
+        };
+
+        let mut schem = Schematic::new();
+        if let Some(mut tag_schem) = root.children.remove("Schematic") {//v3
+            let mut tag_blocks = match tag_schem.children.remove("Blocks") {
+                Some(b) => b,
+                None => return Err(Error::TagMissing("/Schematic/Blocks".to_string())),
+            };
+            let block_data = match tag_blocks.block_data.take() {
+                Some(data) => data?,
+                None => return Err(Error::TagMissing("/Schematic/Blocks/Data".to_string())),
+            };
+            tag_schem.tags.insert("Blocks".to_string(), Value::Compound(tag_blocks.tags));
+
+            let we13 = parse_metadata(&tag_schem.tags, "/Schematic", option)?;
+            schem.metadata = MetaDataIR::from_world_edit13(&we13);
+            schem.regions.push(Region::from_world_edit_13_v3_with_block_data(tag_schem.tags, block_data, option)?);
+            return Ok((schem, we13));
+        }
+
+        let block_data = match root.block_data.take() {
+            Some(data) => data?,
+            None => return Err(Error::TagMissing("/BlockData".to_string())),
+        };
+        let we13 = parse_metadata(&root.tags, "", option)?;
+        schem.metadata = MetaDataIR::from_world_edit13(&we13);
+        schem.regions.push(Region::from_world_edit_13_v2_with_block_data(root.tags, block_data, option)?);
+        return Ok((schem, we13));
+    }
+
+    /// Load `.schem` from file, see `from_world_edit_13_reader_streaming`
+    pub fn from_world_edit_13_file_streaming(filename: &str, option: &WorldEdit13LoadOption) -> Result<(Schematic, WE13MetaData), Error> {
+        let mut file = match File::open(filename) {
+            Ok(f) => f,
+            Err(e) => return Err(Error::FileOpenError(e)),
+        };
+        let mut decoder = GzDecoder::new(&mut file);
+        return Self::from_world_edit_13_reader_streaming(&mut decoder, option);
+    }
+
+    /// Save `.schem` to writer without building the whole nbt tree. The 3d block array is encoded
+    /// only when it's written.
+    pub fn save_world_edit_13_writer_streaming(&self, dest: &mut dyn std::io::Write, option: &WorldEdit13SaveOption) -> Result<(), Error> {
+        let md = self.metadata_world_edit_13()?;
+        let ((root, block_data), path): ((HashMap<String, Value>, BlockDataEncoder), &[&'static str]) = match md.version {
+            2 => (self.to_nbt_world_edit_13_v2_without_block_data(md, option)?, &["BlockData"]),
+            3 => (self.to_nbt_world_edit_13_v3_without_block_data(md, option)?, &["Schematic", "Blocks", "Data"]),
+            _ => return Err(Error::UnsupportedWorldEdit13Version {
+                version: md.version,
+                supported_versions: Self::supported_world_edit_13_versions(),
+            }),
+        };
+        let mut encoder = GzBuilder::new()
+            .comment("Generated by mc_schem")
+            .write(dest, option.compress_level);
+
+        let res: Result<(), fastnbt::error::Error> = fastnbt::to_writer(&mut encoder, &CompoundWithBlockData {
+            tags: &root,
+            path,
+            block_data: &block_data,
+        });
+        if let Err(e) = res {
+            return Err(Error::NBTWriteError(e));
+        }
+        if let Err(e) = encoder.finish() {
+            return Err(Error::NBTWriteError(e.into()));
+        }
+        return Ok(());
+    }
+
+    /// Save `.schem` to file, see `save_world_edit_13_writer_streaming`
+    pub fn save_world_edit_13_file_streaming(&self, filename: &str, option: &WorldEdit13SaveOption) -> Result<(), Error> {
+        let mut file = match File::create(filename) {
+            Ok(f) => f,
+            Err(e) => return Err(Error::FileCreateError(e)),
+        };
+        return self.save_world_edit_13_writer_streaming(&mut file, option);
+    }
+}
+
+
 fn encode_single_block(value: u16) -> [i8; 8] {
     // let index = index as i32;
     //
@@ -808,7 +1196,7 @@ fn test_schem_encode_decoding() {
                 break;
             }
         }
-        let decoded_id = parse_single_block(&code[0..code_length]);
-        assert_eq!(id, decoded_id);
+        let decoded = Region::decode_block_data(code[0..code_length].iter().map(|b| *b as u8), "").unwrap();
+        assert_eq!(decoded, vec![id as u16]);
     }
 }
\ No newline at end of file
@@ -3,17 +3,20 @@ use std::fmt::{Display, Formatter};
 use std::ops::Range;
 use std::time;
 
-use fastnbt::Value;
+use fastnbt::{ByteArray, IntArray, LongArray, Value};
 use math::round::{ceil, floor};
 
 use crate::{unwrap_opt_tag, unwrap_tag};
 use crate::biome::Biome;
+use crate::block::Block;
 use crate::error::Error;
+use crate::old_block::{get_valid_damage_values, OLD_BLOCK_ID};
+use crate::player::EntityBox;
 use crate::region::{Light, PendingTick, PendingTickInfo, WorldSlice};
-use crate::schem::common;
+use crate::schem::{common, litematica};
 use crate::schem::common::ceil_up_to;
-use crate::schem::id_of_nbt_tag;
-use crate::world::{Chunk, ChunkPos, ChunkRefAbsolutePos, ChunkRefRelativePos, ChunkStatus, NBTWithSource, SubChunk};
+use crate::schem::{DataVersion, id_of_nbt_tag};
+use crate::world::{Chunk, ChunkPos, ChunkRefAbsolutePos, ChunkRefRelativePos, ChunkStatus, HeightmapKind, MCASaveOption, NBTWithSource, SubChunk};
 
 impl Display for ChunkStatus {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
@@ -67,18 +70,64 @@ impl ChunkStatus {
         }
         return None;
     }
+
+    /// Parse status of chunks saved before 1.18, including the names used before 1.14
+    fn from_legacy_str(str: &str) -> Option<ChunkStatus> {
+        if let Some(status) = Self::from_str(str) {
+            return Some(status);
+        }
+        let str = str.strip_prefix("minecraft:").unwrap_or(str);
+        return match str {
+            "base" => Some(ChunkStatus::Surface),
+            "carved" | "liquid_carved" | "liquid_carvers" => Some(ChunkStatus::Carvers),
+            "decorated" => Some(ChunkStatus::Features),
+            "lighted" => Some(ChunkStatus::Light),
+            "mobs_spawned" | "finalized" | "heightmaps" => Some(ChunkStatus::Spawn),
+            "fullchunk" | "postprocessed" => Some(ChunkStatus::Full),
+            _ => None,
+        };
+    }
+
+    /// Status name in chunks saved by the game of `data_version`. Statuses were renamed in 18w43a
+    /// (1.14), and `initialize_light` is added in 1.20.
+    fn legacy_name(&self, data_version: i32) -> String {
+        if data_version >= DataVersion::Snapshot_18w43a as i32 {
+            if *self == ChunkStatus::InitializeLight {
+                return ChunkStatus::Light.to_string();
+            }
+            return self.to_string();
+        }
+        return match self {
+            ChunkStatus::Empty | ChunkStatus::StructureStarts | ChunkStatus::StructureReferences
+            | ChunkStatus::Biomes => "empty",
+            ChunkStatus::Noise | ChunkStatus::Surface => "base",
+            ChunkStatus::Carvers => "carved",
+            ChunkStatus::Features => "decorated",
+            ChunkStatus::InitializeLight | ChunkStatus::Light => "lighted",
+            ChunkStatus::Spawn => "mobs_spawned",
+            ChunkStatus::Full => "postprocessed",
+        }.to_string();
+    }
 }
 
 fn parse_pending_tick(nbt: &HashMap<String, Value>, is_block_tick: bool, tag_path: &str) -> Result<([i32; 3], PendingTick), Error> {
     let pos = common::parse_size_compound(nbt, tag_path, true)?;
-    let id = unwrap_opt_tag!(nbt.get("i"),String,"".to_string(),format!("{tag_path}/i"));
-    let p = *unwrap_opt_tag!(nbt.get("p"),Int,0,format!("{tag_path}/p"));
+    let id = match nbt.get("i") {
+        // number ids are used before 1.8
+        Some(Value::Int(id)) => format!("minecraft:{}", OLD_BLOCK_ID[(*id & 0xFF) as usize]),
+        tag => unwrap_opt_tag!(tag,String,"".to_string(),format!("{tag_path}/i")).clone(),
+    };
+    // priority is added in 1.8
+    let p = match nbt.get("p") {
+        Some(tag) => *unwrap_tag!(tag,Int,0,format!("{tag_path}/p")),
+        None => 0,
+    };
     let t = *unwrap_opt_tag!(nbt.get("t"),Int,0,format!("{tag_path}/t"));
 
     let info = if is_block_tick {
-        PendingTickInfo::Block { id: id.clone() }
+        PendingTickInfo::Block { id }
     } else {
-        PendingTickInfo::Fluid { id: id.clone() }
+        PendingTickInfo::Fluid { id }
     };
     let tick = PendingTick {
         priority: p,
@@ -94,6 +143,7 @@ impl Chunk {
     pub fn new() -> Chunk {
         return Chunk {
             time_stamp: time::SystemTime::now().duration_since(time::UNIX_EPOCH).unwrap().as_secs() as u32,
+            data_version: DataVersion::new() as i32,
             status: ChunkStatus::Empty,
             last_update: 0,
             inhabited_time: 0,
@@ -104,7 +154,49 @@ impl Chunk {
             pending_ticks: HashMap::new(),
             file_region: "NoFile".to_string(),
             file_entities: "NoFile".to_string(),
+            unparsed_tags: HashMap::new(),
+            warnings: Vec::new(),
+            dirty: false,
+        };
+    }
+
+    /// Create a chunk filled with air, whose sub chunks cover `y_range`. The chunk is marked as
+    /// fully generated, so that the game won't generate it again.
+    pub fn with_y_range(y_range: Range<i32>) -> Chunk {
+        let mut result = Chunk::new();
+        result.status = ChunkStatus::Full;
+        result.is_light_on = false;
+        result.dirty = true;
+        let air = Block::air();
+        for y in y_range.start.div_euclid(16)..(y_range.end + 15).div_euclid(16) {
+            result.sub_chunks.insert(y as i8, SubChunk::filled_with(&air, Biome::plains));
+        }
+        return result;
+    }
+
+    /// Mark the chunk as modified. Light and heightmaps are outdated after modification, so they
+    /// are dropped. Light will be computed by the game again, and heightmaps are computed when
+    /// the chunk is saved.
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+        self.is_light_on = false;
+        self.unparsed_tags.remove("Heightmaps");
+        self.unparsed_tags.remove("HeightMap");
+    }
+
+    /// Set block at absolute position `a_pos`, x and z are taken modulo 16. Returns `Err(())` if
+    /// the sub chunk doesn't exist, or its palette exceeds 65535.
+    pub fn set_block_at(&mut self, a_pos: [i32; 3], block: &Block) -> Result<(), ()> {
+        let sect_y = match i8::try_from(a_pos[1].div_euclid(16)) {
+            Ok(y) => y,
+            Err(_) => return Err(()),
+        };
+        let sub_chunk = match self.sub_chunks.get_mut(&sect_y) {
+            Some(s) => s,
+            None => return Err(()),
         };
+        let r_pos = [a_pos[0].rem_euclid(16), a_pos[1].rem_euclid(16), a_pos[2].rem_euclid(16)];
+        return sub_chunk.set_block(r_pos, block);
     }
 
     pub fn height(&self) -> i32 {
@@ -119,14 +211,23 @@ impl Chunk {
         let path_in_saves = format!("{region_source_filename}/[{},{}]",
                                     chunk_pos.local_coordinate().x,
                                     chunk_pos.local_coordinate().z);
+        let data_version = match region_nbt.get("DataVersion") {
+            Some(tag) => *unwrap_tag!(tag,Int,0,format!("{path_in_saves}/DataVersion")),
+            // DataVersion is added in 15w32a (1.9)
+            None => i32::MIN,
+        };
+        if data_version < DataVersion::Snapshot_21w43a as i32 {
+            return Self::from_nbt_pre_1_18(region_nbt, region_source_filename, entity_nbt_data, chunk_pos);
+        }
+
         let mut result = Chunk::new();
         result.file_region = region_source_filename.to_string();
+        result.data_version = data_version;
         // chunk status
         {
-            let status: ChunkStatus;
             let str = unwrap_opt_tag!(region_nbt.get("Status"),String,"".to_string(),format!("{path_in_saves}/Status"));
             match ChunkStatus::from_str(str) {
-                Some(s) => status = s,
+                Some(s) => result.status = s,
                 None => {
                     return Err(Error::InvalidChunkStatus {
                         tag_path: format!("{path_in_saves}/Status"),
@@ -134,7 +235,6 @@ impl Chunk {
                     });
                 }
             };
-            result.status = status;
         }
         result.last_update = *unwrap_opt_tag!(region_nbt.get("LastUpdate"),Long,0,format!("{path_in_saves}/LastUpdate"));
         result.inhabited_time = *unwrap_opt_tag!(region_nbt.get("InhabitedTime"),Long,0,format!("{path_in_saves}/InhabitedTime"));
@@ -163,119 +263,336 @@ impl Chunk {
             }
         }
 
-        let pos_lb = chunk_pos.block_pos_lower_bound();
-        let pos_ub = chunk_pos.block_pos_upper_bound();
         // block entities
         {
             let be_list_tag = format!("{path_in_saves}/block_entities");
-            let mut be_list = unwrap_opt_tag!(region_nbt.remove("block_entities"),List,vec![],be_list_tag);
+            let be_list = unwrap_opt_tag!(region_nbt.remove("block_entities"),List,vec![],be_list_tag);
+            result.parse_block_entities(be_list, &be_list_tag, chunk_pos)?;
+        }
+        // pending ticks
+        {
+            let tag_path_block_ticks = format!("{path_in_saves}/block_ticks");
+            let tag_block_ticks = unwrap_opt_tag!(region_nbt.get("block_ticks"),List,vec![],tag_path_block_ticks);
+
+            let tag_path_fluid_ticks = format!("{path_in_saves}/fluid_ticks");
+            let tag_fluid_ticks = unwrap_opt_tag!(region_nbt.get("fluid_ticks"),List,vec![],tag_path_fluid_ticks);
+            result.parse_pending_ticks(tag_block_ticks, &tag_path_block_ticks, tag_fluid_ticks, &tag_path_fluid_ticks)?;
+        }
 
-            let pos_lb = [pos_lb[0], result.y_range().start, pos_lb[1]];
-            let pos_ub = [pos_ub[0], result.y_range().end - 1, pos_ub[1]];
 
-            result.block_entities.reserve(be_list.len());
+        // keep other tags
+        for key in ["DataVersion", "xPos", "yPos", "zPos", "Status", "LastUpdate", "InhabitedTime", "isLightOn",
+            "sections", "block_entities", "block_ticks", "fluid_ticks"] {
+            region_nbt.remove(key);
+        }
+        result.unparsed_tags = region_nbt;
 
-            for (idx, nbt) in be_list.iter_mut().enumerate() {
-                let mut temp = Value::Byte(0);
-                std::mem::swap(&mut temp, nbt);
-                let be_nbt_tag = format!("{path_in_saves}/block_entities/[{idx}]");
-                let be_nbt = unwrap_tag!(temp,Compound,HashMap::new(),be_nbt_tag);
-                let (pos, be) = common::parse_block_entity_nocheck(be_nbt, &be_nbt_tag, true)?;
-                if !common::check_pos_in_range(pos, pos_lb, pos_ub) {
-                    return Err(Error::BlockPosOutOfRange {
-                        tag_path: be_nbt_tag,
-                        pos,
-                        lower_bound: pos_lb,
-                        upper_bound: pos_ub,
+        // entities
+        if let Some(entity_nbt_data) = entity_nbt_data {
+            result.parse_entity_file(entity_nbt_data, chunk_pos)?;
+        }
+
+        return Ok(result);
+    }
+
+    /// Parse chunks saved before 21w43a (1.18), whose tags are wrapped in `Level`. Before 20w45a
+    /// (1.17), entities are stored in `Level` instead of entity files. Tags in `Level` that are
+    /// not parsed are kept in `unparsed_tags`.
+    ///
+    /// Before 17w47a (1.13), blocks are stored as number ids and damages, and they are converted by
+    /// `Block::from_old`. Chunks of McRegion files (Beta 1.3 to 1.1) have no sections and are only
+    /// 128 blocks high, the rest of them is filled with air.
+    fn from_nbt_pre_1_18(mut region_nbt: HashMap<String, Value>, region_source_filename: &str,
+                         entity_nbt_data: Option<NBTWithSource>, chunk_pos: &ChunkPos) -> Result<Chunk, Error> {
+        let path_in_saves = format!("{region_source_filename}/[{},{}]",
+                                    chunk_pos.local_coordinate().x,
+                                    chunk_pos.local_coordinate().z);
+        let mut result = Chunk::new();
+        result.file_region = region_source_filename.to_string();
+
+        let level_path = format!("{path_in_saves}/Level");
+        let mut level = unwrap_opt_tag!(region_nbt.remove("Level"),Compound,HashMap::new(),level_path);
+        result.data_version = match region_nbt.get("DataVersion") {
+            Some(tag) => *unwrap_tag!(tag,Int,0,format!("{path_in_saves}/DataVersion")),
+            None if level.contains_key("Blocks") => DataVersion::Java_1_1 as i32,
+            None => DataVersion::Java_1_8_9 as i32,
+        };
+        let number_id = result.data_version < DataVersion::Snapshot_17w47a as i32;
+        // chunk status, chunks before 1.13 only record whether the terrain is populated
+        if let Some(tag) = level.get("Status") {
+            let str = unwrap_tag!(tag,String,"".to_string(),format!("{level_path}/Status"));
+            match ChunkStatus::from_legacy_str(str) {
+                Some(s) => result.status = s,
+                None => {
+                    return Err(Error::InvalidChunkStatus {
+                        tag_path: format!("{level_path}/Status"),
+                        chunk_status: str.to_string(),
                     });
                 }
-                if result.block_entities.contains_key(&pos) {
-                    return Err(Error::MultipleBlockEntityInOnePos {
-                        pos,
-                        latter_tag_path: be_nbt_tag,
-                    });
+            };
+        } else {
+            let populated = match level.get("TerrainPopulated") {
+                Some(tag) => *unwrap_tag!(tag,Byte,0,format!("{level_path}/TerrainPopulated")) != 0,
+                None => true,
+            };
+            result.status = if populated { ChunkStatus::Full } else { ChunkStatus::Carvers };
+        }
+        result.last_update = *unwrap_opt_tag!(level.get("LastUpdate"),Long,0,format!("{level_path}/LastUpdate"));
+        if let Some(tag) = level.get("InhabitedTime") {
+            result.inhabited_time = *unwrap_tag!(tag,Long,0,format!("{level_path}/InhabitedTime"));
+        }
+        for key in ["isLightOn", "LightPopulated"] {
+            if let Some(tag) = level.get(key) {
+                result.is_light_on = *unwrap_tag!(tag,Byte,1,format!("{level_path}/{key}")) != 0;
+            }
+        }
+
+        // sections, missing sections are filled with air
+        if let Some(sections) = level.get("Sections") {
+            let sections = unwrap_tag!(sections,List,vec![],format!("{level_path}/Sections"));
+            for (idx, nbt) in sections.iter().enumerate() {
+                let path = format!("{level_path}/Sections[{idx}]");
+                let sect_nbt = unwrap_tag!(nbt,Compound,HashMap::new(),path);
+                let parsed = if number_id {
+                    parse_section_number_id(sect_nbt, &path, &mut result.warnings)?
+                } else {
+                    parse_section_pre_1_18(sect_nbt, &path, result.data_version)?
+                };
+                if let Some((sub_chunk, y)) = parsed {
+                    result.sub_chunks.insert(y, sub_chunk);
                 }
-                result.block_entities.insert(pos, be);
             }
         }
+        if level.contains_key("Blocks") {
+            result.parse_mcregion_blocks(&level, &level_path)?;
+            // heightmap of McRegion is a byte array, and it's invalid in Anvil
+            level.remove("HeightMap");
+        }
+        {
+            let y_min = result.sub_chunks.keys().next().map_or(0, |y| (*y).min(0));
+            let y_max = result.sub_chunks.keys().next_back().map_or(15, |y| (*y).max(15));
+            let air = Block::air();
+            for y in y_min..=y_max {
+                result.sub_chunks.entry(y).or_insert_with(|| SubChunk::filled_with(&air, Biome::the_void));
+            }
+        }
+        // biomes
+        if let Some(biomes) = level.get("Biomes") {
+            let path = format!("{level_path}/Biomes");
+            // biomes are stored in bytes before 1.13
+            if let Value::ByteArray(biomes) = biomes {
+                let biomes: Vec<i32> = biomes.iter().map(|b| *b as u8 as i32).collect();
+                result.parse_legacy_biomes(&biomes, &path)?;
+            } else {
+                let biomes = unwrap_tag!(biomes,IntArray,IntArray::new(vec![]),path);
+                result.parse_legacy_biomes(biomes, &path)?;
+            }
+        }
+
+        // block entities
+        {
+            let be_list_tag = format!("{level_path}/TileEntities");
+            let be_list = match level.remove("TileEntities") {
+                Some(be_list) => unwrap_tag!(be_list,List,vec![],be_list_tag),
+                None => vec![],
+            };
+            result.parse_block_entities(be_list, &be_list_tag, chunk_pos)?;
+        }
         // pending ticks
         {
-            //let mut tick_tag_record = HashMap::new();
-            let tag_path_block_ticks = format!("{path_in_saves}/block_ticks");
-            let tag_block_ticks = unwrap_opt_tag!(region_nbt.get("block_ticks"),List,vec![],tag_path_block_ticks);
+            let mut tick_lists = Vec::with_capacity(2);
+            for key in ["TileTicks", "LiquidTicks"] {
+                let path = format!("{level_path}/{key}");
+                let list = match level.get(key) {
+                    Some(list) => unwrap_tag!(list,List,vec![],path).as_slice(),
+                    None => &[],
+                };
+                tick_lists.push((list, path));
+            }
+            result.parse_pending_ticks(tick_lists[0].0, &tick_lists[0].1, tick_lists[1].0, &tick_lists[1].1)?;
+        }
+        // entities
+        if let Some(entity_list) = level.remove("Entities") {
+            let entity_path = format!("{level_path}/Entities");
+            let entity_list = unwrap_tag!(entity_list,List,vec![],entity_path);
+            result.file_entities = region_source_filename.to_string();
+            result.parse_entities(entity_list, &entity_path, chunk_pos)?;
+        }
+        if let Some(entity_nbt_data) = entity_nbt_data {
+            result.parse_entity_file(entity_nbt_data, chunk_pos)?;
+        }
 
+        // keep other tags
+        for key in ["xPos", "zPos", "Status", "LastUpdate", "InhabitedTime", "isLightOn", "Sections", "Biomes",
+            "TileTicks", "LiquidTicks", "TerrainPopulated", "LightPopulated", "Blocks", "Data", "SkyLight", "BlockLight"] {
+            level.remove(key);
+        }
+        result.unparsed_tags = level;
 
-            let tag_path_fluid_ticks = format!("{path_in_saves}/fluid_ticks");
-            let tag_fluid_ticks = unwrap_opt_tag!(region_nbt.get("fluid_ticks"),List,vec![],tag_path_fluid_ticks);
-            result.pending_ticks.reserve(tag_block_ticks.len() + tag_fluid_ticks.len());
-            for (is_block, (list, tag_path)) in [(tag_block_ticks, tag_path_block_ticks),
-                (tag_fluid_ticks, tag_path_fluid_ticks)].iter().enumerate() {
-                let is_block = is_block == 0;
-                for (idx, nbt) in list.iter().enumerate() {
-                    let cur_tag_path = format!("{tag_path}[{idx}]");
-                    let nbt = unwrap_tag!(nbt,Compound,HashMap::new(),cur_tag_path);
-                    let (pos, tick) = parse_pending_tick(nbt, is_block, &cur_tag_path)?;
-
-                    //if result.pending_ticks.contains_key(&pos) {
-                    // let former = &result.pending_ticks[&pos];
-                    // // If the pending tick is repeated, ignore the latter
-                    // if &tick == former {
-                    //     continue;
-                    // }
-                    // debug_assert!(tick_tag_record.contains_key(&pos));
-                    // return Err(Error::MultiplePendingTickInOnePos {
-                    //     pos,
-                    //     former_tag_path: tick_tag_record.remove(&pos).unwrap(),
-                    //     latter_tag_path: cur_tag_path,
-                    // });
-                    //}
-                    if let Some(dst) = result.pending_ticks.get_mut(&pos) {
-                        dst.push(tick);
-                    } else {
-                        result.pending_ticks.insert(pos, vec![tick]);
-                    }
-                    //tick_tag_record.insert(pos, cur_tag_path);
+        return Ok(result);
+    }
+
+    /// Parse block entities in `be_list`, and check that they are inside this chunk
+    fn parse_block_entities(&mut self, mut be_list: Vec<Value>, be_list_tag: &str, chunk_pos: &ChunkPos) -> Result<(), Error> {
+        let pos_lb = chunk_pos.block_pos_lower_bound();
+        let pos_ub = chunk_pos.block_pos_upper_bound();
+        let pos_lb = [pos_lb[0], self.y_range().start, pos_lb[1]];
+        let pos_ub = [pos_ub[0], self.y_range().end - 1, pos_ub[1]];
+
+        self.block_entities.reserve(be_list.len());
+
+        for (idx, nbt) in be_list.iter_mut().enumerate() {
+            let mut temp = Value::Byte(0);
+            std::mem::swap(&mut temp, nbt);
+            let be_nbt_tag = format!("{be_list_tag}/[{idx}]");
+            let be_nbt = unwrap_tag!(temp,Compound,HashMap::new(),be_nbt_tag);
+            let (pos, be) = common::parse_block_entity_nocheck(be_nbt, &be_nbt_tag, true)?;
+            if !common::check_pos_in_range(pos, pos_lb, pos_ub) {
+                return Err(Error::BlockPosOutOfRange {
+                    tag_path: be_nbt_tag,
+                    pos,
+                    lower_bound: pos_lb,
+                    upper_bound: pos_ub,
+                });
+            }
+            if self.block_entities.contains_key(&pos) {
+                return Err(Error::MultipleBlockEntityInOnePos {
+                    pos,
+                    latter_tag_path: be_nbt_tag,
+                });
+            }
+            self.block_entities.insert(pos, be);
+        }
+        return Ok(());
+    }
+
+    fn parse_pending_ticks(&mut self, tag_block_ticks: &[Value], tag_path_block_ticks: &str,
+                           tag_fluid_ticks: &[Value], tag_path_fluid_ticks: &str) -> Result<(), Error> {
+        self.pending_ticks.reserve(tag_block_ticks.len() + tag_fluid_ticks.len());
+        for (is_block, (list, tag_path)) in [(tag_block_ticks, tag_path_block_ticks),
+            (tag_fluid_ticks, tag_path_fluid_ticks)].iter().enumerate() {
+            let is_block = is_block == 0;
+            for (idx, nbt) in list.iter().enumerate() {
+                let cur_tag_path = format!("{tag_path}[{idx}]");
+                let nbt = unwrap_tag!(nbt,Compound,HashMap::new(),cur_tag_path);
+                let (pos, tick) = parse_pending_tick(nbt, is_block, &cur_tag_path)?;
+
+                if let Some(dst) = self.pending_ticks.get_mut(&pos) {
+                    dst.push(tick);
+                } else {
+                    self.pending_ticks.insert(pos, vec![tick]);
                 }
             }
         }
+        return Ok(());
+    }
 
+    fn parse_entity_file(&mut self, entity_nbt_data: NBTWithSource, chunk_pos: &ChunkPos) -> Result<(), Error> {
+        let entity_source_file = entity_nbt_data.source;
+        let mut entity_nbt = entity_nbt_data.nbt;
 
-        // entities
-        if let Some(entity_nbt_data) = entity_nbt_data {
-            let entity_source_file = entity_nbt_data.source;
-            let mut entity_nbt = entity_nbt_data.nbt;
-
-            result.file_entities = entity_source_file.to_string();
-            let entity_path = format!("{entity_source_file}/[{},{}]/Entities",
-                                      chunk_pos.local_coordinate().x,
-                                      chunk_pos.local_coordinate().z);
-            let mut entity_list = unwrap_opt_tag!(entity_nbt.remove("Entities"),List,vec![],entity_path);
-            result.entities.reserve(entity_list.len());
-            for (idx, entity) in entity_list.iter_mut().enumerate() {
-                let cur_path = format!("{entity_path}/[{idx}]");
-                let temp = unwrap_tag!(entity,Compound,HashMap::new(),cur_path);
-                let mut entity = HashMap::new();
-                std::mem::swap(&mut entity, temp);
-
-                let entity = common::parse_entity_litematica(entity, &cur_path)?;
-                // check for position
-                let entity_pos_xz = [entity.block_pos[0], entity.block_pos[2]];
-                for dim in 0..2 {
-                    if entity_pos_xz[dim] < pos_lb[dim] || entity_pos_xz[dim] > pos_ub[dim] {
-                        return Err(Error::BlockPosOutOfRange {
-                            tag_path: format!("{cur_path}/Pos"),
-                            pos: entity.block_pos,
-                            lower_bound: [pos_lb[0], i32::MIN, pos_lb[1]],
-                            upper_bound: [pos_ub[0], i32::MAX, pos_ub[1]],
-                        });
-                    }
+        self.file_entities = entity_source_file.to_string();
+        let entity_path = format!("{entity_source_file}/[{},{}]/Entities",
+                                  chunk_pos.local_coordinate().x,
+                                  chunk_pos.local_coordinate().z);
+        let entity_list = unwrap_opt_tag!(entity_nbt.remove("Entities"),List,vec![],entity_path);
+        return self.parse_entities(entity_list, &entity_path, chunk_pos);
+    }
+
+    /// Parse entities in `entity_list`, and check that they are inside this chunk horizontally
+    fn parse_entities(&mut self, mut entity_list: Vec<Value>, entity_path: &str, chunk_pos: &ChunkPos) -> Result<(), Error> {
+        let pos_lb = chunk_pos.block_pos_lower_bound();
+        let pos_ub = chunk_pos.block_pos_upper_bound();
+        self.entities.reserve(entity_list.len());
+        for (idx, entity) in entity_list.iter_mut().enumerate() {
+            let cur_path = format!("{entity_path}/[{idx}]");
+            let temp = unwrap_tag!(entity,Compound,HashMap::new(),cur_path);
+            let mut entity = HashMap::new();
+            std::mem::swap(&mut entity, temp);
+
+            let entity = EntityBox::from_nbt(entity, &cur_path)?;
+            // check for position
+            let pos = entity.pos();
+            let entity_pos = [pos[0].floor() as i32, pos[1].floor() as i32, pos[2].floor() as i32];
+            let entity_pos_xz = [entity_pos[0], entity_pos[2]];
+            for dim in 0..2 {
+                if entity_pos_xz[dim] < pos_lb[dim] || entity_pos_xz[dim] > pos_ub[dim] {
+                    return Err(Error::BlockPosOutOfRange {
+                        tag_path: format!("{cur_path}/Pos"),
+                        pos: entity_pos,
+                        lower_bound: [pos_lb[0], i32::MIN, pos_lb[1]],
+                        upper_bound: [pos_ub[0], i32::MAX, pos_ub[1]],
+                    });
                 }
+            }
 
-                result.entities.push(entity);
+            self.entities.push(entity);
+        }
+        return Ok(());
+    }
+
+    /// Parse blocks and light of McRegion chunks, which are stored in xzy order without sections
+    fn parse_mcregion_blocks(&mut self, level: &HashMap<String, Value>, level_path: &str) -> Result<(), Error> {
+        let mut arrays: Vec<&[i8]> = Vec::with_capacity(4);
+        for (key, len, required) in [("Blocks", 32768, true), ("Data", 16384, true),
+            ("SkyLight", 16384, false), ("BlockLight", 16384, false)] {
+            let tag_path = format!("{level_path}/{key}");
+            let arr: &[i8] = match level.get(key) {
+                Some(tag) => unwrap_tag!(tag,ByteArray,ByteArray::new(vec![]),tag_path),
+                None if !required => &[],
+                None => return Err(Error::TagMissing(tag_path)),
+            };
+            if !arr.is_empty() && arr.len() != len {
+                return Err(Error::InvalidValue { tag_path, error: format!("The length should be {len}, but found {}", arr.len()) });
+            }
+            arrays.push(arr);
+        }
+        let (blocks, data, sky_light, block_light) = (arrays[0], arrays[1], arrays[2], arrays[3]);
+
+        for sect_y in 0..8 {
+            let mut sub_chunk = SubChunk::new();
+            let mut id_damage = [(0u8, 0u8); 4096];
+            for (counter, dst) in id_damage.iter_mut().enumerate() {
+                let (x, z, y) = (counter % 16, counter / 16 % 16, counter / 256);
+                let src = (sect_y * 16 + y) + z * 128 + x * 2048;
+                *dst = (blocks[src] as u8, nibble_at(data, src));
+                let sl = if sky_light.is_empty() { 15 } else { nibble_at(sky_light, src) };
+                let bl = if block_light.is_empty() { 15 } else { nibble_at(block_light, src) };
+                sub_chunk.sky_block_light_array[counter] = Light::new(sl, bl);
             }
+            set_number_id_blocks(&mut sub_chunk, &id_damage, &format!("{level_path}/Blocks"), &mut self.warnings);
+            self.sub_chunks.insert(sect_y as i8, sub_chunk);
         }
+        return Ok(());
+    }
 
-        return Ok(result);
+    /// Parse numeric biome ids in `Biomes` of chunks saved before 1.18. Before 19w36a (1.15),
+    /// there are 256 biomes, one for each column; since then, biomes are stored in 4x4x4 cells.
+    fn parse_legacy_biomes(&mut self, biomes: &[i32], tag_path: &str) -> Result<(), Error> {
+        if biomes.is_empty() {
+            return Ok(());
+        }
+        if biomes.len() == 256 {
+            for sub_chunk in self.sub_chunks.values_mut() {
+                for (idx, biome) in sub_chunk.biome_array.iter_mut().enumerate() {
+                    let (x, z) = (idx % 4, idx / 4 % 4);
+                    *biome = Biome::from_legacy_id(biomes[z * 4 * 16 + x * 4]);
+                }
+            }
+            return Ok(());
+        }
+        if biomes.len() % 64 != 0 || biomes.len() / 64 > self.sub_chunks.len() {
+            return Err(Error::InvalidValue {
+                tag_path: tag_path.to_string(),
+                error: format!("Biomes should contain 256 ints, or 64 ints for each sub chunk, but found {}", biomes.len()),
+            });
+        }
+        for (sub_chunk, ids) in self.sub_chunks.values_mut().zip(biomes.chunks(64)) {
+            for (biome, id) in sub_chunk.biome_array.iter_mut().zip(ids) {
+                *biome = Biome::from_legacy_id(*id);
+            }
+        }
+        return Ok(());
     }
 
     fn missing_sub_chunks(&self) -> Vec<i8> {
@@ -342,6 +659,185 @@ impl Chunk {
         };
     }
 
+    /// Save chunk into nbt. Returns the nbt stored in region file, and the nbt stored in entities
+    /// file. The latter is `None` if there is no entity in this chunk. Chunks whose `data_version`
+    /// is older than 1.18 are saved in the format of that version.
+    pub fn to_nbt(&self, chunk_pos: &ChunkPos, option: &MCASaveOption) -> Result<(HashMap<String, Value>, Option<HashMap<String, Value>>), Error> {
+        if self.data_version < DataVersion::Snapshot_21w43a as i32 {
+            return self.to_nbt_pre_1_18(chunk_pos, option);
+        }
+        let mut nbt = self.unparsed_tags.clone();
+        let global_pos = chunk_pos.to_global_pos();
+        let min_section_y = self.sub_chunks.keys().next().map_or(0, |y| *y as i32);
+
+        nbt.insert("DataVersion".to_string(), Value::Int(self.data_version));
+        nbt.insert("xPos".to_string(), Value::Int(global_pos.x));
+        nbt.insert("yPos".to_string(), Value::Int(min_section_y));
+        nbt.insert("zPos".to_string(), Value::Int(global_pos.z));
+        nbt.insert("Status".to_string(), Value::String(self.status.to_string()));
+        nbt.insert("LastUpdate".to_string(), Value::Long(self.last_update));
+        nbt.insert("InhabitedTime".to_string(), Value::Long(self.inhabited_time));
+        nbt.insert("isLightOn".to_string(), Value::Byte((option.save_light && self.is_light_on) as i8));
+        // heightmaps of unmodified chunks are kept as loaded
+        if !nbt.contains_key("Heightmaps") {
+            nbt.insert("Heightmaps".to_string(), Value::Compound(self.heightmaps_to_nbt()));
+        }
+        // sections
+        {
+            let mut sections = Vec::with_capacity(self.sub_chunks.len());
+            for (y, sub_chunk) in &self.sub_chunks {
+                sections.push(Value::Compound(section_to_nbt(sub_chunk, *y, option.save_light)));
+            }
+            nbt.insert("sections".to_string(), Value::List(sections));
+        }
+        nbt.insert("block_entities".to_string(), Value::List(self.block_entities_to_nbt()));
+        {
+            let (block_ticks, fluid_ticks) = self.pending_ticks_to_nbt();
+            nbt.insert("block_ticks".to_string(), Value::List(block_ticks));
+            nbt.insert("fluid_ticks".to_string(), Value::List(fluid_ticks));
+        }
+
+        // entities
+        return Ok((nbt, self.entity_file_to_nbt(chunk_pos)));
+    }
+
+    /// Save chunks older than 1.18. Chunks older than 1.13 are saved with number ids, and McRegion
+    /// chunks are saved in Anvil format. Returns `Error::BlockWithoutNumberId` if a block doesn't
+    /// exist before 1.13.
+    fn to_nbt_pre_1_18(&self, chunk_pos: &ChunkPos, option: &MCASaveOption) -> Result<(HashMap<String, Value>, Option<HashMap<String, Value>>), Error> {
+        let mut level = self.unparsed_tags.clone();
+        let global_pos = chunk_pos.to_global_pos();
+        let number_id = self.data_version < DataVersion::Snapshot_17w47a as i32;
+
+        level.insert("xPos".to_string(), Value::Int(global_pos.x));
+        level.insert("zPos".to_string(), Value::Int(global_pos.z));
+        level.insert("LastUpdate".to_string(), Value::Long(self.last_update));
+        level.insert("InhabitedTime".to_string(), Value::Long(self.inhabited_time));
+        let light_on = Value::Byte((option.save_light && self.is_light_on) as i8);
+        if number_id {
+            level.insert("TerrainPopulated".to_string(), Value::Byte((self.status >= ChunkStatus::Features) as i8));
+            level.insert("LightPopulated".to_string(), light_on);
+            // heightmap of 1.12 records the lowest y where sky light is not blocked
+            if !level.contains_key("HeightMap") {
+                let heights = self.compute_heightmap(HeightmapKind::MotionBlocking);
+                level.insert("HeightMap".to_string(), Value::IntArray(IntArray::new(heights.to_vec())));
+            }
+        } else {
+            level.insert("Status".to_string(), Value::String(self.status.legacy_name(self.data_version)));
+            level.insert("isLightOn".to_string(), light_on);
+            if !level.contains_key("Heightmaps") {
+                level.insert("Heightmaps".to_string(), Value::Compound(self.heightmaps_to_nbt()));
+            }
+        }
+        // sections
+        {
+            let mut sections = Vec::with_capacity(self.sub_chunks.len());
+            for (y, sub_chunk) in &self.sub_chunks {
+                let sect = if number_id {
+                    section_to_nbt_number_id(sub_chunk, *y, option.save_light)?
+                } else {
+                    section_to_nbt_pre_1_18(sub_chunk, *y, option.save_light, self.data_version)
+                };
+                sections.push(Value::Compound(sect));
+            }
+            level.insert("Sections".to_string(), Value::List(sections));
+        }
+        // biomes
+        {
+            let biomes: Vec<i32> = if self.data_version >= DataVersion::Snapshot_19w36a as i32 {
+                self.sub_chunks.values().flat_map(|s| s.biome_array.iter().map(|b| b.legacy_id())).collect()
+            } else {
+                let lowest = self.sub_chunks.values().next();
+                (0..256).map(|idx| {
+                    let (x, z) = (idx % 16, idx / 16);
+                    lowest.map_or(Biome::plains, |s| s.biome_array[z / 4 * 4 + x / 4]).legacy_id()
+                }).collect()
+            };
+            if number_id {
+                let biomes = biomes.iter().map(|b| *b as u8 as i8).collect();
+                level.insert("Biomes".to_string(), Value::ByteArray(ByteArray::new(biomes)));
+            } else {
+                level.insert("Biomes".to_string(), Value::IntArray(IntArray::new(biomes)));
+            }
+        }
+        level.insert("TileEntities".to_string(), Value::List(self.block_entities_to_nbt()));
+        {
+            let (block_ticks, fluid_ticks) = self.pending_ticks_to_nbt();
+            level.insert("TileTicks".to_string(), Value::List(block_ticks));
+            level.insert("LiquidTicks".to_string(), Value::List(fluid_ticks));
+        }
+
+        let mut nbt = HashMap::new();
+        if self.data_version >= DataVersion::Snapshot_15w32a as i32 {
+            nbt.insert("DataVersion".to_string(), Value::Int(self.data_version));
+        }
+        // entities are stored in region files before 20w45a
+        if self.data_version < DataVersion::Snapshot_20w45a as i32 {
+            level.insert("Entities".to_string(), Value::List(self.entities_to_nbt()));
+            nbt.insert("Level".to_string(), Value::Compound(level));
+            return Ok((nbt, None));
+        }
+        nbt.insert("Level".to_string(), Value::Compound(level));
+        return Ok((nbt, self.entity_file_to_nbt(chunk_pos)));
+    }
+
+    fn block_entities_to_nbt(&self) -> Vec<Value> {
+        let mut positions: Vec<&[i32; 3]> = self.block_entities.keys().collect();
+        positions.sort();
+        let mut be_list = Vec::with_capacity(positions.len());
+        for pos in positions {
+            let mut be_nbt = self.block_entities[pos].tags.clone();
+            be_nbt.insert("x".to_string(), Value::Int(pos[0]));
+            be_nbt.insert("y".to_string(), Value::Int(pos[1]));
+            be_nbt.insert("z".to_string(), Value::Int(pos[2]));
+            be_list.push(Value::Compound(be_nbt));
+        }
+        return be_list;
+    }
+
+    /// Returns block ticks and fluid ticks
+    fn pending_ticks_to_nbt(&self) -> (Vec<Value>, Vec<Value>) {
+        let mut positions: Vec<&[i32; 3]> = self.pending_ticks.keys().collect();
+        positions.sort();
+        let mut block_ticks = Vec::new();
+        let mut fluid_ticks = Vec::new();
+        for pos in positions {
+            for tick in &self.pending_ticks[pos] {
+                let (id, dest) = match &tick.info {
+                    PendingTickInfo::Block { id } => (id, &mut block_ticks),
+                    PendingTickInfo::Fluid { id } => (id, &mut fluid_ticks),
+                };
+                let mut tick_nbt = common::size_to_compound(pos);
+                tick_nbt.insert("i".to_string(), Value::String(id.clone()));
+                tick_nbt.insert("p".to_string(), Value::Int(tick.priority));
+                tick_nbt.insert("t".to_string(), Value::Int(tick.time));
+                dest.push(Value::Compound(tick_nbt));
+            }
+        }
+        return (block_ticks, fluid_ticks);
+    }
+
+    fn entities_to_nbt(&self) -> Vec<Value> {
+        let mut entity_list = Vec::with_capacity(self.entities.len());
+        for entity in &self.entities {
+            entity_list.push(Value::Compound(entity.to_nbt()));
+        }
+        return entity_list;
+    }
+
+    /// Nbt stored in entities file, `None` if there is no entity
+    fn entity_file_to_nbt(&self, chunk_pos: &ChunkPos) -> Option<HashMap<String, Value>> {
+        if self.entities.is_empty() {
+            return None;
+        }
+        let global_pos = chunk_pos.to_global_pos();
+        let mut entity_nbt = HashMap::new();
+        entity_nbt.insert("DataVersion".to_string(), Value::Int(self.data_version));
+        entity_nbt.insert("Position".to_string(), Value::IntArray(IntArray::new(vec![global_pos.x, global_pos.z])));
+        entity_nbt.insert("Entities".to_string(), Value::List(self.entities_to_nbt()));
+        return Some(entity_nbt);
+    }
+
 }
 
 pub fn bits_per_block(block_types: usize, min_value: u8) -> u8 {
@@ -468,6 +964,50 @@ fn parse_biomes(reg: &mut SubChunk, sect: &HashMap<String, Value>, path: &str) -
     return Ok(());
 }
 
+fn parse_light(reg: &mut SubChunk, sect: &HashMap<String, Value>, path: &str) -> Result<(), Error> {
+    let sky_light = if let Some(s) = sect.get("SkyLight") {
+        let tag_path = format!("{path}/SkyLight");
+        let arr = unwrap_tag!(s,ByteArray,fastnbt::ByteArray::new(vec![]),tag_path).as_ref();
+        if arr.len() != 2048 {
+            return Err(Error::InvalidValue { tag_path, error: format!("The length should be 2048, but found {}", arr.len()) });
+        }
+        arr
+    } else {
+        &[]
+    };
+    let block_light = if let Some(s) = sect.get("BlockLight") {
+        let tag_path = format!("{path}/BlockLight");
+        let arr = unwrap_tag!(s,ByteArray,fastnbt::ByteArray::new(vec![]),tag_path).as_ref();
+        if arr.len() != 2048 {
+            return Err(Error::InvalidValue { tag_path, error: format!("The length should be 2048, but found {}", arr.len()) });
+        }
+        arr
+    } else {
+        &[]
+    };
+
+    for counter in 0..4096 {
+        let sl: u8 = if sky_light.is_empty() {
+            15
+        } else {
+            let b = u8::from_ne_bytes(sky_light[counter / 2].to_ne_bytes());
+            (b >> (4 * (counter % 2))) & 0xF
+        };
+        debug_assert!(sl <= 15);
+        let bl: u8 = if block_light.is_empty() {
+            15
+        } else {
+            let b = u8::from_ne_bytes(block_light[counter / 2].to_ne_bytes());
+            (b >> (4 * (counter % 2))) & 0xF
+        };
+        debug_assert!(bl <= 15);
+
+        let light = Light::new(sl, bl);
+        reg.sky_block_light_array[counter] = light;
+    }
+    return Ok(());
+}
+
 fn parse_section(sect: &HashMap<String, Value>, path: &str) -> Result<Option<(SubChunk, i8)>, Error> {
     let mut subchunk = SubChunk::new();
     // let reg = &mut subchunk.region;
@@ -491,58 +1031,313 @@ fn parse_section(sect: &HashMap<String, Value>, path: &str) -> Result<Option<(Su
     parse_blocks(&mut subchunk, sect, path)?;
 
     // skylight and block light
+    parse_light(&mut subchunk, sect, path)?;
+
+    //biomes
+    parse_biomes(&mut subchunk, sect, path)?;
+
+    return Ok(Some((subchunk, y_pos)));
+}
+
+/// Parse a section of chunks saved before 1.18. Returns `None` if the section only contains light.
+/// Since 20w17a (1.16), a block index never spans 2 longs in `BlockStates`; before that, indices
+/// are packed tightly.
+fn parse_section_pre_1_18(sect: &HashMap<String, Value>, path: &str, data_version: i32) -> Result<Option<(SubChunk, i8)>, Error> {
+    let y_pos = *unwrap_opt_tag!(sect.get("Y"),Byte,0,format!("{path}/Y"));
+    let palette = match sect.get("Palette") {
+        Some(p) => unwrap_tag!(p,List,vec![],format!("{path}/Palette")),
+        None => return Ok(None),
+    };
+
+    let mut subchunk = SubChunk::new();
+    subchunk.palette.reserve(palette.len());
+    for (idx, blk) in palette.iter().enumerate() {
+        let path = format!("{path}/Palette[{idx}]");
+        let blk = unwrap_tag!(blk,Compound,HashMap::new(),path);
+        subchunk.palette.push(common::parse_block(blk, &path)?);
+    }
+    if subchunk.palette.is_empty() {
+        return Err(Error::PaletteIsEmpty { tag_path: format!("{path}/Palette") });
+    }
+    if subchunk.palette.len() > 65535 {
+        return Err(Error::PaletteTooLong(subchunk.palette.len()));
+    }
+    // blocks
     {
-        let sky_light = if let Some(s) = sect.get("SkyLight") {
-            let tag_path = format!("{path}/SkyLight");
-            let arr = unwrap_tag!(s,ByteArray,fastnbt::ByteArray::new(vec![]),tag_path).as_ref();
-            if arr.len() != 2048 {
-                return Err(Error::InvalidValue { tag_path, error: format!("The length should be 2048, but found {}", arr.len()) });
-            }
-            arr
+        let path = format!("{path}/BlockStates");
+        let array_i64 = unwrap_opt_tag!(sect.get("BlockStates"),LongArray,LongArray::new(vec![]),path);
+        let bits = bits_per_block(subchunk.palette.len(), 4);
+        let spanning = data_version < DataVersion::Snapshot_20w17a as i32;
+        let required_len = if spanning {
+            4096 * bits as usize / 64
         } else {
-            &[]
+            MultiBitSet::required_num_u64(4096, bits)
         };
-        let block_light = if let Some(s) = sect.get("BlockLight") {
-            let tag_path = format!("{path}/BlockLight");
-            let arr = unwrap_tag!(s,ByteArray,fastnbt::ByteArray::new(vec![]),tag_path).as_ref();
-            if arr.len() != 2048 {
-                return Err(Error::InvalidValue { tag_path, error: format!("The length should be 2048, but found {}", arr.len()) });
+        if array_i64.len() != required_len {
+            return Err(Error::InvalidValue {
+                tag_path: path,
+                error: format!("This subchunk has 4096 blocks of {} types, required {} i64 element to store them, but found {}",
+                               subchunk.palette.len(), required_len, array_i64.len()),
+            });
+        }
+
+        let block_id_max = subchunk.palette.len() - 1;
+        for (idx, blk_id) in decode_block_states(array_i64, bits, spanning).iter().enumerate() {
+            if *blk_id as usize > block_id_max {
+                return Err(Error::BlockIndexOutOfRange {
+                    tag_path: path,
+                    index: *blk_id as i32,
+                    range: [0, block_id_max as i32],
+                });
             }
-            arr
-        } else {
-            &[]
-        };
+            subchunk.block_id_array[idx] = *blk_id as u16;
+        }
+    }
+    parse_light(&mut subchunk, sect, path)?;
 
-        for counter in 0..4096 {
-            let sl: u8 = if sky_light.is_empty() {
-                15
-            } else {
-                let b = u8::from_ne_bytes(sky_light[counter / 2].to_ne_bytes());
-                (b >> (4 * (counter % 2))) & 0xF
-            };
-            debug_assert!(sl <= 15);
-            let bl: u8 = if block_light.is_empty() {
-                15
-            } else {
-                let b = u8::from_ne_bytes(block_light[counter / 2].to_ne_bytes());
-                (b >> (4 * (counter % 2))) & 0xF
-            };
-            debug_assert!(bl <= 15);
+    return Ok(Some((subchunk, y_pos)));
+}
 
-            let light = Light::new(sl, bl);
-            subchunk.sky_block_light_array[counter] = light;
+/// Parse a section of chunks saved before 1.13, whose blocks are stored as number ids in `Blocks`
+/// and `Add`, and damages in `Data`. Returns `None` if the section only contains light.
+fn parse_section_number_id(sect: &HashMap<String, Value>, path: &str, warnings: &mut Vec<String>) -> Result<Option<(SubChunk, i8)>, Error> {
+    let y_pos = *unwrap_opt_tag!(sect.get("Y"),Byte,0,format!("{path}/Y"));
+    let blocks = match sect.get("Blocks") {
+        Some(b) => unwrap_tag!(b,ByteArray,ByteArray::new(vec![]),format!("{path}/Blocks")),
+        None => return Ok(None),
+    };
+    if blocks.len() != 4096 {
+        return Err(Error::InvalidValue { tag_path: format!("{path}/Blocks"), error: format!("The length should be 4096, but found {}", blocks.len()) });
+    }
+    let data = unwrap_opt_tag!(sect.get("Data"),ByteArray,ByteArray::new(vec![]),format!("{path}/Data"));
+    if data.len() != 2048 {
+        return Err(Error::InvalidValue { tag_path: format!("{path}/Data"), error: format!("The length should be 2048, but found {}", data.len()) });
+    }
+    // Add holds the higher 4 bits of ids, which are only used by mods
+    if let Some(add) = sect.get("Add") {
+        let tag_path = format!("{path}/Add");
+        let add = unwrap_tag!(add,ByteArray,ByteArray::new(vec![]),tag_path);
+        if add.iter().any(|b| *b != 0) {
+            return Err(Error::InvalidValue { tag_path, error: "Block ids above 255 are not supported".to_string() });
         }
     }
 
-    //biomes
-    parse_biomes(&mut subchunk, sect, path)?;
+    let mut subchunk = SubChunk::new();
+    let mut id_damage = [(0u8, 0u8); 4096];
+    for (idx, dst) in id_damage.iter_mut().enumerate() {
+        *dst = (blocks[idx] as u8, nibble_at(data, idx));
+    }
+    set_number_id_blocks(&mut subchunk, &id_damage, &format!("{path}/Blocks"), warnings);
+    parse_light(&mut subchunk, sect, path)?;
 
     return Ok(Some((subchunk, y_pos)));
 }
 
+/// Get the 4-bit value at `idx` in nibble arrays like `Data` and `SkyLight`
+fn nibble_at(arr: &[i8], idx: usize) -> u8 {
+    return (arr[idx / 2] as u8 >> (4 * (idx % 2))) & 0xF;
+}
+
+/// Convert number ids and damages (in yzx order) by `Block::from_old`, and fill them into
+/// `sub_chunk`. Like the game, invalid damages fall back to the default state of the id, and ids
+/// that are not blocks fall back to air. Fixed errors are appended to `warnings`.
+fn set_number_id_blocks(sub_chunk: &mut SubChunk, id_damage: &[(u8, u8); 4096], tag_path: &str, warnings: &mut Vec<String>) {
+    let mut index_of = [[u16::MAX; 16]; 256];
+    sub_chunk.palette.clear();
+    for (idx, (id, damage)) in id_damage.iter().enumerate() {
+        let blk_idx = &mut index_of[*id as usize][*damage as usize];
+        if *blk_idx == u16::MAX {
+            let block = match Block::from_old(*id, *damage, DataVersion::Java_1_12_2) {
+                Ok(b) => b,
+                Err(detail) => {
+                    let block = default_state_of_number_id(*id);
+                    let error = Error::InvalidBlockNumberId {
+                        tag_path: format!("{tag_path}[{idx}]"),
+                        detail,
+                    };
+                    warnings.push(format!("{error}, replaced with {block}"));
+                    block
+                }
+            };
+            *blk_idx = sub_chunk.palette.len() as u16;
+            sub_chunk.palette.push(block);
+        }
+        sub_chunk.block_id_array[idx] = *blk_idx;
+    }
+}
+
+/// The block of number id `id` with the first valid damage, or air if `id` is not a block
+fn default_state_of_number_id(id: u8) -> Block {
+    let mut damages = Vec::new();
+    get_valid_damage_values(id, &mut damages);
+    for damage in damages {
+        if let Ok(block) = Block::from_old(id, damage, DataVersion::Java_1_12_2) {
+            return block;
+        }
+    }
+    return Block::air();
+}
+
+/// Decode 4096 block indices in `BlockStates` or `block_states/data`
+fn decode_block_states(array_i64: &[i64], bits: u8, spanning: bool) -> Vec<u64> {
+    if spanning {
+        let data: Vec<u64> = array_i64.iter().map(|v| *v as u64).collect();
+        let mbs = litematica::MultiBitSet::from_data_vec(data, 4096, bits).unwrap();
+        return (0..4096).map(|idx| mbs.get(idx)).collect();
+    }
+    let mut mbs = MultiBitSet::new(4096, bits);
+    mbs.set_array_from_nbt(array_i64);
+    return (0..4096).map(|idx| mbs.get(idx)).collect();
+}
+
+/// Encode 4096 block indices as `BlockStates` or `block_states/data`
+fn encode_block_states(ids: &[u16; 4096], bits: u8, spanning: bool) -> Vec<i64> {
+    if spanning {
+        let mut mbs = litematica::MultiBitSet::new();
+        mbs.reset(bits, 4096);
+        for (idx, blk_id) in ids.iter().enumerate() {
+            mbs.set(idx, *blk_id as u64).unwrap();
+        }
+        return mbs.as_u64_slice().iter().map(|v| *v as i64).collect();
+    }
+    let mut mbs = MultiBitSet::new(4096, bits);
+    for (idx, blk_id) in ids.iter().enumerate() {
+        mbs.set(idx, *blk_id as u64);
+    }
+    return mbs.to_nbt_array();
+}
+
+/// Rebuild palette, so that unused blocks are removed. Returns the new palette and block indices.
+fn compact_palette(sub_chunk: &SubChunk) -> (Vec<Value>, [u16; 4096]) {
+    let mut new_index_of: Vec<Option<u16>> = vec![None; sub_chunk.palette.len()];
+    let mut palette = Vec::new();
+    let mut new_id_array = [0u16; 4096];
+    for (idx, blk_id) in sub_chunk.block_id_array.iter().enumerate() {
+        let new_id = match new_index_of[*blk_id as usize] {
+            Some(id) => id,
+            None => {
+                let id = palette.len() as u16;
+                new_index_of[*blk_id as usize] = Some(id);
+                palette.push(Value::Compound(sub_chunk.palette[*blk_id as usize].to_nbt()));
+                id
+            }
+        };
+        new_id_array[idx] = new_id;
+    }
+    return (palette, new_id_array);
+}
+
+fn blocks_to_nbt(sub_chunk: &SubChunk) -> HashMap<String, Value> {
+    let (palette, new_id_array) = compact_palette(sub_chunk);
+
+    let mut nbt = HashMap::new();
+    if palette.len() > 1 {
+        let data = encode_block_states(&new_id_array, bits_per_block(palette.len(), 4), false);
+        nbt.insert("data".to_string(), Value::LongArray(LongArray::new(data)));
+    }
+    nbt.insert("palette".to_string(), Value::List(palette));
+    return nbt;
+}
+
+fn biomes_to_nbt(sub_chunk: &SubChunk) -> HashMap<String, Value> {
+    let mut palette: Vec<Biome> = Vec::new();
+    let mut id_array = [0u8; 64];
+    for (idx, biome) in sub_chunk.biome_array.iter().enumerate() {
+        let id = match palette.iter().position(|b| b == biome) {
+            Some(id) => id,
+            None => {
+                palette.push(*biome);
+                palette.len() - 1
+            }
+        };
+        id_array[idx] = id as u8;
+    }
+
+    let mut nbt = HashMap::new();
+    if palette.len() > 1 {
+        let mut mbs = MultiBitSet::new(64, bits_per_block(palette.len(), 1));
+        for (idx, id) in id_array.iter().enumerate() {
+            mbs.set(idx, *id as u64);
+        }
+        nbt.insert("data".to_string(), Value::LongArray(LongArray::new(mbs.to_nbt_array())));
+    }
+    let palette = palette.iter().map(|b| Value::String(format!("minecraft:{b}"))).collect();
+    nbt.insert("palette".to_string(), Value::List(palette));
+    return nbt;
+}
+
+fn section_to_nbt(sub_chunk: &SubChunk, y: i8, save_light: bool) -> HashMap<String, Value> {
+    let mut nbt = HashMap::new();
+    nbt.insert("Y".to_string(), Value::Byte(y));
+    nbt.insert("block_states".to_string(), Value::Compound(blocks_to_nbt(sub_chunk)));
+    nbt.insert("biomes".to_string(), Value::Compound(biomes_to_nbt(sub_chunk)));
+
+    if save_light {
+        light_to_nbt(sub_chunk, &mut nbt);
+    }
+    return nbt;
+}
+
+/// Save a section of chunks older than 1.18, biomes are stored in `Level` instead
+fn section_to_nbt_pre_1_18(sub_chunk: &SubChunk, y: i8, save_light: bool, data_version: i32) -> HashMap<String, Value> {
+    let (palette, new_id_array) = compact_palette(sub_chunk);
+    let bits = bits_per_block(palette.len(), 4);
+    let spanning = data_version < DataVersion::Snapshot_20w17a as i32;
+
+    let mut nbt = HashMap::new();
+    nbt.insert("Y".to_string(), Value::Byte(y));
+    nbt.insert("Palette".to_string(), Value::List(palette));
+    nbt.insert("BlockStates".to_string(), Value::LongArray(LongArray::new(encode_block_states(&new_id_array, bits, spanning))));
+    if save_light {
+        light_to_nbt(sub_chunk, &mut nbt);
+    }
+    return nbt;
+}
+
+/// Save a section of chunks older than 1.13 with number ids and damages. Blocks pasted from newer
+/// schematics keep their modern ids, so they are converted from the latest version to 1.12.2.
+fn section_to_nbt_number_id(sub_chunk: &SubChunk, y: i8, save_light: bool) -> Result<HashMap<String, Value>, Error> {
+    let mut id_damage_of: Vec<(u8, u8)> = Vec::with_capacity(sub_chunk.palette.len());
+    for blk in &sub_chunk.palette {
+        let id_damage = blk.to_old().or_else(|| blk.to_old_from_version(DataVersion::new() as i32));
+        match id_damage {
+            Some(id_damage) => id_damage_of.push(id_damage),
+            None => return Err(Error::BlockWithoutNumberId { block: blk.full_id() }),
+        }
+    }
+    let mut blocks = vec![0i8; 4096];
+    let mut data = vec![0i8; 2048];
+    for (counter, blk_id) in sub_chunk.block_id_array.iter().enumerate() {
+        let (id, damage) = id_damage_of[*blk_id as usize];
+        blocks[counter] = id as i8;
+        data[counter / 2] |= (damage << (4 * (counter % 2))) as i8;
+    }
+
+    let mut nbt = HashMap::new();
+    nbt.insert("Y".to_string(), Value::Byte(y));
+    nbt.insert("Blocks".to_string(), Value::ByteArray(ByteArray::new(blocks)));
+    nbt.insert("Data".to_string(), Value::ByteArray(ByteArray::new(data)));
+    if save_light {
+        light_to_nbt(sub_chunk, &mut nbt);
+    }
+    return Ok(nbt);
+}
+
+fn light_to_nbt(sub_chunk: &SubChunk, nbt: &mut HashMap<String, Value>) {
+    let mut sky_light = vec![0i8; 2048];
+    let mut block_light = vec![0i8; 2048];
+    for (counter, light) in sub_chunk.sky_block_light_array.iter().enumerate() {
+        let shift = 4 * (counter % 2);
+        sky_light[counter / 2] |= (light.sky_light() << shift) as i8;
+        block_light[counter / 2] |= (light.block_light() << shift) as i8;
+    }
+    nbt.insert("SkyLight".to_string(), Value::ByteArray(ByteArray::new(sky_light)));
+    nbt.insert("BlockLight".to_string(), Value::ByteArray(ByteArray::new(block_light)));
+}
+
 // MultiBitSet in chunk.rs and litematic.rs is different. MC doesn't allow to separate an element
 // into 2 u64, but litematica does
-struct MultiBitSet {
+pub(super) struct MultiBitSet {
     array: Vec<u64>,
     num_elements: usize,
     element_bits: u8,
@@ -620,6 +1415,10 @@ impl MultiBitSet {
             self.array.push(val);
         }
     }
+
+    pub fn to_nbt_array(&self) -> Vec<i64> {
+        return self.array.iter().map(|val| i64::from_be_bytes(val.to_be_bytes())).collect();
+    }
 }
 
 #[test]
@@ -637,3 +1436,163 @@ fn test_multi_bit_set() {
     }
 }
 
+
+#[test]
+fn test_pre_1_18_chunk() {
+    use crate::entity::OtherEntity;
+    use crate::region::BlockEntity;
+    use crate::world::XZCoordinate;
+
+    let chunk_pos = ChunkPos::from_global_pos(&XZCoordinate { x: 3, z: -2 });
+    let colors = ["white", "orange", "magenta", "light_blue", "yellow", "lime", "pink", "gray", "light_gray",
+        "cyan", "purple", "blue", "brown", "green", "red", "black"];
+    for version in [DataVersion::Java_1_13_2, DataVersion::Java_1_14_4, DataVersion::Java_1_15_2,
+        DataVersion::Java_1_16_5, DataVersion::Java_1_17_1] {
+        let version = version as i32;
+        let mut chunk = Chunk::with_y_range(0..256);
+        chunk.data_version = version;
+        // 18 kinds of blocks, so that an index takes 5 bits and may span 2 longs before 1.16
+        for (idx, color) in colors.iter().enumerate() {
+            chunk.set_block_at([48 + idx as i32, 64, -30], &Block::from_id(&format!("{color}_wool")).unwrap()).unwrap();
+        }
+        chunk.set_block_at([50, 64, -31], &Block::from_id("stone").unwrap()).unwrap();
+        for sub_chunk in chunk.sub_chunks.values_mut() {
+            // biomes are stored by columns before 1.15
+            sub_chunk.biome_array.fill(Biome::forest);
+            for y in 0..4 {
+                sub_chunk.biome_array[y * 16] = Biome::desert;
+            }
+        }
+        chunk.block_entities.insert([50, 64, -30], BlockEntity::new());
+        let mut entity = OtherEntity::default();
+        entity.id = "minecraft:marker".to_string();
+        entity.entity_fields.pos = [50.5, 70.0, -29.5];
+        chunk.entities.push(EntityBox::new(Box::new(entity)));
+
+        let (nbt, entity_nbt) = chunk.to_nbt(&chunk_pos, &MCASaveOption::default()).unwrap();
+        assert_eq!(entity_nbt.is_some(), version >= DataVersion::Snapshot_20w45a as i32);
+        {
+            let Some(Value::Compound(level)) = nbt.get("Level") else { panic!("Level is missing") };
+            let Some(Value::IntArray(biomes)) = level.get("Biomes") else { panic!("Biomes is missing") };
+            assert_eq!(biomes.len(), if version >= DataVersion::Snapshot_19w36a as i32 { 1024 } else { 256 });
+            let Some(Value::List(sections)) = level.get("Sections") else { panic!("Sections is missing") };
+            let Value::Compound(sect) = &sections[4] else { panic!("Section is not a compound") };
+            let Some(Value::LongArray(block_states)) = sect.get("BlockStates") else { panic!("BlockStates is missing") };
+            assert_eq!(block_states.len(), if version >= DataVersion::Snapshot_20w17a as i32 { 342 } else { 320 });
+        }
+
+        let entity_nbt = entity_nbt.map(|nbt| NBTWithSource { nbt, source: "entities/r.0.-1.mca" });
+        let loaded = Chunk::from_nbt(NBTWithSource { nbt, source: "region/r.0.-1.mca" }, entity_nbt, &chunk_pos).unwrap();
+        assert_eq!(loaded.data_version, version);
+        assert_eq!(loaded.status, ChunkStatus::Full);
+        assert_eq!(loaded.y_range(), 0..256);
+        assert_eq!(loaded.entities.len(), 1);
+        assert!(loaded.block_entities.contains_key(&[50, 64, -30]));
+        for (y, sub_chunk) in &chunk.sub_chunks {
+            let loaded_sub_chunk = &loaded.sub_chunks[y];
+            for idx in 0..4096 {
+                assert_eq!(sub_chunk.palette[sub_chunk.block_id_array[idx] as usize],
+                           loaded_sub_chunk.palette[loaded_sub_chunk.block_id_array[idx] as usize]);
+            }
+            assert_eq!(sub_chunk.biome_array, loaded_sub_chunk.biome_array);
+        }
+    }
+}
+
+#[test]
+fn test_legacy_names() {
+    assert_eq!(ChunkStatus::from_legacy_str("postprocessed"), Some(ChunkStatus::Full));
+    assert_eq!(ChunkStatus::from_legacy_str("minecraft:liquid_carvers"), Some(ChunkStatus::Carvers));
+    assert_eq!(ChunkStatus::Full.legacy_name(DataVersion::Java_1_13_2 as i32), "postprocessed");
+    assert_eq!(Biome::from_legacy_id(18), Biome::forest);
+    // snowy_mountains
+    assert_eq!(Biome::from_legacy_id(13), Biome::snowy_plains);
+    assert_eq!(Biome::from_legacy_id(Biome::windswept_savanna.legacy_id()), Biome::windswept_savanna);
+}
+
+#[test]
+fn test_number_id_chunk() {
+    use crate::world::{AbsolutePosIndexed, XZCoordinate};
+
+    let chunk_pos = ChunkPos::from_global_pos(&XZCoordinate { x: -1, z: 2 });
+    let red_wool = Block::from_old(35, 14, DataVersion::Java_1_12_2).unwrap();
+    let diorite = Block::from_old(1, 3, DataVersion::Java_1_12_2).unwrap();
+    let mut chunk = Chunk::with_y_range(0..256);
+    chunk.data_version = DataVersion::Java_1_12_2 as i32;
+    chunk.set_block_at([-16, 3, 32], &red_wool).unwrap();
+    chunk.set_block_at([-1, 200, 47], &diorite).unwrap();
+    // pasted blocks with modern ids
+    chunk.set_block_at([-2, 10, 40], &Block::from_id("granite").unwrap()).unwrap();
+
+    let (nbt, entity_nbt) = chunk.to_nbt(&chunk_pos, &MCASaveOption::default()).unwrap();
+    assert!(entity_nbt.is_none());
+    {
+        let Some(Value::Compound(level)) = nbt.get("Level") else { panic!("Level is missing") };
+        assert!(!level.contains_key("Status"));
+        assert_eq!(level.get("TerrainPopulated"), Some(&Value::Byte(1)));
+        assert!(matches!(level.get("Biomes"), Some(Value::ByteArray(b)) if b.len() == 256));
+        assert!(matches!(level.get("HeightMap"), Some(Value::IntArray(h)) if h.len() == 256));
+    }
+    let loaded = Chunk::from_nbt(NBTWithSource { nbt, source: "region/r.-1.0.mca" }, None, &chunk_pos).unwrap();
+    assert_eq!(loaded.status, ChunkStatus::Full);
+    assert_eq!(loaded.y_range(), 0..256);
+    let loaded = loaded.as_absolute_pos(&chunk_pos);
+    assert_eq!(loaded.block_at([-16, 3, 32]), Some(&red_wool));
+    assert_eq!(loaded.block_at([-1, 200, 47]), Some(&diorite));
+    assert_eq!(loaded.block_at([-2, 200, 47]), Some(&Block::from_old(0, 0, DataVersion::Java_1_12_2).unwrap()));
+    assert_eq!(loaded.block_at([-2, 10, 40]), Some(&Block::from_old(1, 1, DataVersion::Java_1_12_2).unwrap()));
+
+    // blocks added after 1.12 can't be saved
+    chunk.set_block_at([-2, 10, 40], &Block::from_id("tinted_glass").unwrap()).unwrap();
+    assert!(matches!(chunk.to_nbt(&chunk_pos, &MCASaveOption::default()), Err(Error::BlockWithoutNumberId { .. })));
+}
+
+#[test]
+fn test_mcregion_chunk() {
+    use crate::world::{AbsolutePosIndexed, XZCoordinate};
+
+    let chunk_pos = ChunkPos::from_global_pos(&XZCoordinate { x: 0, z: 0 });
+    // McRegion chunks are 128 blocks high, and stored in xzy order
+    let mut blocks = vec![0i8; 32768];
+    let mut data = vec![0i8; 16384];
+    let idx = 70 + 2 * 128 + 1 * 2048;
+    blocks[idx] = 35;
+    data[idx / 2] = 14 << (4 * (idx % 2));
+    // chest with damage 0, which is accepted by the game
+    blocks[idx + 1] = 54;
+    let mut tick = common::size_to_compound(&[1, 70, 2]);
+    tick.insert("i".to_string(), Value::Int(35));
+    tick.insert("t".to_string(), Value::Int(5));
+
+    let mut level = HashMap::new();
+    level.insert("xPos".to_string(), Value::Int(0));
+    level.insert("zPos".to_string(), Value::Int(0));
+    level.insert("LastUpdate".to_string(), Value::Long(100));
+    level.insert("TerrainPopulated".to_string(), Value::Byte(1));
+    level.insert("Blocks".to_string(), Value::ByteArray(ByteArray::new(blocks)));
+    level.insert("Data".to_string(), Value::ByteArray(ByteArray::new(data)));
+    level.insert("HeightMap".to_string(), Value::ByteArray(ByteArray::new(vec![0; 256])));
+    level.insert("Entities".to_string(), Value::List(vec![]));
+    level.insert("TileEntities".to_string(), Value::List(vec![]));
+    level.insert("TileTicks".to_string(), Value::List(vec![Value::Compound(tick)]));
+    let mut nbt = HashMap::new();
+    nbt.insert("Level".to_string(), Value::Compound(level));
+
+    let chunk = Chunk::from_nbt(NBTWithSource { nbt, source: "region/r.0.0.mcr" }, None, &chunk_pos).unwrap();
+    assert_eq!(chunk.data_version, DataVersion::Java_1_1 as i32);
+    assert_eq!(chunk.y_range(), 0..256);
+    let red_wool = Block::from_old(35, 14, DataVersion::Java_1_12_2).unwrap();
+    assert_eq!(chunk.as_absolute_pos(&chunk_pos).block_at([1, 70, 2]), Some(&red_wool));
+    let chest = Block::from_old(54, 2, DataVersion::Java_1_12_2).unwrap();
+    assert_eq!(chunk.as_absolute_pos(&chunk_pos).block_at([1, 71, 2]), Some(&chest));
+    assert_eq!(chunk.warnings.len(), 1);
+    assert_eq!(chunk.pending_ticks[&[1, 70, 2]][0].info, PendingTickInfo::Block { id: "minecraft:wool".to_string() });
+
+    // saved in Anvil format
+    let (nbt, _) = chunk.to_nbt(&chunk_pos, &MCASaveOption::default()).unwrap();
+    assert!(!nbt.contains_key("DataVersion"));
+    let Some(Value::Compound(level)) = nbt.get("Level") else { panic!("Level is missing") };
+    assert!(!level.contains_key("Blocks"));
+    assert!(matches!(level.get("Sections"), Some(Value::List(s)) if s.len() == 16));
+    assert!(matches!(level.get("HeightMap"), Some(Value::IntArray(_))));
+}
@@ -6,7 +6,7 @@ use crate::world::{AbsolutePosIndexed, ChunkRefAbsolutePos, ChunkRefRelativePos,
 impl ChunkRefRelativePos<'_> {
     fn y_pos_to_section_number(&self, y_r: i32) -> i8 {
         let y_a = y_r + self.chunk.y_offset();
-        return (y_a / 16) as i8;
+        return y_a.div_euclid(16) as i8;
     }
 
     fn to_sub_chunk_r_pos(&self, r_pos: [i32; 3]) -> (i8, &SubChunk, [i32; 3]) {
@@ -80,7 +80,7 @@ impl WorldSlice for ChunkRefRelativePos<'_> {
 
 impl<'s, 'chunk: 's> ChunkRefAbsolutePos<'chunk> {
     fn to_sub_chunk_r_pos(&'s self, a_pos: [i32; 3]) -> (i8, &'chunk SubChunk, [i32; 3]) {
-        let sect_number = (a_pos[1] / 16) as i8;
+        let sect_number = a_pos[1].div_euclid(16) as i8;
         debug_assert!(self.chunk.sub_chunks.contains_key(&sect_number));
         let sub_chunk: &'chunk SubChunk = self.chunk.sub_chunks.get(&sect_number).unwrap();
         let o = self.offset();
@@ -4,15 +4,18 @@ use std::sync::mpsc::{channel, Receiver};
 #[allow(unused_imports)]
 use std::time;
 use fastnbt::Value;
+use ndarray::Array3;
 use flate2::read::GzDecoder;
 use crate::{Error, unwrap_opt_tag, unwrap_tag};
 #[allow(unused_imports)]
-use crate::world::{AbsolutePosIndexed, Chunk, ChunkPos, ChunkRefAbsolutePos, ChunkVariant, Dimension, FileInfo, FilesInMemory, FilesRead, mca, RefOrObject, XZCoordinate};
+use crate::world::{AbsolutePosIndexed, Chunk, ChunkPos, ChunkRefAbsolutePos, ChunkVariant, Dimension, FileInfo, FilesInMemory, FilesRead, FolderOnDisk, mca, MCARawData, MCASaveOption, PasteOption, RefOrObject, XZCoordinate};
 use rayon::prelude::*;
 use crate::block::Block;
 use crate::error::unwrap_opt_i32;
+use crate::player::DimensionId;
 use crate::raid::{Raid, RaidList};
-use crate::region::{BlockEntity, HasOffset, PendingTick};
+use crate::region::{BlockEntity, HasOffset, Light, PendingTick, Region, WorldSlice};
+use crate::schem::{DataVersion, MetaDataIR, Schematic};
 use crate::schem::id_of_nbt_tag;
 
 impl<T> RefOrObject<'_, T> {
@@ -121,7 +124,7 @@ fn parse_raids(data_dir: &dyn FilesRead) -> Result<RaidList, Error> {
 }
 
 impl Dimension {
-    pub fn from_files(files: &dyn FilesRead, parse_directly: bool, y_range: Range<i32>, dimension_id: i32) -> Result<Dimension, Error> {
+    pub fn from_files(files: &dyn FilesRead, parse_directly: bool, y_range: Range<i32>, dimension_id: &DimensionId) -> Result<Dimension, Error> {
         let chunks = mca::parse_multiple_regions(&files.sub_directory("region"),
                                                  Some(&files.sub_directory("entities")),
                                                  y_range.clone(),
@@ -133,13 +136,24 @@ impl Dimension {
         return Ok(Dimension {
             chunks,
             y_range,
+            data_version: DataVersion::new() as i32,
             raids,
         });
     }
 
+    /// Create an empty dimension, `y_range` should be multiples of 16
+    pub fn new(y_range: Range<i32>) -> Dimension {
+        return Dimension {
+            chunks: HashMap::new(),
+            y_range,
+            data_version: DataVersion::new() as i32,
+            raids: RaidList::default(),
+        };
+    }
+
     pub fn block_pos_to_chunk_pos(block_pos: [i32; 3]) -> (ChunkPos, i8) {
-        let cpos = ChunkPos::from_global_pos(&XZCoordinate { x: block_pos[0] / 16, z: block_pos[2] / 16 });
-        let y = block_pos[1] / 16;
+        let cpos = ChunkPos::from_global_pos(&XZCoordinate { x: block_pos[0].div_euclid(16), z: block_pos[2].div_euclid(16) });
+        let y = block_pos[1].div_euclid(16);
         return (cpos, y as i8);
     }
 
@@ -157,7 +171,7 @@ impl Dimension {
         };
     }
 
-    pub fn check_all(&self, dimension_id: i32) -> Result<(), Error> {
+    pub fn check_all(&self, dimension_id: &DimensionId) -> Result<(), Error> {
         let (tx, rx) = channel();
 
         // Collect chunk infos of all chunks
@@ -169,7 +183,7 @@ impl Dimension {
                 Ok(chunk) => {
                     if chunk.to_ref().y_range() != self.y_range {
                         tx.send(Error::IncorrectYRangeInChunk {
-                            dimension_id,
+                            dimension_id: dimension_id.clone(),
                             dimension_y_range: self.y_range.clone(),
                             exception_chunk_x: pos.to_global_pos().x,
                             exception_chunk_z: pos.to_global_pos().z,
@@ -192,7 +206,7 @@ impl Dimension {
         return Ok(());
     }
 
-    pub fn parse_all(&mut self, dimension_id: i32) -> Result<(), Error> {
+    pub fn parse_all(&mut self, dimension_id: &DimensionId) -> Result<(), Error> {
         let (tx, rx) = channel();
         let (_chunk_info_tx, chunk_info_rx) = channel();
         let num_chunks = self.chunks.len();
@@ -203,7 +217,7 @@ impl Dimension {
                 Ok(chunk) => {
                     if chunk.y_range() != self.y_range {
                         tx.send(Error::IncorrectYRangeInChunk {
-                            dimension_id,
+                            dimension_id: dimension_id.clone(),
                             dimension_y_range: self.y_range.clone(),
                             exception_chunk_x: pos.to_global_pos().x,
                             exception_chunk_z: pos.to_global_pos().z,
@@ -227,6 +241,286 @@ impl Dimension {
     }
 }
 
+impl Dimension {
+    /// Save all chunks into `region` and `entities` folders in `dir`. Parsed chunks are converted to
+    /// nbt and compressed again, while unparsed chunks are written as is.
+    pub fn save_to_folder(&self, dir: &str, option: &MCASaveOption) -> Result<(), Error> {
+        let region_dir = format!("{dir}/region");
+        let entity_dir = format!("{dir}/entities");
+
+        let encoded: Result<Vec<(ChunkPos, MCARawData, Option<MCARawData>)>, Error> = self.chunks.par_iter().map(|(pos, variant)| {
+            let parsed;
+            let chunk = match variant {
+                // McRegion chunks are converted to Anvil
+                ChunkVariant::Unparsed(raw) if raw.region_data.source_file.ends_with(".mcr") => {
+                    parsed = raw.parse(pos)?;
+                    &parsed
+                }
+                ChunkVariant::Unparsed(raw) => return Ok((*pos, raw.region_data.clone(), raw.entity_data.clone())),
+                ChunkVariant::Parsed(chunk) => chunk,
+            };
+            let (region_nbt, entity_nbt) = chunk.to_nbt(pos, option)?;
+            let region_data = MCARawData::from_nbt(&region_nbt, chunk.time_stamp,
+                                                   format!("{region_dir}/{}", pos.filename_mca()), option)?;
+            let entity_data = if let Some(nbt) = entity_nbt {
+                Some(MCARawData::from_nbt(&nbt, chunk.time_stamp,
+                                          format!("{entity_dir}/{}", pos.filename_mca()), option)?)
+            } else {
+                None
+            };
+            return Ok((*pos, region_data, entity_data));
+        }).collect();
+
+        let mut region_data = HashMap::with_capacity(self.chunks.len());
+        let mut entity_data = HashMap::new();
+        for (pos, r_data, e_data) in encoded? {
+            region_data.insert(pos, r_data);
+            if let Some(e_data) = e_data {
+                entity_data.insert(pos, e_data);
+            }
+        }
+        mca::write_multiple_mca_files(&region_dir, &region_data)?;
+        mca::write_multiple_mca_files(&entity_dir, &entity_data)?;
+        return Ok(());
+    }
+}
+
+impl Default for PasteOption {
+    fn default() -> Self {
+        return PasteOption {
+            ignore_air: false,
+            ignore_structure_void: true,
+            paste_entities: true,
+        };
+    }
+}
+
+impl Dimension {
+    /// Get the chunk at `chunk_pos` for modifying. Unparsed chunk will be parsed, and a new chunk
+    /// filled with air will be created if it doesn't exist.
+    fn chunk_to_modify(&mut self, chunk_pos: &ChunkPos) -> Result<&mut Chunk, Error> {
+        let y_range = self.y_range.clone();
+        let data_version = self.data_version;
+        let variant = self.chunks.entry(*chunk_pos)
+            .or_insert_with(|| {
+                let mut chunk = Chunk::with_y_range(y_range);
+                chunk.data_version = data_version;
+                return ChunkVariant::Parsed(chunk);
+            });
+        return variant.parse_inplace(chunk_pos);
+    }
+
+    /// Paste all regions of `schem` into this dimension, the global position `g_pos` in schematic
+    /// is placed at `origin + g_pos`. Chunks that don't exist will be created, and all affected
+    /// chunks are marked as dirty. Returns error if any region exceeds the y range of dimension.
+    pub fn paste_schematic(&mut self, schem: &Schematic, origin: [i32; 3], option: &PasteOption) -> Result<(), Error> {
+        // check y range before modifying anything
+        for reg in &schem.regions {
+            let shape = reg.shape();
+            if shape.iter().any(|sz| *sz <= 0) {
+                continue;
+            }
+            let lb = [origin[0] + reg.offset[0], origin[1] + reg.offset[1], origin[2] + reg.offset[2]];
+            for pos in [lb, [lb[0], lb[1] + shape[1] - 1, lb[2]]] {
+                if !self.y_range.contains(&pos[1]) {
+                    return Err(Error::BlockPosOutOfDimension {
+                        pos,
+                        dimension_y_range: self.y_range.clone(),
+                    });
+                }
+            }
+        }
+
+        for reg in &schem.regions {
+            let offset = [origin[0] + reg.offset[0], origin[1] + reg.offset[1], origin[2] + reg.offset[2]];
+            self.paste_region(reg, offset, option)?;
+        }
+        return Ok(());
+    }
+
+    fn paste_region(&mut self, reg: &Region, offset: [i32; 3], option: &PasteOption) -> Result<(), Error> {
+        let shape = reg.shape();
+        let skipped: Vec<bool> = reg.palette.iter().map(|blk| {
+            (option.ignore_air && blk.is_air()) || (option.ignore_structure_void && blk.is_structure_void())
+        }).collect();
+
+        if shape.iter().all(|sz| *sz > 0) {
+            let (chunk_lb, _) = Self::block_pos_to_chunk_pos(offset);
+            let (chunk_ub, _) = Self::block_pos_to_chunk_pos([offset[0] + shape[0] - 1, offset[1], offset[2] + shape[2] - 1]);
+            for chunk_x in chunk_lb.global_x..=chunk_ub.global_x {
+                for chunk_z in chunk_lb.global_z..=chunk_ub.global_z {
+                    let chunk_pos = ChunkPos::from_global_pos(&XZCoordinate { x: chunk_x, z: chunk_z });
+                    let chunk = self.chunk_to_modify(&chunk_pos)?;
+                    chunk.mark_dirty();
+
+                    let block_lb = chunk_pos.block_pos_lower_bound();
+                    let block_ub = chunk_pos.block_pos_upper_bound();
+                    let x_range = (block_lb[0].max(offset[0]) - offset[0])..(block_ub[0].min(offset[0] + shape[0]) - offset[0]);
+                    let z_range = (block_lb[1].max(offset[2]) - offset[2])..(block_ub[1].min(offset[2] + shape[2]) - offset[2]);
+                    for y in 0..shape[1] {
+                        for z in z_range.clone() {
+                            for x in x_range.clone() {
+                                let blk_id = reg.array_yzx[[y as usize, z as usize, x as usize]] as usize;
+                                if skipped[blk_id] {
+                                    continue;
+                                }
+                                let r_pos = [x, y, z];
+                                let a_pos = [x + offset[0], y + offset[1], z + offset[2]];
+                                if chunk.set_block_at(a_pos, &reg.palette[blk_id]).is_err() {
+                                    if !chunk.y_range().contains(&a_pos[1]) {
+                                        return Err(Error::BlockPosOutOfDimension {
+                                            pos: a_pos,
+                                            dimension_y_range: chunk.y_range(),
+                                        });
+                                    }
+                                    return Err(Error::PaletteTooLong(65536));
+                                }
+
+                                // the block is replaced, so is its block entity and pending ticks
+                                chunk.block_entities.remove(&a_pos);
+                                chunk.pending_ticks.remove(&a_pos);
+                                if let Some(be) = reg.block_entities.get(&r_pos) {
+                                    chunk.block_entities.insert(a_pos, be.clone());
+                                }
+                                if let Some(ticks) = reg.pending_ticks.get(&r_pos) {
+                                    chunk.pending_ticks.insert(a_pos, ticks.clone());
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if option.paste_entities {
+            for entity in &reg.entities {
+                let mut entity = entity.clone();
+                entity.pos_shift(offset);
+                let chunk_pos = ChunkPos::from_global_pos(&XZCoordinate {
+                    x: (entity.pos()[0].floor() as i32).div_euclid(16),
+                    z: (entity.pos()[2].floor() as i32).div_euclid(16),
+                });
+                let chunk = self.chunk_to_modify(&chunk_pos)?;
+                chunk.mark_dirty();
+                chunk.entities.push(entity);
+            }
+        }
+        return Ok(());
+    }
+}
+
+impl Dimension {
+    /// Export blocks, block entities, pending ticks, entities and light in `range`(xyz) as a
+    /// schematic with only one region. Unparsed chunks are parsed temporarily, and returns error if
+    /// any chunk in `range` doesn't exist.
+    pub fn to_schematic(&self, range: [Range<i32>; 3]) -> Result<Schematic, Error> {
+        for y in [range[1].start, range[1].end - 1] {
+            if !range[1].is_empty() && !self.y_range.contains(&y) {
+                return Err(Error::BlockPosOutOfDimension {
+                    pos: [range[0].start, y, range[2].start],
+                    dimension_y_range: self.y_range.clone(),
+                });
+            }
+        }
+        let lb = [range[0].start, range[1].start, range[2].start];
+        let shape = [range[0].len() as i32, range[1].len() as i32, range[2].len() as i32];
+        let mut region = Region::with_shape(shape);
+        region.name = "Dimension".to_string();
+        region.light = Some(Array3::from_elem(Region::pos_xyz_to_yzx(&Region::i32_to_usize(&shape)), Light::default()));
+        let mut data_version = None;
+
+        if shape.iter().all(|sz| *sz > 0) {
+            let (chunk_lb, _) = Self::block_pos_to_chunk_pos(lb);
+            let (chunk_ub, _) = Self::block_pos_to_chunk_pos([range[0].end - 1, lb[1], range[2].end - 1]);
+            for chunk_x in chunk_lb.global_x..=chunk_ub.global_x {
+                for chunk_z in chunk_lb.global_z..=chunk_ub.global_z {
+                    let chunk_pos = ChunkPos::from_global_pos(&XZCoordinate { x: chunk_x, z: chunk_z });
+                    let chunk = match self.chunks.get(&chunk_pos) {
+                        Some(variant) => variant.check(&chunk_pos)?,
+                        None => return Err(Error::MissingChunk { chunk_x, chunk_z }),
+                    };
+                    let chunk = chunk.to_ref();
+                    data_version = Some(data_version.unwrap_or(i32::MIN).max(chunk.data_version));
+                    Self::copy_chunk_to_region(chunk, &chunk_pos, &range, &mut region);
+                }
+            }
+        }
+
+        region.shrink_palette()?;
+        let mut schem = Schematic::new();
+        schem.metadata = MetaDataIR::from_data_version_i32(data_version.unwrap_or(DataVersion::new() as i32))?;
+        schem.metadata.schem_origin = Some(lb);
+        schem.regions.push(region);
+        return Ok(schem);
+    }
+
+    fn copy_chunk_to_region(chunk: &Chunk, chunk_pos: &ChunkPos, range: &[Range<i32>; 3], region: &mut Region) {
+        let lb = [range[0].start, range[1].start, range[2].start];
+        let block_lb = chunk_pos.block_pos_lower_bound();
+        let block_ub = chunk_pos.block_pos_upper_bound();
+        let x_range = block_lb[0].max(range[0].start)..block_ub[0].min(range[0].end);
+        let z_range = block_lb[1].max(range[2].start)..block_ub[1].min(range[2].end);
+
+        for (sect_y, sub_chunk) in &chunk.sub_chunks {
+            let sect_lb = *sect_y as i32 * 16;
+            let y_range = sect_lb.max(range[1].start)..(sect_lb + 16).min(range[1].end);
+            if y_range.is_empty() {
+                continue;
+            }
+            let sub_chunk_light = sub_chunk.sky_block_light();
+            // index in sub chunk palette -> index in region palette
+            let mut lut: Vec<Option<u16>> = vec![None; sub_chunk.palette.len()];
+            for y in y_range {
+                for z in z_range.clone() {
+                    for x in x_range.clone() {
+                        let sub_chunk_idx = sub_chunk.block_index_at([x - block_lb[0], y - sect_lb, z - block_lb[1]]).unwrap() as usize;
+                        let region_idx = match lut[sub_chunk_idx] {
+                            Some(idx) => idx,
+                            None => {
+                                let idx = region.find_or_append_to_palette(&sub_chunk.palette[sub_chunk_idx]);
+                                lut[sub_chunk_idx] = Some(idx);
+                                idx
+                            }
+                        };
+                        let r_pos_yzx = [(y - lb[1]) as usize, (z - lb[2]) as usize, (x - lb[0]) as usize];
+                        region.array_yzx[r_pos_yzx] = region_idx;
+                        if let Some(light) = &mut region.light {
+                            light[r_pos_yzx] = sub_chunk_light[[(y - sect_lb) as usize, (z - block_lb[1]) as usize, (x - block_lb[0]) as usize]];
+                        }
+                    }
+                }
+            }
+        }
+
+        let contains = |a_pos: &[i32; 3]| -> bool {
+            return (0..3).all(|dim| range[dim].contains(&a_pos[dim]));
+        };
+        let to_relative = |a_pos: &[i32; 3]| -> [i32; 3] {
+            return [a_pos[0] - lb[0], a_pos[1] - lb[1], a_pos[2] - lb[2]];
+        };
+        for (a_pos, be) in &chunk.block_entities {
+            if contains(a_pos) {
+                region.block_entities.insert(to_relative(a_pos), be.clone());
+            }
+        }
+        for (a_pos, ticks) in &chunk.pending_ticks {
+            if contains(a_pos) {
+                region.pending_ticks.insert(to_relative(a_pos), ticks.clone());
+            }
+        }
+        for entity in &chunk.entities {
+            let pos = entity.pos();
+            let a_pos = [pos[0].floor() as i32, pos[1].floor() as i32, pos[2].floor() as i32];
+            if !contains(&a_pos) {
+                continue;
+            }
+            let mut entity = entity.clone();
+            entity.pos_shift([-lb[0], -lb[1], -lb[2]]);
+            region.entities.push(entity);
+        }
+    }
+}
+
 impl HasOffset for Dimension {
     fn offset(&self) -> [i32; 3] {
         return [0, 0, 0];
@@ -324,8 +618,8 @@ fn test_load_dimension() {
     let files = FilesInMemory::from_7z_file("test_files/world/00_1.20.2.7z", "").unwrap();
     let decompressed = time::SystemTime::now();
 
-    let mut dim = Dimension::from_files(&files, false, -64..320, 0).unwrap();
-    dim.parse_all(0).unwrap();
+    let mut dim = Dimension::from_files(&files, false, -64..320, &DimensionId::overworld()).unwrap();
+    dim.parse_all(&DimensionId::overworld()).unwrap();
 
     let parsed = time::SystemTime::now();
 
@@ -341,9 +635,9 @@ fn test_large_overworld() {
     let files = FilesInMemory::from_7z_file("test_files/world/01_large-world-1.20.2.7z", "").unwrap();
     let decompressed = time::SystemTime::now();
 
-    let mut dim = Dimension::from_files(&files, false, -64..320, 0).unwrap();
+    let mut dim = Dimension::from_files(&files, false, -64..320, &DimensionId::overworld()).unwrap();
 
-    dim.parse_all(0).unwrap();
+    dim.parse_all(&DimensionId::overworld()).unwrap();
     //dim.check_all().unwrap();
 
     let parsed = time::SystemTime::now();
@@ -361,8 +655,8 @@ fn test_load_dimension_mcc_block_entities() {
     let decompressed = time::SystemTime::now();
 
 
-    let mut dim = Dimension::from_files(&files, false, -64..320, 0).unwrap();
-    dim.parse_all(0).unwrap();
+    let mut dim = Dimension::from_files(&files, false, -64..320, &DimensionId::overworld()).unwrap();
+    dim.parse_all(&DimensionId::overworld()).unwrap();
 
 
     let parsed = time::SystemTime::now();
@@ -373,11 +667,112 @@ fn test_load_dimension_mcc_block_entities() {
              parsed.duration_since(decompressed).unwrap().as_millis());
 }
 
+#[test]
+fn test_save_dimension() {
+    let files = FilesInMemory::from_7z_file("test_files/world/02_mcc-block-entities.7z", "").unwrap();
+    let mut dim = Dimension::from_files(&files, false, -64..320, &DimensionId::overworld()).unwrap();
+    // a few chunks near origin, including one chunk stored in .mcc file
+    dim.chunks.retain(|pos, _| pos.global_z == -2 || (pos.global_x == 0 && (-1..=0).contains(&pos.global_z)));
+    dim.parse_all(&DimensionId::overworld()).unwrap();
+
+    let dir = std::env::temp_dir().join("mc_schem_test_save_dimension");
+    let _ = std::fs::remove_dir_all(&dir);
+    let dir = dir.to_str().unwrap();
+    dim.save_to_folder(dir, &MCASaveOption::default()).unwrap();
+
+    let saved = FolderOnDisk::new(dir);
+    let loaded = mca::parse_multiple_regions(&saved.sub_directory("region"),
+                                             Some(&saved.sub_directory("entities")),
+                                             -64..320, &DimensionId::overworld(), true).unwrap();
+    assert_eq!(loaded.len(), dim.chunks.len());
+    assert!(saved.sub_directory("region").files().iter().any(|f| f.name == "c.0.-1.mcc"));
+    for (pos, variant) in &loaded {
+        let ChunkVariant::Parsed(chunk) = variant else { panic!("Chunk {pos:?} is not parsed") };
+        let src = dim.get_chunk(pos).unwrap();
+        assert_eq!(chunk.time_stamp, src.time_stamp);
+        assert_eq!(chunk.data_version, src.data_version);
+        assert_eq!(chunk.block_entities.len(), src.block_entities.len());
+        assert_eq!(chunk.entities.len(), src.entities.len());
+        assert!(chunk.unparsed_tags.contains_key("Heightmaps"));
+        assert_eq!(chunk.sub_chunks.len(), src.sub_chunks.len());
+        for (y, src_sect) in &src.sub_chunks {
+            let sect = &chunk.sub_chunks[y];
+            for idx in 0..4096 {
+                assert_eq!(src_sect.palette[src_sect.block_id_array[idx] as usize],
+                           sect.palette[sect.block_id_array[idx] as usize]);
+            }
+            assert_eq!(src_sect.biome_array, sect.biome_array);
+        }
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_paste_schematic() {
+    let (schem, _) = Schematic::from_litematica_file("test_files/litematica/test01.litematic",
+                                                     &crate::LitematicaLoadOption::default()).unwrap();
+    let mut dim = Dimension::new(-64..320);
+    let origin = [-21, -3, 7];
+    dim.paste_schematic(&schem, origin, &PasteOption { ignore_air: false, ignore_structure_void: false, paste_entities: true }).unwrap();
+
+    let shape = schem.shape();
+    for x in 0..shape[0] {
+        for y in 0..shape[1] {
+            for z in 0..shape[2] {
+                let a_pos = [x + origin[0], y + origin[1], z + origin[2]];
+                let expected = schem.first_block_at([x, y, z]).unwrap();
+                assert_eq!(dim.block_at(a_pos), Some(expected));
+                assert_eq!(dim.block_entity_at(a_pos).is_some(), schem.first_block_entity_at([x, y, z]).is_some());
+            }
+        }
+    }
+    for (pos, variant) in &dim.chunks {
+        let ChunkVariant::Parsed(chunk) = variant else { panic!("Chunk {pos:?} is not parsed") };
+        assert!(chunk.dirty);
+        assert_eq!(chunk.y_range(), -64..320);
+    }
+    assert!(dim.paste_schematic(&schem, [0, 318, 0], &PasteOption::default()).is_err());
+}
+
+#[test]
+fn test_export_schematic() {
+    let files = FilesInMemory::from_7z_file("test_files/world/00_1.20.2.7z", "").unwrap();
+    let mut dim = Dimension::from_files(&files, false, -64..320, &DimensionId::overworld()).unwrap();
+    let range = [-20..13, -64..100, -7..30];
+    let schem = dim.to_schematic(range.clone()).unwrap();
+    assert_eq!(schem.regions.len(), 1);
+    assert_eq!(schem.shape(), [33, 164, 37]);
+
+    // parse chunks in range to compare
+    for x in -2..2 {
+        for z in -1..2 {
+            let pos = ChunkPos::from_global_pos(&XZCoordinate { x, z });
+            dim.chunks.get_mut(&pos).unwrap().parse_inplace(&pos).unwrap();
+            assert_eq!(dim.get_chunk(&pos).unwrap().data_version, schem.metadata.mc_data_version);
+        }
+    }
+    let reg = &schem.regions[0];
+    for x in range[0].clone() {
+        for y in range[1].clone() {
+            for z in range[2].clone() {
+                let r_pos = [x - range[0].start, y - range[1].start, z - range[2].start];
+                assert_eq!(dim.block_at([x, y, z]), reg.block_at(r_pos));
+                assert_eq!(dim.block_entity_at([x, y, z]).is_some(), reg.block_entity_at(r_pos).is_some());
+                let chunk = dim.get_chunk(&Dimension::block_pos_to_chunk_pos([x, y, z]).0).unwrap();
+                let light = chunk.sub_chunks[&(y.div_euclid(16) as i8)].sky_block_light()
+                    [[y.rem_euclid(16) as usize, z.rem_euclid(16) as usize, x.rem_euclid(16) as usize]];
+                assert_eq!(reg.light_at(r_pos), Some(light));
+            }
+        }
+    }
+    assert!(dim.to_schematic([0..16, 300..330, 0..16]).is_err());
+}
+
 #[test]
 fn test_raids() {
     let files = FilesInMemory::from_7z_file("test_files/world/03_raids-1.20.2.7z", "").unwrap();
     // overworld
-    Dimension::from_files(&files, false, -64..320, 0).unwrap();
-    Dimension::from_files(&files.sub_directory("DIM-1"), false, -64..320, -1).unwrap();
-    Dimension::from_files(&files.sub_directory("DIM1"), false, -64..320, 1).unwrap();
+    Dimension::from_files(&files, false, -64..320, &DimensionId::overworld()).unwrap();
+    Dimension::from_files(&files.sub_directory("DIM-1"), false, -64..320, &DimensionId::nether()).unwrap();
+    Dimension::from_files(&files.sub_directory("DIM1"), false, -64..320, &DimensionId::the_end()).unwrap();
 }
\ No newline at end of file
@@ -9,7 +9,7 @@ use std::sync::Arc;
 use sevenz_rust::SevenZReader;
 
 use crate::error::Error;
-use crate::world::{ArcSlice, FileInfo, FilesInMemory, FilesRead, FolderOnDisk, SubDirectory};
+use crate::world::{ArcSlice, FileInfo, FilesInMemory, FilesRead, FolderOnDisk, ListedFiles, SubDirectory};
 
 impl ArcSlice {
     pub fn from(src: Arc<Vec<u8>>) -> Self {
@@ -97,9 +97,10 @@ impl FilesRead for FolderOnDisk {
         let mut result = Vec::new();
         for entry in walkdir::WalkDir::new(&self.path) {
             if let Ok(entry) = entry {
-                let filename: &str;
-                if let Some(f) = entry.file_name().to_str() {
-                    filename = f;
+                // filename relative to the folder, so that sub directories can be filtered by prefix
+                let filename: String;
+                if let Some(f) = entry.path().strip_prefix(&self.path).ok().and_then(|p| p.to_str()) {
+                    filename = f.replace('\\', "/");
                 } else {
                     continue;
                 }
@@ -109,8 +110,8 @@ impl FilesRead for FolderOnDisk {
                         continue;
                     }
                     let tmp = FileInfo {
-                        name: filename.to_string(),
-                        full_name: filename.to_string(),
+                        full_name: format!("{}/{filename}", self.path),
+                        name: filename,
                         size: metadata.len(),
                     };
                     result.push(tmp);
@@ -246,6 +247,38 @@ impl FilesRead for FilesInMemory {
     }
 }
 
+impl<'a> ListedFiles<'a> {
+    pub fn new(root: &'a dyn FilesRead) -> Self {
+        return ListedFiles { root, files: root.files() };
+    }
+}
+
+impl FilesRead for ListedFiles<'_> {
+    fn sub_directory(&self, dir: &str) -> SubDirectory<'_> {
+        return impl_sub_dir(self, dir);
+    }
+
+    fn path(&self) -> String {
+        return self.root.path();
+    }
+
+    fn files(&self) -> Vec<FileInfo> {
+        return self.files.clone();
+    }
+
+    fn open_file(&self, filename: &str) -> Result<Box<dyn Read + '_>, Error> {
+        return self.root.open_file(filename);
+    }
+
+    fn read_file(&self, filename: &str, dest: &mut Vec<u8>) -> Result<(), Error> {
+        return self.root.read_file(filename, dest);
+    }
+
+    fn read_file_nocopy(&self, filename: &str) -> Result<Option<ArcSlice>, Error> {
+        return self.root.read_file_nocopy(filename);
+    }
+}
+
 impl FilesRead for SubDirectory<'_> {
     fn sub_directory(&self, dir: &str) -> SubDirectory {
         let mut new_dir = self.dirname_with_slash.clone();
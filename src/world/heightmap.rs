@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use fastnbt::{LongArray, Value};
+use crate::block::Block;
+use crate::schem::DataVersion;
+use crate::schem::litematica;
+use crate::world::{Chunk, HeightmapKind};
+use crate::world::chunk::{bits_per_block, MultiBitSet};
+use crate::world::light::is_vanilla;
+
+/// Blocks that don't block motion, like plants and decorations
+const NON_SOLID_IDS: &[&str] = &[
+    "air", "cave_air", "void_air", "structure_void", "light", "water", "lava", "bubble_column",
+    "fire", "soul_fire", "cobweb", "ladder", "lever", "redstone_wire", "tripwire", "tripwire_hook",
+    "snow", "vine", "glow_lichen", "sculk_vein", "kelp", "kelp_plant", "seagrass", "tall_seagrass",
+    "sugar_cane", "nether_portal", "end_portal", "end_gateway", "spore_blossom", "hanging_roots",
+    "cave_vines", "cave_vines_plant", "weeping_vines", "weeping_vines_plant", "twisting_vines",
+    "twisting_vines_plant", "dandelion", "poppy", "blue_orchid", "allium", "azure_bluet",
+    "oxeye_daisy", "cornflower", "lily_of_the_valley", "wither_rose", "sunflower", "lilac",
+    "rose_bush", "peony", "torchflower", "pitcher_plant", "pink_petals", "grass", "short_grass",
+    "tall_grass", "fern", "large_fern", "dead_bush", "wheat", "carrots", "potatoes", "beetroots",
+    "melon_stem", "pumpkin_stem", "attached_melon_stem", "attached_pumpkin_stem", "nether_wart",
+    "sweet_berry_bush", "torchflower_crop", "pitcher_crop", "brown_mushroom", "red_mushroom",
+    "nether_sprouts", "warped_roots", "crimson_roots", "bamboo_sapling", "small_dripleaf",
+];
+
+/// Blocks whose id ends with these suffixes don't block motion, like `oak_sign` and `red_tulip`
+const NON_SOLID_SUFFIXES: &[&str] = &[
+    "torch", "_sign", "_banner", "rail", "_button", "_pressure_plate", "_sapling", "_fungus",
+    "_coral", "_coral_fan", "_tulip", "_propagule",
+];
+
+/// Blocks that are always filled with water
+const WATER_FILLED_IDS: &[&str] = &["water", "lava", "bubble_column", "kelp", "kelp_plant", "seagrass", "tall_seagrass"];
+
+fn blocks_motion(blk: &Block) -> bool {
+    if !is_vanilla(blk) {
+        return true;
+    }
+    let id = blk.id.as_str();
+    return !NON_SOLID_IDS.contains(&id) && !NON_SOLID_SUFFIXES.iter().any(|s| id.ends_with(s));
+}
+
+fn contains_fluid(blk: &Block) -> bool {
+    if !is_vanilla(blk) {
+        return false;
+    }
+    return WATER_FILLED_IDS.contains(&blk.id.as_str())
+        || blk.attributes.get("waterlogged").is_some_and(|v| v == "true");
+}
+
+impl HeightmapKind {
+    pub fn all() -> &'static [HeightmapKind] {
+        return &[HeightmapKind::WorldSurface,
+            HeightmapKind::OceanFloor,
+            HeightmapKind::MotionBlocking,
+            HeightmapKind::MotionBlockingNoLeaves];
+    }
+
+    /// Key of this heightmap in `Heightmaps` tag
+    pub fn nbt_name(&self) -> &'static str {
+        return match self {
+            HeightmapKind::WorldSurface => "WORLD_SURFACE",
+            HeightmapKind::OceanFloor => "OCEAN_FLOOR",
+            HeightmapKind::MotionBlocking => "MOTION_BLOCKING",
+            HeightmapKind::MotionBlockingNoLeaves => "MOTION_BLOCKING_NO_LEAVES",
+        };
+    }
+
+    /// Returns true if `blk` is counted by this heightmap
+    pub fn matches(&self, blk: &Block) -> bool {
+        return match self {
+            HeightmapKind::WorldSurface => !(is_vanilla(blk) && matches!(blk.id.as_str(), "air" | "cave_air" | "void_air")),
+            HeightmapKind::OceanFloor => blocks_motion(blk),
+            HeightmapKind::MotionBlocking => blocks_motion(blk) || contains_fluid(blk),
+            HeightmapKind::MotionBlockingNoLeaves => (blocks_motion(blk) || contains_fluid(blk)) && !blk.id.ends_with("leaves"),
+        };
+    }
+}
+
+impl Chunk {
+    /// Compute heightmap of `kind`, indexed by `z * 16 + x`. Each value is the y right above the
+    /// highest block counted by `kind`, or the lowest y of the chunk if no block in the column is
+    /// counted.
+    pub fn compute_heightmap(&self, kind: HeightmapKind) -> [i32; 256] {
+        let mut result: [Option<i32>; 256] = [None; 256];
+        let mut remaining = 256;
+        for (sect_y, sub_chunk) in self.sub_chunks.iter().rev() {
+            let lut: Vec<bool> = sub_chunk.palette.iter().map(|blk| kind.matches(blk)).collect();
+            if !lut.iter().any(|m| *m) {
+                continue;
+            }
+            for (column, height) in result.iter_mut().enumerate() {
+                if height.is_some() {
+                    continue;
+                }
+                for y in (0..16).rev() {
+                    if lut[sub_chunk.block_id_array[y * 256 + column] as usize] {
+                        *height = Some(*sect_y as i32 * 16 + y as i32 + 1);
+                        remaining -= 1;
+                        break;
+                    }
+                }
+            }
+            if remaining <= 0 {
+                break;
+            }
+        }
+        let y_min = self.y_min();
+        return result.map(|h| h.unwrap_or(y_min));
+    }
+
+    /// Height of the column at `x` and `z`, which are taken modulo 16. Returns the y right above
+    /// the highest block counted by `kind`, or the lowest y of the chunk if no block is counted.
+    pub fn height_at(&self, x: i32, z: i32, kind: HeightmapKind) -> i32 {
+        let column = (z.rem_euclid(16) * 16 + x.rem_euclid(16)) as usize;
+        for (sect_y, sub_chunk) in self.sub_chunks.iter().rev() {
+            for y in (0..16).rev() {
+                let blk = &sub_chunk.palette[sub_chunk.block_id_array[y * 256 + column] as usize];
+                if kind.matches(blk) {
+                    return *sect_y as i32 * 16 + y as i32 + 1;
+                }
+            }
+        }
+        return self.y_min();
+    }
+
+    fn y_min(&self) -> i32 {
+        return self.sub_chunks.keys().next().map_or(0, |y| *y as i32 * 16);
+    }
+
+    /// Encode all heightmaps as `Heightmaps` tag. Values are stored relative to the lowest y. Since
+    /// 1.16 (20w17a), a value never spans 2 longs; before that, values are packed tightly.
+    pub fn heightmaps_to_nbt(&self) -> HashMap<String, Value> {
+        let y_min = self.y_min();
+        let height = self.sub_chunks.len() * 16;
+        let bits = bits_per_block(height + 1, 1);
+        let mut nbt = HashMap::new();
+        for kind in HeightmapKind::all() {
+            let heights = self.compute_heightmap(*kind);
+            let array: Vec<i64> = if self.data_version >= DataVersion::Snapshot_20w17a as i32 {
+                let mut mbs = MultiBitSet::new(256, bits);
+                for (idx, h) in heights.iter().enumerate() {
+                    mbs.set(idx, (h - y_min) as u64);
+                }
+                mbs.to_nbt_array()
+            } else {
+                let mut mbs = litematica::MultiBitSet::new();
+                mbs.reset(bits, 256);
+                for (idx, h) in heights.iter().enumerate() {
+                    mbs.set(idx, (h - y_min) as u64).unwrap();
+                }
+                mbs.as_u64_slice().iter().map(|v| *v as i64).collect()
+            };
+            nbt.insert(kind.nbt_name().to_string(), Value::LongArray(LongArray::new(array)));
+        }
+        return nbt;
+    }
+}
+
+#[test]
+fn test_heightmap() {
+    let mut chunk = Chunk::with_y_range(-64..320);
+    for (y, id) in [(10, "stone"), (20, "oak_leaves"), (30, "water"), (40, "torch")] {
+        chunk.set_block_at([3, y, 5], &Block::from_id(id).unwrap()).unwrap();
+    }
+    for (y, id) in [(10, "stone"), (20, "oak_leaves")] {
+        chunk.set_block_at([4, y, 6], &Block::from_id(id).unwrap()).unwrap();
+    }
+    assert_eq!(chunk.height_at(3, 5, HeightmapKind::WorldSurface), 41);
+    assert_eq!(chunk.height_at(3, 5, HeightmapKind::MotionBlocking), 31);
+    assert_eq!(chunk.height_at(3, 5, HeightmapKind::MotionBlockingNoLeaves), 31);
+    assert_eq!(chunk.height_at(3, 5, HeightmapKind::OceanFloor), 21);
+    assert_eq!(chunk.height_at(4, 6, HeightmapKind::MotionBlocking), 21);
+    assert_eq!(chunk.height_at(4, 6, HeightmapKind::MotionBlockingNoLeaves), 11);
+    assert_eq!(chunk.height_at(-13, 21, HeightmapKind::WorldSurface), 41);
+    assert_eq!(chunk.height_at(0, 0, HeightmapKind::WorldSurface), -64);
+    for kind in HeightmapKind::all() {
+        let heights = chunk.compute_heightmap(*kind);
+        assert_eq!(heights[5 * 16 + 3], chunk.height_at(3, 5, *kind));
+        assert_eq!(heights[0], -64);
+    }
+
+    // 9 bits per value, 7 values in a long since 1.16
+    let nbt = chunk.heightmaps_to_nbt();
+    let Some(Value::LongArray(arr)) = nbt.get("WORLD_SURFACE") else { panic!("WORLD_SURFACE is missing") };
+    assert_eq!(arr.len(), 37);
+    let mut mbs = MultiBitSet::new(256, 9);
+    mbs.set_array_from_nbt(arr);
+    assert_eq!(mbs.get(5 * 16 + 3), 41 + 64);
+
+    // values are packed tightly before 1.16
+    chunk.data_version = DataVersion::Java_1_15_2 as i32;
+    let nbt = chunk.heightmaps_to_nbt();
+    let Some(Value::LongArray(arr)) = nbt.get("OCEAN_FLOOR") else { panic!("OCEAN_FLOOR is missing") };
+    assert_eq!(arr.len(), 36);
+    let data: Vec<u64> = arr.iter().map(|v| *v as u64).collect();
+    let mbs = litematica::MultiBitSet::from_data(&data, 256, 9).unwrap();
+    assert_eq!(mbs.get(5 * 16 + 3), 21 + 64);
+}
@@ -0,0 +1,288 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::ops::Range;
+use fastnbt::Value;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use crate::{unwrap_opt_tag, unwrap_tag};
+use crate::error::{Error, remove_opt_bool_or, remove_opt_or, unwrap_opt_f32, unwrap_opt_i32, unwrap_opt_i64, unwrap_opt_i8};
+use crate::player::{DimensionId, Player};
+use crate::schem::{DataVersion, id_of_nbt_tag};
+use crate::world::{FilesRead, LevelData};
+
+fn remove_string_or(nbt: &mut HashMap<String, Value>, key: &str, tag_path: &str) -> Result<String, Error> {
+    return Ok(unwrap_opt_tag!(nbt.remove(key),String,"".to_string(),format!("{tag_path}/{key}")));
+}
+
+fn parse_string_list(list: &[Value], tag_path: &str) -> Result<Vec<String>, Error> {
+    let mut result = Vec::with_capacity(list.len());
+    for (idx, tag) in list.iter().enumerate() {
+        result.push(unwrap_tag!(tag,String,"".to_string(),format!("{tag_path}/[{idx}]")).clone());
+    }
+    return Ok(result);
+}
+
+/// Follow `path` through nested compounds, returns `None` if any tag is missing or not a compound
+fn compound_at<'a>(nbt: &'a HashMap<String, Value>, path: &[&str]) -> Option<&'a HashMap<String, Value>> {
+    let mut current = nbt;
+    for key in path {
+        current = match current.get(*key)? {
+            Value::Compound(c) => c,
+            _ => return None,
+        };
+    }
+    return Some(current);
+}
+
+impl Default for LevelData {
+    fn default() -> Self {
+        return LevelData {
+            data_version: DataVersion::new() as i32,
+            version_name: String::new(),
+            version_series: "main".to_string(),
+            version_is_snapshot: false,
+            level_name: "New World".to_string(),
+            seed: 0,
+            spawn_pos: [0, 64, 0],
+            spawn_angle: 0.0,
+            game_type: 0,
+            hardcore: false,
+            allow_commands: false,
+            difficulty: 2,
+            difficulty_locked: false,
+            time: 0,
+            day_time: 0,
+            last_played: 0,
+            raining: false,
+            rain_time: 0,
+            thundering: false,
+            thunder_time: 0,
+            clear_weather_time: 0,
+            game_rules: BTreeMap::new(),
+            enabled_datapacks: vec!["vanilla".to_string()],
+            disabled_datapacks: Vec::new(),
+            player: None,
+            unparsed_tags: HashMap::new(),
+        };
+    }
+}
+
+impl LevelData {
+    pub fn new() -> LevelData {
+        return LevelData::default();
+    }
+
+    /// Parse the root compound of `level.dat`
+    pub fn from_nbt(mut nbt: HashMap<String, Value>) -> Result<LevelData, Error> {
+        let tag_path = "/Data";
+        let mut data = unwrap_opt_tag!(nbt.remove("Data"),Compound,HashMap::new(),tag_path.to_string());
+        let data = &mut data;
+        let mut result = LevelData {
+            data_version: remove_opt_or(data, "DataVersion", tag_path, 0, unwrap_opt_i32)?,
+            level_name: remove_string_or(data, "LevelName", tag_path)?,
+            spawn_pos: [remove_opt_or(data, "SpawnX", tag_path, 0, unwrap_opt_i32)?,
+                remove_opt_or(data, "SpawnY", tag_path, 64, unwrap_opt_i32)?,
+                remove_opt_or(data, "SpawnZ", tag_path, 0, unwrap_opt_i32)?],
+            spawn_angle: remove_opt_or(data, "SpawnAngle", tag_path, 0.0, unwrap_opt_f32)?,
+            game_type: remove_opt_or(data, "GameType", tag_path, 0, unwrap_opt_i32)?,
+            hardcore: remove_opt_bool_or(data, "hardcore", tag_path, false)?,
+            allow_commands: remove_opt_bool_or(data, "allowCommands", tag_path, false)?,
+            difficulty: remove_opt_or(data, "Difficulty", tag_path, 2, unwrap_opt_i8)?,
+            difficulty_locked: remove_opt_bool_or(data, "DifficultyLocked", tag_path, false)?,
+            time: remove_opt_or(data, "Time", tag_path, 0, unwrap_opt_i64)?,
+            day_time: remove_opt_or(data, "DayTime", tag_path, 0, unwrap_opt_i64)?,
+            last_played: remove_opt_or(data, "LastPlayed", tag_path, 0, unwrap_opt_i64)?,
+            raining: remove_opt_bool_or(data, "raining", tag_path, false)?,
+            rain_time: remove_opt_or(data, "rainTime", tag_path, 0, unwrap_opt_i32)?,
+            thundering: remove_opt_bool_or(data, "thundering", tag_path, false)?,
+            thunder_time: remove_opt_or(data, "thunderTime", tag_path, 0, unwrap_opt_i32)?,
+            clear_weather_time: remove_opt_or(data, "clearWeatherTime", tag_path, 0, unwrap_opt_i32)?,
+            enabled_datapacks: Vec::new(),
+            ..LevelData::default()
+        };
+
+        if let Some(tag) = data.remove("Version") {
+            let path = format!("{tag_path}/Version");
+            let mut version = unwrap_tag!(tag,Compound,HashMap::new(),path);
+            result.version_name = remove_string_or(&mut version, "Name", &path)?;
+            result.version_series = remove_string_or(&mut version, "Series", &path)?;
+            result.version_is_snapshot = remove_opt_bool_or(&mut version, "Snapshot", &path, false)?;
+        }
+
+        // the seed is moved into WorldGenSettings since 1.16
+        result.seed = remove_opt_or(data, "RandomSeed", tag_path, 0, unwrap_opt_i64)?;
+        if let Some(Value::Compound(settings)) = data.get("WorldGenSettings") {
+            result.seed = unwrap_opt_i64(settings, "seed", &format!("{tag_path}/WorldGenSettings"))?;
+        }
+
+        if let Some(tag) = data.remove("GameRules") {
+            let path = format!("{tag_path}/GameRules");
+            for (key, value) in unwrap_tag!(tag,Compound,HashMap::new(),path) {
+                let value = unwrap_tag!(value,String,"".to_string(),format!("{path}/{key}"));
+                result.game_rules.insert(key, value);
+            }
+        }
+
+        if let Some(tag) = data.remove("DataPacks") {
+            let path = format!("{tag_path}/DataPacks");
+            let packs = unwrap_tag!(tag,Compound,HashMap::new(),path);
+            if let Some(enabled) = packs.get("Enabled") {
+                let path = format!("{path}/Enabled");
+                result.enabled_datapacks = parse_string_list(unwrap_tag!(enabled,List,vec![],path), &path)?;
+            }
+            if let Some(disabled) = packs.get("Disabled") {
+                let path = format!("{path}/Disabled");
+                result.disabled_datapacks = parse_string_list(unwrap_tag!(disabled,List,vec![],path), &path)?;
+            }
+        }
+
+        if let Some(tag) = data.remove("Player") {
+            let path = format!("{tag_path}/Player");
+            result.player = Some(Player::from_nbt(unwrap_tag!(tag,Compound,HashMap::new(),path), &path)?);
+        }
+
+        result.unparsed_tags = std::mem::take(data);
+        return Ok(result);
+    }
+
+    /// Root compound of `level.dat`
+    pub fn to_nbt(&self) -> HashMap<String, Value> {
+        let mut data = self.unparsed_tags.clone();
+        if self.data_version != 0 {
+            data.insert("DataVersion".to_string(), Value::Int(self.data_version));
+        }
+        if !self.version_name.is_empty() {
+            let mut version = HashMap::new();
+            version.insert("Id".to_string(), Value::Int(self.data_version));
+            version.insert("Name".to_string(), Value::String(self.version_name.clone()));
+            version.insert("Series".to_string(), Value::String(self.version_series.clone()));
+            version.insert("Snapshot".to_string(), Value::Byte(self.version_is_snapshot as i8));
+            data.insert("Version".to_string(), Value::Compound(version));
+        }
+        data.insert("LevelName".to_string(), Value::String(self.level_name.clone()));
+        if let Some(Value::Compound(settings)) = data.get_mut("WorldGenSettings") {
+            settings.insert("seed".to_string(), Value::Long(self.seed));
+        } else {
+            data.insert("RandomSeed".to_string(), Value::Long(self.seed));
+        }
+        for (key, value) in [("SpawnX", self.spawn_pos[0]), ("SpawnY", self.spawn_pos[1]), ("SpawnZ", self.spawn_pos[2]),
+            ("GameType", self.game_type), ("rainTime", self.rain_time), ("thunderTime", self.thunder_time),
+            ("clearWeatherTime", self.clear_weather_time)] {
+            data.insert(key.to_string(), Value::Int(value));
+        }
+        data.insert("SpawnAngle".to_string(), Value::Float(self.spawn_angle));
+        for (key, value) in [("hardcore", self.hardcore), ("allowCommands", self.allow_commands),
+            ("DifficultyLocked", self.difficulty_locked), ("raining", self.raining), ("thundering", self.thundering)] {
+            data.insert(key.to_string(), Value::Byte(value as i8));
+        }
+        data.insert("Difficulty".to_string(), Value::Byte(self.difficulty));
+        for (key, value) in [("Time", self.time), ("DayTime", self.day_time), ("LastPlayed", self.last_played)] {
+            data.insert(key.to_string(), Value::Long(value));
+        }
+
+        let game_rules = self.game_rules.iter()
+            .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+            .collect();
+        data.insert("GameRules".to_string(), Value::Compound(game_rules));
+
+        let mut packs = HashMap::new();
+        packs.insert("Enabled".to_string(),
+                     Value::List(self.enabled_datapacks.iter().map(|p| Value::String(p.clone())).collect()));
+        packs.insert("Disabled".to_string(),
+                     Value::List(self.disabled_datapacks.iter().map(|p| Value::String(p.clone())).collect()));
+        data.insert("DataPacks".to_string(), Value::Compound(packs));
+        if let Some(player) = &self.player {
+            data.insert("Player".to_string(), Value::Compound(player.to_nbt()));
+        }
+
+        let mut nbt = HashMap::new();
+        nbt.insert("Data".to_string(), Value::Compound(data));
+        return nbt;
+    }
+
+    /// Load from gzip-compressed nbt
+    pub fn from_reader(src: &mut dyn Read) -> Result<LevelData, Error> {
+        let decoder = GzDecoder::new(src);
+        let nbt: HashMap<String, Value> = match fastnbt::from_reader(decoder) {
+            Ok(n) => n,
+            Err(e) => return Err(Error::NBTReadError(e)),
+        };
+        return Self::from_nbt(nbt);
+    }
+
+    pub fn from_file(filename: &str) -> Result<LevelData, Error> {
+        let mut file = match File::open(filename) {
+            Ok(f) => f,
+            Err(e) => return Err(Error::FileOpenError(e)),
+        };
+        return Self::from_reader(&mut file);
+    }
+
+    /// Load `level.dat` in the root of a world
+    pub fn from_files(files: &dyn FilesRead) -> Result<LevelData, Error> {
+        let mut src = files.open_file("level.dat")?;
+        return Self::from_reader(&mut src);
+    }
+
+    /// Write as gzip-compressed nbt
+    pub fn to_writer(&self, dest: &mut dyn Write) -> Result<(), Error> {
+        let mut encoder = GzEncoder::new(dest, Compression::default());
+        if let Err(e) = fastnbt::to_writer(&mut encoder, &self.to_nbt()) {
+            return Err(Error::NBTWriteError(e));
+        }
+        if let Err(e) = encoder.finish() {
+            return Err(Error::IOWriteError(e));
+        }
+        return Ok(());
+    }
+
+    pub fn save_file(&self, filename: &str) -> Result<(), Error> {
+        let mut file = match File::create(filename) {
+            Ok(f) => f,
+            Err(e) => return Err(Error::FileCreateError(e)),
+        };
+        return self.to_writer(&mut file);
+    }
+
+    /// Y range of the dimension type written inline in `WorldGenSettings`, `None` if the dimension
+    /// is not listed or its type is referred by id.
+    pub fn custom_y_range(&self, dimension_id: &DimensionId) -> Option<Range<i32>> {
+        let dim_type = compound_at(&self.unparsed_tags, &["WorldGenSettings", "dimensions", dimension_id.as_str(), "type"])?;
+        if let (Some(Value::Int(min_y)), Some(Value::Int(height))) = (dim_type.get("min_y"), dim_type.get("height")) {
+            return Some(*min_y..(*min_y + *height));
+        }
+        return None;
+    }
+
+    /// Id of the dimension type if `WorldGenSettings` refers it by id, like `minecraft:overworld`
+    pub fn dimension_type_id(&self, dimension_id: &DimensionId) -> Option<&str> {
+        let dim = compound_at(&self.unparsed_tags, &["WorldGenSettings", "dimensions", dimension_id.as_str()])?;
+        return match dim.get("type") {
+            Some(Value::String(s)) => Some(s),
+            _ => None,
+        };
+    }
+
+    /// Y range of a vanilla dimension type, deduced from data version. Unknown types are regarded
+    /// as 256 blocks high starting from 0.
+    pub fn vanilla_y_range(&self, dimension_type_id: &str) -> Range<i32> {
+        let is_overworld = dimension_type_id == "minecraft:overworld" || dimension_type_id == "minecraft:overworld_caves";
+        if is_overworld && self.data_version >= DataVersion::Snapshot_21w37a as i32 {
+            return -64..320;
+        }
+        return 0..256;
+    }
+
+    /// Y range of a dimension. Custom dimension types in `WorldGenSettings` are respected, otherwise
+    /// the range is deduced from data version. Dimension types defined by datapacks are not visible
+    /// here, they are resolved by `World::from_files`.
+    pub fn dimension_y_range(&self, dimension_id: &DimensionId) -> Range<i32> {
+        if let Some(range) = self.custom_y_range(dimension_id) {
+            return range;
+        }
+        let type_id = self.dimension_type_id(dimension_id).unwrap_or(dimension_id.as_str());
+        return self.vanilla_y_range(type_id);
+    }
+}
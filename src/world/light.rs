@@ -0,0 +1,461 @@
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use crate::block::Block;
+use crate::region::Light;
+use crate::world::{Chunk, ChunkPos, ChunkVariant, Dimension, XZCoordinate};
+
+/// Blocks that light passes through without being reduced
+const TRANSPARENT_IDS: &[&str] = &[
+    "air", "cave_air", "void_air", "structure_void", "barrier", "light", "glass", "glass_pane",
+    "iron_bars", "chain", "ladder", "lever", "redstone_wire", "repeater", "comparator", "tripwire",
+    "tripwire_hook", "scaffolding", "snow", "cactus", "sugar_cane", "bamboo", "bamboo_sapling",
+    "kelp", "kelp_plant", "seagrass", "tall_seagrass", "lily_pad", "vine", "glow_lichen",
+    "sculk_vein", "hopper", "cauldron", "water_cauldron", "lava_cauldron", "powder_snow_cauldron",
+    "cake", "flower_pot", "end_rod", "lightning_rod", "bell", "lantern", "soul_lantern", "conduit",
+    "beacon", "campfire", "soul_campfire", "brewing_stand", "enchanting_table", "anvil",
+    "chipped_anvil", "damaged_anvil", "grindstone", "stonecutter", "lectern", "dragon_egg",
+    "sea_pickle", "turtle_egg", "sniffer_egg", "frogspawn", "pointed_dripstone", "amethyst_cluster",
+    "large_amethyst_bud", "medium_amethyst_bud", "small_amethyst_bud", "spawner", "honey_block",
+    "slime_block", "chorus_plant", "chorus_flower", "nether_portal", "end_portal", "end_gateway",
+    "fire", "soul_fire", "big_dripleaf", "big_dripleaf_stem", "small_dripleaf", "spore_blossom",
+    "hanging_roots", "cave_vines", "cave_vines_plant", "weeping_vines", "weeping_vines_plant",
+    "twisting_vines", "twisting_vines_plant", "dandelion", "poppy", "blue_orchid", "allium",
+    "azure_bluet", "oxeye_daisy", "cornflower", "lily_of_the_valley", "wither_rose", "sunflower",
+    "lilac", "rose_bush", "peony", "torchflower", "pitcher_plant", "pink_petals", "grass",
+    "short_grass", "tall_grass", "fern", "large_fern", "dead_bush", "wheat", "carrots", "potatoes",
+    "beetroots", "melon_stem", "pumpkin_stem", "attached_melon_stem", "attached_pumpkin_stem",
+    "nether_wart", "sweet_berry_bush", "cocoa", "torchflower_crop", "pitcher_crop", "chest",
+    "trapped_chest", "ender_chest", "candle", "candle_cake", "brown_mushroom", "red_mushroom",
+    "nether_sprouts", "warped_roots", "crimson_roots", "sculk_sensor", "calibrated_sculk_sensor",
+    "sculk_shrieker", "decorated_pot", "moss_carpet", "daylight_detector",
+];
+
+/// Blocks whose id ends with these suffixes are transparent, like `oak_fence` and `white_candle`
+const TRANSPARENT_SUFFIXES: &[&str] = &[
+    "_glass", "_glass_pane", "torch", "_fence", "_fence_gate", "_wall", "_door", "_trapdoor",
+    "_sign", "_banner", "rail", "_button", "_pressure_plate", "_carpet", "_bed", "_stairs",
+    "_candle", "_candle_cake", "_head", "_skull", "_sapling", "_fungus", "_coral", "_coral_fan",
+    "_tulip", "_propagule",
+];
+
+/// Blocks that reduce light by 1, like water and leaves
+const DAMPENING_IDS: &[&str] = &["water", "bubble_column", "ice", "frosted_ice", "cobweb"];
+
+pub(super) fn is_vanilla(blk: &Block) -> bool {
+    return blk.namespace.is_empty() || blk.namespace == "minecraft";
+}
+
+impl Block {
+    /// Light level emitted by this block, in range [0, 15]. Blocks with `lit=false` don't emit
+    /// light, and unknown blocks with `lit=true` are treated as full light sources.
+    pub fn light_emission(&self) -> u8 {
+        let lit = self.attributes.get("lit").map(|v| v == "true");
+        if lit == Some(false) {
+            return 0;
+        }
+        let int_property = |key: &str, default: u8| -> u8 {
+            return self.attributes.get(key).and_then(|v| v.parse().ok()).unwrap_or(default);
+        };
+        if is_vanilla(self) {
+            let level = match self.id.as_str() {
+                "glowstone" | "sea_lantern" | "jack_o_lantern" | "lantern" | "shroomlight" | "beacon"
+                | "conduit" | "end_gateway" | "end_portal" | "fire" | "lava" | "lava_cauldron"
+                | "campfire" | "redstone_lamp" | "ochre_froglight" | "verdant_froglight"
+                | "pearlescent_froglight" => 15,
+                "torch" | "wall_torch" | "end_rod" => 14,
+                "furnace" | "blast_furnace" | "smoker" => 13,
+                "nether_portal" => 11,
+                "soul_torch" | "soul_wall_torch" | "soul_lantern" | "soul_fire" | "soul_campfire"
+                | "crying_obsidian" => 10,
+                "redstone_ore" | "deepslate_redstone_ore" => 9,
+                "redstone_torch" | "redstone_wall_torch" | "enchanting_table" | "ender_chest"
+                | "glow_lichen" => 7,
+                "amethyst_cluster" => 5,
+                "large_amethyst_bud" => 4,
+                "magma_block" => 3,
+                "medium_amethyst_bud" => 2,
+                "small_amethyst_bud" | "brewing_stand" | "brown_mushroom" | "dragon_egg"
+                | "end_portal_frame" | "sculk_sensor" | "calibrated_sculk_sensor" => 1,
+                "cave_vines" | "cave_vines_plant" => {
+                    if self.attributes.get("berries").is_some_and(|v| v == "true") { 14 } else { 0 }
+                }
+                "light" => int_property("level", 15),
+                "respawn_anchor" => [0, 3, 7, 11, 15][int_property("charges", 0).min(4) as usize],
+                "sea_pickle" => {
+                    if self.attributes.get("waterlogged").is_some_and(|v| v == "true") {
+                        3 * int_property("pickles", 1).min(4) + 3
+                    } else {
+                        0
+                    }
+                }
+                id if lit == Some(true) && (id == "candle" || id.ends_with("_candle")) => {
+                    3 * int_property("candles", 1).min(4)
+                }
+                id if lit == Some(true) && id.ends_with("candle_cake") => 3,
+                _ => 0,
+            };
+            if level > 0 {
+                return level.min(15);
+            }
+        }
+        if lit == Some(true) {
+            return 15;
+        }
+        return 0;
+    }
+
+    /// How much light is reduced when passing through this block, in range [0, 15]. 15 means the
+    /// block is opaque. Unknown blocks are treated as opaque.
+    pub fn light_opacity(&self) -> u8 {
+        if !is_vanilla(self) {
+            return 15;
+        }
+        let id = self.id.as_str();
+        // a slab is transparent unless it's a double slab, which fills the whole block
+        let partial_slab = id.ends_with("_slab") && self.attributes.get("type").map_or(true, |t| t != "double");
+        let mut opacity = if TRANSPARENT_IDS.contains(&id) || partial_slab
+            || TRANSPARENT_SUFFIXES.iter().any(|s| id.ends_with(s)) {
+            0
+        } else if DAMPENING_IDS.contains(&id) || id.ends_with("leaves") {
+            1
+        } else {
+            15
+        };
+        // tinted glass blocks light
+        if id == "tinted_glass" {
+            opacity = 15;
+        }
+        if opacity == 0 && self.attributes.get("waterlogged").is_some_and(|v| v == "true") {
+            opacity = 1;
+        }
+        return opacity;
+    }
+}
+
+/// Opacity and light of a whole chunk, stored in y,z,x
+struct ChunkLight {
+    /// Lowest y of the chunk
+    y_min: i32,
+    height: i32,
+    opacity: Vec<u8>,
+    emission: Vec<u8>,
+    sky: Vec<u8>,
+    block: Vec<u8>,
+}
+
+impl ChunkLight {
+    fn from_chunk(chunk: &Chunk) -> ChunkLight {
+        let (sect_min, sect_max) = match (chunk.sub_chunks.keys().next(), chunk.sub_chunks.keys().next_back()) {
+            (Some(min), Some(max)) => (*min as i32, *max as i32),
+            _ => (0, -1),
+        };
+        let height = (sect_max - sect_min + 1) * 16;
+        let volume = height as usize * 256;
+        let mut result = ChunkLight {
+            y_min: sect_min * 16,
+            height,
+            opacity: vec![0; volume],
+            emission: vec![0; volume],
+            sky: vec![0; volume],
+            block: vec![0; volume],
+        };
+        for (sect_y, sub_chunk) in &chunk.sub_chunks {
+            let opacity_lut: Vec<u8> = sub_chunk.palette.iter().map(|b| b.light_opacity()).collect();
+            let emission_lut: Vec<u8> = sub_chunk.palette.iter().map(|b| b.light_emission()).collect();
+            let offset = (*sect_y as i32 - sect_min) as usize * 4096;
+            for (idx, blk_id) in sub_chunk.block_id_array.iter().enumerate() {
+                result.opacity[offset + idx] = opacity_lut[*blk_id as usize];
+                result.emission[offset + idx] = emission_lut[*blk_id as usize];
+            }
+        }
+        return result;
+    }
+
+    /// Index of position in this chunk, x and z are in [0, 16), while y is global
+    fn index_of(&self, x: i32, y: i32, z: i32) -> Option<usize> {
+        let y = y - self.y_min;
+        if y < 0 || y >= self.height {
+            return None;
+        }
+        return Some(((y * 16 + z) * 16 + x) as usize);
+    }
+
+    fn write_to_chunk(&self, chunk: &mut Chunk) {
+        let sect_min = self.y_min.div_euclid(16);
+        for (sect_y, sub_chunk) in chunk.sub_chunks.iter_mut() {
+            let offset = (*sect_y as i32 - sect_min) as usize * 4096;
+            for (idx, light) in sub_chunk.sky_block_light_array.iter_mut().enumerate() {
+                *light = Light::new(self.sky[offset + idx], self.block[offset + idx]);
+            }
+        }
+        chunk.is_light_on = true;
+    }
+}
+
+/// Move from (x, z) in chunk `c_idx` by (dx, dz), returns the chunk index and local position.
+/// `None` if the destination chunk is not loaded.
+fn step_xz(neighbors: &[[Option<usize>; 4]], c_idx: usize, x: i32, z: i32, dx: i32, dz: i32) -> Option<(usize, i32, i32)> {
+    let (nx, nz) = (x + dx, z + dz);
+    // neighbors are stored in +x, -x, +z, -z
+    let crossed = if nx >= 16 { Some(0) } else if nx < 0 { Some(1) } else if nz >= 16 { Some(2) } else if nz < 0 { Some(3) } else { None };
+    return match crossed {
+        None => Some((c_idx, nx, nz)),
+        Some(side) => Some((neighbors[c_idx][side]?, nx.rem_euclid(16), nz.rem_euclid(16))),
+    };
+}
+
+/// Propagate light in flood fill. Each step reduces light by the opacity of the block it enters,
+/// and at least 1.
+fn propagate(chunks: &mut [ChunkLight], neighbors: &[[Option<usize>; 4]], is_sky: bool,
+             mut queue: VecDeque<(usize, i32, i32, i32)>) {
+    const DIRECTIONS: [[i32; 3]; 6] = [[1, 0, 0], [-1, 0, 0], [0, 1, 0], [0, -1, 0], [0, 0, 1], [0, 0, -1]];
+    while let Some((c_idx, x, y, z)) = queue.pop_front() {
+        let level = {
+            let c = &chunks[c_idx];
+            let idx = c.index_of(x, y, z).unwrap();
+            if is_sky { c.sky[idx] } else { c.block[idx] }
+        };
+        if level <= 1 {
+            continue;
+        }
+        for dir in &DIRECTIONS {
+            let ny = y + dir[1];
+            let Some((n_c_idx, nx, nz)) = step_xz(neighbors, c_idx, x, z, dir[0], dir[2]) else {
+                continue;
+            };
+            let n = &mut chunks[n_c_idx];
+            let n_idx = match n.index_of(nx, ny, nz) {
+                Some(i) => i,
+                None => continue,
+            };
+            let reduce = n.opacity[n_idx].max(1);
+            if level <= reduce {
+                continue;
+            }
+            let dest = if is_sky { &mut n.sky[n_idx] } else { &mut n.block[n_idx] };
+            if *dest < level - reduce {
+                *dest = level - reduce;
+                queue.push_back((n_c_idx, nx, ny, nz));
+            }
+        }
+    }
+}
+
+/// Chunks are lit in square tiles of this size, so that memory is bounded for large dimensions
+const LIGHT_TILE_CHUNKS: i32 = 8;
+
+/// Compute light of chunks together, so that light spreads across their borders. Only light of
+/// chunks that `is_target` accepts is returned.
+fn compute_light_of_chunks(chunks: &[(ChunkPos, &Chunk)], is_target: impl Fn(&ChunkPos) -> bool) -> Vec<(ChunkPos, ChunkLight)> {
+    let index_of: HashMap<ChunkPos, usize> = chunks.iter().enumerate().map(|(idx, (pos, _))| (*pos, idx)).collect();
+    let neighbors: Vec<[Option<usize>; 4]> = chunks.iter().map(|(pos, _)| {
+        let at = |dx: i32, dz: i32| -> Option<usize> {
+            let p = ChunkPos::from_global_pos(&XZCoordinate { x: pos.global_x + dx, z: pos.global_z + dz });
+            return index_of.get(&p).copied();
+        };
+        return [at(1, 0), at(-1, 0), at(0, 1), at(0, -1)];
+    }).collect();
+    let mut lights: Vec<ChunkLight> = chunks.iter().map(|(_, chunk)| ChunkLight::from_chunk(chunk)).collect();
+
+    // sky light goes straight down from the top, until it's blocked
+    let mut block_queue = VecDeque::new();
+    for (c_idx, c) in lights.iter_mut().enumerate() {
+        for z in 0..16 {
+            for x in 0..16 {
+                let mut level = 15u8;
+                for y in (c.y_min..c.y_min + c.height).rev() {
+                    let idx = c.index_of(x, y, z).unwrap();
+                    level = level.saturating_sub(c.opacity[idx]);
+                    if level == 0 {
+                        break;
+                    }
+                    c.sky[idx] = level;
+                }
+            }
+        }
+        for y in c.y_min..c.y_min + c.height {
+            for z in 0..16 {
+                for x in 0..16 {
+                    let idx = c.index_of(x, y, z).unwrap();
+                    if c.emission[idx] > 0 {
+                        c.block[idx] = c.emission[idx];
+                        block_queue.push_back((c_idx, x, y, z));
+                    }
+                }
+            }
+        }
+    }
+    // Spreading sky light up or down never makes it brighter than the straight light, so only
+    // cells brighter than a horizontal neighbor start spreading. This keeps the queue small under
+    // open sky.
+    let mut sky_queue = VecDeque::new();
+    for (c_idx, c) in lights.iter().enumerate() {
+        for y in c.y_min..c.y_min + c.height {
+            for z in 0..16 {
+                for x in 0..16 {
+                    let level = c.sky[c.index_of(x, y, z).unwrap()];
+                    if level <= 1 {
+                        continue;
+                    }
+                    let spreads = [[1, 0], [-1, 0], [0, 1], [0, -1]].iter().any(|[dx, dz]| {
+                        let Some((n_c_idx, nx, nz)) = step_xz(&neighbors, c_idx, x, z, *dx, *dz) else {
+                            return false;
+                        };
+                        let n = &lights[n_c_idx];
+                        return n.index_of(nx, y, nz).is_some_and(|n_idx| n.sky[n_idx] + 1 < level);
+                    });
+                    if spreads {
+                        sky_queue.push_back((c_idx, x, y, z));
+                    }
+                }
+            }
+        }
+    }
+    propagate(&mut lights, &neighbors, true, sky_queue);
+    propagate(&mut lights, &neighbors, false, block_queue);
+
+    return chunks.iter().zip(lights)
+        .filter(|((pos, _), _)| is_target(pos))
+        .map(|((pos, _), light)| (*pos, light)).collect();
+}
+
+impl Chunk {
+    /// Recompute sky light and block light of this chunk alone, light from neighbor chunks is
+    /// ignored. `is_light_on` is set to true.
+    pub fn compute_light(&mut self) {
+        let pos = ChunkPos::from_global_pos(&XZCoordinate { x: 0, z: 0 });
+        let lights = compute_light_of_chunks(&[(pos, &*self)], |_| true);
+        for (_, light) in lights {
+            light.write_to_chunk(self);
+        }
+    }
+}
+
+impl Dimension {
+    /// Recompute sky light and block light of all parsed chunks, and light spreads across borders
+    /// of parsed chunks. Unparsed chunks are not changed, and they don't light their neighbors.
+    ///
+    /// Chunks are lit tile by tile. Light travels less than 16 blocks, so light of a tile only
+    /// depends on the tile and chunks around it.
+    pub fn compute_light(&mut self) {
+        let mut tiles: BTreeMap<(i32, i32), Vec<ChunkPos>> = BTreeMap::new();
+        for (pos, variant) in &self.chunks {
+            if let ChunkVariant::Parsed(_) = variant {
+                let tile = (pos.global_x.div_euclid(LIGHT_TILE_CHUNKS), pos.global_z.div_euclid(LIGHT_TILE_CHUNKS));
+                tiles.entry(tile).or_default().push(*pos);
+            }
+        }
+
+        for targets in tiles.values() {
+            let targets: HashSet<ChunkPos> = targets.iter().copied().collect();
+            let mut positions = HashSet::new();
+            for pos in &targets {
+                for dx in -1..=1 {
+                    for dz in -1..=1 {
+                        positions.insert(ChunkPos::from_global_pos(&XZCoordinate { x: pos.global_x + dx, z: pos.global_z + dz }));
+                    }
+                }
+            }
+            let chunks: Vec<(ChunkPos, &Chunk)> = positions.iter()
+                .filter_map(|pos| self.get_chunk(pos).map(|chunk| (*pos, chunk))).collect();
+            let lights = compute_light_of_chunks(&chunks, |pos| targets.contains(pos));
+            for (pos, light) in lights {
+                if let Some(chunk) = self.get_chunk_mut(&pos) {
+                    light.write_to_chunk(chunk);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_compute_light() {
+    use crate::world::AbsolutePosIndexed;
+    let mut dim = Dimension::new(-64..320);
+    let pos_a = ChunkPos::from_global_pos(&XZCoordinate { x: 0, z: 0 });
+    let pos_b = ChunkPos::from_global_pos(&XZCoordinate { x: 1, z: 0 });
+    let stone = Block::from_id("stone").unwrap();
+    let glowstone = Block::from_id("glowstone").unwrap();
+    for pos in [pos_a, pos_b] {
+        let mut chunk = Chunk::with_y_range(-64..320);
+        for x in 0..16 {
+            for z in 0..16 {
+                chunk.set_block_at([x, 0, z], &stone).unwrap();
+            }
+        }
+        dim.chunks.insert(pos, ChunkVariant::Parsed(chunk));
+    }
+    // a light source under the stone floor, next to the chunk border
+    if let Some(chunk) = dim.get_chunk_mut(&pos_a) {
+        chunk.set_block_at([14, -5, 8], &glowstone).unwrap();
+        chunk.set_block_at([14, 200, 8], &Block::from_id("oak_leaves").unwrap()).unwrap();
+    }
+    dim.compute_light();
+
+    let light_at = |a_pos: [i32; 3]| -> Light {
+        let (chunk_pos, _) = Dimension::block_pos_to_chunk_pos(a_pos);
+        let chunk = dim.get_chunk(&chunk_pos).unwrap();
+        let sub_chunk = &chunk.sub_chunks[&(a_pos[1].div_euclid(16) as i8)];
+        return sub_chunk.sky_block_light()[[a_pos[1].rem_euclid(16) as usize, a_pos[2].rem_euclid(16) as usize, a_pos[0].rem_euclid(16) as usize]];
+    };
+    assert_eq!(dim.block_at([14, -5, 8]), Some(&glowstone));
+    assert_eq!(light_at([5, 1, 5]), Light::new(15, 0));
+    assert_eq!(light_at([14, 100, 8]), Light::new(14, 0));
+    assert_eq!(light_at([5, -1, 5]), Light::new(0, 0));
+    assert_eq!(light_at([14, -4, 8]).block_light(), 14);
+    // light crosses the chunk border
+    assert_eq!(light_at([17, -5, 8]).block_light(), 12);
+    assert_eq!(light_at([14, -5, 14]).block_light(), 9);
+    for (_, variant) in &dim.chunks {
+        let ChunkVariant::Parsed(chunk) = variant else { panic!("Chunk is not parsed") };
+        assert!(chunk.is_light_on);
+    }
+}
+
+#[test]
+fn test_compute_light_across_tiles() {
+    let mut dim = Dimension::new(0..32);
+    let positions: Vec<ChunkPos> = (LIGHT_TILE_CHUNKS - 1..=LIGHT_TILE_CHUNKS)
+        .map(|x| ChunkPos::from_global_pos(&XZCoordinate { x, z: 0 })).collect();
+    let stone = Block::from_id("stone").unwrap();
+    for pos in &positions {
+        let mut chunk = Chunk::with_y_range(0..32);
+        // a roof over the whole area except the first column of the first chunk
+        for x in 0..16 {
+            for z in 0..16 {
+                if *pos != positions[0] || x != 0 || z != 0 {
+                    chunk.set_block_at([x, 20, z], &stone).unwrap();
+                }
+            }
+        }
+        dim.chunks.insert(*pos, ChunkVariant::Parsed(chunk));
+    }
+    if let Some(chunk) = dim.get_chunk_mut(&positions[0]) {
+        chunk.set_block_at([15, 5, 8], &Block::from_id("glowstone").unwrap()).unwrap();
+    }
+    dim.compute_light();
+
+    let light_at = |pos: &ChunkPos, x: usize, y: usize, z: usize| -> Light {
+        let chunk = dim.get_chunk(pos).unwrap();
+        return chunk.sub_chunks[&((y / 16) as i8)].sky_block_light()[[y % 16, z, x]];
+    };
+    // block light crosses the border of tiles
+    assert_eq!(light_at(&positions[1], 0, 5, 8).block_light(), 14);
+    assert_eq!(light_at(&positions[1], 3, 5, 8).block_light(), 11);
+    // sky light spreads sideways under the roof
+    assert_eq!(light_at(&positions[0], 0, 10, 0).sky_light(), 15);
+    assert_eq!(light_at(&positions[0], 2, 10, 1).sky_light(), 12);
+    assert_eq!(light_at(&positions[1], 0, 10, 0).sky_light(), 0);
+}
+
+#[test]
+fn test_light_opacity() {
+    let opacity = |id: &str| Block::from_id(id).unwrap().light_opacity();
+    assert_eq!(opacity("stone_slab"), 0);
+    assert_eq!(opacity("stone_slab[type=top]"), 0);
+    assert_eq!(opacity("stone_slab[type=bottom,waterlogged=true]"), 1);
+    assert_eq!(opacity("stone_slab[type=double]"), 15);
+    assert_eq!(opacity("oak_slab[type=double,waterlogged=false]"), 15);
+    assert_eq!(opacity("oak_stairs[half=top]"), 0);
+    assert_eq!(opacity("oak_door[half=upper]"), 0);
+    assert_eq!(opacity("stone"), 15);
+}
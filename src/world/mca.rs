@@ -1,16 +1,20 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::Write;
 use std::ops::Range;
 use std::sync::Arc;
 use fastnbt::Value;
+use flate2::Compression;
 use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::{GzEncoder, ZlibEncoder};
 use regex::Regex;
 use world::{XZCoordinate, ChunkPos};
 use crate::error::Error;
+use crate::player::DimensionId;
 use crate::world;
-use crate::world::{ArcSlice, Chunk, ChunkVariant, Dimension, FileInfo, MCARawData, NBTWithSource, RefOrObject, UnparsedChunkData};
+use crate::world::{ArcSlice, Chunk, ChunkVariant, Dimension, FileInfo, MCARawData, MCASaveOption, NBTWithSource, RefOrObject, UnparsedChunkData};
 use world::FilesRead;
-use crate::raid::RaidList;
 
 pub const SEGMENT_BYTES: usize = 4096;
 
@@ -33,6 +37,20 @@ pub fn parse_mca_filename(filename: &str) -> Option<XZCoordinate> {
     return Some(XZCoordinate { x, z });
 }
 
+/// Parse filename of McRegion files, which are used from Beta 1.3 to 1.1
+pub fn parse_mcr_filename(filename: &str) -> Option<XZCoordinate> {
+    let reg = Regex::new(r"^r.(-*\d+).(-*\d+).mcr$").unwrap();
+    let cap = reg.captures(filename)?;
+    debug_assert!(cap.iter().len() == 3);
+
+    let mut iter = cap.iter();
+    iter.next();
+    let x = i32::from_str_radix(iter.next().unwrap().unwrap().as_str(), 10).ok()?;
+    let z = i32::from_str_radix(iter.next().unwrap().unwrap().as_str(), 10).ok()?;
+
+    return Some(XZCoordinate { x, z });
+}
+
 pub fn parse_mcc_filename(filename: &str) -> Option<ChunkPos> {
     let reg = Regex::new(r"^c.(-*\d+).(-*\d+).mcc$").unwrap();
     let cap = reg.captures(filename)?;
@@ -53,6 +71,8 @@ fn test_parse_mca_filename() {
         let pos = parse_mca_filename(name).unwrap();
         let filename = format!("r.{}.{}.mca", pos.x, pos.z);
         assert_eq!(filename, name);
+        assert_eq!(parse_mcr_filename(&name.replace(".mca", ".mcr")), Some(pos));
+        assert!(parse_mcr_filename(name).is_none());
     }
 }
 
@@ -149,24 +169,34 @@ impl MCARawData {
     pub fn to_nbt(&self) -> Result<NBTWithSource, Error> {
         let parse_opt: Result<HashMap<String, Value>, fastnbt::error::Error>;
 
-        if self.data[0..2] == [0x78, 0x9c] {//zlib
+        // The highest bit only marks that the data is stored in an external .mcc file
+        let method = self.compress_method & 0x7F;
+        if self.compress_method & 0x80 != 0 && method == 3
+            && self.data.len() >= 2 && self.data[0..2] == [0x78, 0x9c] {
             // Some mcc files are stored as zlib, but the compress method is 130.
             // This is to fix minecraft's error(at least in 1.20.2
             let src = ZlibDecoder::new(self.data.as_slice());
             parse_opt = fastnbt::from_reader(src);
         } else {
-            match self.compress_method {
-                1 | 128 => {//gzip
+            match method {
+                1 => {//gzip
                     let src = GzDecoder::new(self.data.as_slice());
                     parse_opt = fastnbt::from_reader(src);
                 }
-                2 | 129 => {//zlib
+                2 => {//zlib
                     let src = ZlibDecoder::new(self.data.as_slice());
                     parse_opt = fastnbt::from_reader(src);
                 }
-                3 | 130 => {// no compress
+                3 => {// no compress
                     parse_opt = fastnbt::from_reader(self.data.as_slice());
                 }
+                4 => {// lz4
+                    let bytes = decompress_lz4_block_stream(self.data.as_slice())?;
+                    parse_opt = fastnbt::from_bytes(&bytes);
+                }
+                127 => {// custom, the algorithm is named by a string before data
+                    return Err(Error::UnsupportedCustomCompression { algorithm: custom_compress_algorithm(self.data.as_slice()) });
+                }
                 _ => { return Err(Error::InvalidMCACompressType { compress_label: self.compress_method }); }
             }
         }
@@ -181,6 +211,61 @@ impl MCARawData {
     }
 }
 
+impl Default for MCASaveOption {
+    fn default() -> Self {
+        return Self {
+            compress_method: 2,
+            compress_level: Compression::default(),
+            // Light of a loaded chunk may be missing or outdated after editing
+            save_light: false,
+        };
+    }
+}
+
+impl MCARawData {
+    /// Compress nbt as chunk data with `option.compress_method`
+    pub fn from_nbt(nbt: &HashMap<String, Value>, time_stamp: u32, source_file: String, option: &MCASaveOption) -> Result<MCARawData, Error> {
+        let bytes = match fastnbt::to_bytes(nbt) {
+            Ok(b) => b,
+            Err(e) => return Err(Error::NBTWriteError(e)),
+        };
+        let write_res = match option.compress_method {
+            1 => {
+                let mut encoder = GzEncoder::new(Vec::new(), option.compress_level);
+                encoder.write_all(&bytes).and_then(|_| encoder.finish())
+            }
+            2 => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), option.compress_level);
+                encoder.write_all(&bytes).and_then(|_| encoder.finish())
+            }
+            3 => Ok(bytes),
+            4 => Ok(compress_lz4_block_stream(&bytes)),
+            _ => return Err(Error::InvalidMCACompressType { compress_label: option.compress_method }),
+        };
+        let data = match write_res {
+            Ok(d) => d,
+            Err(e) => return Err(Error::IOWriteError(e)),
+        };
+
+        return Ok(MCARawData {
+            time_stamp,
+            compress_method: option.compress_method,
+            data: ArcSlice::from(Arc::new(data)),
+            source_file,
+        });
+    }
+
+    /// Compress method without the `.mcc` flag
+    fn compress_method_in_region(&self) -> u8 {
+        let method = self.compress_method & 0x7F;
+        // Some mcc files are stored as zlib, but labeled as no compress
+        if method == 3 && self.data.len() >= 2 && self.data[0..2] == [0x78, 0x9c] {
+            return 2;
+        }
+        return method;
+    }
+}
+
 impl UnparsedChunkData {
     pub fn to_nbt(&self) -> Result<(NBTWithSource, Option<NBTWithSource>), Error> {
         let region_data = self.region_data.to_nbt()?;
@@ -195,13 +280,203 @@ impl UnparsedChunkData {
     pub fn parse(&self, chunk_pos: &ChunkPos) -> Result<Chunk, Error> {
         let (region_nbt, entity_nbt) = self.to_nbt()?;
 
-        let chunk = Chunk::from_nbt(region_nbt,
-                                    entity_nbt,
-                                    &chunk_pos, )?;
+        let mut chunk = Chunk::from_nbt(region_nbt,
+                                        entity_nbt,
+                                        &chunk_pos, )?;
+        chunk.time_stamp = self.region_data.time_stamp;
         return Ok(chunk);
     }
 }
 
+const LZ4_BLOCK_MAGIC: &[u8; 8] = b"LZ4Block";
+const LZ4_BLOCK_HEADER_BYTES: usize = 21;
+const LZ4_BLOCK_METHOD_RAW: u8 = 0x10;
+const LZ4_BLOCK_METHOD_LZ4: u8 = 0x20;
+const LZ4_BLOCK_SIZE: usize = 1 << 16;
+const LZ4_BLOCK_CHECKSUM_SEED: u32 = 0x9747b28c;
+
+fn lz4_block_checksum(data: &[u8]) -> u32 {
+    return xxhash_rust::xxh32::xxh32(data, LZ4_BLOCK_CHECKSUM_SEED) & 0x0FFFFFFF;
+}
+
+/// Decompress chunk data compressed with LZ4 (compress type 4). Minecraft writes it with
+/// `LZ4BlockOutputStream` of lz4-java, so the data is a sequence of blocks, each block has a
+/// 21-byte header: magic, token, compressed length, decompressed length and checksum. The stream
+/// ends with an empty block.
+fn decompress_lz4_block_stream(mut data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut result = Vec::new();
+    while !data.is_empty() {
+        if data.len() < LZ4_BLOCK_HEADER_BYTES {
+            return Err(Error::InvalidLZ4Block { detail: format!("incomplete block header of {} bytes", data.len()) });
+        }
+        if &data[0..8] != LZ4_BLOCK_MAGIC {
+            return Err(Error::InvalidLZ4Block { detail: "block magic mismatch".to_string() });
+        }
+        let method = data[8] & 0xF0;
+        let compressed_len = i32::from_le_bytes([data[9], data[10], data[11], data[12]]);
+        let decompressed_len = i32::from_le_bytes([data[13], data[14], data[15], data[16]]);
+        let checksum = u32::from_le_bytes([data[17], data[18], data[19], data[20]]);
+        if compressed_len < 0 || decompressed_len < 0 {
+            return Err(Error::InvalidLZ4Block {
+                detail: format!("negative block length, compressed: {compressed_len}, decompressed: {decompressed_len}")
+            });
+        }
+        let (compressed_len, decompressed_len) = (compressed_len as usize, decompressed_len as usize);
+        data = &data[LZ4_BLOCK_HEADER_BYTES..];
+        if decompressed_len == 0 {
+            // End of stream
+            break;
+        }
+        if data.len() < compressed_len {
+            return Err(Error::InvalidLZ4Block {
+                detail: format!("block requires {compressed_len} bytes, but only {} bytes left", data.len())
+            });
+        }
+        let block = &data[0..compressed_len];
+        data = &data[compressed_len..];
+
+        let begin = result.len();
+        match method {
+            LZ4_BLOCK_METHOD_RAW => {
+                if compressed_len != decompressed_len {
+                    return Err(Error::InvalidLZ4Block {
+                        detail: format!("raw block with compressed length {compressed_len} and decompressed length {decompressed_len}")
+                    });
+                }
+                result.extend_from_slice(block);
+            }
+            LZ4_BLOCK_METHOD_LZ4 => {
+                result.resize(begin + decompressed_len, 0);
+                match lz4_flex::block::decompress_into(block, &mut result[begin..]) {
+                    Ok(len) if len == decompressed_len => {}
+                    Ok(len) => return Err(Error::InvalidLZ4Block {
+                        detail: format!("block decompressed to {len} bytes, but {decompressed_len} bytes are expected")
+                    }),
+                    Err(e) => return Err(Error::InvalidLZ4Block { detail: e.to_string() }),
+                }
+            }
+            _ => return Err(Error::InvalidLZ4Block { detail: format!("unknown compress method {method:#x}") }),
+        }
+        if lz4_block_checksum(&result[begin..]) != checksum {
+            return Err(Error::InvalidLZ4Block { detail: "checksum mismatch".to_string() });
+        }
+    }
+    return Ok(result);
+}
+
+/// Compress chunk data in the same format as `LZ4BlockOutputStream` of lz4-java with default
+/// block size, which can be read by minecraft.
+fn compress_lz4_block_stream(data: &[u8]) -> Vec<u8> {
+    // lz4-java stores log2(block size) - 10 in the low bits of token
+    let level = (LZ4_BLOCK_SIZE.trailing_zeros() - 10) as u8;
+    let write_header = |dest: &mut Vec<u8>, method: u8, compressed_len: usize, decompressed_len: usize, checksum: u32| {
+        dest.extend_from_slice(LZ4_BLOCK_MAGIC);
+        dest.push(method | level);
+        dest.extend_from_slice(&(compressed_len as i32).to_le_bytes());
+        dest.extend_from_slice(&(decompressed_len as i32).to_le_bytes());
+        dest.extend_from_slice(&checksum.to_le_bytes());
+    };
+
+    let mut result = Vec::new();
+    for block in data.chunks(LZ4_BLOCK_SIZE) {
+        let checksum = lz4_block_checksum(block);
+        let compressed = lz4_flex::block::compress(block);
+        if compressed.len() < block.len() {
+            write_header(&mut result, LZ4_BLOCK_METHOD_LZ4, compressed.len(), block.len(), checksum);
+            result.extend_from_slice(&compressed);
+        } else {
+            write_header(&mut result, LZ4_BLOCK_METHOD_RAW, block.len(), block.len(), checksum);
+            result.extend_from_slice(block);
+        }
+    }
+    write_header(&mut result, LZ4_BLOCK_METHOD_RAW, 0, 0, 0);
+    return result;
+}
+
+/// Name of the algorithm used by custom compression (compress type 127), which is written as a
+/// length-prefixed string before the compressed data.
+fn custom_compress_algorithm(data: &[u8]) -> String {
+    if data.len() < 2 {
+        return String::new();
+    }
+    let len = u16::from_be_bytes([data[0], data[1]]) as usize;
+    let end = (2 + len).min(data.len());
+    return String::from_utf8_lossy(&data[2..end]).to_string();
+}
+
+#[test]
+fn test_lz4_block_stream() {
+    let mut data: Vec<u8> = (0..200000u32).map(|i| (i / 7 % 13) as u8).collect();
+    data.extend((0..1000u32).map(|i| (i.wrapping_mul(2654435761) >> 24) as u8));
+    let compressed = compress_lz4_block_stream(&data);
+    assert!(compressed.len() < data.len());
+    assert_eq!(decompress_lz4_block_stream(&compressed).unwrap(), data);
+    assert!(decompress_lz4_block_stream(&compress_lz4_block_stream(&[])).unwrap().is_empty());
+
+    let mut broken = compressed.clone();
+    let last = broken.len() - LZ4_BLOCK_HEADER_BYTES - 1;
+    broken[last] ^= 0xFF;
+    assert!(decompress_lz4_block_stream(&broken).is_err());
+
+    let mut nbt = HashMap::new();
+    nbt.insert("DataVersion".to_string(), Value::Int(3953));
+    let option = MCASaveOption { compress_method: 4, ..MCASaveOption::default() };
+    let raw = MCARawData::from_nbt(&nbt, 0, String::new(), &option).unwrap();
+    assert_eq!(raw.to_nbt().unwrap().nbt, nbt);
+
+    let mut custom = vec![0, 14];
+    custom.extend_from_slice(b"example:zstd01");
+    custom.extend_from_slice(&[1, 2, 3]);
+    let raw = MCARawData {
+        time_stamp: 0,
+        compress_method: 127,
+        data: ArcSlice::from(Arc::new(custom)),
+        source_file: String::new(),
+    };
+    match raw.to_nbt() {
+        Err(Error::UnsupportedCustomCompression { algorithm }) => assert_eq!(algorithm, "example:zstd01"),
+        Err(e) => panic!("Expected UnsupportedCustomCompression, got {e}"),
+        Ok(_) => panic!("Custom compression should not be parsed"),
+    }
+}
+
+#[test]
+fn test_external_lz4_chunk() {
+    // Random bytes are not compressible, so the chunk is too large for the region file
+    let mut state: u32 = 1;
+    let payload: Vec<i8> = (0..1100000).map(|_| {
+        state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+        return (state >> 24) as i8;
+    }).collect();
+    let mut nbt = HashMap::new();
+    nbt.insert("DataVersion".to_string(), Value::Int(3953));
+    nbt.insert("Payload".to_string(), Value::ByteArray(fastnbt::ByteArray::new(payload)));
+    let option = MCASaveOption { compress_method: 4, ..MCASaveOption::default() };
+    let raw = MCARawData::from_nbt(&nbt, 7, String::new(), &option).unwrap();
+
+    let pos = ChunkPos::from_global_pos(&XZCoordinate { x: 1, z: -1 });
+    let dir = std::env::temp_dir().join("mc_schem_test_external_lz4_chunk");
+    let _ = std::fs::remove_dir_all(&dir);
+    let dir = dir.to_str().unwrap();
+    write_multiple_mca_files(dir, &HashMap::from([(pos, raw)])).unwrap();
+    let files = world::FolderOnDisk::new(dir);
+    assert!(files.files().iter().any(|f| f.name == pos.filename_mcc()));
+
+    let mca_bytes = ArcSlice::from(Arc::new(files.read_file_as_bytes("r.0.-1.mca").unwrap()));
+    let loaded = parse_mca_single_chunk(&pos, mca_bytes.clone(), "r.0.-1.mca", &files).unwrap().unwrap();
+    assert_eq!(loaded.compress_method, 128 | 4);
+    assert_eq!(loaded.time_stamp, 7);
+    assert_eq!(loaded.to_nbt().unwrap().nbt, nbt);
+
+    // Only the low 7 bits are the compress method
+    let mut invalid = mca_bytes.as_slice().to_vec();
+    invalid[2 * SEGMENT_BYTES + 4] = 128 | 5;
+    match parse_mca_single_chunk(&pos, ArcSlice::from(Arc::new(invalid)), "r.0.-1.mca", &files) {
+        Err(Error::InvalidMCACompressType { compress_label }) => assert_eq!(compress_label, 133),
+        Err(e) => panic!("Expected InvalidMCACompressType, got {e}"),
+        Ok(_) => panic!("Compress type 133 should be rejected"),
+    }
+}
 
 fn get_compress_label(mca_data: &[u8]) -> (u8, usize) {
     let data_bytes: usize;
@@ -213,14 +488,23 @@ fn get_compress_label(mca_data: &[u8]) -> (u8, usize) {
     return (compress_type, data_bytes);
 }
 
+/// Parse all region files in `dir`. McRegion files (`.mcr`) are parsed only if there is no Anvil
+/// file of the same region, because the game keeps them after converting worlds to Anvil.
 pub fn parse_multiple_mca_files(dir: &dyn FilesRead) -> Result<HashMap<ChunkPos, MCARawData>, Error> {
     let files = dir.files();
     let mut mca_files = Vec::with_capacity(files.len());
-    for info in files {
+    for info in &files {
         if let Some(coord) = parse_mca_filename(&info.name) {
             mca_files.push((info, coord));
         }
     }
+    for info in &files {
+        if let Some(coord) = parse_mcr_filename(&info.name) {
+            if !mca_files.iter().any(|(_, c)| *c == coord) {
+                mca_files.push((info, coord));
+            }
+        }
+    }
 
     let mut result = HashMap::new();
 
@@ -253,7 +537,7 @@ pub fn parse_multiple_mca_files(dir: &dyn FilesRead) -> Result<HashMap<ChunkPos,
 
 pub fn parse_multiple_regions(region_dir: &dyn FilesRead,
                               entity_dir: Option<&dyn FilesRead>,
-                              y_range: Range<i32>, dimension_id: i32,
+                              y_range: Range<i32>, dimension_id: &DimensionId,
                               parse_directly: bool)
                               -> Result<HashMap<ChunkPos, ChunkVariant>, Error> {
     let region_data = parse_multiple_mca_files(region_dir)?;
@@ -274,7 +558,8 @@ pub fn parse_multiple_regions(region_dir: &dyn FilesRead,
     }
 
     if parse_directly {
-        let mut temp = Dimension { chunks: result, y_range, raids: RaidList::default() };
+        let mut temp = Dimension::new(y_range);
+        temp.chunks = result;
         temp.parse_all(dimension_id)?;
         return Ok(temp.chunks);
     }
@@ -303,7 +588,7 @@ fn parse_mca_file(file_info: &FileInfo, file_coord: &XZCoordinate,
         for x in 0..32 {
             let local_pos = XZCoordinate { x, z };
             let pos = ChunkPos::from_local_pos(file_coord, &local_pos);
-            let unparsed = parse_mca_single_chunk(&pos, mca_bytes.clone(), region_dir)?;
+            let unparsed = parse_mca_single_chunk(&pos, mca_bytes.clone(), &file_info.name, region_dir)?;
             if let Some(raw) = unparsed {
                 result.insert(pos, raw);
             }
@@ -316,7 +601,7 @@ pub fn offset_in_mca_file(local_coord: &XZCoordinate<u32>) -> u32 {
     return 4 * ((local_coord.x & 31) + (local_coord.z & 31) * 32);
 }
 
-fn parse_mca_single_chunk(chunk_pos: &ChunkPos, mca_bytes: ArcSlice, region_dir: &dyn FilesRead) -> Result<Option<MCARawData>, Error> {
+fn parse_mca_single_chunk(chunk_pos: &ChunkPos, mca_bytes: ArcSlice, filename: &str, region_dir: &dyn FilesRead) -> Result<Option<MCARawData>, Error> {
     let header: [u8; 4];
     let local_coord = chunk_pos.local_coordinate();
     {
@@ -362,11 +647,11 @@ fn parse_mca_single_chunk(chunk_pos: &ChunkPos, mca_bytes: ArcSlice, region_dir:
     //let range = ;
     let (compress_label, compressed_len) = get_compress_label(&mca_bytes[data_beg_idx..data_end_idx]);
 
-    if ![1, 128, 2, 129, 3, 130].contains(&compress_label) {
+    if ![1, 2, 3, 4, 127].contains(&(compress_label & 0x7F)) {
         return Err(Error::InvalidMCACompressType { compress_label });
     }
 
-    if compress_label > 127 {
+    if compress_label & 0x80 != 0 {
         let mcc_filename = chunk_pos.filename_mcc();
         let mcc_bytes = match region_dir.read_file_as_arc_slice(&mcc_filename) {
             Err(e) => return Err(Error::MissingMCCFile {
@@ -388,6 +673,76 @@ fn parse_mca_single_chunk(chunk_pos: &ChunkPos, mca_bytes: ArcSlice, region_dir:
         time_stamp,
         compress_method: compress_label,
         data: mca_bytes.slice((data_beg_idx + 5)..(data_beg_idx + 5 + compressed_len)),
-        source_file: format!("{}/{}", region_dir.path(), chunk_pos.filename_mca()),
+        source_file: format!("{}/{}", region_dir.path(), filename),
     }));
-}
\ No newline at end of file
+}
+
+/// Encode chunks into a region file. Returns bytes of the `.mca` file, and `.mcc` files(filename
+/// and content) of chunks that are too large to be stored in the region file.
+pub fn encode_mca_file(chunks: &[(ChunkPos, &MCARawData)]) -> (Vec<u8>, Vec<(String, Vec<u8>)>) {
+    let mut sorted: BTreeMap<u32, (ChunkPos, &MCARawData)> = BTreeMap::new();
+    for (pos, raw) in chunks {
+        sorted.insert(offset_in_mca_file(&pos.local_coordinate()), (*pos, *raw));
+    }
+
+    let mut mca_bytes = vec![0u8; 2 * SEGMENT_BYTES];
+    let mut mcc_files = Vec::new();
+    for (offset, (pos, raw)) in sorted {
+        let offset = offset as usize;
+        let method = raw.compress_method_in_region();
+        let mut num_segments = (raw.data.len() + 5).div_ceil(SEGMENT_BYTES);
+        let begin = mca_bytes.len();
+        if num_segments > 255 {
+            // Only the header is stored in region file, data goes to the .mcc file
+            num_segments = 1;
+            mca_bytes.extend_from_slice(&1u32.to_be_bytes());
+            mca_bytes.push(method | 128);
+            mcc_files.push((pos.filename_mcc(), raw.data.as_slice().to_vec()));
+        } else {
+            mca_bytes.extend_from_slice(&(raw.data.len() as u32 + 1).to_be_bytes());
+            mca_bytes.push(method);
+            mca_bytes.extend_from_slice(raw.data.as_slice());
+        }
+        mca_bytes.resize(begin + num_segments * SEGMENT_BYTES, 0);
+
+        let offset_by_segment = (begin / SEGMENT_BYTES) as u32;
+        let location = offset_by_segment << 8 | num_segments as u32;
+        mca_bytes[offset..(offset + 4)].copy_from_slice(&location.to_be_bytes());
+        mca_bytes[(offset + SEGMENT_BYTES)..(offset + SEGMENT_BYTES + 4)].copy_from_slice(&raw.time_stamp.to_be_bytes());
+    }
+
+    return (mca_bytes, mcc_files);
+}
+
+fn write_file(dir: &str, filename: &str, bytes: &[u8]) -> Result<(), Error> {
+    let mut file = match File::create(format!("{dir}/{filename}")) {
+        Ok(f) => f,
+        Err(e) => return Err(Error::FileCreateError(e)),
+    };
+    if let Err(e) = file.write_all(bytes) {
+        return Err(Error::IOWriteError(e));
+    }
+    return Ok(());
+}
+
+/// Write chunks into region files in `dir`, one `.mca` file for every 32x32 chunks. The directory
+/// will be created if it doesn't exist, and existing files will be overwritten.
+pub fn write_multiple_mca_files(dir: &str, chunks: &HashMap<ChunkPos, MCARawData>) -> Result<(), Error> {
+    if let Err(e) = std::fs::create_dir_all(dir) {
+        return Err(Error::FileCreateError(e));
+    }
+
+    let mut files: HashMap<XZCoordinate, Vec<(ChunkPos, &MCARawData)>> = HashMap::new();
+    for (pos, raw) in chunks {
+        files.entry(pos.file_coordinate()).or_default().push((*pos, raw));
+    }
+
+    for (file_coord, chunks) in files {
+        let (mca_bytes, mcc_files) = encode_mca_file(&chunks);
+        write_file(dir, &format!("r.{}.{}.mca", file_coord.x, file_coord.z), &mca_bytes)?;
+        for (filename, bytes) in mcc_files {
+            write_file(dir, &filename, &bytes)?;
+        }
+    }
+    return Ok(());
+}
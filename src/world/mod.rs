@@ -23,10 +23,13 @@ use std::sync::Arc;
 
 use fastnbt::Value;
 
-use crate::{BlockEntity, Entity};
+use crate::BlockEntity;
+use crate::player::EntityBox;
 use crate::biome::Biome;
 use crate::block::Block;
 use crate::error::Error;
+use crate::map::MapData;
+use crate::player::{DimensionId, Player};
 use crate::raid::RaidList;
 use crate::region::{Light, PendingTick};
 
@@ -36,6 +39,9 @@ mod chunk;
 mod dimension;
 mod sub_chunk;
 mod chunk_ref;
+mod light;
+mod heightmap;
+mod level_data;
 mod world;
 
 
@@ -61,6 +67,8 @@ pub struct SubChunk {
 #[derive(Debug, Clone)]
 pub struct Chunk {
     pub time_stamp: u32,
+    /// Data version of the game that saved this chunk
+    pub data_version: i32,
     /// Status of chunk
     pub status: ChunkStatus,
     /// Last update in game time
@@ -70,7 +78,7 @@ pub struct Chunk {
     /// If light compute is finished
     pub is_light_on: bool,
     sub_chunks: BTreeMap<i8, SubChunk>,
-    pub entities: Vec<Entity>,
+    pub entities: Vec<EntityBox>,
     pub block_entities: HashMap<[i32; 3], BlockEntity>,
     pub pending_ticks: HashMap<[i32; 3], Vec<PendingTick>>,
 
@@ -78,6 +86,14 @@ pub struct Chunk {
     pub file_region: String,
     /// Related entities file
     pub file_entities: String,
+    /// Tags that are not parsed, like heightmaps and structures. They are kept to be written back.
+    /// For chunks saved before 1.18, these are tags in `Level`.
+    pub unparsed_tags: HashMap<String, Value>,
+    /// Errors that are fixed while loading, like number ids with invalid damage. Values are the
+    /// error messages.
+    pub warnings: Vec<String>,
+    /// If the chunk is modified after loading, like pasting schematics into it
+    pub dirty: bool,
 }
 
 #[derive(Debug, Eq, Hash, PartialEq, Copy, Clone)]
@@ -139,13 +155,68 @@ pub enum ChunkVariant {
 pub struct Dimension {
     pub chunks: HashMap<ChunkPos, ChunkVariant>,
     y_range: Range<i32>,
+    /// Data version of new chunks created in this dimension
+    pub data_version: i32,
     #[allow(dead_code)]
     raids: RaidList,
 }
 
+/// Content of `level.dat`, the global settings and states of a world
+#[derive(Debug, Clone)]
+pub struct LevelData {
+    /// Data version of the game that saved this world, 0 if missing (before 1.9)
+    pub data_version: i32,
+    /// Like `1.20.2`, empty if missing
+    pub version_name: String,
+    /// `main` for vanilla releases and snapshots
+    pub version_series: String,
+    pub version_is_snapshot: bool,
+    pub level_name: String,
+    pub seed: i64,
+    pub spawn_pos: [i32; 3],
+    pub spawn_angle: f32,
+    /// 0 for survival, 1 for creative, 2 for adventure and 3 for spectator
+    pub game_type: i32,
+    pub hardcore: bool,
+    pub allow_commands: bool,
+    /// 0 for peaceful, 1 for easy, 2 for normal and 3 for hard
+    pub difficulty: i8,
+    pub difficulty_locked: bool,
+    /// Total ticks of the world
+    pub time: i64,
+    /// Time of day in ticks, it's changed by sleeping and `/time`
+    pub day_time: i64,
+    /// Milliseconds since unix epoch
+    pub last_played: i64,
+    pub raining: bool,
+    pub rain_time: i32,
+    pub thundering: bool,
+    pub thunder_time: i32,
+    pub clear_weather_time: i32,
+    /// Game rules are stored as strings, like `"true"` and `"3"`
+    pub game_rules: BTreeMap<String, String>,
+    pub enabled_datapacks: Vec<String>,
+    pub disabled_datapacks: Vec<String>,
+    /// The player of a singleplayer world, stored in `Player`
+    pub player: Option<Player>,
+    /// Tags in `Data` that are not parsed, like `WorldGenSettings`. They are kept to be written
+    /// back.
+    pub unparsed_tags: HashMap<String, Value>,
+}
+
 #[derive(Debug, Clone)]
 pub struct World {
-    pub dimensions: BTreeMap<i32, Dimension>,
+    /// Parsed from `level.dat`, `None` if the file doesn't exist
+    pub level_data: Option<LevelData>,
+    /// Dimensions indexed by namespaced id, including custom dimensions of datapacks
+    pub dimensions: BTreeMap<DimensionId, Dimension>,
+    /// Maps in `data/map_<id>.dat`, indexed by map id
+    pub maps: BTreeMap<i32, MapData>,
+    /// Players in `playerdata/<uuid>.dat`, indexed by uuid
+    pub players: BTreeMap<String, Player>,
+    /// Files that failed to load and were skipped, like a corrupted `map_<id>.dat`. Values are the
+    /// error messages, indexed by filename.
+    pub skipped_files: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Clone)]
@@ -153,6 +224,28 @@ pub struct WorldLoadOption {
     parse_directly: bool,
 }
 
+/// Options to write chunks into `.mca` files
+#[derive(Debug, Clone)]
+pub struct MCASaveOption {
+    /// Compress method of chunk data, 1 for gzip, 2 for zlib, 3 for no compression and 4 for LZ4.
+    /// LZ4 is supported by minecraft since 24w04a (1.20.5), and `compress_level` doesn't affect it.
+    pub compress_method: u8,
+    pub compress_level: flate2::Compression,
+    /// Save sky light and block light. If false, light data is dropped and `isLightOn` is set to 0,
+    /// so that the game will compute light again.
+    pub save_light: bool,
+}
+
+/// Options to paste a schematic into a dimension
+#[derive(Debug, Clone)]
+pub struct PasteOption {
+    /// Don't paste air, so that blocks in the dimension are kept at these positions
+    pub ignore_air: bool,
+    /// Don't paste structure void, so that blocks in the dimension are kept at these positions
+    pub ignore_structure_void: bool,
+    /// Paste entities in the schematic
+    pub paste_entities: bool,
+}
 
 #[derive(Debug, Clone)]
 pub struct FileInfo {
@@ -215,6 +308,14 @@ pub struct FilesInMemory {
     pub source: String,
 }
 
+/// Files of `root` listed only once. Sub directories filter the listing of their root, so this
+/// avoids walking a folder on disk again for each sub directory.
+#[derive(Clone)]
+pub struct ListedFiles<'a> {
+    root: &'a dyn FilesRead,
+    files: Vec<FileInfo>,
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd)]
 #[repr(u8)]
 pub enum ChunkStatus {
@@ -232,6 +333,19 @@ pub enum ChunkStatus {
     Full,
 }
 
+/// Kinds of heightmaps stored in chunks
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum HeightmapKind {
+    /// Highest non-air block
+    WorldSurface,
+    /// Highest block that blocks motion, fluids are ignored
+    OceanFloor,
+    /// Highest block that blocks motion or contains fluid
+    MotionBlocking,
+    /// Like `MotionBlocking`, but leaves are ignored
+    MotionBlockingNoLeaves,
+}
+
 /// Part of Minecraft world that can be indexed with absolute position.
 /// `'dim` is the lifetime of the dimension/chunk, while `'this` is the lifetime of the trait object
 /// `'dim` and `'this` may be different, because `Self` is probably a proxy reference struct.
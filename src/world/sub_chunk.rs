@@ -19,6 +19,39 @@ impl SubChunk {
         return result;
     }
 
+    /// Create a sub chunk filled with `block`, and all biomes are `biome`
+    pub fn filled_with(block: &Block, biome: Biome) -> SubChunk {
+        let mut result = Self::new();
+        result.palette.push(block.clone());
+        result.biome_array.fill(biome);
+        return result;
+    }
+
+    /// Index in `block_id_array` and `sky_block_light_array`, they are stored in yzx
+    fn index_of(r_pos: [i32; 3]) -> usize {
+        return (r_pos[1] * 256 + r_pos[2] * 16 + r_pos[0]) as usize;
+    }
+
+    /// Set block at `r_pos`. If the block doesn't exist in palette, it will be appended to palette.
+    /// Returns `Err(())` if `r_pos` is outside the sub chunk or the palette exceeds 65535.
+    pub fn set_block(&mut self, r_pos: [i32; 3], block: &Block) -> Result<(), ()> {
+        if !self.contains_coord(r_pos) {
+            return Err(());
+        }
+        let blk_id = match self.find_in_palette(block) {
+            Some(id) => id,
+            None => {
+                if self.palette.len() >= 65535 {
+                    return Err(());
+                }
+                self.palette.push(block.clone());
+                (self.palette.len() - 1) as u16
+            }
+        };
+        self.block_id_array[Self::index_of(r_pos)] = blk_id;
+        return Ok(());
+    }
+
     pub fn block_id(&self) -> ArrayView3<u16> {
         return ArrayView3::from_shape([16, 16, 16], &self.block_id_array).unwrap();
     }
@@ -73,16 +106,14 @@ impl WorldSlice for SubChunk {
 
     fn block_index_at(&self, r_pos: [i32; 3]) -> Option<u16> {
         if self.contains_coord(r_pos) {
-            let r_pos = [r_pos[0] as usize, r_pos[1] as usize, r_pos[2] as usize];
-            return Some(self.block_id()[r_pos]);
+            return Some(self.block_id_array[Self::index_of(r_pos)]);
         }
         return None;
     }
 
     fn block_at(&self, r_pos: [i32; 3]) -> Option<&Block> {
         if self.contains_coord(r_pos) {
-            let r_pos = [r_pos[0] as usize, r_pos[1] as usize, r_pos[2] as usize];
-            let id = self.block_id()[r_pos];
+            let id = self.block_id_array[Self::index_of(r_pos)];
             return Some(&self.palette[id as usize]);
         }
         return None;
@@ -1,9 +1,12 @@
 use std::collections::BTreeMap;
+use std::ops::Range;
 #[allow(unused_imports)]
 use std::time;
 use crate::Error;
+use crate::map::MapData;
+use crate::player::{DimensionId, Player};
 #[allow(unused_imports)]
-use crate::world::{Dimension, FilesInMemory, FilesRead, World, WorldLoadOption};
+use crate::world::{Dimension, FilesInMemory, FilesRead, LevelData, ListedFiles, mca, World, WorldLoadOption};
 
 impl Default for WorldLoadOption {
     fn default() -> Self {
@@ -13,36 +16,227 @@ impl Default for WorldLoadOption {
     }
 }
 
+/// Id of `map_<id>.dat`, returns `None` for other files
+fn map_id_of_filename(filename: &str) -> Option<i32> {
+    return filename.strip_prefix("map_")?.strip_suffix(".dat")?.parse().ok();
+}
+
+/// Parse `map_<id>.dat` in `data_dir`. Maps that fail to load are skipped, the errors are
+/// recorded in `skipped_files`.
+fn parse_maps(data_dir: &dyn FilesRead, skipped_files: &mut BTreeMap<String, String>) -> BTreeMap<i32, MapData> {
+    let mut maps = BTreeMap::new();
+    for info in data_dir.files() {
+        let Some(id) = map_id_of_filename(&info.name) else {
+            continue;
+        };
+        let map = data_dir.open_file(&info.name).and_then(|mut src| MapData::from_reader(&mut src));
+        match map {
+            Ok(map) => { maps.insert(id, map); }
+            Err(e) => { skipped_files.insert(info.full_name, e.to_string()); }
+        }
+    }
+    return maps;
+}
+
+/// Parse `playerdata/<uuid>.dat`, indexed by uuid. Backups like `<uuid>.dat_old` are skipped, and
+/// players that fail to load are recorded in `skipped_files`.
+fn parse_players(playerdata_dir: &dyn FilesRead, skipped_files: &mut BTreeMap<String, String>) -> BTreeMap<String, Player> {
+    let mut players = BTreeMap::new();
+    for info in playerdata_dir.files() {
+        let Some(uuid) = info.name.strip_suffix(".dat") else {
+            continue;
+        };
+        if uuid.contains('/') {
+            continue;
+        }
+        let player = playerdata_dir.open_file(&info.name).and_then(|mut src| Player::from_reader(&mut src));
+        match player {
+            Ok(player) => { players.insert(uuid.to_string(), player); }
+            Err(e) => { skipped_files.insert(info.full_name, e.to_string()); }
+        }
+    }
+    return players;
+}
+
+/// Id of the custom dimension that a region file belongs to, the filename is relative to
+/// `dimensions`, like `<namespace>/<path>/region/r.0.0.mca`. Returns `None` for other files.
+fn dimension_id_of_filename(filename: &str) -> Option<DimensionId> {
+    let (dir, region_file) = filename.rsplit_once("/region/")?;
+    mca::parse_mca_filename(region_file)?;
+    let (namespace, path) = dir.split_once('/')?;
+    if namespace.is_empty() || path.is_empty() {
+        return None;
+    }
+    return Some(DimensionId::new(&format!("{namespace}:{path}")));
+}
+
+/// Read `data/<namespace>/<kind>/<path>.json` of datapacks in `datapacks`, `id` is `<namespace>:<path>`.
+/// Disabled datapacks are skipped, and if several datapacks have the file, the one that loads last
+/// wins. Datapacks that are not listed in `level.dat` are enabled by the game when loading, so they
+/// are regarded as the last. Zipped datapacks are not supported.
+fn read_datapack_json(datapacks: &dyn FilesRead, level_data: &LevelData, kind: &str, id: &str) -> Result<Option<serde_json::Value>, Error> {
+    let (namespace, path) = id.split_once(':').unwrap_or(("minecraft", id));
+    let suffix = format!("/data/{namespace}/{kind}/{path}.json");
+
+    let mut selected: Option<(usize, String)> = None;
+    for info in datapacks.files() {
+        let Some(pack) = info.name.strip_suffix(&suffix) else {
+            continue;
+        };
+        if pack.contains('/') {
+            continue;
+        }
+        let pack_id = format!("file/{pack}");
+        if level_data.disabled_datapacks.contains(&pack_id) {
+            continue;
+        }
+        let order = level_data.enabled_datapacks.iter().position(|p| *p == pack_id).unwrap_or(usize::MAX);
+        if selected.as_ref().map_or(true, |(o, _)| order >= *o) {
+            selected = Some((order, info.name));
+        }
+    }
+
+    let Some((_, filename)) = selected else {
+        return Ok(None);
+    };
+    let bytes = datapacks.read_file_as_bytes(&filename)?;
+    return match serde_json::from_slice(&bytes) {
+        Ok(json) => Ok(Some(json)),
+        Err(e) => Err(Error::InvalidJson {
+            filename: format!("{}/{filename}", datapacks.path()),
+            detail: e.to_string(),
+        }),
+    };
+}
+
+fn y_range_of_dimension_type(json: &serde_json::Value) -> Option<Range<i32>> {
+    let min_y = json.get("min_y")?.as_i64()? as i32;
+    let height = json.get("height")?.as_i64()? as i32;
+    return Some(min_y..(min_y + height));
+}
+
+/// Y range of a dimension. Dimension types in `level.dat` come first, then the `dimension` and
+/// `dimension_type` json in datapacks, and vanilla dimension types at last.
+fn dimension_y_range(files: &dyn FilesRead, level_data: &LevelData, dimension_id: &DimensionId) -> Result<Range<i32>, Error> {
+    if let Some(range) = level_data.custom_y_range(dimension_id) {
+        return Ok(range);
+    }
+    let datapacks = files.sub_directory("datapacks");
+    let mut type_id = level_data.dimension_type_id(dimension_id).map(|t| t.to_string());
+    if type_id.is_none() {
+        if let Some(dim) = read_datapack_json(&datapacks, level_data, "dimension", dimension_id.as_str())? {
+            match dim.get("type") {
+                Some(serde_json::Value::String(t)) => type_id = Some(t.clone()),
+                // dimension type defined inline
+                Some(t) => if let Some(range) = y_range_of_dimension_type(t) {
+                    return Ok(range);
+                },
+                None => {}
+            }
+        }
+    }
+    // Ids of vanilla dimensions are the same as their dimension types
+    let type_id = type_id.unwrap_or_else(|| dimension_id.as_str().to_string());
+    if let Some(dim_type) = read_datapack_json(&datapacks, level_data, "dimension_type", &type_id)? {
+        if let Some(range) = y_range_of_dimension_type(&dim_type) {
+            return Ok(range);
+        }
+    }
+    return Ok(level_data.vanilla_y_range(&type_id));
+}
+
 impl World {
+    /// Load a world folder. The overworld, the nether and the end are loaded from the root, `DIM-1`
+    /// and `DIM1`, and other dimensions are found in `dimensions/<namespace>/<path>`.
     pub fn from_files(files: &dyn FilesRead, option: &WorldLoadOption) -> Result<World, Error> {
-        let mut world = World { dimensions: BTreeMap::new() };
-        for dim in [0, -1, 1] {
-            let dimension = if dim == 0 {
-                Dimension::from_files(files, option.parse_directly, -64..320, dim)?
-            } else {
-                let dir = format!("DIM{dim}");
-                // let y_range = if dim == -1 { 0..256 } else { -64..320 };
-                let y_range = 0..256;
-                Dimension::from_files(&files.sub_directory(&dir), option.parse_directly, y_range, dim)?
-            };
-            world.dimensions.insert(dim, dimension);
+        // list all files once, the level.dat check, dimensions and datapacks are all filtered from it
+        let files = &ListedFiles::new(files);
+        let level_data = if files.files().iter().any(|info| info.name == "level.dat") {
+            Some(LevelData::from_files(files)?)
+        } else {
+            None
+        };
+        let mut skipped_files = BTreeMap::new();
+        let maps = parse_maps(&files.sub_directory("data"), &mut skipped_files);
+        let players = parse_players(&files.sub_directory("playerdata"), &mut skipped_files);
+        let mut world = World {
+            level_data,
+            dimensions: BTreeMap::new(),
+            maps,
+            players,
+            skipped_files,
         };
+        let level_data = world.level_data.clone().unwrap_or_default();
+
+        // Directory of each dimension, `None` for the root
+        let mut dimension_dirs: BTreeMap<DimensionId, Option<String>> = BTreeMap::new();
+        dimension_dirs.insert(DimensionId::overworld(), None);
+        dimension_dirs.insert(DimensionId::nether(), Some("DIM-1".to_string()));
+        dimension_dirs.insert(DimensionId::the_end(), Some("DIM1".to_string()));
+        for info in files.sub_directory("dimensions").files() {
+            if let Some(id) = dimension_id_of_filename(&info.name) {
+                let dir = format!("dimensions/{}", id.as_str().replacen(':', "/", 1));
+                dimension_dirs.insert(id, Some(dir));
+            }
+        }
+
+        for (id, dir) in dimension_dirs {
+            let y_range = dimension_y_range(files, &level_data, &id)?;
+            let mut dimension = match &dir {
+                None => Dimension::from_files(files, option.parse_directly, y_range, &id)?,
+                Some(dir) => Dimension::from_files(&files.sub_directory(dir), option.parse_directly, y_range, &id)?,
+            };
+            if world.level_data.is_some() {
+                dimension.data_version = level_data.data_version;
+            }
+            world.dimensions.insert(id, dimension);
+        }
         return Ok(world);
     }
 
+    /// Data version in `level.dat`
+    pub fn data_version(&self) -> Option<i32> {
+        return self.level_data.as_ref().map(|l| l.data_version);
+    }
+
+    pub fn dimension(&self, id: &DimensionId) -> Option<&Dimension> {
+        return self.dimensions.get(id);
+    }
     pub fn overworld(&self) -> Option<&Dimension> {
-        return self.dimensions.get(&0);
+        return self.dimension(&DimensionId::overworld());
     }
     pub fn nether(&self) -> Option<&Dimension> {
-        return self.dimensions.get(&-1);
+        return self.dimension(&DimensionId::nether());
     }
     pub fn the_end(&self) -> Option<&Dimension> {
-        return self.dimensions.get(&1);
+        return self.dimension(&DimensionId::the_end());
+    }
+
+    /// Get map by the id in `map_<id>.dat`
+    pub fn map(&self, id: i32) -> Option<&MapData> {
+        return self.maps.get(&id);
+    }
+
+    /// All players of the world. The singleplayer player in `level.dat` comes first, followed by
+    /// players in `playerdata` with different uuid.
+    pub fn players(&self) -> Vec<&Player> {
+        let mut result = Vec::with_capacity(self.players.len() + 1);
+        let host = self.level_data.as_ref().and_then(|l| l.player.as_ref());
+        if let Some(host) = host {
+            result.push(host);
+        }
+        for player in self.players.values() {
+            if host.is_some_and(|h| h.entity_fields.uuid == player.entity_fields.uuid) {
+                continue;
+            }
+            result.push(player);
+        }
+        return result;
     }
 
     pub fn parse_all_dimensions(&mut self) -> Result<(), Error> {
         for (id, dim) in &mut self.dimensions {
-            dim.parse_all(*id)?;
+            dim.parse_all(id)?;
         }
         return Ok(());
     }
@@ -67,4 +261,202 @@ fn load_all_worlds() {
         let cost = parsed.duration_since(begin).unwrap().as_millis();
         println!("Spend {cost} milliseconds");
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn load_maps_of_world() {
+    use std::sync::Arc;
+    use crate::map::MapData;
+    let mut src = FilesInMemory::from_7z_file("test_files/world/00_1.20.2.7z", "").expect("Read 7z file and decompress");
+    let mut map = MapData::new();
+    map.x_center = 128;
+    map.colors.colors[0] = 4 * 12 + 1;
+    let mut bytes = Vec::new();
+    map.to_writer(&mut bytes).unwrap();
+    src.files.insert("data/map_7.dat".to_string(), Arc::new(bytes));
+    // maps before 1.9 have no data version
+    let mut nbt = map.to_nbt();
+    nbt.remove("DataVersion");
+    let mut bytes = Vec::new();
+    let mut encoder = flate2::write::GzEncoder::new(&mut bytes, flate2::Compression::default());
+    fastnbt::to_writer(&mut encoder, &nbt).unwrap();
+    encoder.finish().unwrap();
+    src.files.insert("data/map_8.dat".to_string(), Arc::new(bytes));
+    src.files.insert("data/map_9.dat".to_string(), Arc::new(b"not a map".to_vec()));
+
+    let world = World::from_files(&src, &WorldLoadOption::default()).expect("Parse world from files in memory");
+    assert_eq!(world.maps.len(), 2);
+    assert_eq!(world.map(8).unwrap().data_version, crate::schem::DataVersion::Java_1_8_9 as i32);
+    assert!(!world.map(8).unwrap().to_nbt().contains_key("DataVersion"));
+    assert!(world.map(9).is_none());
+    assert!(world.skipped_files.contains_key("data/map_9.dat"));
+    let loaded = world.map(7).expect("map_7.dat should be loaded");
+    assert_eq!(loaded.x_center, 128);
+    assert_eq!(loaded.colors, map.colors);
+    assert!(world.map(0).is_none());
+    assert_eq!(map_id_of_filename("map_12.dat"), Some(12));
+    assert_eq!(map_id_of_filename("raids.dat"), None);
+}
+
+#[test]
+fn load_level_data() {
+    use fastnbt::Value;
+    let src = FilesInMemory::from_7z_file("test_files/world/00_1.20.2.7z", "").expect("Read 7z file and decompress");
+    let world = World::from_files(&src, &WorldLoadOption::default()).expect("Parse world from files in memory");
+    let level = world.level_data.as_ref().expect("level.dat should be loaded");
+    assert_eq!(world.data_version(), Some(3578));
+    assert_eq!(level.version_name, "1.20.2");
+    assert_eq!(level.level_name, "New World");
+    assert_eq!(level.seed, -9035914282494439381);
+    assert_eq!(level.game_type, 1);
+    assert_eq!(level.game_rules.get("doWeatherCycle").map(|s| s.as_str()), Some("true"));
+    assert!(level.enabled_datapacks.contains(&"vanilla".to_string()));
+    assert_eq!(world.overworld().unwrap().y_range, -64..320);
+    assert_eq!(world.nether().unwrap().y_range, 0..256);
+    assert_eq!(world.overworld().unwrap().data_version, 3578);
+
+    let mut bytes = Vec::new();
+    level.to_writer(&mut bytes).unwrap();
+    let mut loaded = LevelData::from_reader(&mut bytes.as_slice()).unwrap();
+    assert_eq!(loaded.seed, level.seed);
+    assert_eq!(loaded.spawn_pos, level.spawn_pos);
+    assert_eq!(loaded.game_rules, level.game_rules);
+    assert_eq!(loaded.disabled_datapacks, level.disabled_datapacks);
+    assert_eq!(loaded.unparsed_tags, level.unparsed_tags);
+
+    // custom height of the overworld and worlds before 1.18
+    if let Some(Value::Compound(settings)) = loaded.unparsed_tags.get_mut("WorldGenSettings") {
+        if let Some(Value::Compound(dims)) = settings.get_mut("dimensions") {
+            if let Some(Value::Compound(overworld)) = dims.get_mut("minecraft:overworld") {
+                let mut dim_type = std::collections::HashMap::new();
+                dim_type.insert("min_y".to_string(), Value::Int(-128));
+                dim_type.insert("height".to_string(), Value::Int(512));
+                overworld.insert("type".to_string(), Value::Compound(dim_type));
+            }
+        }
+    }
+    assert_eq!(loaded.dimension_y_range(&DimensionId::overworld()), -128..384);
+    let mut old = LevelData::new();
+    old.data_version = 2730;
+    assert_eq!(old.dimension_y_range(&DimensionId::overworld()), 0..256);
+}
+
+#[test]
+fn load_players() {
+    let mut src = FilesInMemory::from_7z_file("test_files/world/03_raids-1.20.2.7z", "").expect("Read 7z file and decompress");
+    let broken = "playerdata/00000000-0000-0000-0000-000000000000.dat";
+    src.files.insert(broken.to_string(), std::sync::Arc::new(vec![0x1f, 0x8b, 0]));
+    let world = World::from_files(&src, &WorldLoadOption::default()).expect("Parse world from files in memory");
+    assert!(world.players.contains_key("065992de-c443-44b6-98f7-9f3470f3e9fa"));
+    assert_eq!(world.players.len(), 1);
+    assert!(world.skipped_files.contains_key(broken));
+    let players = world.players();
+    assert_eq!(players.len(), 1);
+    let player = players[0];
+    assert_eq!(player.data_version, 3578);
+    assert_eq!(player.player_fields.xp_info.level, 12);
+    assert_eq!(player.player_fields.selected_item_slot, 1);
+    assert_eq!(player.player_fields.selected_item().map(|i| i.id.as_str()), Some("minecraft:netherite_axe"));
+    assert_eq!(player.player_fields.dimension, DimensionId::overworld());
+    assert!(player.player_fields.abilities.instant_build);
+    let pos = player.entity_fields.pos;
+    assert!((pos[0] + 289.27).abs() < 0.01 && pos[1] == -53.0 && (pos[2] - 191.53).abs() < 0.01);
+
+    let attack = player.mob_fields.attribute("minecraft:generic.attack_damage").expect("Attack damage attribute");
+    assert_eq!(attack.modifiers.len(), 1);
+    assert_eq!(attack.modifiers[0].amount, 303.0);
+    let strength = player.mob_fields.active_effects.iter().find(|e| e.id == "minecraft:strength").expect("Strength effect");
+    assert_eq!(strength.amplifier, 100);
+    assert_eq!(strength.duration, -1);
+
+    let nbt = player.to_nbt();
+    let loaded = Player::from_nbt(nbt.clone(), "").expect("Parse written player");
+    assert_eq!(loaded.to_nbt(), nbt);
+    assert_eq!(loaded.player_fields.inventory.0.len(), player.player_fields.inventory.0.len());
+
+    // players before 1.16 store dimension as number
+    let mut old_player = player.clone();
+    old_player.data_version = crate::schem::DataVersion::Java_1_15_2 as i32;
+    old_player.player_fields.dimension = DimensionId::nether();
+    let nbt = old_player.to_nbt();
+    assert_eq!(nbt.get("Dimension"), Some(&fastnbt::Value::Int(-1)));
+    assert_eq!(Player::from_nbt(nbt, "").unwrap().player_fields.dimension, DimensionId::nether());
+}
+
+#[test]
+fn load_custom_dimensions() {
+    use std::sync::Arc;
+    let mut src = FilesInMemory::from_7z_file("test_files/world/00_1.20.2.7z", "").expect("Read 7z file and decompress");
+    let dimension_files: Vec<(String, Arc<Vec<u8>>)> = src.files.iter()
+        .filter(|(name, _)| name.starts_with("region/") || name.starts_with("data/raids"))
+        .map(|(name, bytes)| (name.clone(), bytes.clone())).collect();
+    assert!(!dimension_files.is_empty());
+    for (name, bytes) in dimension_files {
+        src.files.insert(format!("dimensions/example/sky/islands/{name}"), bytes);
+    }
+    src.files.insert("datapacks/sky/data/example/dimension/sky/islands.json".to_string(),
+                     Arc::new(br#"{"type": "example:tall", "generator": {"type": "minecraft:flat"}}"#.to_vec()));
+    src.files.insert("datapacks/sky/data/example/dimension_type/tall.json".to_string(),
+                     Arc::new(br#"{"min_y": -64, "height": 384, "logical_height": 384}"#.to_vec()));
+
+    let mut world = World::from_files(&src, &WorldLoadOption::default()).expect("Parse world from files in memory");
+    let id = DimensionId::new("example:sky/islands");
+    let sky = world.dimension(&id).expect("Custom dimension should be loaded");
+    assert_eq!(sky.y_range, -64..320);
+    assert_eq!(sky.chunks.len(), world.overworld().unwrap().chunks.len());
+    assert_eq!(world.dimensions.len(), 4);
+    world.parse_all_dimensions().expect("Parse all dimensions");
+
+    assert_eq!(dimension_id_of_filename("example/sky/islands/region/r.0.-1.mca"), Some(id));
+    assert_eq!(dimension_id_of_filename("example/sky/islands/region/c.0.-1.mcc"), None);
+    assert_eq!(dimension_id_of_filename("example/entities/r.0.0.mca"), None);
+
+    // Without the datapack, the range of unknown dimension types is used
+    let mut level_data = world.level_data.clone().unwrap();
+    level_data.disabled_datapacks.push("file/sky".to_string());
+    assert_eq!(dimension_y_range(&src, &level_data, &DimensionId::new("example:sky/islands")).unwrap(), 0..256);
+    assert_eq!(dimension_y_range(&src, &level_data, &DimensionId::overworld()).unwrap(), -64..320);
+
+    // sub directories of listed files are filtered from the same listing
+    let listed = ListedFiles::new(&src);
+    assert_eq!(listed.sub_directory("datapacks").files().len(), 2);
+    assert_eq!(listed.sub_directory("dimensions").files().len(), src.sub_directory("dimensions").files().len());
+    assert_eq!(dimension_y_range(&listed, &level_data, &DimensionId::overworld()).unwrap(), -64..320);
+}
+
+#[test]
+fn load_players_with_item_components() {
+    use std::collections::HashMap;
+    use fastnbt::Value;
+    use crate::item::Item;
+    let src = FilesInMemory::from_7z_file("test_files/world/03_raids-1.20.2.7z", "").expect("Read 7z file and decompress");
+    let world = World::from_files(&src, &WorldLoadOption::default()).expect("Parse world from files in memory");
+    let mut nbt = world.players()[0].to_nbt();
+
+    // Items since 1.20.5 have int `count` and `components`
+    let mut components = HashMap::new();
+    components.insert("minecraft:damage".to_string(), Value::Int(12));
+    let mut sword = HashMap::new();
+    sword.insert("id".to_string(), Value::String("minecraft:diamond_sword".to_string()));
+    sword.insert("count".to_string(), Value::Int(1));
+    sword.insert("Slot".to_string(), Value::Byte(1));
+    sword.insert("components".to_string(), Value::Compound(components.clone()));
+    let mut torches = HashMap::new();
+    torches.insert("id".to_string(), Value::String("minecraft:torch".to_string()));
+    torches.insert("count".to_string(), Value::Int(64));
+    torches.insert("Slot".to_string(), Value::Byte(2));
+    nbt.insert("Inventory".to_string(), Value::List(vec![Value::Compound(sword), Value::Compound(torches)]));
+    nbt.insert("DataVersion".to_string(), Value::Int(3839));
+
+    let player = Player::from_nbt(nbt.clone(), "").expect("Parse player with 1.20.5 items");
+    let selected = player.player_fields.selected_item().expect("Sword in slot 1");
+    assert_eq!(selected.id, "minecraft:diamond_sword");
+    assert_eq!(selected.components.as_ref(), Some(&components));
+    assert_eq!(player.player_fields.inventory.0[&2].count, 64);
+    assert_eq!(player.to_nbt(), nbt);
+
+    let old = Item { count: 3, id: "minecraft:stone".to_string(), tags: HashMap::new(), components: None, unparsed_tags: HashMap::new() };
+    let old_nbt = old.to_nbt();
+    assert_eq!(old_nbt.get("Count"), Some(&Value::Byte(3)));
+    assert!(Item::from_nbt(&old_nbt, "").unwrap().components.is_none());
+}
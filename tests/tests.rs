@@ -26,7 +26,7 @@ use flate2::read::{GzDecoder};
 use ndarray::Array3;
 use rand::Rng;
 use mc_schem::block::CommonBlock;
-use mc_schem::{Schematic, WorldEdit12LoadOption, LitematicaLoadOption, LitematicaSaveOption, Block, schem, old_block, DataVersion, WorldEdit13SaveOption, Region, BlockEntity, MetaDataIR, WorldEdit13LoadOption};
+use mc_schem::{Schematic, WorldEdit12LoadOption, LitematicaLoadOption, LitematicaSaveOption, Block, schem, old_block, DataVersion, WorldEdit13SaveOption, Region, BlockEntity, MetaDataIR, WorldEdit13LoadOption, WorldEdit12SaveOption};
 
 #[test]
 fn block_id_parse() {
@@ -481,13 +481,254 @@ fn load_save_world_edit13() {
     }
 }
 
+#[test]
+fn streaming_load_save() {
+    fn assert_same(a: &Schematic, b: &Schematic) {
+        assert_eq!(a.metadata.mc_data_version, b.metadata.mc_data_version);
+        assert_eq!(a.regions.len(), b.regions.len());
+        for reg_a in &a.regions {
+            let reg_b = b.regions.iter().find(|r| r.name == reg_a.name).unwrap();
+            assert_eq!(reg_a.offset, reg_b.offset);
+            assert_eq!(reg_a.palette, reg_b.palette);
+            assert_eq!(reg_a.array_yzx, reg_b.array_yzx);
+            assert_eq!(reg_a.block_entities.len(), reg_b.block_entities.len());
+            for (pos, be) in &reg_a.block_entities {
+                assert_eq!(be.tags, reg_b.block_entities[pos].tags);
+            }
+            assert_eq!(reg_a.entities.len(), reg_b.entities.len());
+        }
+    }
+
+    let out_dir = "./target/test/streaming_load_save";
+    create_dir_all(out_dir).unwrap();
+
+    for name in ["test01", "test02", "test03", "multi-region01"] {
+        let src_filename = format!("./test_files/litematica/{name}.litematic");
+        let dst_filename = format!("{out_dir}/{name}.litematic");
+        let schem = Schematic::from_litematica_file(&src_filename, &LitematicaLoadOption::default()).unwrap().0;
+        let streamed = Schematic::from_litematica_file_streaming(&src_filename, &LitematicaLoadOption::default()).unwrap().0;
+        assert_same(&schem, &streamed);
+
+        streamed.save_litematica_file_streaming(&dst_filename, &LitematicaSaveOption::default()).unwrap();
+        let reloaded = Schematic::from_litematica_file(&dst_filename, &LitematicaLoadOption::default()).unwrap().0;
+        assert_same(&schem, &reloaded);
+    }
+
+    for name in ["test01", "test02", "test03", "full-blocks-1.20.2"] {
+        let src_filename = format!("./test_files/schem/{name}.schem");
+        let dst_filename = format!("{out_dir}/{name}.schem");
+        let schem = Schematic::from_world_edit_13_file(&src_filename, &WorldEdit13LoadOption::default()).unwrap().0;
+        let streamed = Schematic::from_world_edit_13_file_streaming(&src_filename, &WorldEdit13LoadOption::default()).unwrap().0;
+        assert_same(&schem, &streamed);
+
+        streamed.save_world_edit_13_file_streaming(&dst_filename, &WorldEdit13SaveOption::default()).unwrap();
+        let reloaded = Schematic::from_world_edit_13_file(&dst_filename, &WorldEdit13LoadOption::default()).unwrap().0;
+        assert_eq!(schem.shape(), reloaded.shape());
+        assert_eq!(schem.regions[0].block_entities.len(), reloaded.regions[0].block_entities.len());
+        let (mut blocks, mut reloaded_blocks) = (Vec::new(), Vec::new());
+        let shape = schem.shape();
+        for y in 0..shape[1] {
+            for z in 0..shape[2] {
+                for x in 0..shape[0] {
+                    blocks.push(schem.first_block_at([x, y, z]).cloned());
+                    reloaded_blocks.push(reloaded.first_block_at([x, y, z]).cloned());
+                }
+            }
+        }
+        assert_eq!(blocks, reloaded_blocks);
+    }
+}
+
+#[test]
+fn load_with_format_detection() {
+    use std::io::{Read, Write};
+    use flate2::write::ZlibEncoder;
+    use mc_schem::SchemFormat;
+
+    let out_dir = "./target/test/load_with_format_detection";
+    create_dir_all(out_dir).unwrap();
+    let files = [
+        ("./test_files/litematica/test01.litematic", SchemFormat::Litematica),
+        ("./test_files/vanilla_structure/test01.nbt", SchemFormat::VanillaStructure),
+        ("./test_files/schem/test01.schem", SchemFormat::WorldEdit13),
+        ("./test_files/schem/full-blocks-1.20.2.schem", SchemFormat::WorldEdit13),
+        ("./test_files/schematic/full-blocks-1.12.2.schematic", SchemFormat::WorldEdit12),
+    ];
+    for (idx, (file, expected)) in files.iter().enumerate() {
+        let expected_shape = Schematic::from_file(file).unwrap().0.shape();
+
+        // gzip
+        let (schem, _, format) = Schematic::from_reader_auto(&mut File::open(file).unwrap()).unwrap();
+        assert_eq!(format, *expected);
+        assert_eq!(schem.shape(), expected_shape);
+
+        // uncompressed
+        let mut raw = Vec::new();
+        GzDecoder::new(File::open(file).unwrap()).read_to_end(&mut raw).unwrap();
+        let (_, _, format) = Schematic::from_reader_auto(&mut raw.as_slice()).unwrap();
+        assert_eq!(format, *expected);
+
+        // zlib
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&raw).unwrap();
+        let zlib = encoder.finish().unwrap();
+        let (_, _, format) = Schematic::from_reader_auto(&mut zlib.as_slice()).unwrap();
+        assert_eq!(format, *expected);
+
+        // without extension
+        let no_ext = format!("{out_dir}/file{idx}");
+        std::fs::copy(file, &no_ext).unwrap();
+        assert_eq!(Schematic::from_file(&no_ext).unwrap().0.shape(), expected_shape);
+    }
+
+    assert!(Schematic::from_reader_auto(&mut "not a schematic".as_bytes()).is_err());
+}
+
+#[test]
+fn world_edit_13_biomes() {
+    use mc_schem::biome::Biome;
+    use mc_schem::region::RegionBiomes;
+    let out_dir = "./target/test/world_edit_13_biomes";
+    create_dir_all(out_dir).unwrap();
+
+    let mut schem = Schematic::from_world_edit_13_file("./test_files/schem/test01.schem", &WorldEdit13LoadOption::default()).unwrap().0;
+    assert!(schem.regions[0].biomes.is_none());
+    let shape = schem.shape();
+    let choices = [Biome::plains, Biome::desert, Biome::cherry_grove, Biome::deep_dark];
+
+    // v2 stores one biome per column, and v3 stores one biome per block
+    for (data_version, per_block) in [(DataVersion::Java_1_19_4, false), (DataVersion::Java_1_20_4, true)] {
+        schem.metadata.mc_data_version = data_version as i32;
+        let reg = &mut schem.regions[0];
+        reg.fill_biome(Biome::plains, per_block);
+        for y in 0..shape[1] {
+            for z in 0..shape[2] {
+                for x in 0..shape[0] {
+                    reg.set_biome([x, y, z], choices[((x + 2 * y + 3 * z) % 4) as usize]).unwrap();
+                }
+            }
+        }
+
+        let filename = format!("{out_dir}/biomes-{}.schem", data_version as i32);
+        schem.save_world_edit_13_file(&filename, &WorldEdit13SaveOption::default()).unwrap();
+        let loaded = Schematic::from_world_edit_13_file(&filename, &WorldEdit13LoadOption::default()).unwrap().0;
+        let streamed = Schematic::from_world_edit_13_file_streaming(&filename, &WorldEdit13LoadOption::default()).unwrap().0;
+        for reloaded in [&loaded, &streamed] {
+            match &reloaded.regions[0].biomes {
+                Some(RegionBiomes::Columns(_)) => assert!(!per_block),
+                Some(RegionBiomes::Blocks(_)) => assert!(per_block),
+                None => panic!("Biomes are not loaded from {filename}"),
+            }
+            for y in 0..shape[1] {
+                for z in 0..shape[2] {
+                    for x in 0..shape[0] {
+                        assert_eq!(schem.first_biome_at([x, y, z]), reloaded.first_biome_at([x, y, z]));
+                    }
+                }
+            }
+        }
+    }
+
+    // biomes from datapacks fail strict loading, and are loaded as the default biome with a warning
+    {
+        use std::io::Read;
+        use std::sync::Arc;
+        use mc_schem::error::{DefaultErrorHandler, Error};
+        let filename = format!("{out_dir}/biomes-{}.schem", DataVersion::Java_1_20_4 as i32);
+        let mut bytes = Vec::new();
+        GzDecoder::new(File::open(&filename).unwrap()).read_to_end(&mut bytes).unwrap();
+        let mut nbt: HashMap<String, Value> = fastnbt::from_bytes(&bytes).unwrap();
+        let Some(Value::Compound(tag_schem)) = nbt.get_mut("Schematic") else { panic!("No /Schematic") };
+        let Some(Value::Compound(tag_biomes)) = tag_schem.get_mut("Biomes") else { panic!("No /Schematic/Biomes") };
+        let Some(Value::Compound(tag_palette)) = tag_biomes.get_mut("Palette") else { panic!("No /Schematic/Biomes/Palette") };
+        let idx = tag_palette.remove("minecraft:deep_dark").unwrap();
+        tag_palette.insert("some_datapack:custom_biome".to_string(), idx);
+
+        let handler = Arc::new(DefaultErrorHandler::new());
+        let option = WorldEdit13LoadOption { error_handler: handler.clone() };
+        let strict = Schematic::from_world_edit_13_nbt(nbt.clone(), &WorldEdit13LoadOption::default());
+        assert!(matches!(strict, Err(Error::InvalidBiome { .. })));
+        let (lenient, _) = Schematic::from_world_edit_13_nbt(nbt, &option).unwrap();
+        let warnings = handler.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(matches!(&warnings[0], Error::InvalidBiome { biome, .. } if biome == "some_datapack:custom_biome"));
+        for y in 0..shape[1] {
+            for z in 0..shape[2] {
+                for x in 0..shape[0] {
+                    let expected = match schem.first_biome_at([x, y, z]) {
+                        Some(Biome::deep_dark) => Some(Biome::default()),
+                        b => b,
+                    };
+                    assert_eq!(lenient.first_biome_at([x, y, z]), expected);
+                }
+            }
+        }
+    }
+}
+
 #[test]
 fn load_save_world_edit12() {
     use schem::WorldEdit12LoadOption;
     //let src_dir = "./test_files/schematic";
     let out_dir = "./target/test/load_save_world_edit12";
     create_dir_all(out_dir).unwrap();
-    let _ = Schematic::from_world_edit_12_file("./test_files/schematic/full-blocks-1.12.2.schematic", &WorldEdit12LoadOption::default()).unwrap();
+    let (schem, _, number_id) = Schematic::from_world_edit_12_file("./test_files/schematic/full-blocks-1.12.2.schematic", &WorldEdit12LoadOption::default()).unwrap();
+
+    let dst_filename = format!("{out_dir}/out01.schematic");
+    let replaced = schem.save_world_edit_12_file(&dst_filename, &WorldEdit12SaveOption::default()).expect("Failed to save .schematic file");
+    assert!(replaced.is_empty());
+
+    let (saved, _, saved_number_id) = Schematic::from_world_edit_12_file(&dst_filename, &WorldEdit12LoadOption::default()).expect("Failed to load saved .schematic file");
+    assert_eq!(schem.shape(), saved.shape());
+    assert_eq!(schem.regions[0].block_entities.len(), saved.regions[0].block_entities.len());
+    let shape = schem.shape();
+    for y in 0..shape[1] {
+        for z in 0..shape[2] {
+            for x in 0..shape[0] {
+                let pos = [x, y, z];
+                assert_eq!(schem.first_block_at(pos), saved.first_block_at(pos));
+            }
+        }
+    }
+    assert_eq!(number_id, saved_number_id);
+
+    // blocks introduced after 1.13 have no number id
+    let mut modern = Schematic::new();
+    let mut region = Region::with_shape([2, 1, 1]);
+    region.set_block([1, 0, 0], &Block::from_id("minecraft:cherry_planks").unwrap()).unwrap();
+    modern.regions.push(region);
+    assert!(modern.to_nbt_world_edit_12(&WorldEdit12SaveOption::default()).is_err());
+    let option = WorldEdit12SaveOption {
+        number_id_fallback: schem::NumberIdFallback::ReplaceWith { id: 1, damage: 0 },
+        ..WorldEdit12SaveOption::default()
+    };
+    let (_, replaced) = modern.to_nbt_world_edit_12(&option).unwrap();
+    assert_eq!(replaced, vec![Block::from_id("minecraft:cherry_planks").unwrap()]);
+
+    // blocks of modern schematics are converted to 1.12 before saving, only blocks added later
+    // are replaced
+    let modern = Schematic::from_litematica_file("./test_files/litematica/test01.litematic", &LitematicaLoadOption::default()).unwrap().0;
+    let dst_filename = format!("{out_dir}/modern.schematic");
+    let option = WorldEdit12SaveOption {
+        number_id_fallback: schem::NumberIdFallback::ReplaceWith { id: 20, damage: 0 },
+        ..WorldEdit12SaveOption::default()
+    };
+    let replaced = modern.save_world_edit_12_file(&dst_filename, &option).unwrap();
+    assert_eq!(replaced, vec![Block::from_id("minecraft:tinted_glass").unwrap()]);
+    let (saved, _, _) = Schematic::from_world_edit_12_file(&dst_filename, &WorldEdit12LoadOption::default()).unwrap();
+    let shape = modern.shape();
+    assert_eq!(shape, saved.shape());
+    for y in 0..shape[1] {
+        for z in 0..shape[2] {
+            for x in 0..shape[0] {
+                let pos = [x, y, z];
+                let expected = modern.first_block_at(pos).unwrap()
+                    .convert_data_version(modern.metadata.mc_data_version, DataVersion::Java_1_12_2 as i32)
+                    .unwrap_or(Block::from_id("minecraft:glass").unwrap());
+                assert_eq!(saved.first_block_at(pos), Some(&expected));
+            }
+        }
+    }
 }
 
 #[test]
@@ -693,6 +934,492 @@ fn test_merge_regions() {
     schem.save_litematica_file(out_file, &LitematicaSaveOption::default()).unwrap()
 }
 
+#[test]
+fn convert_data_version() {
+    use mc_schem::DataVersionConvertOption;
+    use std::collections::HashSet;
+    // 1.12.2 -> 1.13.2, every converted block should exist in 1.13.2
+    let mut schem = Schematic::from_world_edit_12_file("./test_files/schematic/full-blocks-1.12.2.schematic", &WorldEdit12LoadOption::default()).unwrap().0;
+    let reference = Schematic::from_litematica_file("./test_files/litematica/full-blocks-1.13.2.litematic", &LitematicaLoadOption::default()).unwrap().0;
+    let report = schem.convert_data_version(DataVersion::Java_1_13_2, &DataVersionConvertOption::default());
+    assert!(report.unrepresentable_blocks.is_empty());
+    assert_eq!(schem.metadata.mc_data_version, DataVersion::Java_1_13_2 as i32);
+    let known: HashSet<&Block> = reference.regions.iter().flat_map(|r| r.palette.iter()).collect();
+    for blk in &schem.regions[0].palette {
+        // signs are missing in the reference file
+        if blk.id == "sign" || blk.id == "wall_sign" {
+            continue;
+        }
+        assert!(known.contains(blk), "{blk} doesn't exist in 1.13.2");
+    }
+
+    // renamed blocks and changed properties
+    let grass = Block::from_id("minecraft:grass").unwrap();
+    assert_eq!(grass.convert_data_version(DataVersion::Java_1_20_1 as i32, DataVersion::Java_1_20_4 as i32).unwrap().id, "short_grass");
+    let wall = Block::from_id("minecraft:cobblestone_wall[east=true,north=false,south=false,up=true,waterlogged=false,west=false]").unwrap();
+    let wall_1_16 = wall.convert_data_version(DataVersion::Java_1_15_2 as i32, DataVersion::Java_1_16_5 as i32).unwrap();
+    assert_eq!(wall_1_16.attributes["east"], "low");
+    assert_eq!(wall_1_16.attributes["north"], "none");
+    assert_eq!(wall_1_16.convert_data_version(DataVersion::Java_1_16_5 as i32, DataVersion::Java_1_15_2 as i32).unwrap(), wall);
+
+    // blocks added after the target version are reported
+    let mut schem = Schematic::from_world_edit_13_file("./test_files/schem/full-blocks-1.20.2.schem", &WorldEdit13LoadOption::default()).unwrap().0;
+    let option = DataVersionConvertOption { unrepresentable_replacement: Some(CommonBlock::Air) };
+    let report = schem.convert_data_version(DataVersion::Java_1_16_5, &option);
+    assert!(report.unrepresentable_blocks.iter().any(|b| b.id == "cherry_planks"));
+    assert!(report.unrepresentable_blocks.iter().all(|b| b.id != "stone"));
+    assert!(schem.regions[0].palette.iter().all(|b| b.id != "cherry_planks" && b.id != "dirt_path"));
+}
+
+#[test]
+fn rotate_and_mirror() {
+    use mc_schem::schem::transform::{Mirror, Rotation};
+    let src = Schematic::from_litematica_file("./test_files/litematica/multi-region01.litematic",
+                                              &LitematicaLoadOption::default()).unwrap().0;
+    let shape = src.shape();
+
+    let mut schem = Schematic::new();
+    schem.regions = src.regions.clone();
+    schem.rotate(Rotation::Clockwise90);
+    assert_eq!(schem.shape(), [shape[2], shape[1], shape[0]]);
+    for x in 0..shape[0] {
+        for y in 0..shape[1] {
+            for z in 0..shape[2] {
+                let expected = src.first_block_at([x, y, z]).map(|b| b.rotated(Rotation::Clockwise90));
+                let rotated = schem.first_block_at([shape[2] - 1 - z, y, x]).cloned();
+                assert_eq!(expected, rotated);
+                assert_eq!(src.first_block_entity_at([x, y, z]).is_some(),
+                           schem.first_block_entity_at([shape[2] - 1 - z, y, x]).is_some());
+            }
+        }
+    }
+    // rotating back
+    schem.rotate(Rotation::CounterClockwise90);
+    for (reg, src_reg) in schem.regions.iter().zip(src.regions.iter()) {
+        assert_eq!(reg.offset, src_reg.offset);
+        assert_eq!(reg.array_yzx, src_reg.array_yzx);
+        assert_eq!(reg.palette, src_reg.palette);
+    }
+
+    // mirror twice
+    for mirror in [Mirror::X, Mirror::Z] {
+        schem.mirror(mirror);
+        schem.mirror(mirror);
+        for (reg, src_reg) in schem.regions.iter().zip(src.regions.iter()) {
+            assert_eq!(reg.offset, src_reg.offset);
+            assert_eq!(reg.array_yzx, src_reg.array_yzx);
+            assert_eq!(reg.palette, src_reg.palette);
+        }
+    }
+    schem.transpose_xz();
+    assert_eq!(schem.first_block_at([1, 0, 0]).map(|b| b.id.clone()), src.first_block_at([0, 0, 1]).map(|b| b.id.clone()));
+}
+
+#[test]
+fn diff_schematics() {
+    use mc_schem::schem::diff::{BlockDiff, BlockEntityDiff};
+    use mc_schem::player::EntityBox;
+    let src = Schematic::from_litematica_file("./test_files/litematica/multi-region01.litematic",
+                                              &LitematicaLoadOption::default()).unwrap().0;
+    assert!(src.diff(&src).is_empty());
+
+    let stone = Block::from_id("minecraft:stone").unwrap();
+    let dirt = Block::from_id("minecraft:dirt").unwrap();
+    let stairs_n = Block::from_id("minecraft:oak_stairs[facing=north]").unwrap();
+    let stairs_e = Block::from_id("minecraft:oak_stairs[facing=east]").unwrap();
+
+    // old covers x in [0, 3), new covers x in [1, 4)
+    let mut old_reg = Region::with_shape([3, 1, 1]);
+    old_reg.set_block([0, 0, 0], &stone).unwrap();
+    old_reg.set_block([1, 0, 0], &stairs_n).unwrap();
+    old_reg.set_block([2, 0, 0], &stone).unwrap();
+    let mut be = BlockEntity::new();
+    be.tags.insert("id".to_string(), Value::String("minecraft:chest".to_string()));
+    old_reg.block_entities.insert([2, 0, 0], be.clone());
+    let mut entity_nbt = HashMap::new();
+    entity_nbt.insert("id".to_string(), Value::String("minecraft:pig".to_string()));
+    entity_nbt.insert("Pos".to_string(), Value::List(vec![Value::Double(0.5), Value::Double(0.0), Value::Double(0.5)]));
+    let mut entity = EntityBox::from_nbt(entity_nbt, "").unwrap();
+    old_reg.entities.push(entity.clone());
+
+    let mut new_reg = Region::with_shape([3, 1, 1]);
+    new_reg.offset = [1, 0, 0];
+    new_reg.set_block([0, 0, 0], &stairs_e).unwrap();
+    new_reg.set_block([1, 0, 0], &stone).unwrap();
+    new_reg.set_block([2, 0, 0], &dirt).unwrap();
+    be.tags.insert("Lock".to_string(), Value::String("key".to_string()));
+    new_reg.block_entities.insert([1, 0, 0], be);
+    // the same pig at the same global position
+    entity.pos_shift([-1, 0, 0]);
+    new_reg.entities.push(entity.clone());
+    let mut cow_nbt = entity.to_nbt();
+    cow_nbt.insert("id".to_string(), Value::String("minecraft:cow".to_string()));
+    new_reg.entities.push(EntityBox::from_nbt(cow_nbt, "").unwrap());
+
+    let mut old = Schematic::new();
+    old.regions.push(old_reg);
+    let mut new = Schematic::new();
+    new.regions.push(new_reg);
+
+    let diff = old.diff(&new);
+    assert_eq!(diff.blocks.len(), 3);
+    assert_eq!(diff.blocks[&[0, 0, 0]], BlockDiff::Removed(stone.clone()));
+    assert_eq!(diff.blocks[&[1, 0, 0]], BlockDiff::StateChanged { old: stairs_n, new: stairs_e.clone() });
+    assert_eq!(diff.blocks[&[3, 0, 0]], BlockDiff::Added(dirt));
+    assert_eq!(diff.block_entities.len(), 1);
+    assert!(matches!(diff.block_entities[&[2, 0, 0]], BlockEntityDiff::Changed { .. }));
+    assert!(diff.removed_entities.is_empty());
+    assert_eq!(diff.added_entities.len(), 1);
+    assert_eq!(diff.added_entities[0].id(), "minecraft:cow");
+
+    let changed = diff.to_schematic(MetaDataIR::default()).unwrap();
+    assert_eq!(changed.regions[0].offset, [0, 0, 0]);
+    assert_eq!(changed.shape(), [4, 1, 1]);
+    assert_eq!(changed.first_block_at([0, 0, 0]), Some(&Block::air()));
+    assert_eq!(changed.first_block_at([1, 0, 0]), Some(&stairs_e));
+    assert!(changed.first_block_at([2, 0, 0]).unwrap().is_structure_void());
+    assert!(changed.first_block_entity_at([2, 0, 0]).is_some());
+    assert_eq!(changed.regions[0].entities.len(), 1);
+}
+
+#[test]
+fn litematica_preview() {
+    use mc_schem::render::{MapColor, PreviewOption, RenderOption, RenderView};
+    let out_dir = "./target/test/litematica_preview";
+    create_dir_all(out_dir).unwrap();
+
+    let mut reg = Region::with_shape([4, 3, 2]);
+    reg.fill_with(&Block::from_id("minecraft:stone").unwrap());
+    reg.set_block([0, 2, 0], &Block::from_id("minecraft:grass_block").unwrap()).unwrap();
+    reg.set_block([3, 2, 1], &Block::air()).unwrap();
+    let mut schem = Schematic::new();
+    schem.regions.push(reg);
+
+    let top = schem.render_top_down(&RenderOption { block_size: 1, ..RenderOption::default() });
+    assert_eq!((top.width, top.height), (4, 2));
+    let [r, g, b] = MapColor::Grass.rgb();
+    assert_eq!(top.pixel_at(0, 0), [r, g, b, 255]);
+    let [r, g, b] = MapColor::Stone.rgb();
+    assert_eq!(top.pixel_at(3, 1), [r, g, b, 255]);
+
+    let iso = schem.render_isometric(&RenderOption::default());
+    assert_eq!((iso.width, iso.height), (24, 24));
+    // the corner of the image is outside of all blocks
+    assert_eq!(iso.pixel_at(0, 0)[3], 0);
+    assert!(iso.pixels.iter().any(|p| p[3] == 255));
+
+    assert!(schem.metadata.litematica_preview.is_none());
+    schem.update_litematica_preview(&PreviewOption { size: 140, view: RenderView::Isometric });
+    let preview = schem.metadata.litematica_preview.clone().unwrap();
+    assert_eq!(preview.len(), 140 * 140);
+
+    let filename = format!("{out_dir}/preview.litematic");
+    schem.save_litematica_file(&filename, &LitematicaSaveOption::default()).unwrap();
+    let (loaded, raw) = Schematic::from_litematica_file(&filename, &LitematicaLoadOption::default()).unwrap();
+    assert_eq!(raw.preview_image_data.as_ref(), Some(&preview));
+    assert_eq!(loaded.metadata.litematica_preview.as_ref(), Some(&preview));
+    let streamed = Schematic::from_litematica_file_streaming(&filename, &LitematicaLoadOption::default()).unwrap().0;
+    assert_eq!(streamed.metadata.litematica_preview, Some(preview));
+
+    let loaded = Schematic::from_litematica_file("./test_files/litematica/test01.litematic", &LitematicaLoadOption::default()).unwrap().0;
+    assert!(loaded.metadata.litematica_preview.is_none());
+}
+
+#[test]
+fn render_png() {
+    use mc_schem::error::Error;
+    use mc_schem::render::{Image, MapColor, RenderOption, TexturePack};
+    let out_dir = "./target/test/render_png";
+    create_dir_all(out_dir).unwrap();
+
+    let schem = Schematic::from_litematica_file("./test_files/litematica/test03.litematic", &LitematicaLoadOption::default()).unwrap().0;
+    let shape = schem.shape();
+    let option = RenderOption::default();
+    for (idx, image) in [schem.render_top_down(&option), schem.render_isometric(&option)].iter().enumerate() {
+        let png = image.to_png();
+        let decoded = Image::from_png(&png).unwrap();
+        assert_eq!(&decoded, image);
+        image.save_png(&format!("{out_dir}/{idx}.png")).unwrap();
+        assert_eq!(&Image::from_png_file(&format!("{out_dir}/{idx}.png")).unwrap(), image);
+    }
+    // bit depth and color type are the 9th and 10th byte of IHDR
+    let png = schem.render_top_down(&option).to_png();
+    for (bit_depth, color_type) in [(3, 0), (0, 0), (0, 3), (4, 2), (16, 0)] {
+        let mut invalid = png.clone();
+        invalid[24] = bit_depth;
+        invalid[25] = color_type;
+        let result = Image::from_png(&invalid);
+        assert!(matches!(result, Err(Error::InvalidPNG { .. })), "bit depth {bit_depth}, color type {color_type}: {result:?}");
+    }
+    // files that can not be decoded are skipped
+    let texture_dir = format!("{out_dir}/pack/assets/minecraft/textures/block");
+    create_dir_all(&texture_dir).unwrap();
+    std::fs::write(format!("{texture_dir}/stone.png"), &png).unwrap();
+    let mut invalid = png.clone();
+    invalid[24] = 3;
+    std::fs::write(format!("{texture_dir}/broken.png"), &invalid).unwrap();
+    let pack = TexturePack::from_dir(&format!("{out_dir}/pack")).unwrap();
+    assert_eq!(pack.len(), 1);
+    let top = schem.render_top_down(&option);
+    assert_eq!((top.width, top.height), (shape[0] as u32 * 4, shape[2] as u32 * 4));
+
+    let slice_option = RenderOption { block_size: 1, ..RenderOption::default() };
+    let slices = schem.render_slices(&slice_option);
+    assert_eq!(slices.len(), shape[1] as usize);
+    for (y, slice) in slices.iter().enumerate() {
+        assert_eq!(Some(slice), schem.render_slice(y as i32, &slice_option).as_ref());
+    }
+    assert!(schem.render_slice(shape[1], &option).is_none());
+
+    // rotating by 90 degrees swaps x and z
+    let rotated = schem.render_top_down(&RenderOption { block_size: 1, rotation: 1, ..RenderOption::default() });
+    assert_eq!((rotated.width, rotated.height), (shape[2] as u32, shape[0] as u32));
+
+    let mut reg = Region::with_shape([2, 1, 1]);
+    reg.set_block([0, 0, 0], &Block::from_id("minecraft:oak_slab").unwrap()).unwrap();
+    reg.set_block([1, 0, 0], &Block::from_id("minecraft:stone").unwrap()).unwrap();
+    let mut small = Schematic::new();
+    small.regions.push(reg);
+    let mut textures = TexturePack::new();
+    textures.insert("oak_planks", [1, 2, 3]);
+    let image = small.render_top_down(&RenderOption { block_size: 1, texture_pack: Some(textures), ..RenderOption::default() });
+    assert_eq!(image.pixel_at(0, 0), [1, 2, 3, 255]);
+    let [r, g, b] = MapColor::Stone.rgb();
+    assert_eq!(image.pixel_at(1, 0), [r, g, b, 255]);
+}
+
+#[test]
+fn map_art_colors() {
+    use mc_schem::render::{MapArtOption, MapColor, MapShade};
+    let color_of = |id: &str| Block::from_id(id).unwrap().map_color();
+    for id in ["minecraft:stone_bricks", "minecraft:end_stone_bricks", "minecraft:mud_bricks", "minecraft:stone_brick_wall"] {
+        assert_ne!(color_of(id), MapColor::ColorRed, "{id}");
+    }
+    assert_ne!(color_of("minecraft:ender_chest"), MapColor::Wood);
+    assert_eq!(color_of("minecraft:ender_chest"), MapColor::Stone);
+    assert_eq!(color_of("minecraft:end_stone_bricks"), MapColor::Sand);
+    assert_eq!(color_of("minecraft:mud_bricks"), MapColor::TerracottaLightGray);
+    assert_eq!(color_of("minecraft:bricks"), MapColor::ColorRed);
+    assert_eq!(color_of("minecraft:red_wool"), MapColor::ColorRed);
+    assert_eq!(color_of("minecraft:oak_log[axis=y]"), MapColor::Wood);
+    assert_eq!(color_of("minecraft:oak_log[axis=x]"), MapColor::Podzol);
+    assert_eq!(color_of("minecraft:red_bed[part=head]"), MapColor::Wool);
+    // blocks missing from the table are guessed from their ids
+    assert_eq!(color_of("minecraft:some_future_planks"), MapColor::Wood);
+
+    let stone = Block::from_id("minecraft:stone").unwrap();
+    let water = Block::from_id("minecraft:water[level=0]").unwrap();
+    // x = 0: a stair going up to the south, then down
+    // x = 1: flat, with glass on top which is invisible
+    // x = 2: water of depth 1 and 8
+    let mut reg = Region::with_shape([3, 8, 5]);
+    for (z, y) in [0, 1, 2, 2, 0].iter().enumerate() {
+        reg.set_block([0, *y, z as i32], &stone).unwrap();
+        reg.set_block([1, 0, z as i32], &stone).unwrap();
+    }
+    reg.set_block([1, 1, 2], &Block::from_id("minecraft:glass").unwrap()).unwrap();
+    reg.set_block([2, 0, 1], &stone).unwrap();
+    reg.set_block([2, 1, 1], &water).unwrap();
+    for y in 0..8 {
+        reg.set_block([2, y, 3], &water).unwrap();
+    }
+
+    let map = reg.map_art(&MapArtOption::default());
+    assert_eq!(map.colors.len(), 128 * 128);
+    // the row at north of the map is empty, so the first row is light
+    assert_eq!(map.color_at(0, 0), Some((MapColor::Stone, MapShade::Light)));
+    assert_eq!(map.color_at(0, 1), Some((MapColor::Stone, MapShade::Light)));
+    assert_eq!(map.color_at(0, 3), Some((MapColor::Stone, MapShade::Normal)));
+    assert_eq!(map.color_at(0, 4), Some((MapColor::Stone, MapShade::Dark)));
+    assert_eq!(map.color_at(1, 2), Some((MapColor::Stone, MapShade::Normal)));
+    assert_eq!(map.color_at(2, 1), Some((MapColor::Water, MapShade::Light)));
+    assert_eq!(map.color_at(2, 3), Some((MapColor::Water, MapShade::Dark)));
+    assert_eq!(map.color_at(2, 0), Some((MapColor::None, MapShade::Dark)));
+    assert_eq!(map.color_at(100, 100), Some((MapColor::None, MapShade::Dark)));
+    assert_eq!(map.colors[0], MapColor::Stone as u8 * 4 + 2);
+    let bytes = map.to_byte_array();
+    assert_eq!(bytes[128 * 4], (MapColor::Stone as u8 * 4) as i8);
+
+    let image = map.to_image();
+    let [r, g, b] = MapColor::Stone.shaded_rgb(MapShade::Dark);
+    assert_eq!(image.pixel_at(0, 4), [r, g, b, 255]);
+    assert_eq!(image.pixel_at(100, 100)[3], 0);
+
+    // the row at north of the map is used to shade the first row
+    let map = reg.map_art(&MapArtOption { origin: [0, 3] });
+    assert_eq!(map.color_at(0, 0), Some((MapColor::Stone, MapShade::Normal)));
+    assert_eq!(map.color_at(0, 1), Some((MapColor::Stone, MapShade::Dark)));
+    let map = reg.map_art(&MapArtOption { origin: [0, 2] });
+    assert_eq!(map.color_at(0, 0), Some((MapColor::Stone, MapShade::Light)));
+    assert_eq!(map.color_at(0, 2), Some((MapColor::Stone, MapShade::Dark)));
+}
+
+#[test]
+fn map_data_file() {
+    use mc_schem::map::{MapBanner, MapData, MapFrame};
+    use mc_schem::render::{MapColor, MapShade};
+    let out_dir = "./target/test/map_data_file";
+    create_dir_all(out_dir).unwrap();
+
+    let mut map = MapData::new();
+    map.scale = 2;
+    map.x_center = 64;
+    map.z_center = -192;
+    map.locked = true;
+    map.colors.colors[130] = MapColor::Water.to_color_byte(MapShade::Darkest);
+    map.colors.colors[131] = MapColor::Grass.to_color_byte(MapShade::Light);
+    map.banners.push(MapBanner { color: "red".to_string(), name: Some("{\"text\":\"home\"}".to_string()), pos: [1, 70, -3] });
+    map.frames.push(MapFrame { entity_id: 42, rotation: 180, pos: [5, 64, 9] });
+    map.unparsed_tags.insert("custom".to_string(), fastnbt::Value::Int(7));
+
+    let filename = format!("{out_dir}/map_0.dat");
+    map.save_file(&filename).unwrap();
+    let loaded = MapData::from_file(&filename).unwrap();
+    assert_eq!(loaded.scale, 2);
+    assert_eq!(loaded.dimension, "minecraft:overworld");
+    assert_eq!([loaded.x_center, loaded.z_center], [64, -192]);
+    assert!(loaded.locked);
+    assert_eq!(loaded.colors, map.colors);
+    assert_eq!(loaded.banners, map.banners);
+    assert_eq!(loaded.frames, map.frames);
+    assert_eq!(loaded.unparsed_tags.get("custom"), Some(&fastnbt::Value::Int(7)));
+
+    // all 4 shades are kept in rgba
+    let image = loaded.to_image();
+    let [r, g, b] = MapColor::Water.shaded_rgb(MapShade::Darkest);
+    assert_eq!(image.pixel_at(2, 1), [r, g, b, 255]);
+    assert_eq!(image.pixel_at(0, 0)[3], 0);
+    let mut converted = MapData::new();
+    converted.set_image(&image).unwrap();
+    assert_eq!(converted.colors, map.colors);
+    assert!(converted.set_image(&image.fit_into_square(64)).is_err());
+    assert_eq!(converted.colors, map.colors);
+
+    // maps before 1.16 store dimension as number
+    let mut nbt = map.to_nbt();
+    if let Some(fastnbt::Value::Compound(data)) = nbt.get_mut("data") {
+        data.insert("dimension".to_string(), fastnbt::Value::Byte(-1));
+    }
+    assert_eq!(MapData::from_nbt(nbt).unwrap().dimension, "minecraft:the_nether");
+    // and they are written back as number
+    let mut old_map = map.clone();
+    old_map.dimension = "minecraft:the_nether".to_string();
+    for (version, dimension) in [(DataVersion::Java_1_12_2, fastnbt::Value::Byte(-1)),
+        (DataVersion::Java_1_15_2, fastnbt::Value::Int(-1)),
+        (DataVersion::Java_1_16, fastnbt::Value::String("minecraft:the_nether".to_string()))] {
+        old_map.data_version = version as i32;
+        let nbt = old_map.to_nbt();
+        let Some(fastnbt::Value::Compound(data)) = nbt.get("data") else { panic!("No /data") };
+        assert_eq!(data.get("dimension"), Some(&dimension));
+        assert_eq!(MapData::from_nbt(nbt).unwrap().dimension, "minecraft:the_nether");
+    }
+
+    // banners and frames have lowercase keys since 1.20.5
+    let banner = MapBanner { color: "red".to_string(), name: Some("\"home\"".to_string()), pos: [1, 70, -3] };
+    let frame = MapFrame { entity_id: 42, rotation: 180, pos: [5, 64, 9] };
+    let xyz = |pos: [i32; 3]| {
+        let mut nbt = HashMap::new();
+        for (key, val) in ["X", "Y", "Z"].iter().zip(pos) {
+            nbt.insert(key.to_string(), Value::Int(val));
+        }
+        return Value::Compound(nbt);
+    };
+    let mut old_banner = HashMap::new();
+    old_banner.insert("Color".to_string(), Value::String("red".to_string()));
+    old_banner.insert("Name".to_string(), Value::String("\"home\"".to_string()));
+    old_banner.insert("Pos".to_string(), xyz(banner.pos));
+    let mut old_frame = HashMap::new();
+    old_frame.insert("EntityId".to_string(), Value::Int(42));
+    old_frame.insert("Rotation".to_string(), Value::Int(180));
+    old_frame.insert("Pos".to_string(), xyz(frame.pos));
+    let mut new_banner = HashMap::new();
+    new_banner.insert("color".to_string(), Value::String("red".to_string()));
+    new_banner.insert("name".to_string(), Value::String("\"home\"".to_string()));
+    new_banner.insert("pos".to_string(), Value::IntArray(fastnbt::IntArray::new(banner.pos.to_vec())));
+    let mut new_frame = HashMap::new();
+    new_frame.insert("entity_id".to_string(), Value::Int(42));
+    new_frame.insert("rotation".to_string(), Value::Int(180));
+    new_frame.insert("pos".to_string(), Value::IntArray(fastnbt::IntArray::new(frame.pos.to_vec())));
+    for (version, banner_nbt, frame_nbt) in [(DataVersion::Java_1_20_4, old_banner, old_frame),
+        (DataVersion::Java_1_20_5, new_banner, new_frame)] {
+        let version = version as i32;
+        assert_eq!(MapBanner::from_nbt(&banner_nbt, "", version).unwrap(), banner);
+        assert_eq!(MapFrame::from_nbt(&frame_nbt, "", version).unwrap(), frame);
+        assert_eq!(banner.to_nbt(version), banner_nbt);
+        assert_eq!(frame.to_nbt(version), frame_nbt);
+
+        let mut map = map.clone();
+        map.data_version = version;
+        let loaded = MapData::from_nbt(map.to_nbt()).unwrap();
+        assert_eq!(loaded.banners, map.banners);
+        assert_eq!(loaded.frames, map.frames);
+    }
+    // white is the default color of banners since 1.20.5
+    let mut white_banner = HashMap::new();
+    white_banner.insert("pos".to_string(), Value::IntArray(fastnbt::IntArray::new(vec![0, 0, 0])));
+    let parsed = MapBanner::from_nbt(&white_banner, "", DataVersion::Java_1_20_5 as i32).unwrap();
+    assert_eq!(parsed.color, "white");
+    assert_eq!(parsed.to_nbt(DataVersion::Java_1_20_5 as i32), white_banner);
+}
+
+#[test]
+fn material_list() {
+    use mc_schem::MaterialListOption;
+    let blocks = ["minecraft:wall_torch[facing=north]", "minecraft:oak_wall_sign", "minecraft:stone_slab[type=double]",
+        "minecraft:red_bed[part=head]", "minecraft:red_bed[part=foot]", "minecraft:oak_door[half=upper]",
+        "minecraft:oak_door[half=lower]", "minecraft:water[level=0]", "minecraft:water[level=3]",
+        "minecraft:fire", "minecraft:potted_poppy", "minecraft:chest", "minecraft:air"];
+    let mut reg = Region::with_shape([blocks.len() as i32, 1, 1]);
+    for (x, id) in blocks.iter().enumerate() {
+        reg.set_block([x as i32, 0, 0], &Block::from_id(id).unwrap()).unwrap();
+    }
+    let item = |id: &str, count: i8| {
+        let mut item = HashMap::new();
+        item.insert("id".to_string(), Value::String(id.to_string()));
+        item.insert("Count".to_string(), Value::Byte(count));
+        return item;
+    };
+    // a shulker box with 5 diamonds, and 100 torches in the chest
+    let mut shulker = item("minecraft:shulker_box", 1);
+    let mut be_tag = HashMap::new();
+    be_tag.insert("Items".to_string(), Value::List(vec![Value::Compound(item("minecraft:diamond", 5))]));
+    let mut tag = HashMap::new();
+    tag.insert("BlockEntityTag".to_string(), Value::Compound(be_tag));
+    shulker.insert("tag".to_string(), Value::Compound(tag));
+    let mut chest = BlockEntity::new();
+    chest.tags.insert("Items".to_string(), Value::List(vec![
+        Value::Compound(item("minecraft:torch", 64)),
+        Value::Compound(item("torch", 36)),
+        Value::Compound(shulker)]));
+    reg.block_entities.insert([11, 0, 0], chest);
+    let mut schem = Schematic::new();
+    schem.regions.push(reg);
+
+    let list = schem.material_list(&MaterialListOption::default());
+    assert_eq!(list.count_of("minecraft:torch"), 1);
+    assert_eq!(list.count_of("minecraft:oak_sign"), 1);
+    assert_eq!(list.count_of("minecraft:stone_slab"), 2);
+    assert_eq!(list.count_of("minecraft:red_bed"), 1);
+    assert_eq!(list.count_of("minecraft:oak_door"), 1);
+    assert_eq!(list.count_of("minecraft:water_bucket"), 1);
+    assert_eq!(list.count_of("minecraft:flower_pot"), 1);
+    assert_eq!(list.count_of("minecraft:poppy"), 1);
+    assert_eq!(list.count_of("minecraft:chest"), 1);
+    assert_eq!(list.count_of("minecraft:fire"), 0);
+    assert_eq!(list.count_of("minecraft:air"), 0);
+    assert_eq!(list.entries.len(), 9);
+    assert_eq!(list.entries[0].item, "minecraft:stone_slab");
+
+    let list = schem.material_list(&MaterialListOption { include_inventories: true });
+    assert_eq!(list.count_of("minecraft:torch"), 101);
+    assert_eq!(list.count_of("minecraft:shulker_box"), 1);
+    assert_eq!(list.count_of("minecraft:diamond"), 5);
+    let torch = list.entries.iter().find(|e| e.item == "minecraft:torch").unwrap();
+    assert_eq!(torch.breakdown(), (0, 1, 37));
+    assert!(list.to_csv().starts_with("item,count,stack_size,shulker_boxes,stacks,remainder\nminecraft:torch,101,64,0,1,37\n"));
+    assert!(list.to_json().contains("{\"item\": \"minecraft:torch\", \"count\": 101,"));
+}
+
 #[test]
 fn test_3d_array_order() {
     let mut arr: ndarray::Array3<u16> = Array3::zeros([2, 3, 4]);
@@ -769,4 +1496,313 @@ fn test_3d_array_order() {
 //     for (key, _) in &nbt {
 //         println!("\t{key}");
 //     }
-// }
\ No newline at end of file
+// }
+
+#[test]
+fn typed_entities() {
+    use std::io::Read;
+    use mc_schem::entity::{ArmorStand, ItemFrame, Minecart, Mob, Painting};
+    use mc_schem::item::Item;
+    use mc_schem::player::EntityBox;
+
+    let (schem, _) = Schematic::from_file("./test_files/litematica/test02.litematic").unwrap();
+    assert!(!schem.regions[0].entities.is_empty());
+    for entity in &schem.regions[0].entities {
+        assert!(entity.is_living_body());
+        let mob = entity.downcast_ref::<Mob>().expect("Zombies and creepers are mobs");
+        // unknown tags are kept
+        assert!(mob.unparsed_tags.contains_key("CanPickUpLoot"));
+    }
+    // entities are written back exactly as they are read, including the order of attributes
+    let mut bytes = Vec::new();
+    GzDecoder::new(File::open("./test_files/litematica/test02.litematic").unwrap()).read_to_end(&mut bytes).unwrap();
+    let nbt: HashMap<String, Value> = fastnbt::from_bytes(&bytes).unwrap();
+    let Some(Value::Compound(regions)) = nbt.get("Regions") else { panic!("No regions") };
+    let mut num_entities = 0;
+    for region in regions.values() {
+        let Value::Compound(region) = region else { panic!("Region is not a compound") };
+        let Some(Value::List(entities)) = region.get("Entities") else { continue; };
+        for orig in entities {
+            let Value::Compound(orig) = orig else { panic!("Entity is not a compound") };
+            assert_eq!(&EntityBox::from_nbt(orig.clone(), "").unwrap().to_nbt(), orig);
+            num_entities += 1;
+        }
+    }
+    assert_eq!(num_entities, schem.regions[0].entities.len());
+
+    // attributes have lowercase keys since 1.20.5, and modifiers have `id` instead of uuid since 1.21
+    {
+        use mc_schem::player::MobFields;
+        let compound = |pairs: Vec<(&str, Value)>| {
+            return Value::Compound(pairs.into_iter().map(|(k, v)| (k.to_string(), v)).collect());
+        };
+        let armor_modifier = compound(vec![("id", Value::String("minecraft:armor.boots".to_string())),
+            ("amount", Value::Double(2.0)), ("operation", Value::String("add_value".to_string()))]);
+        let spawn_modifier = compound(vec![("name", Value::String("Random spawn bonus".to_string())),
+            ("uuid", Value::IntArray(fastnbt::IntArray::new(vec![1, 2, 3, 4]))),
+            ("amount", Value::Double(0.05)), ("operation", Value::String("add_multiplied_base".to_string()))]);
+        let attributes = Value::List(vec![
+            compound(vec![("id", Value::String("minecraft:generic.max_health".to_string())), ("base", Value::Double(20.0))]),
+            compound(vec![("id", Value::String("minecraft:generic.armor".to_string())), ("base", Value::Double(0.0)),
+                ("modifiers", Value::List(vec![armor_modifier]))]),
+            compound(vec![("id", Value::String("minecraft:generic.follow_range".to_string())), ("base", Value::Double(35.0)),
+                ("modifiers", Value::List(vec![spawn_modifier]))]),
+        ]);
+        let mut zombie_nbt = HashMap::new();
+        zombie_nbt.insert("id".to_string(), Value::String("minecraft:zombie".to_string()));
+        zombie_nbt.insert("Pos".to_string(), Value::List(vec![Value::Double(0.5), Value::Double(0.0), Value::Double(0.5)]));
+        zombie_nbt.insert("Health".to_string(), Value::Float(20.0));
+        zombie_nbt.insert("attributes".to_string(), attributes.clone());
+        let zombie = EntityBox::from_nbt(zombie_nbt.clone(), "").unwrap();
+        let mob = zombie.downcast_ref::<Mob>().unwrap();
+        let names: Vec<&str> = mob.mob_fields.attributes.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, ["minecraft:generic.max_health", "minecraft:generic.armor", "minecraft:generic.follow_range"]);
+        let armor = mob.mob_fields.attribute("minecraft:generic.armor").unwrap();
+        assert_eq!(armor.modifiers[0].name, "minecraft:armor.boots");
+        assert!(armor.modifiers[0].uuid.is_none());
+        let follow_range = mob.mob_fields.attribute("minecraft:generic.follow_range").unwrap();
+        assert_eq!(follow_range.modifiers[0].uuid, Some([1, 2, 3, 4]));
+        assert_eq!(zombie.to_nbt(), zombie_nbt);
+
+        // players decide the layout by data version
+        let mut nbt = HashMap::new();
+        nbt.insert("attributes".to_string(), attributes);
+        let fields = MobFields::from_nbt(&mut nbt.clone(), "", DataVersion::Java_1_20_5 as i32).unwrap();
+        assert_eq!(fields.attributes.len(), 3);
+        let mut written = HashMap::new();
+        fields.to_nbt(&mut written);
+        assert_eq!(written, nbt);
+        let legacy = MobFields::from_nbt(&mut nbt.clone(), "", DataVersion::Java_1_20_4 as i32).unwrap();
+        assert!(legacy.attributes.is_empty());
+    }
+
+    let (schem, _) = Schematic::from_file("./test_files/litematica/multi-region01.litematic").unwrap();
+    let minecarts: Vec<&EntityBox> = schem.regions.iter().flat_map(|r| r.entities.iter()).collect();
+    assert!(!minecarts.is_empty());
+    for minecart in minecarts {
+        let minecart = minecart.downcast_ref::<Minecart>().expect("Hopper minecarts");
+        assert_eq!(minecart.id, "minecraft:hopper_minecart");
+        assert!(minecart.display_block.is_none());
+    }
+
+    let mut item_nbt = HashMap::new();
+    item_nbt.insert("id".to_string(), Value::String("minecraft:item_frame".to_string()));
+    item_nbt.insert("Pos".to_string(), Value::List(vec![Value::Double(1.5), Value::Double(2.5), Value::Double(0.03125)]));
+    item_nbt.insert("Facing".to_string(), Value::Byte(3));
+    item_nbt.insert("TileX".to_string(), Value::Int(1));
+    item_nbt.insert("TileY".to_string(), Value::Int(2));
+    item_nbt.insert("TileZ".to_string(), Value::Int(0));
+    item_nbt.insert("ItemRotation".to_string(), Value::Byte(2));
+    item_nbt.insert("Item".to_string(), Value::Compound(Item { count: 1, id: "minecraft:map".to_string(), tags: HashMap::new(), components: None, unparsed_tags: HashMap::new() }.to_nbt()));
+    item_nbt.insert("custom".to_string(), Value::Int(7));
+    let frame = EntityBox::from_nbt(item_nbt.clone(), "").unwrap();
+    // only tags that exist are written back
+    assert_eq!(frame.to_nbt(), item_nbt);
+    let parsed = frame.downcast_ref::<ItemFrame>().unwrap();
+    assert_eq!(parsed.hanging_fields.tile_pos, [1, 2, 0]);
+    assert_eq!(parsed.item.as_ref().map(|i| i.id.as_str()), Some("minecraft:map"));
+    assert_eq!(parsed.item_rotation, Some(2));
+    assert_eq!(parsed.unparsed_tags.get("custom"), Some(&Value::Int(7)));
+    assert!(!frame.is_living_body());
+    assert_eq!(frame.block_pos(), [1, 2, 0]);
+    let mut shifted = frame.clone();
+    shifted.pos_shift([16, 0, -16]);
+    assert_eq!(shifted.block_pos(), [17, 2, -16]);
+    assert_eq!(shifted.pos(), [17.5, 2.5, -15.96875]);
+    let reparsed = EntityBox::from_nbt(frame.to_nbt(), "").unwrap();
+    assert_eq!(reparsed.to_nbt(), frame.to_nbt());
+
+    // items since 1.20.5 have int `count` and `components`
+    let mut components = HashMap::new();
+    components.insert("minecraft:map_id".to_string(), Value::Int(3));
+    let mut new_item = HashMap::new();
+    new_item.insert("id".to_string(), Value::String("minecraft:filled_map".to_string()));
+    new_item.insert("count".to_string(), Value::Int(1));
+    new_item.insert("components".to_string(), Value::Compound(components.clone()));
+    let mut frame_nbt = frame.to_nbt();
+    frame_nbt.insert("Item".to_string(), Value::Compound(new_item.clone()));
+    let frame = EntityBox::from_nbt(frame_nbt, "").unwrap();
+    let item = frame.downcast_ref::<ItemFrame>().unwrap().item.as_ref().unwrap();
+    assert_eq!(item.count, 1);
+    assert_eq!(item.components, Some(components));
+    assert!(item.tags.is_empty());
+    assert_eq!(frame.to_nbt().get("Item"), Some(&Value::Compound(new_item)));
+
+    // items before 1.13 keep `Damage`
+    let mut old_item = HashMap::new();
+    old_item.insert("id".to_string(), Value::String("minecraft:wool".to_string()));
+    old_item.insert("Count".to_string(), Value::Byte(1));
+    old_item.insert("Damage".to_string(), Value::Short(14));
+    let item = Item::from_nbt(&old_item, "").unwrap();
+    assert_eq!(item.unparsed_tags.get("Damage"), Some(&Value::Short(14)));
+    assert_eq!(item.to_nbt(), old_item);
+    let mut frame_nbt = item_nbt.clone();
+    frame_nbt.insert("Item".to_string(), Value::Compound(old_item));
+    assert_eq!(EntityBox::from_nbt(frame_nbt.clone(), "").unwrap().to_nbt(), frame_nbt);
+
+    // paintings before 1.19
+    let mut painting_nbt = HashMap::new();
+    painting_nbt.insert("id".to_string(), Value::String("minecraft:painting".to_string()));
+    painting_nbt.insert("Pos".to_string(), Value::List(vec![Value::Double(0.5), Value::Double(1.0), Value::Double(0.03125)]));
+    painting_nbt.insert("Motive".to_string(), Value::String("minecraft:kebab".to_string()));
+    painting_nbt.insert("Facing".to_string(), Value::Byte(2));
+    for key in ["TileX", "TileY", "TileZ"] {
+        painting_nbt.insert(key.to_string(), Value::Int(0));
+    }
+    let painting = EntityBox::from_nbt(painting_nbt, "").unwrap();
+    assert_eq!(painting.downcast_ref::<Painting>().unwrap().variant, "minecraft:kebab");
+    // written back in the keys they are read with
+    let painting_nbt = painting.to_nbt();
+    assert_eq!(painting_nbt.get("Motive"), Some(&Value::String("minecraft:kebab".to_string())));
+    assert_eq!(painting_nbt.get("Facing"), Some(&Value::Byte(2)));
+    assert!(!painting_nbt.contains_key("variant") && !painting_nbt.contains_key("facing"));
+    let mut painting = painting.downcast_ref::<Painting>().unwrap().clone();
+    painting.legacy_keys = false;
+    assert_eq!(painting.to_nbt().get("variant"), Some(&Value::String("minecraft:kebab".to_string())));
+    assert_eq!(painting.to_nbt().get("facing"), Some(&Value::Byte(2)));
+
+    let mut stand_nbt = HashMap::new();
+    stand_nbt.insert("id".to_string(), Value::String("minecraft:armor_stand".to_string()));
+    stand_nbt.insert("Pos".to_string(), Value::List(vec![Value::Double(0.5), Value::Double(0.0), Value::Double(0.5)]));
+    stand_nbt.insert("Health".to_string(), Value::Float(20.0));
+    stand_nbt.insert("ShowArms".to_string(), Value::Byte(1));
+    stand_nbt.insert("ArmorItems".to_string(), Value::List(vec![
+        Value::Compound(HashMap::new()), Value::Compound(HashMap::new()), Value::Compound(HashMap::new()),
+        Value::Compound(Item { count: 1, id: "minecraft:diamond_helmet".to_string(), tags: HashMap::new(), components: None, unparsed_tags: HashMap::new() }.to_nbt())]));
+    let stand = EntityBox::from_nbt(stand_nbt, "").unwrap();
+    assert!(stand.is_living_body());
+    let parsed = stand.downcast_ref::<ArmorStand>().unwrap();
+    assert_eq!(parsed.show_arms, Some(true));
+    let armor_items = parsed.armor_items.as_ref().unwrap();
+    assert!(armor_items[0].is_none());
+    assert_eq!(armor_items[3].as_ref().map(|i| i.id.as_str()), Some("minecraft:diamond_helmet"));
+    assert!(parsed.hand_items.is_none());
+    assert_eq!(stand.block_pos(), [0, 0, 0]);
+    assert_eq!(EntityBox::from_nbt(stand.to_nbt(), "").unwrap().to_nbt(), stand.to_nbt());
+}
+
+#[test]
+fn lenient_loading() {
+    use std::io::Read;
+    use std::sync::Arc;
+    use mc_schem::error::{DefaultErrorHandler, Error};
+    use mc_schem::region::WorldSlice;
+
+    fn load_nbt(filename: &str) -> HashMap<String, Value> {
+        let mut bytes = Vec::new();
+        GzDecoder::new(File::open(filename).unwrap()).read_to_end(&mut bytes).unwrap();
+        return fastnbt::from_bytes(&bytes).unwrap();
+    }
+    fn compound<'a>(value: &'a mut Value) -> &'a mut HashMap<String, Value> {
+        if let Value::Compound(c) = value {
+            return c;
+        }
+        panic!("Not a compound");
+    }
+    fn list<'a>(value: Option<&'a mut Value>) -> &'a mut Vec<Value> {
+        if let Some(Value::List(l)) = value {
+            return l;
+        }
+        panic!("Not a list");
+    }
+
+    let out_dir = "./target/test/lenient_loading";
+    create_dir_all(out_dir).unwrap();
+
+    // vanilla structure with bad id, bad block index and a block out of the structure
+    let mut nbt = load_nbt("./test_files/vanilla_structure/test01.nbt");
+    let palette = list(nbt.get_mut("palette"));
+    compound(&mut palette[0]).insert("Name".to_string(), Value::String("minecraft:Not A Block!".to_string()));
+    let blocks = list(nbt.get_mut("blocks"));
+    compound(&mut blocks[0]).insert("state".to_string(), Value::Int(9999));
+    compound(&mut blocks[1]).insert("pos".to_string(), Value::List(vec![Value::Int(-1), Value::Int(0), Value::Int(0)]));
+    let filename = format!("{out_dir}/damaged.nbt");
+    let file = File::create(&filename).unwrap();
+    let mut encoder = GzBuilder::new().write(file, Compression::default());
+    fastnbt::to_writer(&mut encoder, &nbt).unwrap();
+    encoder.finish().unwrap();
+
+    assert!(Schematic::from_file(&filename).is_err());
+    let (schem, _, warnings) = Schematic::from_file_lenient(&filename).unwrap();
+    assert_eq!(warnings.len(), 3);
+    assert!(matches!(warnings[0], Error::InvalidBlockId { .. }));
+    assert!(matches!(warnings[1], Error::BlockIndexOutOfRange { .. }));
+    assert!(matches!(warnings[2], Error::BlockPosOutOfRange { .. }));
+    assert!(schem.regions[0].palette.contains(&Block::structure_void()));
+    assert!(schem.regions[0].palette.contains(&Block::air()));
+
+    // litematica with bad id, and a tile entity out of the region
+    let mut nbt = load_nbt("./test_files/litematica/test01.litematic");
+    {
+        let regions = compound(nbt.get_mut("Regions").unwrap());
+        let region = compound(regions.values_mut().next().unwrap());
+        let palette = list(region.get_mut("BlockStatePalette"));
+        compound(palette.last_mut().unwrap()).insert("Name".to_string(), Value::String("minecraft:Not A Block!".to_string()));
+        let mut tile_entity = HashMap::new();
+        for (key, val) in [("x", 10000), ("y", 0), ("z", 0)] {
+            tile_entity.insert(key.to_string(), Value::Int(val));
+        }
+        list(region.get_mut("TileEntities")).push(Value::Compound(tile_entity));
+    }
+    assert!(Schematic::from_litematica_nbt(nbt.clone(), &LitematicaLoadOption::default()).is_err());
+
+    let handler = Arc::new(DefaultErrorHandler::new());
+    let option = LitematicaLoadOption { error_handler: handler.clone() };
+    let bytes = fastnbt::to_bytes(&nbt).unwrap();
+    let (schem, _) = Schematic::from_litematica_nbt(nbt, &option).unwrap();
+    assert_eq!(handler.take_warnings().len(), 2);
+    let (streamed, _) = Schematic::from_litematica_reader_streaming(&mut bytes.as_slice(), &option).unwrap();
+    assert_eq!(handler.take_warnings().len(), 2);
+    assert_eq!(schem.regions[0].palette, streamed.regions[0].palette);
+    assert_eq!(schem.regions[0].block_entities.len(), streamed.regions[0].block_entities.len());
+    assert!(handler.take_warnings().is_empty());
+
+    // litematica with truncated `BlockStates`
+    let mut nbt = load_nbt("./test_files/litematica/test01.litematic");
+    {
+        let regions = compound(nbt.get_mut("Regions").unwrap());
+        let region = compound(regions.values_mut().next().unwrap());
+        let Some(Value::LongArray(block_states)) = region.get("BlockStates") else { panic!("BlockStates is missing") };
+        let truncated = block_states[..block_states.len() / 2].to_vec();
+        region.insert("BlockStates".to_string(), Value::LongArray(fastnbt::LongArray::new(truncated)));
+    }
+    let bytes = fastnbt::to_bytes(&nbt).unwrap();
+    let result = Schematic::from_litematica_nbt(nbt, &option);
+    assert!(matches!(result, Err(Error::InvalidValue { .. })));
+    let result = Schematic::from_litematica_reader_streaming(&mut bytes.as_slice(), &option);
+    assert!(matches!(result, Err(Error::InvalidValue { .. })));
+
+    // region with only one block in palette
+    let mut schem = Schematic::new();
+    schem.regions.push(Region::with_shape([3, 2, 5]));
+    schem.regions[0].palette = vec![Block::from_id("minecraft:stone").unwrap()];
+    let filename = format!("{out_dir}/single_block.litematic");
+    schem.save_litematica_file(&filename, &LitematicaSaveOption::default()).unwrap();
+    let (loaded, _) = Schematic::from_litematica_file(&filename, &LitematicaLoadOption::default()).unwrap();
+    assert_eq!(loaded.regions[0].palette, schem.regions[0].palette);
+    assert_eq!(loaded.regions[0].array_yzx, schem.regions[0].array_yzx);
+
+    // .schematic with damage out of range and invalid damage
+    let mut nbt = load_nbt("./test_files/schematic/full-blocks-1.12.2.schematic");
+    {
+        let Some(Value::ByteArray(blocks)) = nbt.get("Blocks") else { panic!("Blocks is missing") };
+        let mut blocks = blocks.to_vec();
+        let Some(Value::ByteArray(data)) = nbt.get("Data") else { panic!("Data is missing") };
+        let mut data = data.to_vec();
+        blocks[0..3].copy_from_slice(&[1, 1, 1]);
+        data[0..3].copy_from_slice(&[16, 16, 15]);
+        nbt.insert("Blocks".to_string(), Value::ByteArray(fastnbt::ByteArray::new(blocks)));
+        nbt.insert("Data".to_string(), Value::ByteArray(fastnbt::ByteArray::new(data)));
+    }
+    let result = Schematic::from_world_edit_12_nbt(nbt.clone(), &WorldEdit12LoadOption::default());
+    assert!(matches!(result, Err(Error::InvalidBlockNumberId { .. })));
+    let handler = Arc::new(DefaultErrorHandler::new());
+    let option = WorldEdit12LoadOption { error_handler: handler.clone(), ..WorldEdit12LoadOption::default() };
+    let (schem, _, _) = Schematic::from_world_edit_12_nbt(nbt, &option).unwrap();
+    let warnings = handler.take_warnings();
+    assert_eq!(warnings.len(), 2);
+    assert!(warnings.iter().all(|w| matches!(w, Error::InvalidBlockNumberId { .. })));
+    for x in 0..3 {
+        assert_eq!(schem.regions[0].block_at([x, 0, 0]), Some(&Block::structure_void()));
+    }
+}